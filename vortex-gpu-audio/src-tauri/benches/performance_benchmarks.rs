@@ -1,22 +1,23 @@
-/// Performance benchmarks for Vortex GPU Audio
-/// 
-/// Run with: cargo bench
-/// 
-/// These benchmarks measure:
-/// - Lock-free ring buffer throughput
-/// - Audio processing latency
-/// - GPU memory transfer speeds
-/// - Filter processing performance
+//! Performance benchmarks for Vortex GPU Audio
+//! 
+//! Run with: cargo bench
+//! 
+//! These benchmarks measure:
+//! - Lock-free ring buffer throughput
+//! - Audio processing latency
+//! - GPU memory transfer speeds
+//! - Filter processing performance
 
 use criterion::{black_box, criterion_group, criterion_main, Criterion, BenchmarkId, Throughput};
 use vortex_gpu_audio::lockfree::{LockFreeRingBuffer, AudioRingBuffer};
 use vortex_gpu_audio::gpu::{GpuProcessor, EqBand, EqFilterType};
 use vortex_gpu_audio::audio::{
-    AudioEngine, AudioConfig, AudioMemoryPool, PoolTier,
-    dsp::{EqProcessor, Convolver, Resampler, ResamplerQuality},
-    filters::{FilterChain, BiquadFilter},
+    AudioMemoryPool,
+    dsp::{EqProcessor, Resampler, resampler::ResamplerQuality},
+    filters::{ChannelLayout, Filter, FilterChain, BiquadFilter},
 };
-use std::sync::{Arc, Mutex};
+use parking_lot::Mutex;
+use std::sync::Arc;
 
 fn bench_ring_buffer_write(c: &mut Criterion) {
     let mut group = c.benchmark_group("ring_buffer_write");
@@ -121,7 +122,7 @@ fn bench_gpu_buffer_allocation(c: &mut Criterion) {
         group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, &size| {
             b.iter(|| {
                 let buffer = backend.allocate_buffer(size * 4).unwrap();
-                black_box(backend.free_buffer(buffer).unwrap());
+                backend.free_buffer(buffer).unwrap();
             });
         });
     }
@@ -141,7 +142,7 @@ fn bench_gpu_memory_transfer(c: &mut Criterion) {
         let buffer = backend.allocate_buffer(8192 * 4).unwrap();
         
         b.iter(|| {
-            black_box(backend.copy_to_device(&buffer, &data).unwrap());
+            backend.copy_to_device(&buffer, &data).unwrap();
         });
         
         backend.free_buffer(buffer).unwrap();
@@ -154,7 +155,7 @@ fn bench_gpu_memory_transfer(c: &mut Criterion) {
         backend.copy_to_device(&buffer, &data).unwrap();
         
         b.iter(|| {
-            black_box(backend.copy_from_device(&buffer, &mut output).unwrap());
+            backend.copy_from_device(&buffer, &mut output).unwrap();
         });
         
         backend.free_buffer(buffer).unwrap();
@@ -184,7 +185,7 @@ fn bench_eq_processing(c: &mut Criterion) {
         }];
         
         b.iter(|| {
-            black_box(backend.process_eq(&input, &output, &bands, 512).unwrap());
+            backend.process_eq(&input, &output, &bands, 512).unwrap();
         });
         
         backend.free_buffer(input).unwrap();
@@ -219,7 +220,7 @@ fn bench_eq_processing(c: &mut Criterion) {
         ];
         
         b.iter(|| {
-            black_box(backend.process_eq(&input, &output, &bands, 512).unwrap());
+            backend.process_eq(&input, &output, &bands, 512).unwrap();
         });
         
         backend.free_buffer(input).unwrap();
@@ -244,7 +245,7 @@ fn bench_fft_processing(c: &mut Criterion) {
             backend.copy_to_device(&input, &data).unwrap();
             
             b.iter(|| {
-                black_box(backend.process_fft(&input, &output, fft_size).unwrap());
+                backend.process_fft(&input, &output, fft_size).unwrap();
             });
             
             backend.free_buffer(input).unwrap();
@@ -273,7 +274,7 @@ fn bench_convolution_processing(c: &mut Criterion) {
         backend.copy_to_device(&ir, &ir_data).unwrap();
         
         b.iter(|| {
-            black_box(backend.process_convolution(&input, &ir, &output, 512, 4).unwrap());
+            backend.process_convolution(&input, &ir, &output, 512, 4).unwrap();
         });
         
         backend.free_buffer(input).unwrap();
@@ -315,7 +316,7 @@ fn bench_eq_processor(c: &mut Criterion) {
     group.throughput(Throughput::Elements(2048));
     
     group.bench_function("512_band_process_2048_samples", |b| {
-        let mut eq = EqProcessor::new_512band(48000.0).unwrap();
+        let mut eq = EqProcessor::new_512band(48000.0, 1).unwrap();
         eq.set_band_gain(0, 3.0).unwrap();
         eq.set_band_gain(256, -2.0).unwrap();
         
@@ -327,6 +328,80 @@ fn bench_eq_processor(c: &mut Criterion) {
         });
     });
     
+    // The serial cascade EqProcessor used to run, for comparison
+    group.bench_function("512_biquad_cascade_2048_samples", |b| {
+        let mut cascade: Vec<BiquadFilter> = (0..512)
+            .map(|i| {
+                let frequency = 20.0 * 1000.0f32.powf(i as f32 / 511.0);
+                let gain_db = if i % 2 == 0 { 3.0 } else { -2.0 };
+                BiquadFilter::peaking(frequency, 48000.0, 1.0, gain_db)
+            })
+            .collect();
+        
+        let mut samples = vec![0.5f32; 2048];
+        let mut scratch = vec![0.0f32; 2048];
+        
+        b.iter(|| {
+            for filter in &mut cascade {
+                filter.process(&samples, &mut scratch, ChannelLayout::MONO);
+                std::mem::swap(&mut samples, &mut scratch);
+            }
+            black_box(&samples);
+        });
+    });
+    
+    // No active band: the FIR stage is a plain delay
+    group.bench_function("512_band_flat_process_2048_samples", |b| {
+        let mut eq = EqProcessor::new_512band(48000.0, 1).unwrap();
+        let input = vec![0.5f32; 2048];
+        let mut output = vec![0.0f32; 2048];
+        
+        b.iter(|| {
+            black_box(eq.process(&input, &mut output).unwrap());
+        });
+    });
+    
+    group.bench_function("512_band_all_active_2048_samples", |b| {
+        let mut eq = EqProcessor::new_512band(48000.0, 1).unwrap();
+        for band in 0..512 {
+            eq.set_band_gain(band, if band % 2 == 0 { 3.0 } else { -2.0 }).unwrap();
+        }
+        
+        let input = vec![0.5f32; 2048];
+        let mut output = vec![0.0f32; 2048];
+        
+        b.iter(|| {
+            black_box(eq.process(&input, &mut output).unwrap());
+        });
+    });
+    
+    // The control-side cost of a band change: the curve update and FIR design
+    group.bench_function("512_band_set_gain", |b| {
+        let mut eq = EqProcessor::new_512band(48000.0, 1).unwrap();
+        eq.set_band_gain(100, 3.0).unwrap();
+        let mut gain = 3.0;
+        
+        b.iter(|| {
+            gain = -gain;
+            eq.set_band_gain(256, gain).unwrap();
+        });
+    });
+    
+    // A band change plus the block that swaps the new FIR in
+    group.bench_function("512_band_change_and_process_2048_samples", |b| {
+        let mut eq = EqProcessor::new_512band(48000.0, 1).unwrap();
+        eq.set_band_gain(100, 3.0).unwrap();
+        let input = vec![0.5f32; 2048];
+        let mut output = vec![0.0f32; 2048];
+        let mut gain = 3.0;
+        
+        b.iter(|| {
+            gain = -gain;
+            eq.set_band_gain(256, gain).unwrap();
+            black_box(eq.process(&input, &mut output).unwrap());
+        });
+    });
+    
     group.finish();
 }
 
@@ -336,16 +411,16 @@ fn bench_filter_chain(c: &mut Criterion) {
     
     group.bench_function("4_filters_1024_samples", |b| {
         let mut chain = FilterChain::new();
-        chain.add_filter(Box::new(BiquadFilter::peaking(1000.0, 48000.0, 1.0, 3.0)));
-        chain.add_filter(Box::new(BiquadFilter::peaking(4000.0, 48000.0, 1.0, -2.0)));
-        chain.add_filter(Box::new(BiquadFilter::peaking(8000.0, 48000.0, 1.0, 1.0)));
-        chain.add_filter(Box::new(BiquadFilter::peaking(12000.0, 48000.0, 1.0, -1.0)));
+        chain.add_filter(Box::new(BiquadFilter::peaking(1000.0, 48000.0, 1.0, 3.0))).unwrap();
+        chain.add_filter(Box::new(BiquadFilter::peaking(4000.0, 48000.0, 1.0, -2.0))).unwrap();
+        chain.add_filter(Box::new(BiquadFilter::peaking(8000.0, 48000.0, 1.0, 1.0))).unwrap();
+        chain.add_filter(Box::new(BiquadFilter::peaking(12000.0, 48000.0, 1.0, -1.0))).unwrap();
         
         let input = vec![0.5f32; 1024];
         let mut output = vec![0.0f32; 1024];
         
         b.iter(|| {
-            chain.process(&input, &mut output);
+            chain.process(&input, &mut output, ChannelLayout::MONO);
             black_box(&output);
         });
     });
@@ -357,7 +432,7 @@ fn bench_resampler(c: &mut Criterion) {
     let mut group = c.benchmark_group("resampler");
     
     group.bench_function("44.1k_to_48k_standard_1024", |b| {
-        let mut resampler = Resampler::new(44100, 48000, ResamplerQuality::Standard).unwrap();
+        let mut resampler = Resampler::new(44100, 48000, ResamplerQuality::Standard, 1).unwrap();
         let input = vec![0.5f32; 1024];
         let mut output = vec![0.0f32; 2048];
         
@@ -0,0 +1,106 @@
+//! Heap allocation checks for the real-time path (test builds only)
+//!
+//! Test builds install `CountingAllocator` as the global allocator. While a
+//! `NoAllocScope` is open it counts every allocation, reallocation and free
+//! made by the current thread, and the scope panics on drop if there were
+//! any, so audio callbacks can assert they never touch the heap.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    // Heap operations since the open scope started, or None outside a scope
+    static OPERATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// System allocator that counts heap operations inside a `NoAllocScope`
+pub struct CountingAllocator;
+
+fn record() {
+    // The thread-local is gone while the thread is being torn down
+    let _ = OPERATIONS.try_with(|count| {
+        if let Some(n) = count.get() {
+            count.set(Some(n + 1));
+        }
+    });
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        record();
+        System.dealloc(ptr, layout)
+    }
+}
+
+/// Region of the current thread that must not touch the heap
+///
+/// Scopes do not nest; opening one restarts the count.
+pub struct NoAllocScope {
+    context: &'static str,
+}
+
+impl NoAllocScope {
+    pub fn enter(context: &'static str) -> Self {
+        OPERATIONS.with(|count| count.set(Some(0)));
+        Self { context }
+    }
+
+    /// Heap operations so far in this scope
+    pub fn operations(&self) -> usize {
+        OPERATIONS.with(|count| count.get().unwrap_or(0))
+    }
+}
+
+impl Drop for NoAllocScope {
+    fn drop(&mut self) {
+        let operations = OPERATIONS.with(|count| count.replace(None).unwrap_or(0));
+        if operations > 0 && !std::thread::panicking() {
+            panic!("{} made {} heap operations", self.context, operations);
+        }
+    }
+}
+
+/// Heap operations `f` makes on the current thread
+pub fn heap_operations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    OPERATIONS.with(|count| count.set(Some(0)));
+    let result = f();
+    let operations = OPERATIONS.with(|count| count.replace(None).unwrap_or(0));
+    (result, operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_heap_operations() {
+        let (_, operations) = heap_operations(|| drop(std::hint::black_box(vec![1.0f32; 16])));
+        assert_eq!(operations, 2);
+
+        let mut buffer = Vec::with_capacity(16);
+        let (_, operations) = heap_operations(|| buffer.extend_from_slice(&[0.0f32; 16]));
+        assert_eq!(operations, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "callback made 1 heap operations")]
+    fn test_scope_panics_on_allocation() {
+        let _scope = NoAllocScope::enter("callback");
+        std::mem::forget(std::hint::black_box(Box::new(0u64)));
+    }
+}
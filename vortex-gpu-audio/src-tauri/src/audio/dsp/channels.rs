@@ -0,0 +1,95 @@
+//! Channel layouts shared by the multichannel DSP blocks
+//!
+//! Every block is created for a fixed channel count and keeps its filter state
+//! per channel, so channels never bleed into each other. Audio is passed in
+//! one of two layouts:
+//! - interleaved: whole frames in one slice, `[L0, R0, L1, R1, ...]`, as the
+//!   engine's ring buffers carry them. Lengths count samples and must be a
+//!   multiple of the channel count.
+//! - planar: one slice per channel, all of the same length.
+//!
+//! `process` takes interleaved buffers and `process_planar` planar ones. Both
+//! return the number of frames written, and the same audio produces the same
+//! output in either layout.
+
+use crate::error::VortexError;
+
+/// Validate a channel count given at construction
+pub(crate) fn validate_channels(channels: u16) -> Result<usize, VortexError> {
+    if channels == 0 {
+        return Err(crate::error::AudioError::InvalidParameter(
+            "At least one channel is required".to_string()
+        ).into());
+    }
+    Ok(channels as usize)
+}
+
+/// Number of whole frames in an interleaved buffer of `len` samples
+pub(crate) fn interleaved_frames(len: usize, channels: usize) -> Result<usize, VortexError> {
+    if !len.is_multiple_of(channels) {
+        return Err(crate::error::AudioError::InvalidParameter(format!(
+            "Expected whole frames of {} channels, got {} samples",
+            channels, len
+        )).into());
+    }
+    Ok(len / channels)
+}
+
+/// Number of frames in a planar buffer set: one equally long slice per channel
+pub(crate) fn planar_frames<T, B: AsRef<[T]>>(planes: &[B], channels: usize) -> Result<usize, VortexError> {
+    let frames = planes.first().map_or(0, |plane| plane.as_ref().len());
+    if planes.len() != channels || planes.iter().any(|plane| plane.as_ref().len() != frames) {
+        return Err(crate::error::AudioError::InvalidParameter(format!(
+            "Expected {} planar channels of equal length",
+            channels
+        )).into());
+    }
+    Ok(frames)
+}
+
+/// Append interleaved samples to per-channel buffers
+pub(crate) fn deinterleave(input: &[f32], planes: &mut [Vec<f32>]) {
+    let channels = planes.len();
+    for (channel, plane) in planes.iter_mut().enumerate() {
+        plane.extend(input.iter().skip(channel).step_by(channels));
+    }
+}
+
+/// Write the first `frames` samples of each channel buffer as interleaved frames
+pub(crate) fn interleave<B: AsRef<[f32]>>(planes: &[B], frames: usize, output: &mut [f32]) {
+    let channels = planes.len();
+    for (channel, plane) in planes.iter().enumerate() {
+        for (out, &sample) in output[channel..].iter_mut().step_by(channels).zip(&plane.as_ref()[..frames]) {
+            *out = sample;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let input = [1.0, -1.0, 2.0, -2.0, 3.0, -3.0];
+        let mut planes = vec![Vec::new(), Vec::new()];
+        deinterleave(&input, &mut planes);
+        assert_eq!(planes, vec![vec![1.0, 2.0, 3.0], vec![-1.0, -2.0, -3.0]]);
+
+        let mut output = [0.0; 6];
+        interleave(&planes, 3, &mut output);
+        assert_eq!(output, input);
+    }
+
+    #[test]
+    fn test_layout_validation() {
+        assert!(validate_channels(0).is_err());
+        assert_eq!(interleaved_frames(6, 2).unwrap(), 3);
+        assert!(interleaved_frames(5, 2).is_err());
+
+        let planes = [vec![0.0f32; 4], vec![0.0; 4]];
+        assert_eq!(planar_frames(&planes, 2).unwrap(), 4);
+        assert!(planar_frames(&planes, 3).is_err());
+        assert!(planar_frames(&[vec![0.0f32; 4], vec![0.0; 3]], 2).is_err());
+    }
+}
@@ -1,17 +1,114 @@
 use crate::error::VortexError;
+use super::channels;
+use rustfft::num_complex::Complex32;
+use rustfft::{Fft, FftPlanner};
+use std::sync::Arc;
+
+/// IR partitioning strategy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionScheme {
+    /// Every IR partition is `partition_size` long
+    Uniform,
+    /// Direct-form FIR for the first `direct_taps` taps, followed by FFT
+    /// partitions that double in size from `direct_taps` up to `partition_size`
+    NonUniform { direct_taps: usize },
+}
 
 /// Partition-based convolution processor
+///
+/// Overlap-save convolution with a frequency-domain delay line (FDL). IR
+/// partition spectra are precomputed; each completed input block is
+/// transformed once and pushed into the FDL, so the cost per block is one
+/// FFT/IFFT pair plus one complex multiply-accumulate per partition.
+///
+/// Both schemes are zero-latency: output is sample-aligned with the input.
+/// - `Uniform`: while an input block is only partially filled, the partial
+///   block is convolved with the first partition and added to the precomputed
+///   contribution of all older blocks. Every `process` call therefore costs an
+///   FFT of `2 * partition_size`, which is wasteful for calls much shorter
+///   than the partition.
+/// - `NonUniform`: the IR head runs as a direct-form FIR and later segments use
+///   progressively larger partitions that start late enough in the IR for
+///   their block latency to be hidden. Small engine buffers only pay for
+///   small transforms. Every segment after the first has a block of slack,
+///   so the FFT, multiply-accumulates and IFFT for its next block are spread
+///   over the calls of the current one: a call of `n` frames runs about
+///   `n / B` of a block-`B` segment's `partitions + 2` steps, plus at most
+///   one step (one transform or one partition's multiply-accumulate) of
+///   rounding. Only the first FFT segment (block `direct_taps`) does its
+///   whole block's work in the call that completes it.
+///
+/// Every channel is convolved with the same IR but keeps its own history;
+/// buffers are interleaved for `process` and planar for `process_planar`
+/// (see `channels`). Partition spectra are shared between channels.
+///
+/// Processing does not allocate once the staging buffers have grown to the
+/// block size (see `prepare`), and neither does `update_ir` with an IR of the
+/// current length.
 pub struct Convolver {
     ir: Vec<f32>,
+    channels: usize,
     partition_size: usize,
     num_partitions: usize,
-    // State buffers
-    overlap_buffer: Vec<f32>,
+    scheme: PartitionScheme,
+    // Direct-form head (NonUniform only), taps stored reversed
+    direct_taps: Vec<f32>,
+    // Partition spectra of each segment, bins 0..=block_size, scaled by
+    // 1/fft_size
+    spectra: Vec<Vec<Vec<Complex32>>>,
+    states: Vec<ChannelState>,
+    // Per-channel staging for interleaved buffers, reused across calls
+    input_planes: Vec<Vec<f32>>,
+    output_planes: Vec<Vec<f32>>,
+}
+
+/// An impulse response split into a convolver's partitions
+///
+/// Prepared by `Convolver::partition_ir` off the audio thread and exchanged
+/// by `Convolver::swap_ir` on it, so the partition FFTs never run there.
+#[derive(Default)]
+pub struct PartitionedIr {
+    ir: Vec<f32>,
+    direct_taps: Vec<f32>,
+    spectra: Vec<Vec<Vec<Complex32>>>,
+}
+
+/// Convolution history of one channel
+#[derive(Clone)]
+struct ChannelState {
+    direct_history: Vec<f32>,
+    direct_pos: usize,
+    // FFT partition segments, all running on this channel's input
+    segments: Vec<PartitionedSegment>,
 }
 
 impl Convolver {
     /// Create a new convolver with the given impulse response
-    pub fn new(ir: Vec<f32>, partition_size: usize) -> Result<Self, VortexError> {
+    pub fn new(ir: Vec<f32>, partition_size: usize, channels: u16) -> Result<Self, VortexError> {
+        Self::with_scheme(ir, partition_size, PartitionScheme::Uniform, channels)
+    }
+
+    /// Create a zero-latency non-uniform convolver
+    ///
+    /// `direct_taps` IR taps run as a direct-form FIR; the rest is handled by
+    /// FFT partitions growing from `direct_taps` up to `max_partition_size`.
+    pub fn new_non_uniform(
+        ir: Vec<f32>,
+        direct_taps: usize,
+        max_partition_size: usize,
+        channels: u16,
+    ) -> Result<Self, VortexError> {
+        Self::with_scheme(ir, max_partition_size, PartitionScheme::NonUniform { direct_taps }, channels)
+    }
+
+    /// Create a convolver with an explicit partitioning scheme
+    pub fn with_scheme(
+        ir: Vec<f32>,
+        partition_size: usize,
+        scheme: PartitionScheme,
+        channels: u16,
+    ) -> Result<Self, VortexError> {
+        let channels = channels::validate_channels(channels)?;
         if ir.is_empty() {
             return Err(crate::error::AudioError::InvalidParameter(
                 "Impulse response cannot be empty".to_string()
@@ -24,35 +121,100 @@ impl Convolver {
             ).into());
         }
         
-        let num_partitions = (ir.len() + partition_size - 1) / partition_size;
-        let overlap_buffer = vec![0.0; partition_size * 2];
-        
-        Ok(Self {
-            ir,
+        if let PartitionScheme::NonUniform { direct_taps } = scheme {
+            if direct_taps == 0 || !direct_taps.is_power_of_two() || direct_taps > partition_size {
+                return Err(crate::error::AudioError::InvalidParameter(
+                    format!(
+                        "Direct taps must be a power of 2 no larger than the partition size, got {}",
+                        direct_taps
+                    )
+                ).into());
+            }
+        }
+
+        let mut convolver = Self {
+            ir: Vec::new(),
+            channels,
             partition_size,
-            num_partitions,
-            overlap_buffer,
-        })
+            num_partitions: 0,
+            scheme,
+            direct_taps: Vec::new(),
+            spectra: Vec::new(),
+            states: Vec::new(),
+            input_planes: vec![Vec::new(); channels],
+            output_planes: vec![Vec::new(); channels],
+        };
+        convolver.set_ir(ir)?;
+
+        Ok(convolver)
     }
-    
-    /// Process audio through convolution
-    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> Result<(), VortexError> {
-        // Simplified implementation - full version would use FFT-based convolution
-        // This is a direct convolution (inefficient for large IRs)
-        
-        let ir_len = self.ir.len();
-        
-        for i in 0..output.len() {
-            let mut sum = 0.0;
-            
-            for j in 0..ir_len.min(i + 1) {
-                sum += input[i - j] * self.ir[j];
-            }
-            
-            output[i] = sum;
+
+    /// Grow the interleaved staging buffers to `max_frames`, so `process`
+    /// does not allocate for blocks up to that size
+    pub fn prepare(&mut self, max_frames: usize) {
+        for plane in self.input_planes.iter_mut().chain(&mut self.output_planes) {
+            plane.reserve(max_frames.saturating_sub(plane.len()));
         }
-        
-        Ok(())
+    }
+
+    /// Process interleaved audio through convolution
+    ///
+    /// `input` may hold any number of frames; state carries across calls so
+    /// consecutive blocks produce the same result as one long convolution.
+    /// Returns the number of frames written.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> Result<usize, VortexError> {
+        let frames = channels::interleaved_frames(input.len(), self.channels)?;
+        if output.len() < input.len() {
+            return Err(crate::error::AudioError::InvalidParameter(
+                format!("Output buffer too small: {} < {}", output.len(), input.len())
+            ).into());
+        }
+
+        self.input_planes.iter_mut().for_each(Vec::clear);
+        channels::deinterleave(input, &mut self.input_planes);
+        for ((state, input), output) in self.states.iter_mut().zip(&self.input_planes).zip(&mut self.output_planes) {
+            output.resize(frames, 0.0);
+            state.process(&self.direct_taps, &self.spectra, input, output);
+        }
+        channels::interleave(&self.output_planes, frames, output);
+
+        Ok(frames)
+    }
+
+    /// Process planar audio through convolution, one slice per channel
+    ///
+    /// Each output slice must be at least as long as the input slices.
+    pub fn process_planar(&mut self, input: &[&[f32]], output: &mut [&mut [f32]]) -> Result<usize, VortexError> {
+        let frames = channels::planar_frames(input, self.channels)?;
+        let capacity = channels::planar_frames(output, self.channels)?;
+        if capacity < frames {
+            return Err(crate::error::AudioError::InvalidParameter(
+                format!("Output buffer too small: {} < {}", capacity, frames)
+            ).into());
+        }
+
+        for ((state, input), output) in self.states.iter_mut().zip(input).zip(output.iter_mut()) {
+            state.process(&self.direct_taps, &self.spectra, input, &mut output[..frames]);
+        }
+
+        Ok(frames)
+    }
+
+    /// Convolve one channel of planar audio, `output` as long as `input`
+    pub fn process_channel(&mut self, channel: usize, input: &[f32], output: &mut [f32]) -> Result<usize, VortexError> {
+        if channel >= self.channels {
+            return Err(crate::error::AudioError::InvalidParameter(
+                format!("Channel {} out of range for {} channels", channel, self.channels)
+            ).into());
+        }
+        if output.len() != input.len() {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "Output must match the input length".to_string()
+            ).into());
+        }
+
+        self.states[channel].process(&self.direct_taps, &self.spectra, input, output);
+        Ok(input.len())
     }
     
     /// Update the impulse response
@@ -64,59 +226,953 @@ impl Convolver {
         }
         
         self.ir = ir;
-        self.num_partitions = (self.ir.len() + self.partition_size - 1) / self.partition_size;
+        self.prepare_segments();
         self.reset();
         
         Ok(())
     }
+
+    /// Replace the impulse response while keeping the convolution history
+    ///
+    /// An IR of the current length keeps the partition layout, so the input
+    /// already in the delay lines is convolved with the new response and the
+    /// output continues without a gap. It is copied into the existing
+    /// buffers, without allocating. Other lengths fall back to `set_ir`.
+    pub fn update_ir(&mut self, ir: &[f32]) -> Result<(), VortexError> {
+        if ir.len() != self.ir.len() {
+            return self.set_ir(ir.to_vec());
+        }
+
+        self.ir.copy_from_slice(ir);
+        let head = self.direct_taps.len();
+        for (tap, &x) in self.direct_taps.iter_mut().zip(self.ir[..head].iter().rev()) {
+            *tap = x;
+        }
+
+        if let Some(first) = self.states.first_mut() {
+            for (segment, spectra) in first.segments.iter_mut().zip(&mut self.spectra) {
+                segment.write_spectra(&self.ir[segment.ir_range(self.ir.len())], spectra);
+            }
+        }
+        self.restart_jobs();
+
+        Ok(())
+    }
+
+    /// Partition `ir`, of the current length, into `target` for any
+    /// convolver laid out like this one
+    ///
+    /// Only this convolver's FFT scratch is used, so its history and output
+    /// are untouched. `target`'s buffers are reused once they have the layout.
+    pub fn partition_ir(&mut self, ir: &[f32], target: &mut PartitionedIr) -> Result<(), VortexError> {
+        if ir.len() != self.ir.len() {
+            return Err(crate::error::AudioError::InvalidParameter(
+                format!("Impulse response must have {} taps, got {}", self.ir.len(), ir.len())
+            ).into());
+        }
+
+        target.ir.clear();
+        target.ir.extend_from_slice(ir);
+        target.direct_taps.clear();
+        target.direct_taps.extend(ir[..self.direct_taps.len()].iter().rev());
+        target.spectra.clone_from(&self.spectra);
+        if let Some(first) = self.states.first_mut() {
+            for (segment, spectra) in first.segments.iter_mut().zip(&mut target.spectra) {
+                segment.write_spectra(&ir[segment.ir_range(ir.len())], spectra);
+            }
+        }
+        Ok(())
+    }
+
+    /// Exchange the impulse response for one prepared by `partition_ir`,
+    /// keeping the convolution history
+    ///
+    /// Behaves like `update_ir` but only swaps buffers; `ir` receives the
+    /// previous response, so it can be reused or dropped elsewhere.
+    pub fn swap_ir(&mut self, ir: &mut PartitionedIr) -> Result<(), VortexError> {
+        if ir.ir.len() != self.ir.len()
+            || ir.direct_taps.len() != self.direct_taps.len()
+            || ir.spectra.len() != self.spectra.len()
+            || ir.spectra.iter().zip(&self.spectra).any(|(a, b)| a.len() != b.len())
+        {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "Partitioned IR does not match the convolver's layout".to_string()
+            ).into());
+        }
+
+        std::mem::swap(&mut self.ir, &mut ir.ir);
+        std::mem::swap(&mut self.direct_taps, &mut ir.direct_taps);
+        std::mem::swap(&mut self.spectra, &mut ir.spectra);
+        self.restart_jobs();
+        Ok(())
+    }
+
+    /// Redo spread work already accumulated with the previous spectra
+    fn restart_jobs(&mut self) {
+        for state in &mut self.states {
+            state.segments.iter_mut().for_each(PartitionedSegment::restart_job);
+        }
+    }
+
+    /// Take over the convolution history of `other`
+    ///
+    /// Both convolvers must share channel count, scheme, partition size and
+    /// IR length; the IRs themselves may differ, so this convolver continues
+    /// `other`'s input with its own response. History is copied into the
+    /// existing buffers without allocating; the multiply-accumulates `other`
+    /// already spread over the current block are redone with this IR over the
+    /// following calls.
+    pub fn copy_state_from(&mut self, other: &Convolver) -> Result<(), VortexError> {
+        if self.channels != other.channels
+            || self.scheme != other.scheme
+            || self.partition_size != other.partition_size
+            || self.ir.len() != other.ir.len()
+        {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "Convolver layouts differ".to_string()
+            ).into());
+        }
+
+        for (state, source) in self.states.iter_mut().zip(&other.states) {
+            state.copy_from(source);
+        }
+        Ok(())
+    }
     
     /// Reset processor state
     pub fn reset(&mut self) {
-        self.overlap_buffer.fill(0.0);
+        self.states.iter_mut().for_each(ChannelState::reset);
     }
     
+    /// Number of channels convolved
+    pub fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
     /// Get IR length
     pub fn ir_length(&self) -> usize {
         self.ir.len()
     }
+
+    /// Get partition (block) size; the largest partition for `NonUniform`
+    pub fn partition_size(&self) -> usize {
+        self.partition_size
+    }
+
+    /// Get number of FFT partitions across all segments
+    pub fn num_partitions(&self) -> usize {
+        self.num_partitions
+    }
+
+    /// Get the partitioning scheme
+    pub fn scheme(&self) -> PartitionScheme {
+        self.scheme
+    }
+
+    /// Algorithmic latency in samples
+    ///
+    /// Both schemes hide their block latency, so this is always zero.
+    pub fn latency_samples(&self) -> usize {
+        0
+    }
+
+    /// Split the IR into the direct-form head and FFT segments, for every channel
+    fn prepare_segments(&mut self) {
+        let mut planner = FftPlanner::new();
+        let mut segments = Vec::new();
+        let mut spectra = Vec::new();
+
+        match self.scheme {
+            PartitionScheme::Uniform => {
+                self.direct_taps.clear();
+                let mut segment = PartitionedSegment::new(&mut planner, self.partition_size, 0);
+                spectra.push(segment.partition_spectra(&self.ir));
+                segments.push(segment);
+            }
+            PartitionScheme::NonUniform { direct_taps } => {
+                let head = direct_taps.min(self.ir.len());
+                self.direct_taps = self.ir[..head].iter().rev().copied().collect();
+
+                // Level 0 (block D) covers [D, 4D) starting one partition in.
+                // Every following level doubles the block and starts two
+                // partitions in, so [2B, 4B) at block B; the last level
+                // (block = partition_size) takes the rest of the IR.
+                let mut block = direct_taps;
+                let mut offset = direct_taps;
+                while offset < self.ir.len() {
+                    let first_partition = offset / block;
+                    let end = if block == self.partition_size {
+                        self.ir.len()
+                    } else if first_partition == 1 {
+                        (offset + 3 * block).min(self.ir.len())
+                    } else {
+                        (offset + 2 * block).min(self.ir.len())
+                    };
+
+                    let mut segment = PartitionedSegment::new(&mut planner, block, first_partition);
+                    spectra.push(segment.partition_spectra(&self.ir[offset..end]));
+                    segments.push(segment);
+
+                    offset = end;
+                    block = (block * 2).min(self.partition_size);
+                }
+            }
+        }
+
+        for (segment, spectra) in segments.iter_mut().zip(&spectra) {
+            segment.size_fdl(spectra.len());
+        }
+        self.num_partitions = spectra.iter().map(Vec::len).sum();
+        self.spectra = spectra;
+        let state = ChannelState {
+            direct_history: vec![0.0; 2 * self.direct_taps.len()],
+            direct_pos: 0,
+            segments,
+        };
+        self.states = vec![state; self.channels];
+    }
+}
+
+impl ChannelState {
+    /// Convolve one channel's `input` into `output` (same length)
+    fn process(&mut self, direct_taps: &[f32], spectra: &[Vec<Vec<Complex32>>], input: &[f32], output: &mut [f32]) {
+        self.process_direct(direct_taps, input, output);
+
+        for (segment, spectra) in self.segments.iter_mut().zip(spectra) {
+            segment.process_accumulate(spectra, input, output);
+        }
+    }
+
+    fn copy_from(&mut self, other: &ChannelState) {
+        self.direct_history.copy_from_slice(&other.direct_history);
+        self.direct_pos = other.direct_pos;
+        for (segment, source) in self.segments.iter_mut().zip(&other.segments) {
+            segment.copy_state_from(source);
+            segment.restart_job();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.direct_history.fill(0.0);
+        self.direct_pos = 0;
+        for segment in &mut self.segments {
+            segment.reset();
+        }
+    }
+
+    /// Direct-form FIR over the IR head (no-op for `Uniform`)
+    fn process_direct(&mut self, direct_taps: &[f32], input: &[f32], output: &mut [f32]) {
+        let taps = direct_taps.len();
+        if taps == 0 {
+            output.fill(0.0);
+            return;
+        }
+
+        // History is stored twice so the last `taps` samples are always one
+        // contiguous slice, oldest first
+        for (out, &x) in output.iter_mut().zip(input) {
+            self.direct_pos = (self.direct_pos + 1) % taps;
+            self.direct_history[self.direct_pos] = x;
+            self.direct_history[self.direct_pos + taps] = x;
+
+            let window = &self.direct_history[self.direct_pos + 1..self.direct_pos + 1 + taps];
+            *out = window.iter().zip(direct_taps).map(|(x, h)| x * h).sum();
+        }
+    }
+}
+
+/// A run of equally-sized IR partitions sharing one frequency-domain delay line
+///
+/// Partition `k` of the segment is applied to the input block `k` blocks in
+/// the past. With `first_partition == 0` the current block is convolved as it
+/// fills; otherwise the output of each block is fully determined by earlier
+/// blocks. With `first_partition == 1` it is computed when the previous block
+/// completes. From `first_partition == 2` on it only depends on blocks up to
+/// two back, so it is computed as a job of `partitions + 2` steps (forward
+/// FFT, one multiply-accumulate per partition, IFFT) paced over the previous
+/// block. The partition spectra are owned by the `Convolver` and passed in.
+#[derive(Clone)]
+struct PartitionedSegment {
+    block_size: usize,
+    first_partition: usize,
+    // FFT plans (fft_size = 2 * block_size)
+    fft_forward: Arc<dyn Fft<f32>>,
+    fft_inverse: Arc<dyn Fft<f32>>,
+    partitions: usize,
+    // State buffers
+    overlap_buffer: Vec<f32>,
+    block_fill: usize,
+    fdl: Vec<Vec<Complex32>>,
+    fdl_head: usize,
+    tail_spectrum: Vec<Complex32>,
+    tail_output: Vec<f32>,
+    // Spread job (first_partition >= 2): the window completed before the
+    // current block, the output being computed for the next block, and the
+    // number of job steps done
+    window: Vec<f32>,
+    next_output: Vec<f32>,
+    job_step: usize,
+    // Scratch buffers
+    spectrum: Vec<Complex32>,
+    time_buffer: Vec<Complex32>,
+    fft_scratch: Vec<Complex32>,
+}
+
+impl PartitionedSegment {
+    /// Segment starting at `first_partition * block_size` in the full IR
+    ///
+    /// The FDL is sized by `size_fdl` once the partition count is known.
+    fn new(planner: &mut FftPlanner<f32>, block_size: usize, first_partition: usize) -> Self {
+        let fft_size = block_size * 2;
+        let fft_forward = planner.plan_fft_forward(fft_size);
+        let fft_inverse = planner.plan_fft_inverse(fft_size);
+        let scratch_len = fft_forward
+            .get_inplace_scratch_len()
+            .max(fft_inverse.get_inplace_scratch_len());
+        let spread = first_partition > 1;
+
+        Self {
+            block_size,
+            first_partition,
+            fft_forward,
+            fft_inverse,
+            partitions: 0,
+            overlap_buffer: vec![0.0; fft_size],
+            block_fill: 0,
+            fdl: Vec::new(),
+            fdl_head: 0,
+            tail_spectrum: vec![Complex32::default(); block_size + 1],
+            tail_output: vec![0.0; block_size],
+            window: if spread { vec![0.0; fft_size] } else { Vec::new() },
+            next_output: if spread { vec![0.0; block_size] } else { Vec::new() },
+            job_step: 0,
+            spectrum: vec![Complex32::default(); block_size + 1],
+            time_buffer: vec![Complex32::default(); fft_size],
+            fft_scratch: vec![Complex32::default(); scratch_len],
+        }
+    }
+
+    /// Size the FDL for `partitions` partitions
+    fn size_fdl(&mut self, partitions: usize) {
+        // Partition k pairs with the block k - 1 blocks before the one that
+        // just completed; partition 0 (if present) uses the live block instead.
+        // A spread job computes one block further ahead, so k pairs with the
+        // block k - 2 before the newest one in the FDL.
+        let fdl_len = if self.first_partition > 1 {
+            self.first_partition + partitions - 2
+        } else {
+            (self.first_partition + partitions).saturating_sub(1)
+        };
+        self.fdl = vec![vec![Complex32::default(); self.block_size + 1]; fdl_len];
+        self.partitions = partitions;
+    }
+
+    /// Taps of an IR of `ir_len` covered by this segment
+    fn ir_range(&self, ir_len: usize) -> std::ops::Range<usize> {
+        let start = self.first_partition * self.block_size;
+        start..(start + self.partitions * self.block_size).min(ir_len)
+    }
+
+    /// Spectra of `taps` split into blocks, scaled for the inverse FFT
+    fn partition_spectra(&mut self, taps: &[f32]) -> Vec<Vec<Complex32>> {
+        let mut spectra = vec![vec![Complex32::default(); self.block_size + 1]; taps.len().div_ceil(self.block_size)];
+        self.write_spectra(taps, &mut spectra);
+        spectra
+    }
+
+    /// Overwrite `spectra` with those of `taps`, one block per partition
+    fn write_spectra(&mut self, taps: &[f32], spectra: &mut [Vec<Complex32>]) {
+        let block = self.block_size;
+        let scale = 1.0 / (2 * block) as f32;
+
+        for (chunk, spectrum) in taps.chunks(block).zip(spectra) {
+            self.time_buffer.fill(Complex32::default());
+            for (slot, &tap) in self.time_buffer.iter_mut().zip(chunk) {
+                *slot = Complex32::new(tap * scale, 0.0);
+            }
+            self.fft_forward.process_with_scratch(&mut self.time_buffer, &mut self.fft_scratch);
+            spectrum.copy_from_slice(&self.time_buffer[..=block]);
+        }
+    }
+
+    /// Copy the delay line and block position of an identically shaped segment
+    fn copy_state_from(&mut self, other: &PartitionedSegment) {
+        self.overlap_buffer.copy_from_slice(&other.overlap_buffer);
+        self.block_fill = other.block_fill;
+        for (spectrum, source) in self.fdl.iter_mut().zip(&other.fdl) {
+            spectrum.copy_from_slice(source);
+        }
+        self.fdl_head = other.fdl_head;
+        self.tail_spectrum.copy_from_slice(&other.tail_spectrum);
+        self.tail_output.copy_from_slice(&other.tail_output);
+        self.window.copy_from_slice(&other.window);
+        self.next_output.copy_from_slice(&other.next_output);
+        self.job_step = other.job_step;
+    }
+
+    fn reset(&mut self) {
+        self.overlap_buffer.fill(0.0);
+        self.block_fill = 0;
+        for spectrum in &mut self.fdl {
+            spectrum.fill(Complex32::default());
+        }
+        self.fdl_head = 0;
+        self.tail_spectrum.fill(Complex32::default());
+        self.tail_output.fill(0.0);
+        // The first job runs over silent history, like every later one
+        self.window.fill(0.0);
+        self.next_output.fill(0.0);
+        self.job_step = 0;
+    }
+
+    /// Redo the multiply-accumulates of a running spread job, e.g. after the
+    /// partition spectra changed; the FDL push (step 0) is kept
+    fn restart_job(&mut self) {
+        if self.first_partition > 1 && self.job_step > 1 {
+            self.job_step = 1;
+        }
+    }
+
+    /// Convolve `input` with this segment's `spectra` and add the result into `output`
+    fn process_accumulate(&mut self, spectra: &[Vec<Complex32>], input: &[f32], output: &mut [f32]) {
+        let block = self.block_size;
+        let mut offset = 0;
+
+        while offset < input.len() {
+            let count = (block - self.block_fill).min(input.len() - offset);
+            let start = block + self.block_fill;
+
+            self.overlap_buffer[start..start + count]
+                .copy_from_slice(&input[offset..offset + count]);
+
+            if self.first_partition == 0 {
+                // Spectrum of [previous block | current (partial) block]
+                self.forward_window();
+
+                // Y = X * H0 + sum_{k>=1} X_{m-k} * H_k
+                for ((y, x), (h, t)) in self.time_buffer.iter_mut()
+                    .zip(&self.spectrum)
+                    .zip(spectra[0].iter().zip(&self.tail_spectrum))
+                {
+                    *y = x * h + t;
+                }
+                self.inverse_to_time_buffer();
+
+                // Overlap-save: the second half of the circular result is valid
+                for (out, y) in output[offset..offset + count]
+                    .iter_mut()
+                    .zip(&self.time_buffer[start..start + count])
+                {
+                    *out += y.re;
+                }
+            } else {
+                let fill = self.block_fill;
+                for (out, y) in output[offset..offset + count]
+                    .iter_mut()
+                    .zip(&self.tail_output[fill..fill + count])
+                {
+                    *out += y;
+                }
+            }
+
+            self.block_fill += count;
+            offset += count;
+
+            if self.first_partition > 1 {
+                // Keep the next block's job in step with this block's fill
+                let target = ((self.partitions + 2) * self.block_fill).div_ceil(block);
+                self.run_job(spectra, target);
+            }
+
+            if self.block_fill == block {
+                self.advance_block(spectra);
+            }
+        }
+    }
+
+    /// Transform the overlap-save window into `spectrum` (bins 0..=block_size)
+    ///
+    /// Spread segments transform the saved `window` instead.
+    fn forward_window(&mut self) {
+        let window = if self.first_partition > 1 { &self.window } else { &self.overlap_buffer };
+        for (slot, &sample) in self.time_buffer.iter_mut().zip(window) {
+            *slot = Complex32::new(sample, 0.0);
+        }
+        self.fft_forward.process_with_scratch(&mut self.time_buffer, &mut self.fft_scratch);
+        self.spectrum.copy_from_slice(&self.time_buffer[..=self.block_size]);
+    }
+
+    /// Inverse-transform the half spectrum held in `time_buffer[..=block_size]`
+    fn inverse_to_time_buffer(&mut self) {
+        let fft_size = self.time_buffer.len();
+        // The input is real, so the upper half is the conjugate mirror
+        for k in 1..self.block_size {
+            self.time_buffer[fft_size - k] = self.time_buffer[k].conj();
+        }
+        self.fft_inverse.process_with_scratch(&mut self.time_buffer, &mut self.fft_scratch);
+    }
+
+    /// Run spread job steps until `target` of them are done
+    ///
+    /// Step 0 transforms the saved window and pushes it into the FDL, steps
+    /// `1..=partitions` multiply-accumulate one partition each, and the last
+    /// step inverse-transforms the sum into `next_output`.
+    fn run_job(&mut self, spectra: &[Vec<Complex32>], target: usize) {
+        let block = self.block_size;
+
+        while self.job_step < target {
+            match self.job_step {
+                0 => {
+                    self.forward_window();
+                    self.fdl_head = (self.fdl_head + self.fdl.len() - 1) % self.fdl.len();
+                    self.fdl[self.fdl_head].copy_from_slice(&self.spectrum);
+                }
+                step if step <= self.partitions => {
+                    if step == 1 {
+                        self.tail_spectrum.fill(Complex32::default());
+                    }
+                    let partition = self.first_partition + step - 1;
+                    let x = &self.fdl[(self.fdl_head + partition - 2) % self.fdl.len()];
+                    for ((t, x), h) in self.tail_spectrum.iter_mut().zip(x).zip(&spectra[step - 1]) {
+                        *t += x * h;
+                    }
+                }
+                _ => {
+                    self.time_buffer[..=block].copy_from_slice(&self.tail_spectrum);
+                    self.inverse_to_time_buffer();
+                    for (out, y) in self.next_output.iter_mut().zip(&self.time_buffer[block..]) {
+                        *out = y.re;
+                    }
+                }
+            }
+            self.job_step += 1;
+        }
+    }
+
+    /// Push the completed block into the FDL and precompute the contribution
+    /// of all past blocks to the next one
+    ///
+    /// Spread segments instead take the output their job just finished and
+    /// start the job for the block after next.
+    fn advance_block(&mut self, spectra: &[Vec<Complex32>]) {
+        let block = self.block_size;
+
+        if self.first_partition > 1 {
+            std::mem::swap(&mut self.tail_output, &mut self.next_output);
+            self.window.copy_from_slice(&self.overlap_buffer);
+            self.job_step = 0;
+        } else {
+            self.advance_now(spectra);
+        }
+
+        self.overlap_buffer.copy_within(block.., 0);
+        self.overlap_buffer[block..].fill(0.0);
+        self.block_fill = 0;
+    }
+
+    /// FDL push, multiply-accumulate and IFFT for the next block in one go
+    fn advance_now(&mut self, spectra: &[Vec<Complex32>]) {
+        let block = self.block_size;
+
+        if self.first_partition > 0 {
+            // The live path never transformed the completed window
+            self.forward_window();
+        }
+
+        if !self.fdl.is_empty() {
+            self.fdl_head = (self.fdl_head + self.fdl.len() - 1) % self.fdl.len();
+            self.fdl[self.fdl_head].copy_from_slice(&self.spectrum);
+        }
+
+        self.tail_spectrum.fill(Complex32::default());
+        for (index, h) in spectra.iter().enumerate() {
+            let partition = self.first_partition + index;
+            if partition == 0 {
+                continue;
+            }
+            let x = &self.fdl[(self.fdl_head + partition - 1) % self.fdl.len()];
+            for ((t, x), h) in self.tail_spectrum.iter_mut().zip(x).zip(h) {
+                *t += x * h;
+            }
+        }
+
+        if self.first_partition > 0 {
+            self.time_buffer[..=block].copy_from_slice(&self.tail_spectrum);
+            self.inverse_to_time_buffer();
+            for (out, y) in self.tail_output.iter_mut().zip(&self.time_buffer[block..]) {
+                *out = y.re;
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    /// Reference time-domain convolution in f64
+    fn reference_convolution(input: &[f32], ir: &[f32]) -> Vec<f32> {
+        (0..input.len())
+            .map(|n| {
+                let taps = ir.len().min(n + 1);
+                (0..taps).map(|j| input[n - j] as f64 * ir[j] as f64).sum::<f64>() as f32
+            })
+            .collect()
+    }
+
+    /// Deterministic pseudo-random signal in [-1, 1)
+    fn noise(len: usize, seed: u32) -> Vec<f32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 8) as f32 / (1u32 << 23) as f32 - 1.0
+            })
+            .collect()
+    }
+
     #[test]
     fn test_convolver_creation() {
         let ir = vec![1.0, 0.5, 0.25];
-        let convolver = Convolver::new(ir, 512);
+        let convolver = Convolver::new(ir, 512, 1);
         assert!(convolver.is_ok());
     }
     
     #[test]
     fn test_invalid_partition_size() {
         let ir = vec![1.0];
-        let convolver = Convolver::new(ir, 500); // Not power of 2
+        let convolver = Convolver::new(ir, 500, 1); // Not power of 2
         assert!(convolver.is_err());
     }
     
     #[test]
     fn test_empty_ir() {
         let ir = vec![];
-        let convolver = Convolver::new(ir, 512);
+        let convolver = Convolver::new(ir, 512, 1);
         assert!(convolver.is_err());
     }
     
     #[test]
     fn test_basic_convolution() {
         let ir = vec![1.0, 0.5];
-        let mut convolver = Convolver::new(ir, 512).unwrap();
+        let mut convolver = Convolver::new(ir, 512, 1).unwrap();
         
         let input = vec![1.0, 0.0, 0.0, 0.0];
         let mut output = vec![0.0; 4];
         
         assert!(convolver.process(&input, &mut output).is_ok());
         // Impulse response: should get [1.0, 0.5, 0.0, 0.0]
-        assert_eq!(output[0], 1.0);
-        assert_eq!(output[1], 0.5);
+        assert!((output[0] - 1.0).abs() < 1e-6);
+        assert!((output[1] - 0.5).abs() < 1e-6);
+        assert!(output[2].abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_partition_count() {
+        let convolver = Convolver::new(vec![0.1; 1000], 256, 1).unwrap();
+        assert_eq!(convolver.num_partitions(), 4);
+        assert_eq!(convolver.ir_length(), 1000);
+    }
+
+    #[test]
+    fn test_matches_reference_across_irregular_blocks() {
+        let ir = noise(3000, 7);
+        let input = noise(10_000, 42);
+        let expected = reference_convolution(&input, &ir);
+
+        let mut convolver = Convolver::new(ir, 256, 1).unwrap();
+        let mut output = vec![0.0; input.len()];
+
+        // Block sizes that straddle partition boundaries in every way
+        let mut offset = 0;
+        for &size in [1, 37, 256, 300, 64, 1024, 5].iter().cycle() {
+            if offset >= input.len() {
+                break;
+            }
+            let end = (offset + size).min(input.len());
+            convolver.process(&input[offset..end], &mut output[offset..end]).unwrap();
+            offset = end;
+        }
+
+        for (n, (&y, &r)) in output.iter().zip(&expected).enumerate() {
+            assert!((y - r).abs() < 1e-3, "sample {}: {} vs {}", n, y, r);
+        }
+    }
+
+    #[test]
+    fn test_impulse_reproduces_long_ir() {
+        let ir = noise(5000, 3);
+        let mut convolver = Convolver::new(ir.clone(), 512, 1).unwrap();
+
+        let mut input = vec![0.0; 6000];
+        input[0] = 1.0;
+        let mut output = vec![0.0; 6000];
+        for (inp, out) in input.chunks(64).zip(output.chunks_mut(64)) {
+            convolver.process(inp, out).unwrap();
+        }
+
+        for (n, &tap) in ir.iter().enumerate() {
+            assert!((output[n] - tap).abs() < 1e-5, "tap {}", n);
+        }
+        assert!(output[5000..].iter().all(|s| s.abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_reset_clears_history() {
+        let ir = noise(600, 11);
+        let mut convolver = Convolver::new(ir, 128, 1).unwrap();
+
+        let mut output = vec![0.0; 512];
+        convolver.process(&noise(512, 5), &mut output).unwrap();
+        convolver.reset();
+
+        let silence = vec![0.0; 512];
+        convolver.process(&silence, &mut output).unwrap();
+        assert!(output.iter().all(|s| s.abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_update_ir_keeps_history() {
+        let first = noise(3000, 17);
+        let second = noise(3000, 23);
+        let input = noise(8192, 29);
+        let expected = reference_convolution(&input, &second);
+
+        let mut convolver = Convolver::new_non_uniform(first, 64, 512, 1).unwrap();
+        let mut output = vec![0.0; input.len()];
+        convolver.process(&input[..4096], &mut output[..4096]).unwrap();
+        convolver.update_ir(&second).unwrap();
+        convolver.process(&input[4096..], &mut output[4096..]).unwrap();
+
+        // Blocks already scheduled at the swap finish with the old IR
+        for n in 4096 + 512..input.len() {
+            assert!((output[n] - expected[n]).abs() < 1e-3, "sample {}: {} vs {}", n, output[n], expected[n]);
+        }
+    }
+
+    #[test]
+    fn test_swap_ir_matches_update_ir() {
+        let first = noise(3000, 17);
+        let second = noise(3000, 23);
+        let input = noise(8192, 29);
+
+        // Partitioned by a convolver the processing ones never see
+        let mut partitioner = Convolver::new_non_uniform(first.clone(), 64, 512, 1).unwrap();
+        let mut prepared = PartitionedIr::default();
+        assert!(partitioner.partition_ir(&second[..100], &mut prepared).is_err());
+        partitioner.partition_ir(&second, &mut prepared).unwrap();
+
+        let mut updated = Convolver::new_non_uniform(first.clone(), 64, 512, 2).unwrap();
+        let mut swapped = Convolver::new_non_uniform(first.clone(), 64, 512, 2).unwrap();
+        let mut expected = vec![0.0; input.len()];
+        let mut output = vec![0.0; input.len()];
+        updated.process(&input[..3002], &mut expected[..3002]).unwrap();
+        swapped.process(&input[..3002], &mut output[..3002]).unwrap();
+        updated.update_ir(&second).unwrap();
+        swapped.swap_ir(&mut prepared).unwrap();
+        updated.process(&input[3002..], &mut expected[3002..]).unwrap();
+        swapped.process(&input[3002..], &mut output[3002..]).unwrap();
+        assert_eq!(output, expected);
+        assert_eq!(prepared.ir, first);
+
+        let mut uniform = PartitionedIr::default();
+        Convolver::new(first, 512, 1).unwrap().partition_ir(&second, &mut uniform).unwrap();
+        assert!(swapped.swap_ir(&mut uniform).is_err());
+    }
+
+    #[test]
+    fn test_copy_state_continues_other_stream() {
+        let ir = noise(3000, 31);
+        let input = noise(6000, 37);
+        let expected = reference_convolution(&input, &ir);
+
+        let mut source = Convolver::new_non_uniform(ir.clone(), 64, 512, 2).unwrap();
+        let stereo: Vec<f32> = input[..2500].iter().flat_map(|&x| [x, -x]).collect();
+        source.process(&stereo, &mut vec![0.0; stereo.len()]).unwrap();
+
+        // Channel 0 carries on where the source left off
+        let mut convolver = Convolver::new_non_uniform(ir, 64, 512, 2).unwrap();
+        convolver.copy_state_from(&source).unwrap();
+        let mut output = vec![0.0; input.len() - 2500];
+        convolver.process_channel(0, &input[2500..], &mut output).unwrap();
+        for (n, (&a, &b)) in output.iter().zip(&expected[2500..]).enumerate() {
+            assert!((a - b).abs() < 1e-3, "sample {}: {} vs {}", n, a, b);
+        }
+
+        let other = Convolver::new_non_uniform(vec![1.0; 100], 64, 512, 2).unwrap();
+        assert!(convolver.copy_state_from(&other).is_err());
+        assert!(convolver.process_channel(2, &input, &mut vec![0.0; input.len()]).is_err());
+    }
+
+    #[test]
+    fn test_set_ir_repartitions() {
+        let mut convolver = Convolver::new(vec![1.0], 64, 1).unwrap();
+        convolver.set_ir(vec![0.0; 200]).unwrap();
+        assert_eq!(convolver.num_partitions(), 4);
+        assert!(convolver.set_ir(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_non_uniform_matches_reference() {
+        let ir = noise(6000, 21);
+        let input = noise(12_000, 8);
+        let expected = reference_convolution(&input, &ir);
+
+        let mut convolver = Convolver::new_non_uniform(ir, 64, 1024, 1).unwrap();
+        assert_eq!(convolver.latency_samples(), 0);
+
+        let mut output = vec![0.0; input.len()];
+        let mut offset = 0;
+        for &size in [64, 64, 17, 64, 200, 3].iter().cycle() {
+            if offset >= input.len() {
+                break;
+            }
+            let end = (offset + size).min(input.len());
+            convolver.process(&input[offset..end], &mut output[offset..end]).unwrap();
+            offset = end;
+        }
+
+        for (n, (&y, &r)) in output.iter().zip(&expected).enumerate() {
+            assert!((y - r).abs() < 1e-3, "sample {}: {} vs {}", n, y, r);
+        }
+    }
+
+    #[test]
+    fn test_non_uniform_impulse_is_zero_latency() {
+        let ir = noise(9000, 4);
+        let mut convolver = Convolver::new_non_uniform(ir.clone(), 128, 2048, 1).unwrap();
+
+        let mut input = vec![0.0; 10_240];
+        input[0] = 1.0;
+        let mut output = vec![0.0; input.len()];
+        for (inp, out) in input.chunks(64).zip(output.chunks_mut(64)) {
+            convolver.process(inp, out).unwrap();
+        }
+
+        for (n, &tap) in ir.iter().enumerate() {
+            assert!((output[n] - tap).abs() < 1e-5, "tap {}", n);
+        }
+        assert!(output[9000..].iter().all(|s| s.abs() < 1e-5));
+    }
+
+    #[test]
+    fn test_non_uniform_short_ir_uses_direct_form_only() {
+        let convolver = Convolver::new_non_uniform(vec![0.5; 100], 256, 4096, 1).unwrap();
+        assert_eq!(convolver.num_partitions(), 0);
+        assert_eq!(
+            convolver.scheme(),
+            PartitionScheme::NonUniform { direct_taps: 256 }
+        );
+    }
+
+    #[test]
+    fn test_non_uniform_invalid_direct_taps() {
+        assert!(Convolver::new_non_uniform(vec![1.0; 10], 100, 1024, 1).is_err());
+        assert!(Convolver::new_non_uniform(vec![1.0; 10], 0, 1024, 1).is_err());
+        assert!(Convolver::new_non_uniform(vec![1.0; 10], 2048, 1024, 1).is_err());
+    }
+
+    #[test]
+    fn test_non_uniform_matches_uniform() {
+        let ir = noise(4096, 99);
+        let input = noise(8192, 100);
+
+        let mut uniform = Convolver::new(ir.clone(), 512, 1).unwrap();
+        let mut non_uniform = Convolver::new_non_uniform(ir, 64, 512, 1).unwrap();
+
+        let mut a = vec![0.0; input.len()];
+        let mut b = vec![0.0; input.len()];
+        for ((inp, a), b) in input.chunks(64).zip(a.chunks_mut(64)).zip(b.chunks_mut(64)) {
+            uniform.process(inp, a).unwrap();
+            non_uniform.process(inp, b).unwrap();
+        }
+
+        for (x, y) in a.iter().zip(&b) {
+            assert!((x - y).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_non_uniform_spreads_large_segments() {
+        let first = noise(8192, 41);
+        let second = noise(8192, 43);
+        let input = noise(16_384, 47);
+        let expected = reference_convolution(&input, &second);
+
+        let mut convolver = Convolver::new_non_uniform(first, 64, 1024, 1).unwrap();
+        let mut output = vec![0.0; input.len()];
+        for (start, (inp, out)) in (0..).step_by(64).zip(input.chunks(64).zip(output.chunks_mut(64))) {
+            if start == 4032 {
+                convolver.update_ir(&second).unwrap();
+            }
+            let before: Vec<usize> = convolver.states[0].segments.iter().map(|s| s.job_step).collect();
+            convolver.process(inp, out).unwrap();
+
+            for (segment, before) in convolver.states[0].segments.iter().zip(before) {
+                if segment.first_partition < 2 {
+                    continue;
+                }
+                // Each call does its share of the job, never the whole block's work
+                let steps = segment.partitions + 2;
+                let share = (steps * 64).div_ceil(segment.block_size);
+                let done = if segment.block_fill == 0 { steps } else { segment.job_step };
+                if start != 4032 {
+                    // The update's call also redoes the restarted work
+                    assert!(done - before <= share + 1, "block {}", segment.block_size);
+                }
+                if start >= 4096 {
+                    assert_eq!(segment.job_step, (steps * segment.block_fill).div_ceil(segment.block_size));
+                }
+            }
+        }
+
+        // The new IR applies from the next block of every segment
+        for n in 4096..input.len() {
+            assert!((output[n] - expected[n]).abs() < 1e-3, "sample {}: {} vs {}", n, output[n], expected[n]);
+        }
+    }
+
+    #[test]
+    fn test_stereo_matches_independent_channels() {
+        let ir = noise(3000, 12);
+        let left = noise(6000, 1);
+        let right = noise(6000, 2);
+        let interleaved: Vec<f32> = left.iter().zip(&right).flat_map(|(&l, &r)| [l, r]).collect();
+
+        for scheme in [PartitionScheme::Uniform, PartitionScheme::NonUniform { direct_taps: 64 }] {
+            let mono: Vec<Vec<f32>> = [&left, &right]
+                .iter()
+                .map(|channel| {
+                    let mut convolver = Convolver::with_scheme(ir.clone(), 512, scheme, 1).unwrap();
+                    let mut output = vec![0.0; channel.len()];
+                    for (inp, out) in channel.chunks(100).zip(output.chunks_mut(100)) {
+                        convolver.process(inp, out).unwrap();
+                    }
+                    output
+                })
+                .collect();
+
+            let mut stereo = Convolver::with_scheme(ir.clone(), 512, scheme, 2).unwrap();
+            let mut output = vec![0.0; interleaved.len()];
+            for (inp, out) in interleaved.chunks(200).zip(output.chunks_mut(200)) {
+                assert_eq!(stereo.process(inp, out).unwrap(), 100);
+            }
+            let (l, r): (Vec<f32>, Vec<f32>) = output.chunks(2).map(|frame| (frame[0], frame[1])).unzip();
+            assert_eq!(l, mono[0]);
+            assert_eq!(r, mono[1]);
+
+            let mut planar = Convolver::with_scheme(ir.clone(), 512, scheme, 2).unwrap();
+            let (mut l, mut r) = (vec![0.0; left.len()], vec![0.0; left.len()]);
+            for start in (0..left.len()).step_by(100) {
+                let range = start..start + 100;
+                planar.process_planar(
+                    &[&left[range.clone()], &right[range.clone()]],
+                    &mut [&mut l[range.clone()], &mut r[range]],
+                ).unwrap();
+            }
+            assert_eq!([l, r], [mono[0].clone(), mono[1].clone()]);
+
+            assert!(stereo.process(&interleaved[..3], &mut output).is_err());
+        }
     }
 }
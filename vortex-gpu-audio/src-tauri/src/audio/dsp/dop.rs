@@ -0,0 +1,427 @@
+//! DSD over PCM (DoP) packing
+//!
+//! DoP carries a native DSD bitstream through PCM-only transports by placing
+//! 16 DSD bits in each 24-bit sample, with a marker byte that lets the DAC
+//! recognise the stream. Playing it as ordinary PCM yields low-level noise.
+
+use crate::error::VortexError;
+use super::dsd_processor::DsdRate;
+
+/// DoP marker bytes, alternating every frame (DoP open standard 1.1)
+pub const DOP_MARKERS: [u8; 2] = [0x05, 0xFA];
+
+/// DSD bits carried by each 24-bit DoP sample
+const BITS_PER_SAMPLE: u32 = 16;
+
+/// Consecutive valid frames required before PCM is treated as DoP
+pub const DOP_DETECT_FRAMES: usize = 32;
+
+/// Full-scale value of a 24-bit sample, for exact f32 transport
+const SCALE_24BIT: f32 = 8_388_608.0;
+
+/// PCM carrier rate for a DSD rate (DSD64: 176.4 kHz)
+pub fn carrier_rate(dsd_rate: DsdRate) -> u32 {
+    dsd_rate.sample_rate() / BITS_PER_SAMPLE
+}
+
+/// DSD rate carried by DoP at a PCM sample rate, if any
+pub fn dsd_rate_for_carrier(pcm_rate: u32) -> Option<DsdRate> {
+    DsdRate::from_sample_rate(pcm_rate * BITS_PER_SAMPLE)
+        .filter(|rate| matches!(rate, DsdRate::Dsd64 | DsdRate::Dsd128 | DsdRate::Dsd256))
+}
+
+/// Convert a 24-bit sample to f32 exactly (every 24-bit value is representable)
+pub fn sample_to_f32(sample: i32) -> f32 {
+    sample as f32 / SCALE_24BIT
+}
+
+/// Inverse of `sample_to_f32`
+pub fn sample_from_f32(sample: f32) -> i32 {
+    (sample * SCALE_24BIT).round() as i32
+}
+
+/// Pack one DoP sample: marker in the top byte, oldest DSD bit in bit 15
+fn pack(marker: u8, first: u8, second: u8) -> i32 {
+    // Input bytes are LSB-first; DoP carries bits MSB-first
+    let word = (u32::from(marker) << 16)
+        | (u32::from(first.reverse_bits()) << 8)
+        | u32::from(second.reverse_bits());
+    // Sign-extend from 24 bits
+    ((word << 8) as i32) >> 8
+}
+
+fn marker_of(sample: i32) -> u8 {
+    (sample >> 16) as u8
+}
+
+/// DSD over PCM packer
+///
+/// Packs per-channel DSD bitstreams (LSB-first bytes, as yielded by
+/// `DsdReader`) into interleaved 24-bit PCM frames at `carrier_rate`. Each
+/// sample carries 16 DSD bits under a marker byte that alternates between 0x05
+/// and 0xFA on every frame. The marker phase and any odd trailing byte carry
+/// over between calls, so a stream can be encoded in blocks of any size.
+pub struct DopEncoder {
+    dsd_rate: DsdRate,
+    channels: usize,
+    // Index into DOP_MARKERS for the next frame
+    next_marker: usize,
+    // Odd byte per channel left over from the previous call, when
+    // `has_pending`; allocated once so encoding does not allocate
+    pending: Vec<u8>,
+    has_pending: bool,
+}
+
+impl DopEncoder {
+    /// Create an encoder for DSD64, DSD128 or DSD256
+    pub fn new(dsd_rate: DsdRate, channels: u16) -> Result<Self, VortexError> {
+        if dsd_rate_for_carrier(carrier_rate(dsd_rate)).is_none() {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "DoP carries DSD64 to DSD256, not {:?}",
+                dsd_rate
+            )).into());
+        }
+        if channels == 0 {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "DoP needs at least one channel".to_string()
+            ).into());
+        }
+
+        Ok(Self {
+            dsd_rate,
+            channels: channels as usize,
+            next_marker: 0,
+            pending: vec![0; channels as usize],
+            has_pending: false,
+        })
+    }
+
+    /// DSD rate being packed
+    pub fn dsd_rate(&self) -> DsdRate {
+        self.dsd_rate
+    }
+
+    /// PCM sample rate of the DoP stream
+    pub fn carrier_rate(&self) -> u32 {
+        carrier_rate(self.dsd_rate)
+    }
+
+    /// Frames the next `encode` call produces for `bytes_per_channel` input bytes
+    pub fn output_frames(&self, bytes_per_channel: usize) -> usize {
+        (bytes_per_channel + usize::from(self.has_pending)) / 2
+    }
+
+    /// Pack one block of per-channel bitstreams into interleaved 24-bit samples
+    ///
+    /// All channels must supply the same number of bytes. `output` must hold
+    /// `output_frames(bytes) * channels` samples. Returns the frames written.
+    pub fn encode<B: AsRef<[u8]>>(&mut self, input: &[B], output: &mut [i32]) -> Result<usize, VortexError> {
+        if input.len() != self.channels {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "Expected {} channels, got {}",
+                self.channels,
+                input.len()
+            )).into());
+        }
+        let bytes = input[0].as_ref().len();
+        if input.iter().any(|ch| ch.as_ref().len() != bytes) {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "All channels must supply the same number of bytes".to_string()
+            ).into());
+        }
+        let frames = self.output_frames(bytes);
+        if output.len() < frames * self.channels {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "Output holds {} samples, {} needed",
+                output.len(),
+                frames * self.channels
+            )).into());
+        }
+
+        // Byte `index` of the combined pending + input stream for a channel
+        let offset = usize::from(self.has_pending);
+        let pending = &self.pending;
+        let byte_at = |ch: usize, index: usize| match index.checked_sub(offset) {
+            None => pending[ch],
+            Some(i) => input[ch].as_ref()[i],
+        };
+
+        for (frame, samples) in output.chunks_exact_mut(self.channels).take(frames).enumerate() {
+            let marker = DOP_MARKERS[self.next_marker];
+            for (ch, sample) in samples.iter_mut().enumerate() {
+                *sample = pack(marker, byte_at(ch, 2 * frame), byte_at(ch, 2 * frame + 1));
+            }
+            self.next_marker ^= 1;
+        }
+
+        // With no new bytes, an odd byte still pending stays pending
+        self.has_pending = !(bytes + offset).is_multiple_of(2);
+        if self.has_pending && bytes > 0 {
+            for (pending, channel) in self.pending.iter_mut().zip(input) {
+                *pending = channel.as_ref()[bytes - 1];
+            }
+        }
+
+        Ok(frames)
+    }
+
+    /// Like `encode`, writing samples as f32 scaled so 24-bit values stay exact
+    pub fn encode_f32<B: AsRef<[u8]>>(&mut self, input: &[B], output: &mut [f32]) -> Result<usize, VortexError> {
+        let mut samples = vec![0i32; output.len()];
+        let frames = self.encode(input, &mut samples)?;
+        for (out, &sample) in output.iter_mut().zip(&samples[..frames * self.channels]) {
+            *out = sample_to_f32(sample);
+        }
+        Ok(frames)
+    }
+
+    /// Restart the marker sequence and drop any pending byte
+    pub fn reset(&mut self) {
+        self.next_marker = 0;
+        self.has_pending = false;
+    }
+}
+
+/// DSD over PCM unpacker
+///
+/// Recovers per-channel LSB-first DSD bytes from interleaved 24-bit PCM.
+/// Marker continuity is tracked across calls; a frame that breaks the
+/// alternation (or disagrees between channels) means the stream is not DoP.
+pub struct DopDecoder {
+    channels: usize,
+    // Marker the next frame must carry, once locked onto a stream
+    expected_marker: Option<u8>,
+}
+
+impl DopDecoder {
+    /// Create a decoder for `channels` interleaved channels
+    pub fn new(channels: u16) -> Result<Self, VortexError> {
+        if channels == 0 {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "DoP needs at least one channel".to_string()
+            ).into());
+        }
+
+        Ok(Self {
+            channels: channels as usize,
+            expected_marker: None,
+        })
+    }
+
+    /// Check whether interleaved 24-bit PCM carries DoP
+    ///
+    /// Requires `DOP_DETECT_FRAMES` consecutive frames whose markers alternate
+    /// and agree across channels.
+    pub fn detect(samples: &[i32], channels: u16) -> bool {
+        let channels = channels as usize;
+        if channels == 0 || samples.len() < DOP_DETECT_FRAMES * channels {
+            return false;
+        }
+
+        let mut expected = None;
+        samples
+            .chunks_exact(channels)
+            .take(DOP_DETECT_FRAMES)
+            .all(|frame| Self::frame_marker(frame, &mut expected).is_some())
+    }
+
+    /// Like `detect`, for samples carried as f32
+    pub fn detect_f32(samples: &[f32], channels: u16) -> bool {
+        let len = samples.len().min(DOP_DETECT_FRAMES * channels as usize);
+        let converted: Vec<i32> = samples[..len].iter().map(|&s| sample_from_f32(s)).collect();
+        Self::detect(&converted, channels)
+    }
+
+    /// Validate one frame against the expected marker and advance it
+    fn frame_marker(frame: &[i32], expected: &mut Option<u8>) -> Option<u8> {
+        let marker = marker_of(frame[0]);
+        let valid = DOP_MARKERS.contains(&marker)
+            && expected.is_none_or(|e| e == marker)
+            && frame.iter().all(|&s| marker_of(s) == marker);
+        if !valid {
+            return None;
+        }
+
+        *expected = Some(if marker == DOP_MARKERS[0] { DOP_MARKERS[1] } else { DOP_MARKERS[0] });
+        Some(marker)
+    }
+
+    /// Unpack interleaved DoP samples, appending two bytes per frame to each channel
+    ///
+    /// Returns the frames decoded. Fails without consuming anything if any frame
+    /// is not valid DoP.
+    pub fn decode(&mut self, samples: &[i32], output: &mut [Vec<u8>]) -> Result<usize, VortexError> {
+        if output.len() != self.channels || !samples.len().is_multiple_of(self.channels) {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "Expected whole frames of {} channels",
+                self.channels
+            )).into());
+        }
+
+        let mut expected = self.expected_marker;
+        for (i, frame) in samples.chunks_exact(self.channels).enumerate() {
+            if Self::frame_marker(frame, &mut expected).is_none() {
+                return Err(crate::error::AudioError::InvalidParameter(format!(
+                    "Frame {} is not valid DoP",
+                    i
+                )).into());
+            }
+        }
+        self.expected_marker = expected;
+
+        for frame in samples.chunks_exact(self.channels) {
+            for (&sample, bytes) in frame.iter().zip(output.iter_mut()) {
+                bytes.push(((sample >> 8) as u8).reverse_bits());
+                bytes.push((sample as u8).reverse_bits());
+            }
+        }
+
+        Ok(samples.len() / self.channels)
+    }
+
+    /// Like `decode`, for samples carried as f32
+    pub fn decode_f32(&mut self, samples: &[f32], output: &mut [Vec<u8>]) -> Result<usize, VortexError> {
+        let converted: Vec<i32> = samples.iter().map(|&s| sample_from_f32(s)).collect();
+        self.decode(&converted, output)
+    }
+
+    /// Forget the marker phase, e.g. after a stream discontinuity
+    pub fn reset(&mut self) {
+        self.expected_marker = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Per-channel pseudo-random LSB-first bitstreams
+    fn bitstream(channels: usize, bytes: usize, seed: u32) -> Vec<Vec<u8>> {
+        let mut state = seed;
+        (0..channels)
+            .map(|_| {
+                (0..bytes)
+                    .map(|_| {
+                        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                        (state >> 16) as u8
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_carrier_rates() {
+        assert_eq!(carrier_rate(DsdRate::Dsd64), 176_400);
+        assert_eq!(carrier_rate(DsdRate::Dsd128), 352_800);
+        assert_eq!(carrier_rate(DsdRate::Dsd256), 705_600);
+        assert_eq!(dsd_rate_for_carrier(352_800), Some(DsdRate::Dsd128));
+        assert_eq!(dsd_rate_for_carrier(1_411_200), None);
+        assert_eq!(dsd_rate_for_carrier(48_000), None);
+
+        assert!(DopEncoder::new(DsdRate::Dsd512, 2).is_err());
+        assert_eq!(DopEncoder::new(DsdRate::Dsd256, 2).unwrap().carrier_rate(), 705_600);
+    }
+
+    #[test]
+    fn test_frame_layout() {
+        let mut encoder = DopEncoder::new(DsdRate::Dsd64, 2).unwrap();
+        // Left: oldest bit set only; right: newest bit of the second byte set only
+        let input = [vec![0x01, 0x00, 0x01, 0x00], vec![0x00, 0x80, 0x00, 0x80]];
+        let mut output = [0i32; 4];
+        assert_eq!(encoder.encode(&input, &mut output).unwrap(), 2);
+
+        assert_eq!(output[0] & 0xFF_FFFF, 0x05_8000);
+        assert_eq!(output[1] & 0xFF_FFFF, 0x05_0001);
+        assert_eq!(output[2] & 0xFF_FFFF, 0xFA_8000);
+        assert_eq!(output[3] & 0xFF_FFFF, 0xFA_0001);
+        // 0xFA frames are negative 24-bit values
+        assert!(output[2] < 0);
+    }
+
+    #[test]
+    fn test_round_trip_is_bit_exact() {
+        for channels in [1usize, 2, 6] {
+            let input = bitstream(channels, 10_001, channels as u32);
+            let mut encoder = DopEncoder::new(DsdRate::Dsd128, channels as u16).unwrap();
+            let mut decoder = DopDecoder::new(channels as u16).unwrap();
+            let mut recovered = vec![Vec::new(); channels];
+
+            // Odd block sizes exercise the pending-byte carry
+            let mut offset = 0;
+            for size in [1usize, 3, 4096, 77, 2].iter().cycle() {
+                let end = (offset + size).min(10_001);
+                let block: Vec<&[u8]> = input.iter().map(|ch| &ch[offset..end]).collect();
+                let mut samples = vec![0i32; encoder.output_frames(end - offset) * channels];
+                let frames = encoder.encode(&block, &mut samples).unwrap();
+                assert_eq!(decoder.decode(&samples, &mut recovered).unwrap(), frames);
+                offset = end;
+                if offset == 10_001 {
+                    break;
+                }
+            }
+
+            // The odd final byte waits for its partner
+            for (original, recovered) in input.iter().zip(&recovered) {
+                assert_eq!(&original[..10_000], &recovered[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_f32_transport_is_bit_exact() {
+        let input = bitstream(2, 4096, 7);
+        let mut encoder = DopEncoder::new(DsdRate::Dsd256, 2).unwrap();
+        let mut pcm = vec![0.0f32; 4096];
+        assert_eq!(encoder.encode_f32(&input, &mut pcm).unwrap(), 2048);
+        assert!(pcm.iter().all(|s| (-1.0..1.0).contains(s)));
+
+        assert!(DopDecoder::detect_f32(&pcm, 2));
+        let mut decoder = DopDecoder::new(2).unwrap();
+        let mut recovered = vec![Vec::new(); 2];
+        decoder.decode_f32(&pcm, &mut recovered).unwrap();
+        assert_eq!(recovered, input);
+    }
+
+    #[test]
+    fn test_detection() {
+        let input = bitstream(2, 256, 3);
+        let mut encoder = DopEncoder::new(DsdRate::Dsd64, 2).unwrap();
+        let mut samples = vec![0i32; 256];
+        encoder.encode(&input, &mut samples).unwrap();
+        assert!(DopDecoder::detect(&samples, 2));
+
+        // Too short to decide
+        assert!(!DopDecoder::detect(&samples[..2 * DOP_DETECT_FRAMES - 2], 2));
+
+        // Ordinary PCM: silence, a tone, and a repeated marker without alternation
+        assert!(!DopDecoder::detect(&[0; 256], 2));
+        let tone: Vec<i32> = (0..256).map(|i| ((i as f32 * 0.1).sin() * 4_000_000.0) as i32).collect();
+        assert!(!DopDecoder::detect(&tone, 2));
+        assert!(!DopDecoder::detect(&[0x05_0000; 256], 2));
+
+        // Markers must agree across channels
+        let mut skewed = samples.clone();
+        skewed[11] = pack(DOP_MARKERS[0], 0, 0);
+        assert!(!DopDecoder::detect(&skewed, 2));
+    }
+
+    #[test]
+    fn test_decoder_rejects_broken_alternation() {
+        let input = bitstream(1, 64, 9);
+        let mut encoder = DopEncoder::new(DsdRate::Dsd64, 1).unwrap();
+        let mut samples = vec![0i32; 32];
+        encoder.encode(&input, &mut samples).unwrap();
+
+        let mut decoder = DopDecoder::new(1).unwrap();
+        let mut output = vec![Vec::new()];
+        decoder.decode(&samples[..5], &mut output).unwrap();
+
+        // Skipping a frame breaks continuity with the previous call
+        assert!(decoder.decode(&samples[6..], &mut output).is_err());
+        assert_eq!(output[0].len(), 10, "failed decode must not append");
+
+        decoder.reset();
+        assert_eq!(decoder.decode(&samples[6..], &mut output).unwrap(), 26);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::VortexError;
+use super::{channels, fir_design};
 
 /// DSD sample rates
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -20,96 +21,699 @@ impl DsdRate {
             DsdRate::Dsd1024 => 45158400,
         }
     }
-    
+
+    /// Map a 1-bit sample rate in Hz onto the matching DSD rate
+    pub fn from_sample_rate(sample_rate: u32) -> Option<Self> {
+        [DsdRate::Dsd64, DsdRate::Dsd128, DsdRate::Dsd256, DsdRate::Dsd512, DsdRate::Dsd1024]
+            .into_iter()
+            .find(|rate| rate.sample_rate() == sample_rate)
+    }
+
     pub fn decimation_factor(&self, target_rate: u32) -> u32 {
         self.sample_rate() / target_rate
     }
 }
 
+/// Decimation filter quality profiles
+///
+/// Guaranteed figures for the audio band (DC to 20 kHz, or 0.4535 x the
+/// output rate if lower), verified by the spectral tests below:
+///
+/// | Profile       | Passband ripple | Alias rejection |
+/// |---------------|-----------------|-----------------|
+/// | `Fast`        | < 0.01 dB       | >= 80 dB        |
+/// | `Balanced`    | < 0.001 dB      | >= 100 dB       |
+/// | `HighQuality` | < 0.001 dB      | >= 120 dB       |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecimationProfile {
+    Fast,
+    #[default]
+    Balanced,
+    HighQuality,
+}
+
+impl DecimationProfile {
+    /// Stopband attenuation every stage is designed for
+    pub fn stopband_attenuation_db(&self) -> f64 {
+        match self {
+            DecimationProfile::Fast => 80.0,
+            DecimationProfile::Balanced => 100.0,
+            DecimationProfile::HighQuality => 120.0,
+        }
+    }
+}
+
+/// Headroom over the profile attenuation; Kaiser length estimates run slightly short
+const DESIGN_MARGIN_DB: f64 = 6.0;
+
+/// Upper edge of the protected audio band
+pub(super) const PASSBAND_HZ: f64 = 20_000.0;
+
+/// Passband edge relative to the output rate when 20 kHz does not fit (44.1 kHz: 20 kHz)
+pub(super) const MAX_PASSBAND_RATIO: f64 = 0.4535;
+
+/// Decimation performed by the lookup-table stage (one output per input byte)
+const LUT_DECIMATION: u32 = 8;
+
+/// DSD idle pattern: equal ones and zeros, i.e. digital silence
+const DSD_SILENCE: u8 = 0x69;
+
+/// First stage: FIR on the raw bitstream, decimating by 8
+///
+/// Every input byte holds 8 one-bit samples of +/-1, so each byte of filter
+/// history contributes one of 256 precomputed partial sums. A filter of
+/// `8 * K` taps costs K table lookups per output sample.
+#[derive(Clone)]
+struct LutFirStage {
+    taps: Vec<f64>,
+    // tables[k][byte]: contribution of the byte k bytes before the newest
+    tables: Vec<[f32; 256]>,
+    // K-1 previous bytes followed by the bytes of the current call
+    history: Vec<u8>,
+}
+
+impl LutFirStage {
+    fn new(taps: Vec<f64>) -> Self {
+        let bytes = taps.len() / 8;
+        let tables = (0..bytes)
+            .map(|k| {
+                let mut table = [0.0f32; 256];
+                for (byte, entry) in table.iter_mut().enumerate() {
+                    // Bit 0 is the oldest sample of the byte, bit 7 the newest
+                    *entry = (0..8)
+                        .map(|bit| {
+                            let value = if (byte >> bit) & 1 == 1 { 1.0 } else { -1.0 };
+                            taps[8 * k + 7 - bit] * value
+                        })
+                        .sum::<f64>() as f32;
+                }
+                table
+            })
+            .collect();
+
+        Self {
+            taps,
+            tables,
+            history: vec![DSD_SILENCE; bytes - 1],
+        }
+    }
+
+    fn process(&mut self, input: &[u8], output: &mut Vec<f32>) {
+        let bytes = self.tables.len();
+        self.history.extend_from_slice(input);
+
+        for window in self.history.windows(bytes) {
+            let sample = self
+                .tables
+                .iter()
+                .zip(window.iter().rev())
+                .map(|(table, &byte)| table[byte as usize])
+                .sum::<f32>();
+            output.push(sample);
+        }
+
+        self.history.drain(..input.len());
+    }
+
+    fn reset(&mut self) {
+        self.history.clear();
+        self.history.resize(self.tables.len() - 1, DSD_SILENCE);
+    }
+}
+
+/// Half-band FIR decimating by 2
+///
+/// Every other tap of a half-band filter is zero and the centre tap is 0.5,
+/// so only the odd-offset taps need multiplies, and only for the outputs kept.
+#[derive(Clone)]
+struct HalfBandStage {
+    taps: Vec<f64>,
+    // Non-zero taps at offsets 1, 3, 5, ... from the centre
+    side_taps: Vec<f32>,
+    // Unconsumed input; the next output window starts at index 0
+    buffer: Vec<f32>,
+}
+
+impl HalfBandStage {
+    fn new(taps: Vec<f64>) -> Self {
+        let center = taps.len() / 2;
+        let side_taps = taps[center + 1..].iter().step_by(2).map(|&t| t as f32).collect();
+
+        Self {
+            buffer: vec![0.0; taps.len() - 1],
+            taps,
+            side_taps,
+        }
+    }
+
+    fn span(&self) -> usize {
+        self.taps.len() - 1
+    }
+
+    fn output_len(&self, input_len: usize) -> usize {
+        (self.buffer.len() + input_len).saturating_sub(self.span()).div_ceil(2)
+    }
+
+    fn process(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let span = self.span();
+        let center = span / 2;
+        self.buffer.extend_from_slice(input);
+
+        let mut start = 0;
+        while start + span < self.buffer.len() {
+            let c = start + center;
+            let mut sample = 0.5 * self.buffer[c];
+            for (j, &tap) in self.side_taps.iter().enumerate() {
+                let offset = 2 * j + 1;
+                sample += tap * (self.buffer[c - offset] + self.buffer[c + offset]);
+            }
+            output.push(sample);
+            start += 2;
+        }
+
+        self.buffer.drain(..start);
+    }
+
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.buffer.resize(self.span(), 0.0);
+    }
+}
+
+/// Design the lookup-table stage for a DSD rate
+fn design_lut_stage(dsd_rate: f64, passband_hz: f64, attenuation_db: f64) -> Vec<f64> {
+    // Everything that folds onto the audio band at rate / 8 must be rejected
+    let passband = passband_hz / dsd_rate;
+    let stopband = 1.0 / LUT_DECIMATION as f64 - passband;
+
+    let length = fir_design::kaiser_length(attenuation_db, stopband - passband).next_multiple_of(8);
+    fir_design::lowpass(length, (passband + stopband) / 2.0, fir_design::kaiser_beta(attenuation_db))
+}
+
+/// Design a half-band stage running at `input_rate`, decimating from it or interpolating to it
+pub(super) fn design_half_band(input_rate: f64, passband_hz: f64, attenuation_db: f64) -> Vec<f64> {
+    // Half-band filters are symmetric about input_rate / 4
+    let passband = passband_hz / input_rate;
+    let transition = 0.5 - 2.0 * passband;
+
+    // Length 4m + 3 keeps the outermost taps at odd offsets (non-zero)
+    let length = fir_design::kaiser_length(attenuation_db, transition);
+    let length = (length.saturating_sub(3)).next_multiple_of(4) + 3;
+    let mut taps = fir_design::lowpass(length, 0.25, fir_design::kaiser_beta(attenuation_db));
+
+    // Pin the structural zeros and renormalize the odd taps for unity DC gain
+    let center = length / 2;
+    let mut side_sum = 0.0;
+    for (n, tap) in taps.iter_mut().enumerate() {
+        let offset = n.abs_diff(center);
+        if offset == 0 {
+            *tap = 0.5;
+        } else if offset % 2 == 0 {
+            *tap = 0.0;
+        } else {
+            side_sum += *tap;
+        }
+    }
+    for (n, tap) in taps.iter_mut().enumerate() {
+        if n.abs_diff(center) % 2 == 1 {
+            *tap *= 0.5 / side_sum;
+        }
+    }
+    taps
+}
+
 /// DSD to PCM processor
+///
+/// Multistage decimator: a lookup-table FIR takes the 1-bit stream down by 8,
+/// then half-band stages halve the rate until the target is reached. Each
+/// stage only protects the audio band from aliasing, which keeps the filters
+/// short. Filter state persists across `process` calls, so a bitstream can be
+/// fed in blocks of any size.
+///
+/// Input bytes hold 8 samples each, oldest in the least significant bit (the
+/// DSF layout; `DsdReader` normalizes DSDIFF to it). Bits map to +/-1.0.
+/// Bitstreams are per channel by nature, so input is always one byte slice
+/// per channel; the PCM output is interleaved for `process` and planar for
+/// `process_planar` (see `channels`). Each channel has its own cascade state.
 pub struct DsdProcessor {
     dsd_rate: DsdRate,
     target_rate: u32,
     decimation_factor: u32,
-    // FIR filter state (simplified for skeleton)
-    filter_state: Vec<f32>,
+    profile: DecimationProfile,
+    cascades: Vec<Cascade>,
+    // Ping-pong buffers between stages, reused across calls and channels
+    stage_input: Vec<f32>,
+    stage_output: Vec<f32>,
 }
 
 impl DsdProcessor {
-    /// Create a new DSD processor
-    pub fn new(dsd_rate: DsdRate, target_rate: u32) -> Result<Self, VortexError> {
+    /// Create a new DSD processor with the default filter profile
+    pub fn new(dsd_rate: DsdRate, target_rate: u32, channels: u16) -> Result<Self, VortexError> {
+        Self::with_profile(dsd_rate, target_rate, DecimationProfile::default(), channels)
+    }
+
+    /// Create a new DSD processor with an explicit filter profile
+    ///
+    /// `target_rate` must be the DSD rate divided by 8 times a power of two
+    /// (e.g. 44.1, 88.2, 176.4 or 352.8 kHz from DSD64).
+    pub fn with_profile(
+        dsd_rate: DsdRate,
+        target_rate: u32,
+        profile: DecimationProfile,
+        channels: u16,
+    ) -> Result<Self, VortexError> {
+        let channels = channels::validate_channels(channels)?;
+        let input_rate = dsd_rate.sample_rate();
+        let valid = target_rate > 0
+            && input_rate.is_multiple_of(target_rate)
+            && (input_rate / target_rate).is_multiple_of(LUT_DECIMATION)
+            && (input_rate / target_rate / LUT_DECIMATION).is_power_of_two();
+        if !valid {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "DSD rate {} Hz cannot be decimated to {} Hz by 8 x 2^n",
+                input_rate, target_rate
+            )).into());
+        }
+
         let decimation_factor = dsd_rate.decimation_factor(target_rate);
-        
+        let passband_hz = PASSBAND_HZ.min(MAX_PASSBAND_RATIO * target_rate as f64);
+        let attenuation = profile.stopband_attenuation_db() + DESIGN_MARGIN_DB;
+
+        let lut_stage = LutFirStage::new(design_lut_stage(input_rate as f64, passband_hz, attenuation));
+
+        let mut half_band_stages = Vec::new();
+        let mut rate = input_rate / LUT_DECIMATION;
+        while rate > target_rate {
+            half_band_stages.push(HalfBandStage::new(design_half_band(rate as f64, passband_hz, attenuation)));
+            rate /= 2;
+        }
+
+        let cascade = Cascade { lut_stage, half_band_stages };
         Ok(Self {
             dsd_rate,
             target_rate,
             decimation_factor,
-            filter_state: vec![0.0; 256], // Simplified filter state
+            profile,
+            cascades: vec![cascade; channels],
+            stage_input: Vec::new(),
+            stage_output: Vec::new(),
         })
     }
-    
-    /// Process DSD bitstream to PCM
-    pub fn process(&mut self, dsd_input: &[u8], pcm_output: &mut [f32]) -> Result<usize, VortexError> {
-        // Simplified implementation - full implementation would use FIR decimation
-        let samples_out = dsd_input.len() * 8 / self.decimation_factor as usize;
-        let samples_out = samples_out.min(pcm_output.len());
-        
-        for i in 0..samples_out {
-            // Simplified conversion: accumulate bits
-            let bit_offset = i * self.decimation_factor as usize;
-            let mut accumulator = 0.0;
-            
-            for j in 0..self.decimation_factor as usize {
-                let bit_index = (bit_offset + j) / 8;
-                let bit_position = (bit_offset + j) % 8;
-                
-                if bit_index < dsd_input.len() {
-                    let bit = (dsd_input[bit_index] >> bit_position) & 1;
-                    accumulator += if bit == 1 { 1.0 } else { -1.0 };
-                }
+
+    /// Filter profile in use
+    pub fn profile(&self) -> DecimationProfile {
+        self.profile
+    }
+
+    /// Number of channels decimated
+    pub fn channels(&self) -> u16 {
+        self.cascades.len() as u16
+    }
+
+    /// Number of PCM frames the next `process` call produces for `input_bytes` per channel
+    pub fn output_len(&self, input_bytes: usize) -> usize {
+        self.cascades[0]
+            .half_band_stages
+            .iter()
+            .fold(input_bytes, |len, stage| stage.output_len(len))
+    }
+
+    /// Group delay of the filter cascade in output samples
+    pub fn latency_samples(&self) -> f64 {
+        let cascade = &self.cascades[0];
+        let mut delay = (cascade.lut_stage.taps.len() - 1) as f64 / 2.0 / self.decimation_factor as f64;
+        let mut stage_factor = LUT_DECIMATION as f64;
+        for stage in &cascade.half_band_stages {
+            delay += (stage.taps.len() - 1) as f64 / 2.0 * stage_factor / self.decimation_factor as f64;
+            stage_factor *= 2.0;
+        }
+        delay
+    }
+
+    /// Decimate per-channel DSD bitstreams to interleaved PCM
+    ///
+    /// `dsd_input` holds one equally long byte slice per channel.
+    /// `pcm_output` must hold at least `output_len(bytes) * channels` samples.
+    /// Returns the number of frames written.
+    pub fn process<B: AsRef<[u8]>>(&mut self, dsd_input: &[B], pcm_output: &mut [f32]) -> Result<usize, VortexError> {
+        let channel_count = self.cascades.len();
+        let frames = self.output_len(channels::planar_frames(dsd_input, channel_count)?);
+        if frames * channel_count > pcm_output.len() {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "PCM output holds {} samples, {} needed",
+                pcm_output.len(),
+                frames * channel_count
+            )).into());
+        }
+
+        for (channel, (cascade, input)) in self.cascades.iter_mut().zip(dsd_input).enumerate() {
+            cascade.process(input.as_ref(), &mut self.stage_input, &mut self.stage_output);
+            debug_assert_eq!(self.stage_input.len(), frames);
+            for (out, &sample) in pcm_output[channel..].iter_mut().step_by(channel_count).zip(&self.stage_input) {
+                *out = sample;
             }
-            
-            pcm_output[i] = accumulator / self.decimation_factor as f32;
         }
-        
-        Ok(samples_out)
+        Ok(frames)
+    }
+
+    /// Decimate per-channel DSD bitstreams to planar PCM
+    ///
+    /// Every output slice must hold at least `output_len(bytes)` samples.
+    pub fn process_planar<B: AsRef<[u8]>>(
+        &mut self,
+        dsd_input: &[B],
+        pcm_output: &mut [&mut [f32]],
+    ) -> Result<usize, VortexError> {
+        let channel_count = self.cascades.len();
+        let frames = self.output_len(channels::planar_frames(dsd_input, channel_count)?);
+        let capacity = channels::planar_frames(pcm_output, channel_count)?;
+        if frames > capacity {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "PCM output holds {} samples per channel, {} needed",
+                capacity, frames
+            )).into());
+        }
+
+        for ((cascade, input), output) in self.cascades.iter_mut().zip(dsd_input).zip(pcm_output.iter_mut()) {
+            cascade.process(input.as_ref(), &mut self.stage_input, &mut self.stage_output);
+            output[..frames].copy_from_slice(&self.stage_input);
+        }
+        Ok(frames)
     }
-    
+
     /// Reset processor state
     pub fn reset(&mut self) {
-        self.filter_state.fill(0.0);
+        for cascade in &mut self.cascades {
+            cascade.lut_stage.reset();
+            cascade.half_band_stages.iter_mut().for_each(HalfBandStage::reset);
+        }
+    }
+}
+
+/// Filter state of one channel
+#[derive(Clone)]
+struct Cascade {
+    lut_stage: LutFirStage,
+    half_band_stages: Vec<HalfBandStage>,
+}
+
+impl Cascade {
+    /// Run `input` through every stage; the PCM ends up in `stage_input`
+    fn process(&mut self, input: &[u8], stage_input: &mut Vec<f32>, stage_output: &mut Vec<f32>) {
+        stage_input.clear();
+        self.lut_stage.process(input, stage_input);
+
+        for stage in &mut self.half_band_stages {
+            stage_output.clear();
+            stage.process(stage_input, stage_output);
+            std::mem::swap(stage_input, stage_output);
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    /// Response of the whole cascade at `frequency` Hz, referred to the DSD rate
+    ///
+    /// By the noble identities the cascade equals one filter
+    /// H1(f) H2(8f) H3(16f) ... at the DSD rate followed by decimation.
+    fn cascade_magnitude(processor: &DsdProcessor, frequency: f64) -> f64 {
+        let input_rate = processor.dsd_rate.sample_rate() as f64;
+        let cascade = &processor.cascades[0];
+        let mut magnitude = fir_design::magnitude(&cascade.lut_stage.taps, frequency / input_rate);
+        let mut rate = input_rate / LUT_DECIMATION as f64;
+        for stage in &cascade.half_band_stages {
+            magnitude *= fir_design::magnitude(&stage.taps, frequency / rate);
+            rate /= 2.0;
+        }
+        magnitude
+    }
+
+    /// Second-order sigma-delta modulator producing LSB-first bytes
+    fn modulate(signal: impl Fn(usize) -> f64, bytes: usize) -> Vec<u8> {
+        let (mut i1, mut i2) = (0.0f64, 0.0f64);
+        let mut feedback = 0.0;
+        (0..bytes)
+            .map(|byte| {
+                let mut packed = 0u8;
+                for bit in 0..8 {
+                    i1 += signal(byte * 8 + bit) - feedback;
+                    i2 += i1 - feedback;
+                    feedback = if i2 >= 0.0 { 1.0 } else { -1.0 };
+                    if feedback > 0.0 {
+                        packed |= 1 << bit;
+                    }
+                }
+                packed
+            })
+            .collect()
+    }
+
+    /// Amplitude of a sinusoid at `frequency` Hz in `signal` (Hann-windowed DFT bin)
+    fn tone_amplitude(signal: &[f32], frequency: f64, sample_rate: f64) -> f64 {
+        let n = signal.len() as f64;
+        let (mut re, mut im) = (0.0, 0.0);
+        for (i, &x) in signal.iter().enumerate() {
+            let window = 0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / n).cos();
+            let phase = 2.0 * std::f64::consts::PI * frequency * i as f64 / sample_rate;
+            re += x as f64 * window * phase.cos();
+            im -= x as f64 * window * phase.sin();
+        }
+        4.0 * (re * re + im * im).sqrt() / n
+    }
+
     #[test]
     fn test_dsd_rate() {
         assert_eq!(DsdRate::Dsd64.sample_rate(), 2822400);
         assert_eq!(DsdRate::Dsd128.sample_rate(), 5644800);
     }
-    
+
+    #[test]
+    fn test_dsd_rate_from_sample_rate() {
+        assert_eq!(DsdRate::from_sample_rate(2822400), Some(DsdRate::Dsd64));
+        assert_eq!(DsdRate::from_sample_rate(45158400), Some(DsdRate::Dsd1024));
+        assert_eq!(DsdRate::from_sample_rate(3072000), None);
+    }
+
     #[test]
     fn test_decimation_factor() {
         let rate = DsdRate::Dsd64;
         assert_eq!(rate.decimation_factor(44100), 64);
     }
-    
+
     #[test]
     fn test_processor_creation() {
-        let processor = DsdProcessor::new(DsdRate::Dsd64, 44100);
+        let processor = DsdProcessor::new(DsdRate::Dsd64, 44100, 1);
         assert!(processor.is_ok());
     }
-    
+
+    #[test]
+    fn test_invalid_target_rates() {
+        assert!(DsdProcessor::new(DsdRate::Dsd64, 48000, 1).is_err());
+        assert!(DsdProcessor::new(DsdRate::Dsd64, 705600, 1).is_err());
+        assert!(DsdProcessor::new(DsdRate::Dsd64, 0, 1).is_err());
+        assert!(DsdProcessor::new(DsdRate::Dsd64, 352800, 1).is_ok());
+        assert!(DsdProcessor::new(DsdRate::Dsd1024, 44100, 1).is_ok());
+    }
+
     #[test]
     fn test_basic_processing() {
-        let mut processor = DsdProcessor::new(DsdRate::Dsd64, 44100).unwrap();
+        let mut processor = DsdProcessor::new(DsdRate::Dsd64, 44100, 1).unwrap();
         let dsd_input = vec![0xFF; 128]; // All ones
         let mut pcm_output = vec![0.0; 16];
-        
-        let result = processor.process(&dsd_input, &mut pcm_output);
-        assert!(result.is_ok());
+
+        let result = processor.process(&[&dsd_input], &mut pcm_output);
+        assert_eq!(result.unwrap(), 16);
+    }
+
+    #[test]
+    fn test_dc_settles_to_full_scale() {
+        let mut processor = DsdProcessor::new(DsdRate::Dsd128, 88200, 1).unwrap();
+        let mut pcm = vec![0.0; 4096];
+        let frames = processor.process(&[vec![0xFF; 4096 * 8]], &mut pcm).unwrap();
+        assert_eq!(frames, 4096);
+        assert!(pcm[1024..].iter().all(|&s| (s - 1.0).abs() < 1e-4));
+
+        // Idle pattern decodes to silence
+        processor.reset();
+        processor.process(&[vec![DSD_SILENCE; 4096 * 8]], &mut pcm).unwrap();
+        assert!(pcm.iter().all(|&s| s.abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_output_too_small() {
+        let mut processor = DsdProcessor::new(DsdRate::Dsd64, 44100, 1).unwrap();
+        let mut pcm = vec![0.0; 15];
+        assert!(processor.process(&[[0x69; 128]], &mut pcm).is_err());
+    }
+
+    #[test]
+    fn test_state_persists_across_blocks() {
+        let bits = modulate(|n| 0.5 * (n as f64 * 0.0007).sin(), 20_000);
+
+        let mut whole = DsdProcessor::new(DsdRate::Dsd64, 44100, 1).unwrap();
+        let mut expected = vec![0.0; whole.output_len(bits.len())];
+        whole.process(&[&bits], &mut expected).unwrap();
+
+        let mut chunked = DsdProcessor::new(DsdRate::Dsd64, 44100, 1).unwrap();
+        let mut actual = Vec::new();
+        let mut pcm = vec![0.0; 1024];
+        let mut offset = 0;
+        for size in [1, 7, 8, 333, 4096, 15, 1].iter().cycle() {
+            let end = (offset + size).min(bits.len());
+            let frames = chunked.process(&[&bits[offset..end]], &mut pcm).unwrap();
+            actual.extend_from_slice(&pcm[..frames]);
+            offset = end;
+            if offset == bits.len() {
+                break;
+            }
+        }
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_stereo_matches_independent_channels() {
+        let left = modulate(|n| 0.5 * (n as f64 * 0.0007).sin(), 8192);
+        let right = modulate(|n| -0.25 * (n as f64 * 0.0002).cos(), 8192);
+
+        let mono: Vec<Vec<f32>> = [&left, &right]
+            .iter()
+            .map(|bits| {
+                let mut processor = DsdProcessor::new(DsdRate::Dsd64, 88200, 1).unwrap();
+                let mut pcm = vec![0.0; processor.output_len(bits.len())];
+                processor.process(&[bits], &mut pcm).unwrap();
+                pcm
+            })
+            .collect();
+
+        let mut stereo = DsdProcessor::new(DsdRate::Dsd64, 88200, 2).unwrap();
+        let frames = stereo.output_len(left.len());
+        let mut interleaved = vec![0.0; frames * 2];
+        assert_eq!(stereo.process(&[&left, &right], &mut interleaved).unwrap(), frames);
+        let (l, r): (Vec<f32>, Vec<f32>) = interleaved.chunks(2).map(|frame| (frame[0], frame[1])).unzip();
+        assert_eq!(l, mono[0]);
+        assert_eq!(r, mono[1]);
+
+        stereo.reset();
+        let (mut l, mut r) = (vec![0.0; frames], vec![0.0; frames]);
+        stereo.process_planar(&[&left, &right], &mut [&mut l, &mut r]).unwrap();
+        assert_eq!([l, r], [mono[0].clone(), mono[1].clone()]);
+
+        // Every channel must supply the same number of bytes
+        assert!(stereo.process(&[&left[..8], &right[..16]], &mut interleaved).is_err());
+        assert!(stereo.process(&[&left], &mut interleaved).is_err());
+    }
+
+    #[test]
+    fn test_matches_direct_cascade_reference() {
+        // The LUT and half-band shortcuts must equal plain convolution + decimation
+        let bits = modulate(|n| 0.3 * (n as f64 * 0.0003).sin(), 2048);
+        let mut processor = DsdProcessor::new(DsdRate::Dsd64, 88200, 1).unwrap();
+        let mut pcm = vec![0.0; processor.output_len(bits.len())];
+        let frames = processor.process(&[&bits], &mut pcm).unwrap();
+
+        let to_bipolar = |bytes: &[u8]| -> Vec<f64> {
+            bytes
+                .iter()
+                .flat_map(|&byte| (0..8).map(move |bit| if (byte >> bit) & 1 == 1 { 1.0 } else { -1.0 }))
+                .collect()
+        };
+        // Keep every `factor`-th output, starting with the first complete window
+        let decimate = |history: Vec<f64>, signal: &[f64], taps: &[f64], factor: usize| -> Vec<f64> {
+            let mut padded = history;
+            padded.extend_from_slice(signal);
+            padded
+                .windows(taps.len())
+                .step_by(factor)
+                .map(|w| w.iter().rev().zip(taps).map(|(x, t)| x * t).sum())
+                .collect()
+        };
+
+        let lut_taps = &processor.cascades[0].lut_stage.taps;
+        let idle = to_bipolar(&vec![DSD_SILENCE; lut_taps.len() / 8 - 1]);
+        let mut expected = decimate(idle, &to_bipolar(&bits), lut_taps, 8);
+        for stage in &processor.cascades[0].half_band_stages {
+            expected = decimate(vec![0.0; stage.taps.len() - 1], &expected, &stage.taps, 2);
+        }
+
+        assert_eq!(frames, expected.len());
+        for (i, (&actual, &expected)) in pcm.iter().zip(&expected).enumerate() {
+            assert!((actual as f64 - expected).abs() < 1e-5, "sample {}: {} vs {}", i, actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_spectral_specification() {
+        let profiles = [
+            (DecimationProfile::Fast, 0.01, 80.0),
+            (DecimationProfile::Balanced, 0.001, 100.0),
+            (DecimationProfile::HighQuality, 0.001, 120.0),
+        ];
+        let configurations = [
+            (DsdRate::Dsd64, 44100),
+            (DsdRate::Dsd64, 176400),
+            (DsdRate::Dsd256, 44100),
+            (DsdRate::Dsd512, 352800),
+        ];
+
+        for (profile, ripple_db, rejection_db) in profiles {
+            for (dsd_rate, target_rate) in configurations {
+                let processor = DsdProcessor::with_profile(dsd_rate, target_rate, profile, 1).unwrap();
+                let passband = PASSBAND_HZ.min(MAX_PASSBAND_RATIO * target_rate as f64);
+                let context = format!("{:?} {:?} -> {} Hz", profile, dsd_rate, target_rate);
+
+                // Passband flatness
+                for i in 0..=200 {
+                    let gain_db = 20.0 * cascade_magnitude(&processor, passband * i as f64 / 200.0).log10();
+                    assert!(gain_db.abs() < ripple_db, "{}: ripple {} dB", context, gain_db);
+                }
+
+                // Every band that aliases onto the passband after decimation
+                let images = dsd_rate.sample_rate() / target_rate / 2;
+                for k in 1..=images {
+                    let center = k as f64 * target_rate as f64;
+                    for i in 0..=40 {
+                        let frequency = center - passband + 2.0 * passband * i as f64 / 40.0;
+                        let gain_db = 20.0 * cascade_magnitude(&processor, frequency).log10();
+                        assert!(
+                            gain_db < -rejection_db,
+                            "{}: {:.0} Hz aliases at {:.1} dB",
+                            context,
+                            frequency,
+                            gain_db
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_modulated_tone_decodes_cleanly() {
+        // ~1 kHz at -12 dBFS (centred on a DFT bin) plus 300 kHz at -12 dBFS,
+        // which folds onto 8.7 kHz unless the decimator rejects it
+        let rate = DsdRate::Dsd64.sample_rate() as f64;
+        let settled_len = 32768 - 1024;
+        let tone = 720.0 * 44100.0 / settled_len as f64;
+        let signal = |n: usize| {
+            let t = n as f64 / rate;
+            0.25 * (2.0 * std::f64::consts::PI * tone * t).sin()
+                + 0.25 * (2.0 * std::f64::consts::PI * 300_000.0 * t).sin()
+        };
+        let bits = modulate(signal, 1 << 18);
+
+        let mut processor = DsdProcessor::new(DsdRate::Dsd64, 44100, 1).unwrap();
+        let mut pcm = vec![0.0; processor.output_len(bits.len())];
+        let frames = processor.process(&[&bits], &mut pcm).unwrap();
+        assert_eq!(frames, 32768);
+        let settled = &pcm[1024..frames];
+
+        let fundamental = tone_amplitude(settled, tone, 44100.0);
+        assert!((fundamental - 0.25).abs() < 0.25 * 0.01, "1 kHz amplitude {}", fundamental);
+        let alias = tone_amplitude(settled, 7.0 * 44100.0 - 300_000.0, 44100.0);
+        assert!(20.0 * (alias / 0.25).log10() < -80.0, "300 kHz aliased to {}", alias);
     }
 }
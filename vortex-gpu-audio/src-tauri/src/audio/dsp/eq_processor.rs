@@ -1,8 +1,16 @@
 use crate::error::VortexError;
 use crate::gpu::GpuProcessor;
-use super::filters::{BiquadFilter, BiquadCoefficients};
+use crate::audio::filters::{
+    BiquadCoefficients, BiquadFilter, BiquadTopology, ChannelLayout, ChannelMask, DelayLine, Filter, FilterMetadata,
+};
+use super::channels;
+use super::convolver::{Convolver, PartitionedIr};
+use super::graphic_eq::GraphicEq;
+use crossbeam_queue::ArrayQueue;
+use rustfft::num_complex::Complex64;
 use std::sync::Arc;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use uuid::Uuid;
 
 /// EQ band configuration
 #[derive(Debug, Clone)]
@@ -13,25 +21,420 @@ pub struct EqBand {
     pub enabled: bool,
 }
 
+impl EqBand {
+    /// Whether the band shapes the response; near-flat bands are skipped
+    fn is_active(&self) -> bool {
+        self.enabled && self.gain_db.abs() >= 0.1
+    }
+}
+
+/// Taps of the FIR realizing the combined band curve
+pub const FIR_LENGTH: usize = 8192;
+
+/// Direct-form head of the FIR convolver, so short engine buffers stay cheap
+const FIR_DIRECT_TAPS: usize = 64;
+
+/// Largest FFT partition of the FIR convolver
+const FIR_PARTITION_SIZE: usize = 1024;
+
+/// Default time for band changes to take full effect
+pub const DEFAULT_SMOOTHING_MS: f32 = 20.0;
+
+/// Bands below this fraction of the sample rate ring for longer than the
+/// FIR holds; in `PhaseMode::Minimum` they run as biquads instead
+const BIQUAD_BAND_RATIO: f32 = 1e-3;
+
+/// Updates the audio thread can hand back before a control thread collects
+/// them; it takes at most one per published design, and publishing collects
+/// them first
+const RETIRED_CAPACITY: usize = 2;
+
+/// How much faster than real time an idle convolver catches up on the input
+/// it missed while the curve was flat; the extra work per block matches a
+/// crossfade's
+const PRIME_RATE: usize = 2;
+
+/// Phase behaviour of the EQ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PhaseMode {
+    /// The bands' own phase shift, as a biquad cascade would have: zero latency
+    Minimum,
+    /// Symmetric FIR with the target magnitude: no phase shift, `FIR_LENGTH / 2` latency
+    Linear,
+    /// FIR keeping this share (0 to 1) of the band phase; the latency
+    /// shrinks from linear phase towards zero as the share grows
+    Mixed(f32),
+}
+
+impl PhaseMode {
+    /// Share of the band phase the design keeps
+    fn minimum_share(self) -> f32 {
+        match self {
+            PhaseMode::Minimum => 1.0,
+            PhaseMode::Linear => 0.0,
+            PhaseMode::Mixed(share) => share,
+        }
+    }
+
+    /// Bulk delay of the FIR, in samples
+    fn latency_samples(self) -> usize {
+        let delay = (1.0 - self.minimum_share() as f64) * (FIR_LENGTH / 2) as f64;
+        delay.round() as usize
+    }
+}
+
+/// Band state and FIR design, shared by a processor and its controllers
+///
+/// Only control threads lock it: they redesign here and hand the result to
+/// the audio thread as an `EqUpdate`.
+struct EqDesign {
+    bands: Vec<EqBand>,
+    sample_rate: f32,
+    curve: GraphicEq,
+    phase_mode: PhaseMode,
+    // Bands below `BIQUAD_BAND_RATIO`, ascending
+    low_bands: Vec<usize>,
+    // Designed FIR taps, reused across redesigns
+    taps: Vec<f32>,
+    // Single-channel convolver laid out like the processor's, used only to
+    // partition the taps
+    partitioner: Convolver,
+    // The curve changed since the FIR was last designed
+    fir_dirty: bool,
+}
+
+impl EqDesign {
+    /// Update the curve for a new band gain
+    fn set_band_gain(&mut self, band_index: usize, gain_db: f32) -> Result<(), VortexError> {
+        if band_index >= self.bands.len() {
+            return Err(crate::error::AudioError::InvalidParameter(
+                format!("Band index {} out of range", band_index)
+            ).into());
+        }
+
+        let in_fir = self.in_fir(band_index);
+        if in_fir && self.bands[band_index].is_active() {
+            let old = self.band_coefficients(&self.bands[band_index]);
+            self.curve.remove_band(&old);
+        }
+        self.bands[band_index].gain_db = gain_db;
+        if in_fir {
+            if self.bands[band_index].is_active() {
+                let coeffs = self.band_coefficients(&self.bands[band_index]);
+                self.curve.add_band(&coeffs);
+            }
+            self.fir_dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Recompute the FIR curve from scratch, after the set of FIR bands changed
+    fn rebuild_curve(&mut self) {
+        self.curve.clear();
+        for index in 0..self.bands.len() {
+            if self.in_fir(index) && self.bands[index].is_active() {
+                let coeffs = self.band_coefficients(&self.bands[index]);
+                self.curve.add_band(&coeffs);
+            }
+        }
+        self.fir_dirty = true;
+    }
+
+    /// Bring `update` up to date: the FIR is designed and partitioned if the
+    /// curve changed, the biquad bands are always copied
+    fn write_update(&mut self, update: &mut EqUpdate) {
+        if self.fir_dirty {
+            self.fir_dirty = false;
+            let delay = self.phase_mode.latency_samples();
+            self.curve.design_into(self.phase_mode.minimum_share(), delay, &mut self.taps);
+            self.partitioner
+                .partition_ir(&self.taps, &mut update.ir)
+                .expect("the partitioner is built for FIR_LENGTH taps");
+            update.fir = true;
+            update.flat = !(0..self.bands.len()).any(|i| self.in_fir(i) && self.bands[i].is_active());
+        }
+
+        update.low_bands.clear();
+        for &index in &self.low_bands {
+            let band = &self.bands[index];
+            update.low_bands.push((self.band_coefficients(band), band.is_active()));
+        }
+    }
+
+    /// Whether a band is part of the FIR curve in the current phase mode
+    fn in_fir(&self, band_index: usize) -> bool {
+        self.phase_mode != PhaseMode::Minimum || self.low_bands.binary_search(&band_index).is_err()
+    }
+
+    fn band_coefficients(&self, band: &EqBand) -> BiquadCoefficients {
+        BiquadCoefficients::peaking(band.frequency, self.sample_rate, band.q, band.gain_db)
+    }
+}
+
+/// A finished design, handed to the audio thread
+#[derive(Default)]
+struct EqUpdate {
+    // Partitioned FIR, only swapped in when `fir` is set
+    ir: PartitionedIr,
+    fir: bool,
+    // No band shapes the FIR, so it is a pure delay
+    flat: bool,
+    // Coefficients of the biquad bands, in `low_bands` order, and whether
+    // each is active
+    low_bands: Vec<(BiquadCoefficients, bool)>,
+}
+
+/// Where the FIR stage's output comes from
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FirPath {
+    /// The curve is flat, so the FIR is a pure delay: the input comes out of
+    /// the history `latency_samples` late and the convolver idles
+    Flat,
+    /// As `Flat`, while the convolver catches up on the history it missed,
+    /// starting `behind` frames back, before taking over
+    Priming { behind: usize },
+    /// The convolver runs the FIR
+    Convolver,
+}
+
+/// Ring of the most recent interleaved input frames
+struct InputHistory {
+    samples: Vec<f32>,
+    channels: usize,
+    // Frame the next input goes to, which is the oldest held
+    head: usize,
+    // Frames pushed since the last clear, up to the ring's size
+    filled: usize,
+}
+
+impl InputHistory {
+    fn new(frames: usize, channels: usize) -> Self {
+        Self {
+            samples: vec![0.0; frames * channels],
+            channels,
+            head: 0,
+            filled: 0,
+        }
+    }
+
+    fn frames(&self) -> usize {
+        self.samples.len() / self.channels
+    }
+
+    /// Hold at least `frames` frames, keeping the input already held
+    fn reserve(&mut self, frames: usize) {
+        let held = self.frames();
+        if frames <= held {
+            return;
+        }
+        // Oldest first, so the new head lands on the added silence
+        let mut samples = vec![0.0; frames * self.channels];
+        let split = self.head * self.channels;
+        let (newer, older) = self.samples.split_at(split);
+        let start = (frames - held) * self.channels;
+        samples[start..start + older.len()].copy_from_slice(older);
+        samples[start + older.len()..].copy_from_slice(newer);
+        self.samples = samples;
+        self.head = 0;
+    }
+
+    /// Append interleaved frames; at most the ring's size
+    fn push(&mut self, input: &[f32]) {
+        let start = self.head * self.channels;
+        let first = input.len().min(self.samples.len() - start);
+        self.samples[start..start + first].copy_from_slice(&input[..first]);
+        self.samples[..input.len() - first].copy_from_slice(&input[first..]);
+        self.advance(input.len() / self.channels);
+    }
+
+    /// Append one slice per channel; at most the ring's size
+    fn push_planar(&mut self, input: &[&[f32]]) {
+        let frames = self.frames();
+        for (channel, plane) in input.iter().enumerate() {
+            for (i, &x) in plane.iter().enumerate() {
+                self.samples[(self.head + i) % frames * self.channels + channel] = x;
+            }
+        }
+        self.advance(input.first().map_or(0, |plane| plane.len()));
+    }
+
+    fn advance(&mut self, frames: usize) {
+        self.head = (self.head + frames) % self.frames();
+        self.filled = (self.filled + frames).min(self.frames());
+    }
+
+    /// The `frames` frames starting `back` frames before the head, as two
+    /// interleaved runs in order
+    fn slices(&self, back: usize, frames: usize) -> (&[f32], &[f32]) {
+        let start = (self.head + self.frames() - back) % self.frames() * self.channels;
+        let len = frames * self.channels;
+        let first = len.min(self.samples.len() - start);
+        (&self.samples[start..start + first], &self.samples[..len - first])
+    }
+
+    /// Copy interleaved frames from `back` frames before the head into `output`
+    fn read(&self, back: usize, output: &mut [f32]) {
+        let (first, second) = self.slices(back, output.len() / self.channels);
+        output[..first.len()].copy_from_slice(first);
+        output[first.len()..].copy_from_slice(second);
+    }
+
+    /// `read` for one channel into a planar `output`
+    fn read_channel(&self, channel: usize, back: usize, output: &mut [f32]) {
+        let (first, second) = self.slices(back, output.len());
+        let frames = first.chunks(self.channels).chain(second.chunks(self.channels));
+        for (out, frame) in output.iter_mut().zip(frames) {
+            *out = frame[channel];
+        }
+    }
+
+    fn clear(&mut self) {
+        self.samples.fill(0.0);
+        self.head = 0;
+        self.filled = 0;
+    }
+}
+
+/// State shared by an `EqProcessor` and its `EqController`s
+struct EqShared {
+    design: Mutex<EqDesign>,
+    // The latest design the audio thread has not taken yet
+    updates: ArrayQueue<EqUpdate>,
+    // Updates the audio thread is done with, holding the IR they replaced
+    retired: ArrayQueue<EqUpdate>,
+}
+
+impl EqShared {
+    fn set_band_gain(&self, band_index: usize, gain_db: f32) -> Result<(), VortexError> {
+        let mut design = self.design.lock();
+        design.set_band_gain(band_index, gain_db)?;
+        self.publish(&mut design);
+        Ok(())
+    }
+
+    /// Queue the current design for the audio thread
+    ///
+    /// Runs on the calling (control) thread with the design locked. A design
+    /// still waiting is refreshed in place; otherwise a retired update's
+    /// buffers are reused, so steady-state redesigns do not allocate either.
+    fn publish(&self, design: &mut EqDesign) {
+        let mut update = match self.updates.pop() {
+            Some(pending) => pending,
+            None => {
+                let mut update = self.retired.pop().unwrap_or_default();
+                update.fir = false;
+                update
+            }
+        };
+        while self.retired.pop().is_some() {}
+
+        design.write_update(&mut update);
+        // Publishing holds the design lock, so the queue has room
+        let _ = self.updates.push(update);
+    }
+}
+
+/// Handle for changing band gains from another thread
+///
+/// Each change redesigns the FIR on the calling thread and queues it; the
+/// processor swaps it in at the start of its next block, so the audio thread
+/// never designs and never waits on the UI. Changes arriving before that
+/// block are taken over together.
+#[derive(Clone)]
+pub struct EqController {
+    shared: Arc<EqShared>,
+}
+
+impl EqController {
+    /// Set gain for a specific band
+    pub fn set_band_gain(&self, band_index: usize, gain_db: f32) -> Result<(), VortexError> {
+        self.shared.set_band_gain(band_index, gain_db)
+    }
+
+    /// Get number of bands
+    pub fn num_bands(&self) -> usize {
+        self.shared.design.lock().bands.len()
+    }
+}
+
 /// 512-band parametric EQ processor
+///
+/// The bands are not run as a biquad cascade. Their combined response is
+/// kept on the bin grid of a `FIR_LENGTH`-tap FIR (see `GraphicEq`), turned
+/// into taps by frequency sampling with the share of band phase and bulk
+/// delay the `PhaseMode` asks for, and run through the partitioned
+/// convolver. Processing cost is independent of the number of bands.
+///
+/// While no band shapes the FIR the convolver idles and the input only goes
+/// through the phase mode's delay. The processor keeps the last `FIR_LENGTH`
+/// frames of input, so when a band becomes active the convolver catches up
+/// on them at `PRIME_RATE` times real time before fading in.
+///
+/// A band change updates the curve, designs the FIR and partitions it on the
+/// thread making the change, directly or through an `EqController`. The
+/// audio thread picks the finished FIR up at the start of its next block and
+/// only swaps buffers; the FIR it replaces goes back to the control side.
+///
+/// The lowest bands (below `BIQUAD_BAND_RATIO` of the sample rate, so more
+/// of them at high sample rates) would be truncated by the FIR. In
+/// `PhaseMode::Minimum` they run as biquads after the FIR instead, each in
+/// the `BiquadTopology` its frequency calls for.
+///
+/// Band changes are smoothed over `DEFAULT_SMOOTHING_MS` unless set
+/// otherwise: the new FIR runs alongside the previous one, fed the same
+/// history, and the output crossfades between them; further designs wait
+/// for the crossfade to finish. Flattening the curve fades to the delay the
+/// same way. Biquad bands ramp their coefficients over
+/// the same time.
+///
+/// Every channel is filtered with the same FIR and biquads. Buffers are
+/// interleaved for `process` and planar for `process_planar` (see
+/// `channels`). After `prepare`, processing blocks up to the prepared size
+/// does not allocate, band changes included, so the processor can run on
+/// the audio thread driven by an `EqController`.
+///
+/// As a `Filter` in a chain or graph, the processor reports the phase
+/// mode's latency, and delays bypassed and masked channels by as much.
 pub struct EqProcessor {
-    bands: Vec<EqBand>,
-    filters: Vec<BiquadFilter>,
+    metadata: FilterMetadata,
+    shared: Arc<EqShared>,
     sample_rate: f32,
+    convolver: Convolver,
+    path: FirPath,
+    history: InputHistory,
+    // Previous FIR during a crossfade, with `fade_position` of
+    // `fade_length` frames done; `fade_from` is `FirPath::Flat` when fading
+    // in from the delay, otherwise `fading` runs the previous FIR
+    fading: Convolver,
+    fade_from: FirPath,
+    fade_length: usize,
+    fade_position: usize,
+    fade_buffer: Vec<f32>,
+    // Biquad bands per channel: filters[channel][i] realizes the design's
+    // low_bands[i], whose coefficients and activity were last taken over
+    // into low_bands[i]
+    low_bands: Vec<(BiquadCoefficients, bool)>,
+    filters: Vec<Vec<BiquadFilter>>,
+    // Per-channel staging for interleaved buffers, reused across calls
+    planes: Vec<Vec<f32>>,
+    scratch: Vec<f32>,
+    phase_mode: PhaseMode,
     gpu_processor: Option<Arc<RwLock<GpuProcessor>>>,
     use_gpu: bool,
+    // The input delayed by `latency_samples`, for bypass and masked channels
+    dry: DelayLine,
 }
 
 impl EqProcessor {
     /// Create a new EQ processor with specified number of bands
-    pub fn new(num_bands: usize, sample_rate: f32) -> Result<Self, VortexError> {
+    pub fn new(num_bands: usize, sample_rate: f32, channels: u16) -> Result<Self, VortexError> {
+        let channel_count = channels::validate_channels(channels)?;
         let mut bands = Vec::with_capacity(num_bands);
-        let mut filters = Vec::with_capacity(num_bands);
         
         // Initialize bands logarithmically distributed from 20Hz to 20kHz
         for i in 0..num_bands {
             let t = i as f32 / (num_bands - 1) as f32;
-            let frequency = 20.0 * (20000.0 / 20.0).powf(t);
+            let frequency = 20.0 * (20000.0f32 / 20.0).powf(t);
             
             bands.push(EqBand {
                 frequency,
@@ -39,59 +442,307 @@ impl EqProcessor {
                 q: 1.0,
                 enabled: true,
             });
-            
-            let coeffs = BiquadCoefficients::peaking(frequency, sample_rate, 1.0, 0.0);
-            filters.push(BiquadFilter::new(
-                format!("EQ Band {}", i),
-                coeffs,
-            ));
         }
         
-        Ok(Self {
+        let low_bands: Vec<usize> = (0..num_bands)
+            .filter(|&i| bands[i].frequency / sample_rate < BIQUAD_BAND_RATIO)
+            .collect();
+        let fade_length = (DEFAULT_SMOOTHING_MS / 1000.0 * sample_rate).round() as usize;
+        let low_coefficients: Vec<(BiquadCoefficients, bool)> = low_bands
+            .iter()
+            .map(|&i| {
+                let band = &bands[i];
+                (BiquadCoefficients::peaking(band.frequency, sample_rate, band.q, band.gain_db), band.is_active())
+            })
+            .collect();
+        let filters = (0..channel_count)
+            .map(|_| {
+                low_bands
+                    .iter()
+                    .zip(&low_coefficients)
+                    .map(|(&i, &(coeffs, _))| {
+                        let mut filter = BiquadFilter::new(format!("EQ Band {}", i), coeffs);
+                        filter.set_topology(BiquadTopology::for_frequency(bands[i].frequency, sample_rate));
+                        filter.set_smoothing(fade_length);
+                        filter
+                    })
+                    .collect()
+            })
+            .collect();
+        
+        let mut curve = GraphicEq::new(FIR_LENGTH);
+        let taps = curve.design(PhaseMode::Minimum.minimum_share(), 0);
+        let convolver = Convolver::new_non_uniform(taps.clone(), FIR_DIRECT_TAPS, FIR_PARTITION_SIZE, channels)?;
+        let fading = Convolver::new_non_uniform(taps.clone(), FIR_DIRECT_TAPS, FIR_PARTITION_SIZE, channels)?;
+        let partitioner = Convolver::new_non_uniform(taps.clone(), FIR_DIRECT_TAPS, FIR_PARTITION_SIZE, 1)?;
+        let design = EqDesign {
             bands,
-            filters,
             sample_rate,
+            curve,
+            phase_mode: PhaseMode::Minimum,
+            low_bands,
+            taps,
+            partitioner,
+            fir_dirty: false,
+        };
+        
+        Ok(Self {
+            metadata: FilterMetadata {
+                id: Uuid::new_v4().to_string(),
+                name: format!("{}-band EQ", num_bands),
+                enabled: true,
+                bypass: false,
+                channel_mask: ChannelMask::ALL,
+            },
+            shared: Arc::new(EqShared {
+                design: Mutex::new(design),
+                updates: ArrayQueue::new(1),
+                retired: ArrayQueue::new(RETIRED_CAPACITY),
+            }),
+            sample_rate,
+            convolver,
+            path: FirPath::Flat,
+            history: InputHistory::new(FIR_LENGTH, channel_count),
+            fading,
+            fade_from: FirPath::Convolver,
+            fade_length,
+            fade_position: fade_length,
+            fade_buffer: Vec::new(),
+            low_bands: low_coefficients,
+            filters,
+            planes: vec![Vec::new(); channel_count],
+            scratch: Vec::new(),
+            phase_mode: PhaseMode::Minimum,
             gpu_processor: None,
             use_gpu: false,
+            dry: DelayLine::default(),
         })
     }
     
     /// Create a 512-band EQ processor
-    pub fn new_512band(sample_rate: f32) -> Result<Self, VortexError> {
-        Self::new(512, sample_rate)
+    pub fn new_512band(sample_rate: f32, channels: u16) -> Result<Self, VortexError> {
+        Self::new(512, sample_rate, channels)
     }
     
     /// Set gain for a specific band
+    ///
+    /// Designs the FIR on the calling thread; it takes effect when the next
+    /// block is processed.
     pub fn set_band_gain(&mut self, band_index: usize, gain_db: f32) -> Result<(), VortexError> {
-        if band_index >= self.bands.len() {
+        self.shared.set_band_gain(band_index, gain_db)
+    }
+
+    /// Handle for setting band gains from another thread
+    pub fn controller(&self) -> EqController {
+        EqController {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Time for band changes to take full effect, in milliseconds
+    ///
+    /// Zero switches instantly, as a fresh design always did before.
+    pub fn set_smoothing_ms(&mut self, smoothing_ms: f32) -> Result<(), VortexError> {
+        if !smoothing_ms.is_finite() || smoothing_ms < 0.0 {
             return Err(crate::error::AudioError::InvalidParameter(
-                format!("Band index {} out of range", band_index)
+                format!("Smoothing time must be non-negative, got {} ms", smoothing_ms)
             ).into());
         }
-        
-        self.bands[band_index].gain_db = gain_db;
-        
-        // Update filter coefficients
-        let band = &self.bands[band_index];
-        let coeffs = BiquadCoefficients::peaking(
-            band.frequency,
-            self.sample_rate,
-            band.q,
-            gain_db,
-        );
-        self.filters[band_index].set_coefficients(coeffs);
-        
+
+        self.fade_length = (smoothing_ms / 1000.0 * self.sample_rate).round() as usize;
+        self.fade_position = self.fade_position.min(self.fade_length);
+        for filter in self.filters.iter_mut().flatten() {
+            filter.set_smoothing(self.fade_length);
+        }
         Ok(())
     }
+
+    /// Time for band changes to take full effect, in milliseconds
+    pub fn smoothing_ms(&self) -> f32 {
+        self.fade_length as f32 * 1000.0 / self.sample_rate
+    }
+
+    /// Swap in the latest published design, if any
+    ///
+    /// With `continuous`, the output carries on from the current signal: a
+    /// new FIR takes over a copy of the current one's history and, with
+    /// smoothing, the current one is faded out; an idle convolver catches up
+    /// first. Otherwise the caller resets the convolver. The update goes back
+    /// holding the replaced FIR, so nothing is allocated or freed here.
+    fn take_update(&mut self, continuous: bool) -> Result<(), VortexError> {
+        let Some(mut update) = self.shared.updates.pop() else {
+            return Ok(());
+        };
+
+        let result = self.apply_update(&mut update, continuous);
+        // Cannot fill up (see `RETIRED_CAPACITY`); dropping here is the fallback
+        let _ = self.shared.retired.push(update);
+        result
+    }
+
+    fn apply_update(&mut self, update: &mut EqUpdate, continuous: bool) -> Result<(), VortexError> {
+        if update.fir {
+            let crossfade = continuous && self.fade_length > 0;
+            if update.flat {
+                if self.path == FirPath::Convolver && crossfade {
+                    std::mem::swap(&mut self.convolver, &mut self.fading);
+                    self.start_fade(FirPath::Convolver);
+                }
+                self.path = FirPath::Flat;
+            } else {
+                match self.path {
+                    FirPath::Convolver if crossfade => {
+                        std::mem::swap(&mut self.convolver, &mut self.fading);
+                        self.convolver.copy_state_from(&self.fading)?;
+                        self.start_fade(FirPath::Convolver);
+                    }
+                    FirPath::Flat if continuous && self.history.filled > 0 => {
+                        self.convolver.reset();
+                        self.path = FirPath::Priming {
+                            behind: self.history.filled.min(FIR_LENGTH),
+                        };
+                    }
+                    FirPath::Priming { .. } if continuous => {}
+                    FirPath::Flat => {
+                        // Nothing was missed since the last reset
+                        self.convolver.reset();
+                        self.path = FirPath::Convolver;
+                    }
+                    _ => self.path = FirPath::Convolver,
+                }
+            }
+            self.convolver.swap_ir(&mut update.ir)?;
+        }
+
+        for (position, &(coeffs, active)) in update.low_bands.iter().enumerate() {
+            if self.low_bands[position].0 != coeffs {
+                for bank in &mut self.filters {
+                    bank[position].set_coefficients(coeffs);
+                }
+            }
+            self.low_bands[position] = (coeffs, active);
+        }
+        Ok(())
+    }
+
+    /// Take over a published design once any crossfade has finished
+    fn take_pending_update(&mut self) -> Result<(), VortexError> {
+        if self.fade_position < self.fade_length {
+            return Ok(());
+        }
+        self.take_update(true)
+    }
+
+    fn start_fade(&mut self, from: FirPath) {
+        self.fade_from = from;
+        self.fade_position = 0;
+    }
+
+    /// Feed a priming convolver up to `PRIME_RATE` times `frames` frames of
+    /// history, after the block's input went into the history
+    ///
+    /// Once caught up the convolver takes over, faded in from the delay.
+    fn prime(&mut self, frames: usize) -> Result<(), VortexError> {
+        let FirPath::Priming { behind } = self.path else {
+            return Ok(());
+        };
+
+        let mut behind = behind + frames;
+        let mut budget = (PRIME_RATE * frames).min(behind);
+        let mut buffer = std::mem::take(&mut self.fade_buffer);
+        while budget > 0 {
+            // In pieces no longer than a block, which the buffers are sized for
+            let count = budget.min(frames);
+            let (first, second) = self.history.slices(behind, count);
+            for run in [first, second] {
+                if !run.is_empty() {
+                    buffer.resize(run.len(), 0.0);
+                    self.convolver.process(run, &mut buffer)?;
+                }
+            }
+            behind -= count;
+            budget -= count;
+        }
+        self.fade_buffer = buffer;
+
+        if behind > 0 {
+            self.path = FirPath::Priming { behind };
+        } else {
+            self.path = FirPath::Convolver;
+            if self.fade_length > 0 {
+                self.start_fade(FirPath::Flat);
+            }
+        }
+        Ok(())
+    }
+
+    /// Choose between minimum, linear and mixed phase
+    ///
+    /// Redesigns on the calling thread and switches without a crossfade.
+    pub fn set_phase_mode(&mut self, mode: PhaseMode) -> Result<(), VortexError> {
+        if let PhaseMode::Mixed(share) = mode {
+            if !(0.0..=1.0).contains(&share) {
+                return Err(crate::error::AudioError::InvalidParameter(
+                    format!("Mixed phase share must be between 0 and 1, got {}", share)
+                ).into());
+            }
+        }
+
+        if mode != self.phase_mode {
+            self.phase_mode = mode;
+            {
+                let mut design = self.shared.design.lock();
+                design.phase_mode = mode;
+                design.rebuild_curve();
+                self.shared.publish(&mut design);
+            }
+
+            // The latency changes, so there is no continuity to preserve
+            self.fade_position = self.fade_length;
+            self.take_update(false)?;
+            self.convolver.reset();
+            self.filters.iter_mut().flatten().for_each(Filter::reset);
+            self.dry.set_len(self.latency_samples() * self.channels() as usize);
+        }
+        Ok(())
+    }
+
+    /// Current phase mode
+    pub fn phase_mode(&self) -> PhaseMode {
+        self.phase_mode
+    }
+
+    /// Delay the current phase mode adds, in samples
+    ///
+    /// Zero for `PhaseMode::Minimum`; otherwise the bulk delay of the FIR.
+    pub fn latency_samples(&self) -> usize {
+        self.phase_mode.latency_samples()
+    }
     
+    /// Size the staging buffers for blocks of up to `max_frames`, so that
+    /// processing them does not allocate
+    pub fn prepare(&mut self, max_frames: usize) {
+        let channels = self.channels() as usize;
+        self.convolver.prepare(max_frames);
+        self.fading.prepare(max_frames);
+        self.history.reserve(FIR_LENGTH + max_frames);
+        for plane in &mut self.planes {
+            plane.reserve(max_frames.saturating_sub(plane.len()));
+        }
+        self.scratch.reserve(max_frames.saturating_sub(self.scratch.len()));
+        self.fade_buffer.reserve((max_frames * channels).saturating_sub(self.fade_buffer.len()));
+    }
+
     /// Enable GPU acceleration
     pub fn enable_gpu(&mut self, gpu: Arc<RwLock<GpuProcessor>>) {
         self.gpu_processor = Some(gpu);
         self.use_gpu = true;
     }
     
-    /// Process audio through all EQ bands
-    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> Result<(), VortexError> {
+    /// Process interleaved audio through all EQ bands
+    ///
+    /// Returns the number of frames written.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> Result<usize, VortexError> {
         if self.use_gpu && self.gpu_processor.is_some() {
             // TODO: GPU processing implementation
             // For now, fall back to CPU
@@ -100,79 +751,761 @@ impl EqProcessor {
             self.process_cpu(input, output)
         }
     }
-    
+
+    /// Process planar audio through all EQ bands, one slice per channel
+    pub fn process_planar(&mut self, input: &[&[f32]], output: &mut [&mut [f32]]) -> Result<usize, VortexError> {
+        let channels = self.channels() as usize;
+        let frames = channels::planar_frames(input, channels)?;
+        if channels::planar_frames(output, channels)? != frames {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "Output must match the input length".to_string()
+            ).into());
+        }
+
+        self.take_pending_update()?;
+        self.history.reserve(FIR_LENGTH + frames);
+        self.history.push_planar(input);
+        // Back to the delayed input for this block's first frame
+        let delayed = frames + self.latency_samples();
+        let fading = self.fade_position < self.fade_length;
+        if self.path == FirPath::Convolver && !fading {
+            self.convolver.process_planar(input, output)?;
+        } else {
+            let mut buffer = std::mem::take(&mut self.fade_buffer);
+            buffer.resize(self.fade_length.saturating_sub(self.fade_position).min(frames), 0.0);
+            for (channel, (input, output)) in input.iter().zip(output.iter_mut()).enumerate() {
+                match self.path {
+                    FirPath::Convolver => {
+                        self.convolver.process_channel(channel, input, output)?;
+                    }
+                    _ => self.history.read_channel(channel, delayed, output),
+                }
+                if fading {
+                    match self.fade_from {
+                        FirPath::Convolver => {
+                            self.fading.process_channel(channel, &input[..buffer.len()], &mut buffer)?;
+                        }
+                        _ => self.history.read_channel(channel, delayed, &mut buffer),
+                    }
+                    self.crossfade(output, &buffer, 1);
+                }
+            }
+            self.fade_buffer = buffer;
+            if fading {
+                self.fade_position += frames;
+            }
+        }
+        self.prime(frames)?;
+        if self.biquads_active() {
+            for (channel, output) in output.iter_mut().enumerate() {
+                self.process_channel(channel, output);
+            }
+        }
+        Ok(frames)
+    }
+
     /// CPU-based processing
-    fn process_cpu(&mut self, input: &[f32], output: &mut [f32]) -> Result<(), VortexError> {
-        // Initialize output with input
-        output.copy_from_slice(input);
-        
-        let mut temp_buffer = vec![0.0f32; input.len()];
-        
-        // Process through each enabled band
-        for (i, filter) in self.filters.iter_mut().enumerate() {
-            if !self.bands[i].enabled || self.bands[i].gain_db.abs() < 0.1 {
+    fn process_cpu(&mut self, input: &[f32], output: &mut [f32]) -> Result<usize, VortexError> {
+        let frames = channels::interleaved_frames(input.len(), self.channels() as usize)?;
+        if output.len() != input.len() {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "Output must match the input length".to_string()
+            ).into());
+        }
+
+        self.take_pending_update()?;
+        self.history.reserve(FIR_LENGTH + frames);
+        self.history.push(input);
+        // Back to the delayed input for this block's first frame
+        let delayed = frames + self.latency_samples();
+        match self.path {
+            FirPath::Convolver => {
+                self.convolver.process(input, output)?;
+            }
+            _ => self.history.read(delayed, output),
+        }
+        if self.fade_position < self.fade_length {
+            // Only the frames still fading are needed from the previous path
+            let channels = self.channels() as usize;
+            let count = (self.fade_length - self.fade_position).min(frames) * channels;
+            let mut buffer = std::mem::take(&mut self.fade_buffer);
+            buffer.resize(count, 0.0);
+            match self.fade_from {
+                FirPath::Convolver => {
+                    self.fading.process(&input[..count], &mut buffer)?;
+                }
+                _ => self.history.read(delayed, &mut buffer),
+            }
+            self.crossfade(output, &buffer, channels);
+            self.fade_buffer = buffer;
+            self.fade_position += frames;
+        }
+        self.prime(frames)?;
+        if self.biquads_active() {
+            let mut planes = std::mem::take(&mut self.planes);
+            planes.iter_mut().for_each(Vec::clear);
+            channels::deinterleave(output, &mut planes);
+            for (channel, plane) in planes.iter_mut().enumerate() {
+                self.process_channel(channel, plane);
+            }
+            channels::interleave(&planes, frames, output);
+            self.planes = planes;
+        }
+
+        Ok(frames)
+    }
+
+    /// Blend the previous path's `previous` output into `output` (new path)
+    ///
+    /// Weights follow the crossfade from `fade_position`; frames past its
+    /// end, or past the end of `previous`, keep the new path's output.
+    fn crossfade(&self, output: &mut [f32], previous: &[f32], channels: usize) {
+        let remaining = self.fade_length - self.fade_position;
+        for (frame, (new, old)) in output
+            .chunks_mut(channels)
+            .zip(previous.chunks(channels))
+            .take(remaining)
+            .enumerate()
+        {
+            let weight = (self.fade_position + frame) as f32 / self.fade_length as f32;
+            for (new, &old) in new.iter_mut().zip(old) {
+                *new = old + (*new - old) * weight;
+            }
+        }
+    }
+
+    /// Run one channel's active biquad bands over `samples` in place
+    ///
+    /// A band that has just been flattened keeps running until its ramp ends.
+    fn process_channel(&mut self, channel: usize, samples: &mut [f32]) {
+        self.scratch.resize(samples.len(), 0.0);
+
+        for (&(_, active), filter) in self.low_bands.iter().zip(&mut self.filters[channel]) {
+            if !active && !filter.is_ramping() {
+                // Skipped filters restart from silence, like an identity
+                filter.reset();
                 continue;
             }
-            
-            filter.process(output, &mut temp_buffer);
-            output.copy_from_slice(&temp_buffer);
+
+            filter.process(samples, &mut self.scratch, ChannelLayout::MONO);
+            samples.copy_from_slice(&self.scratch);
         }
-        
-        Ok(())
     }
-    
+
+    /// Whether any band currently runs as a biquad
+    fn biquads_active(&self) -> bool {
+        self.phase_mode == PhaseMode::Minimum
+            && self.low_bands.iter().enumerate().any(|(i, &(_, active))| {
+                active || self.filters.iter().any(|bank| bank[i].is_ramping())
+            })
+    }
+
+    /// Number of channels processed
+    pub fn channels(&self) -> u16 {
+        self.convolver.channels()
+    }
+
+    /// Complex response of the enabled bands at `frequencies` (Hz)
+    ///
+    /// The design target of the FIR and biquads, including the phase mode's
+    /// latency, evaluated analytically at the processor's sample rate.
+    /// Near-flat bands are skipped as in processing.
+    pub fn frequency_response(&self, frequencies: &[f32]) -> Vec<Complex64> {
+        let share = self.phase_mode.minimum_share() as f64;
+        let delay = self.latency_samples() as f64;
+        let mut magnitude = vec![1.0f64; frequencies.len()];
+        let mut phase: Vec<f64> = frequencies
+            .iter()
+            .map(|&f| -2.0 * std::f64::consts::PI * f as f64 / self.sample_rate as f64 * delay)
+            .collect();
+
+        let design = self.shared.design.lock();
+        for band in design.bands.iter().filter(|band| band.is_active()) {
+            let coeffs = design.band_coefficients(band);
+            for (i, &frequency) in frequencies.iter().enumerate() {
+                let h = coeffs.response(frequency, self.sample_rate);
+                magnitude[i] *= h.norm();
+                phase[i] += share * h.arg();
+            }
+        }
+
+        magnitude
+            .iter()
+            .zip(&phase)
+            .map(|(&m, &p)| Complex64::from_polar(m, p))
+            .collect()
+    }
+
     /// Get number of bands
     pub fn num_bands(&self) -> usize {
-        self.bands.len()
+        self.shared.design.lock().bands.len()
     }
     
     /// Reset all bands to flat response (0dB)
-    pub fn reset(&mut self) {
-        for i in 0..self.bands.len() {
-            let _ = self.set_band_gain(i, 0.0);
+    ///
+    /// `Filter::reset` clears the signal history instead.
+    pub fn flatten_bands(&mut self) {
+        {
+            let mut design = self.shared.design.lock();
+            design.bands.iter_mut().for_each(|band| band.gain_db = 0.0);
+            design.curve.clear();
+            design.fir_dirty = true;
+            self.shared.publish(&mut design);
         }
+        self.filters.iter_mut().flatten().for_each(Filter::reset);
+    }
+}
+
+impl Filter for EqProcessor {
+    /// Processes the channels the processor was created for; a block with a
+    /// different layout, or that fails to process, passes through delayed
+    fn process(&mut self, input: &[f32], output: &mut [f32], layout: ChannelLayout) {
+        let processed = layout.channels() == self.channels() as usize
+            && match EqProcessor::process(self, input, output) {
+                Ok(_) => true,
+                Err(e) => {
+                    log::error!("EQ processing failed: {}", e);
+                    false
+                }
+            };
+
+        // Run the dry delay on every block, so a switch to bypass continues
+        // from the current signal
+        let mask = self.metadata.channel_mask;
+        for (i, (out, &x)) in output.iter_mut().zip(input).enumerate() {
+            let dry = self.dry.tick(x);
+            if !processed || !mask.contains(i % layout.channels()) {
+                *out = dry;
+            }
+        }
+    }
+
+    fn process_bypassed(&mut self, input: &[f32], output: &mut [f32], _layout: ChannelLayout) {
+        self.dry.process(input, output);
+    }
+
+    fn prepare(&mut self, layout: ChannelLayout, max_samples: usize) {
+        EqProcessor::prepare(self, max_samples / layout.channels());
+        self.dry.set_len(self.latency_samples() * self.channels() as usize);
+    }
+
+    fn latency_samples(&self) -> usize {
+        EqProcessor::latency_samples(self)
+    }
+
+    fn metadata(&self) -> &FilterMetadata {
+        &self.metadata
+    }
+
+    fn set_bypass(&mut self, bypass: bool) {
+        self.metadata.bypass = bypass;
+    }
+
+    fn set_channel_mask(&mut self, mask: ChannelMask) {
+        self.metadata.channel_mask = mask;
+    }
+
+    /// The design response at the processor's own sample rate
+    fn frequency_response(&self, frequencies: &[f32], _sample_rate: f32) -> Vec<Complex64> {
+        EqProcessor::frequency_response(self, frequencies)
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.metadata.bypass
+    }
+
+    /// Clears the signal history; band gains are kept
+    fn reset(&mut self) {
+        self.convolver.reset();
+        self.fading.reset();
+        self.history.clear();
+        if let FirPath::Priming { .. } = self.path {
+            // Cleared history is exactly what the reset convolver assumes
+            self.path = FirPath::Convolver;
+        }
+        self.fade_position = self.fade_length;
+        self.filters.iter_mut().flatten().for_each(Filter::reset);
+        self.dry.reset();
+    }
+
+    fn clone_box(&self) -> Box<dyn Filter> {
+        let bands = self.shared.design.lock().bands.clone();
+        let mut eq = EqProcessor::new(bands.len(), self.sample_rate, self.channels())
+            .expect("settings were valid for the original");
+        eq.metadata = self.metadata.clone();
+        eq.fade_length = self.fade_length;
+        eq.fade_position = self.fade_length;
+        eq.phase_mode = self.phase_mode;
+        {
+            // One design for all bands, taken over without a crossfade
+            let mut design = eq.shared.design.lock();
+            design.bands = bands;
+            design.phase_mode = self.phase_mode;
+            design.rebuild_curve();
+            eq.shared.publish(&mut design);
+        }
+        eq.take_update(false).expect("the design matches the clone's convolver");
+        eq.dry.set_len(self.dry.len());
+        Box::new(eq)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::audio::dsp::fir_design;
+    
+    /// A band as last set on the control side
+    fn design_band(eq: &EqProcessor, index: usize) -> EqBand {
+        eq.shared.design.lock().bands[index].clone()
+    }
     
     #[test]
     fn test_eq_creation() {
-        let eq = EqProcessor::new_512band(48000.0);
+        let eq = EqProcessor::new_512band(48000.0, 2);
         assert!(eq.is_ok());
         assert_eq!(eq.unwrap().num_bands(), 512);
     }
     
     #[test]
     fn test_set_band_gain() {
-        let mut eq = EqProcessor::new(10, 48000.0).unwrap();
+        let mut eq = EqProcessor::new(10, 48000.0, 1).unwrap();
         assert!(eq.set_band_gain(0, 6.0).is_ok());
-        assert_eq!(eq.bands[0].gain_db, 6.0);
+        assert_eq!(design_band(&eq, 0).gain_db, 6.0);
     }
     
     #[test]
     fn test_invalid_band_index() {
-        let mut eq = EqProcessor::new(10, 48000.0).unwrap();
+        let mut eq = EqProcessor::new(10, 48000.0, 1).unwrap();
         assert!(eq.set_band_gain(100, 6.0).is_err());
     }
     
     #[test]
     fn test_process() {
-        let mut eq = EqProcessor::new(10, 48000.0).unwrap();
+        let mut eq = EqProcessor::new(10, 48000.0, 1).unwrap();
         let input = vec![1.0; 512];
         let mut output = vec![0.0; 512];
         
-        assert!(eq.process(&input, &mut output).is_ok());
+        assert_eq!(eq.process(&input, &mut output).unwrap(), 512);
     }
     
     #[test]
-    fn test_reset() {
-        let mut eq = EqProcessor::new(10, 48000.0).unwrap();
+    fn test_flatten_bands() {
+        let mut eq = EqProcessor::new(10, 48000.0, 1).unwrap();
         eq.set_band_gain(0, 6.0).unwrap();
-        eq.reset();
+        eq.flatten_bands();
+        
+        assert_eq!(design_band(&eq, 0).gain_db, 0.0);
+    }
+    
+    #[test]
+    fn test_frequency_response_matches_processing() {
+        let mut eq = EqProcessor::new(10, 48000.0, 1).unwrap();
+        eq.set_band_gain(4, 6.0).unwrap();
+        eq.set_band_gain(7, -9.0).unwrap();
+        
+        let frequencies = [design_band(&eq, 4).frequency, design_band(&eq, 7).frequency, 20.0];
+        let response = eq.frequency_response(&frequencies);
+        
+        // Steady-state gain of a tone at each frequency
+        for (&frequency, h) in frequencies.iter().zip(&response) {
+            eq.convolver.reset();
+            let tone: Vec<f32> = (0..96000)
+                .map(|n| (2.0 * std::f32::consts::PI * frequency * n as f32 / 48000.0).sin())
+                .collect();
+            let mut output = vec![0.0; tone.len()];
+            eq.process(&tone, &mut output).unwrap();
+            let peak = output[48000..].iter().fold(0.0f32, |m, &x| m.max(x.abs()));
+            assert!((peak as f64 / h.norm() - 1.0).abs() < 0.01, "{} Hz", frequency);
+        }
+        assert!((20.0 * response[0].norm().log10() - 6.0).abs() < 0.5);
+    }
+    
+    #[test]
+    fn test_stereo_matches_independent_channels() {
+        let left: Vec<f32> = (0..1024).map(|i| (i as f32 * 0.05).sin()).collect();
+        let right: Vec<f32> = (0..1024).map(|i| (i as f32 * 0.7).cos() * 0.5).collect();
+        let interleaved: Vec<f32> = left.iter().zip(&right).flat_map(|(&l, &r)| [l, r]).collect();
+        let configure = |channels| {
+            let mut eq = EqProcessor::new(10, 48000.0, channels).unwrap();
+            eq.set_band_gain(3, 6.0).unwrap();
+            eq.set_band_gain(7, -4.0).unwrap();
+            eq
+        };
+        
+        let mono: Vec<Vec<f32>> = [&left, &right]
+            .iter()
+            .map(|channel| {
+                let mut eq = configure(1);
+                let mut output = vec![0.0; channel.len()];
+                eq.process(channel, &mut output).unwrap();
+                output
+            })
+            .collect();
+        
+        let mut stereo = configure(2);
+        let mut output = vec![0.0; interleaved.len()];
+        assert_eq!(stereo.process(&interleaved, &mut output).unwrap(), 1024);
+        let (l, r): (Vec<f32>, Vec<f32>) = output.chunks(2).map(|frame| (frame[0], frame[1])).unzip();
+        assert_eq!(l, mono[0]);
+        assert_eq!(r, mono[1]);
+        
+        let mut planar = configure(2);
+        let (mut l, mut r) = (vec![0.0; 1024], vec![0.0; 1024]);
+        planar.process_planar(&[&left, &right], &mut [&mut l, &mut r]).unwrap();
+        assert_eq!([l, r], [mono[0].clone(), mono[1].clone()]);
         
-        assert_eq!(eq.bands[0].gain_db, 0.0);
+        assert!(stereo.process(&interleaved[..3], &mut output[..3]).is_err());
+    }
+    
+    #[test]
+    fn test_512_bands_match_target() {
+        let mut eq = EqProcessor::new_512band(48000.0, 1).unwrap();
+        for band in (0..512).step_by(3) {
+            eq.set_band_gain(band, if band % 2 == 0 { 4.0 } else { -3.0 }).unwrap();
+        }
+        
+        let ir = impulse_response(&mut eq);
+        let taps: Vec<f64> = ir[..FIR_LENGTH].iter().map(|&x| x as f64).collect();
+        let frequencies = [100.0, 1000.0, 5000.0, 15000.0];
+        for (&frequency, target) in frequencies.iter().zip(eq.frequency_response(&frequencies)) {
+            let gain_db = 20.0 * fir_design::magnitude(&taps, frequency as f64 / 48000.0).log10();
+            let target_db = 20.0 * target.norm().log10();
+            assert!((gain_db - target_db).abs() < 0.05, "{} Hz: {} vs {} dB", frequency, gain_db, target_db);
+        }
+    }
+    
+    #[test]
+    fn test_band_change_keeps_history() {
+        let tone: Vec<f32> = (0..8192).map(|n| (n as f32 * 0.03).sin()).collect();
+        let configure = || {
+            let mut eq = EqProcessor::new(32, 48000.0, 1).unwrap();
+            eq.set_band_gain(10, 6.0).unwrap();
+            eq
+        };
+        
+        let mut steady = configure();
+        let mut expected = vec![0.0; tone.len()];
+        steady.process(&tone, &mut expected).unwrap();
+        
+        // Redesigning mid-stream with the same curve must not disturb the output
+        let mut changed = configure();
+        let mut output = vec![0.0; tone.len()];
+        changed.process(&tone[..3000], &mut output[..3000]).unwrap();
+        changed.set_band_gain(20, 3.0).unwrap();
+        changed.set_band_gain(20, 0.0).unwrap();
+        changed.process(&tone[3000..], &mut output[3000..]).unwrap();
+        for (n, (a, b)) in output.iter().zip(&expected).enumerate() {
+            assert!((a - b).abs() < 1e-4, "sample {}: {} vs {}", n, a, b);
+        }
+    }
+    
+    #[test]
+    fn test_low_bands_run_as_biquads_at_high_rates() {
+        let mut eq = EqProcessor::new(64, 192000.0, 2).unwrap();
+        // 20 Hz and ~24 Hz sit below 0.1% of 192 kHz
+        assert!(eq.low_bands.len() > 2);
+        assert_eq!(eq.filters[0][0].topology(), BiquadTopology::StateVariable);
+        eq.set_band_gain(0, 9.0).unwrap();
+        eq.set_band_gain(30, -6.0).unwrap();
+        
+        // The 20 Hz boost rings for ~30000 samples, far longer than the FIR
+        for (band, phase_mode) in [(0, PhaseMode::Minimum), (30, PhaseMode::Minimum), (30, PhaseMode::Linear)] {
+            eq.set_phase_mode(phase_mode).unwrap();
+            let frequency = design_band(&eq, band).frequency;
+            let tone: Vec<f32> = (0..192000 * 2)
+                .flat_map(|n| {
+                    let x = (2.0 * std::f64::consts::PI * frequency as f64 * n as f64 / 192000.0).sin() as f32;
+                    [x, x]
+                })
+                .collect();
+            let mut output = vec![0.0; tone.len()];
+            eq.process(&tone, &mut output).unwrap();
+            
+            let peak = output[192000 * 2..].iter().fold(0.0f32, |m, &x| m.max(x.abs()));
+            let expected = eq.frequency_response(&[frequency])[0].norm();
+            assert!((peak as f64 / expected - 1.0).abs() < 0.01, "{} Hz {:?}: {} vs {}", frequency, phase_mode, peak, expected);
+        }
+    }
+    
+    #[test]
+    fn test_band_change_crossfades() {
+        // Jumps show up as spikes in the second difference of the output
+        let largest_curvature = |smoothing_ms| {
+            let mut eq = EqProcessor::new(32, 48000.0, 1).unwrap();
+            eq.set_smoothing_ms(smoothing_ms).unwrap();
+            let frequency = design_band(&eq, 12).frequency;
+            let tone: Vec<f32> = (0..9600)
+                .map(|n| (2.0 * std::f32::consts::PI * frequency * n as f32 / 48000.0).sin())
+                .collect();
+            let mut output = vec![0.0; tone.len()];
+            for (n, (input, output)) in tone.chunks(256).zip(output.chunks_mut(256)).enumerate() {
+                if n == 10 {
+                    eq.set_band_gain(12, 12.0).unwrap();
+                }
+                eq.process(input, output).unwrap();
+            }
+            output.windows(3).map(|w| (w[2] - 2.0 * w[1] + w[0]).abs()).fold(0.0f32, f32::max)
+        };
+        
+        // The tone curves by at most 0.02 per sample, 0.08 at +12 dB
+        let instant = largest_curvature(0.0);
+        let smoothed = largest_curvature(DEFAULT_SMOOTHING_MS);
+        assert!(instant > 0.2, "{}", instant);
+        assert!(smoothed < 0.1, "{}", smoothed);
+        assert!(EqProcessor::new(32, 48000.0, 1).unwrap().set_smoothing_ms(-1.0).is_err());
+    }
+    
+    #[test]
+    fn test_controller_applies_on_next_block() {
+        let mut eq = EqProcessor::new(32, 48000.0, 2).unwrap();
+        let controller = eq.controller();
+        assert_eq!(controller.num_bands(), 32);
+        assert!(controller.set_band_gain(32, 6.0).is_err());
+        std::thread::spawn(move || {
+            controller.set_band_gain(5, 6.0).unwrap();
+            controller.set_band_gain(9, -3.0).unwrap();
+        })
+        .join()
+        .unwrap();
+        
+        // Both changes were designed on the controller's thread into one update
+        assert_eq!(eq.shared.updates.len(), 1);
+        let mut output = vec![0.0; 512];
+        eq.process(&[0.0; 512], &mut output).unwrap();
+        assert!(eq.shared.updates.is_empty());
+        assert_eq!(eq.shared.retired.len(), 1);
+        
+        // Direct changes go through the same design; the next one reuses the
+        // retired update
+        eq.set_band_gain(5, 2.0).unwrap();
+        eq.controller().set_band_gain(9, 1.0).unwrap();
+        assert!(eq.shared.retired.is_empty());
+        assert_eq!(design_band(&eq, 5).gain_db, 2.0);
+        assert_eq!(design_band(&eq, 9).gain_db, 1.0);
+        
+        let ir = impulse_response(&mut eq);
+        let left: Vec<f64> = ir.iter().step_by(2).take(FIR_LENGTH).map(|&x| x as f64).collect();
+        for band in [5, 9] {
+            let frequency = design_band(&eq, band).frequency;
+            let target = eq.frequency_response(&[frequency])[0].norm();
+            let actual = fir_design::magnitude(&left, frequency as f64 / 48000.0);
+            assert!((actual / target - 1.0).abs() < 0.01, "band {}: {} vs {}", band, actual, target);
+        }
+    }
+
+    #[test]
+    fn test_update_waits_for_crossfade() {
+        let mut eq = EqProcessor::new(32, 48000.0, 1).unwrap();
+        let controller = eq.controller();
+        let mut output = vec![0.0; 256];
+        
+        // The first design has no signal to fade from and applies at once
+        controller.set_band_gain(10, 3.0).unwrap();
+        eq.process(&[0.0; 256], &mut output).unwrap();
+        controller.set_band_gain(12, 6.0).unwrap();
+        eq.process(&[0.0; 256], &mut output).unwrap();
+        controller.set_band_gain(14, -6.0).unwrap();
+        controller.set_band_gain(16, 3.0).unwrap();
+        
+        // The fade takes four blocks; the second design waits it out
+        for _ in 0..3 {
+            eq.process(&[0.0; 256], &mut output).unwrap();
+            assert_eq!(eq.shared.updates.len(), 1);
+        }
+        eq.process(&[0.0; 256], &mut output).unwrap();
+        assert!(eq.shared.updates.is_empty());
+    }
+
+    #[test]
+    fn test_flat_curve_skips_convolver() {
+        let mut eq = EqProcessor::new(10, 48000.0, 2).unwrap();
+        eq.set_phase_mode(PhaseMode::Linear).unwrap();
+        eq.set_band_gain(3, 0.05).unwrap();
+        let input: Vec<f32> = (0..8192 * 2).map(|i| (i as f32 * 0.37).sin()).collect();
+        let mut output = vec![0.0; input.len()];
+        for (input, output) in input.chunks(512).zip(output.chunks_mut(512)) {
+            eq.process(input, output).unwrap();
+        }
+        
+        // A near-flat band leaves the FIR a delay, which the history gives exactly
+        assert_eq!(eq.path, FirPath::Flat);
+        let latency = eq.latency_samples() * 2;
+        assert!(output[..latency].iter().all(|&x| x == 0.0));
+        assert_eq!(output[latency..], input[..input.len() - latency]);
+    }
+
+    #[test]
+    fn test_leaving_flat_catches_up() {
+        let tone: Vec<f32> = (0..48000).map(|n| (n as f32 * 0.03).sin() + (n as f32 * 0.4).sin() * 0.3).collect();
+        let mut steady = EqProcessor::new(32, 48000.0, 1).unwrap();
+        steady.set_band_gain(10, 6.0).unwrap();
+        let mut expected = vec![0.0; tone.len()];
+        steady.process(&tone, &mut expected).unwrap();
+        
+        // Planar blocks: flat, then the same band, then flat again
+        let mut eq = EqProcessor::new(32, 48000.0, 1).unwrap();
+        let mut output = vec![0.0; tone.len()];
+        let mut caught_up = None;
+        for (block, (input, output)) in tone.chunks(256).zip(output.chunks_mut(256)).enumerate() {
+            match block {
+                40 => eq.set_band_gain(10, 6.0).unwrap(),
+                120 => eq.set_band_gain(10, 0.0).unwrap(),
+                _ => {}
+            }
+            eq.process_planar(&[input], &mut [output]).unwrap();
+            if block > 40 && caught_up.is_none() && settled(&eq) {
+                caught_up = Some((block + 1) * 256);
+            }
+        }
+        
+        // Catching up on 40 blocks at twice real time, then the crossfade
+        let caught_up = caught_up.unwrap();
+        assert!(caught_up <= (41 + 40 + 4) * 256, "{}", caught_up);
+        for n in caught_up..120 * 256 {
+            assert!((output[n] - expected[n]).abs() < 1e-4, "sample {}: {} vs {}", n, output[n], expected[n]);
+        }
+        assert_eq!(eq.path, FirPath::Flat);
+        assert_eq!(output[125 * 256..], tone[125 * 256..]);
+    }
+
+    #[test]
+    fn test_prepared_processing_does_not_allocate() {
+        use crate::audio::alloc_check::heap_operations;
+
+        for phase_mode in [PhaseMode::Minimum, PhaseMode::Linear] {
+            let mut eq = EqProcessor::new(32, 48000.0, 2).unwrap();
+            eq.set_phase_mode(phase_mode).unwrap();
+            eq.prepare(256);
+            let controller = eq.controller();
+            let input: Vec<f32> = (0..512).map(|i| (i as f32 * 0.1).sin()).collect();
+            let mut output = vec![0.0; 512];
+
+            // Gain changes redesign the FIR and crossfade to it in place; the
+            // first also has the idle convolver catch up on the flat blocks
+            for block in 0..40 {
+                if block % 10 == 5 {
+                    controller.set_band_gain(block / 10, 3.0).unwrap();
+                }
+                let (result, operations) = heap_operations(|| eq.process(&input, &mut output));
+                result.unwrap();
+                assert_eq!(operations, 0, "{:?} block {}", phase_mode, block);
+            }
+        }
+    }
+
+    /// Whether the processor has taken over the latest design and finished
+    /// catching up, fading and ramping to it
+    fn settled(eq: &EqProcessor) -> bool {
+        eq.shared.updates.is_empty()
+            && !matches!(eq.path, FirPath::Priming { .. })
+            && eq.fade_position >= eq.fade_length
+            && !eq.filters.iter().flatten().any(BiquadFilter::is_ramping)
+    }
+
+    /// Interleaved response to a unit impulse on every channel, once the
+    /// processor has settled on the current design
+    fn impulse_response(eq: &mut EqProcessor) -> Vec<f32> {
+        let channels = eq.channels() as usize;
+        let silence = vec![0.0; 256 * channels];
+        while !settled(eq) {
+            eq.process(&silence, &mut silence.clone()).unwrap();
+        }
+        let mut impulse = vec![0.0; FIR_LENGTH * 2 * channels];
+        impulse[..channels].fill(1.0);
+        let mut output = vec![0.0; impulse.len()];
+        eq.process(&impulse, &mut output).unwrap();
+        output
+    }
+    
+    #[test]
+    fn test_phase_mode_latency() {
+        let mut eq = EqProcessor::new(10, 48000.0, 1).unwrap();
+        assert_eq!(eq.latency_samples(), 0);
+        eq.set_phase_mode(PhaseMode::Linear).unwrap();
+        assert_eq!(eq.latency_samples(), FIR_LENGTH / 2);
+        eq.set_phase_mode(PhaseMode::Mixed(0.5)).unwrap();
+        assert_eq!(eq.latency_samples(), FIR_LENGTH / 4);
+        
+        assert!(eq.set_phase_mode(PhaseMode::Mixed(1.5)).is_err());
+        assert_eq!(eq.phase_mode(), PhaseMode::Mixed(0.5));
+    }
+    
+    #[test]
+    fn test_linear_phase_fir() {
+        let mut eq = EqProcessor::new(10, 48000.0, 1).unwrap();
+        eq.set_band_gain(4, 6.0).unwrap();
+        eq.set_band_gain(7, -9.0).unwrap();
+        eq.set_phase_mode(PhaseMode::Linear).unwrap();
+        
+        let ir = impulse_response(&mut eq);
+        let latency = eq.latency_samples();
+        let peak = ir.iter().enumerate().fold(0, |best, (n, &x)| if x.abs() > ir[best].abs() { n } else { best });
+        assert_eq!(peak, latency);
+        for offset in 1..latency {
+            assert!((ir[latency - offset] - ir[latency + offset]).abs() < 1e-6, "asymmetric at {}", offset);
+        }
+        
+        let taps: Vec<f64> = ir[..FIR_LENGTH].iter().map(|&x| x as f64).collect();
+        let frequencies = [design_band(&eq, 4).frequency, design_band(&eq, 7).frequency, 1000.0];
+        for (&frequency, target) in frequencies.iter().zip(eq.frequency_response(&frequencies)) {
+            let gain_db = 20.0 * fir_design::magnitude(&taps, frequency as f64 / 48000.0).log10();
+            let target_db = 20.0 * target.norm().log10();
+            assert!((gain_db - target_db).abs() < 0.05, "{} Hz: {} vs {} dB", frequency, gain_db, target_db);
+        }
+    }
+    
+    #[test]
+    fn test_mixed_phase_tracks_band_changes() {
+        let mut eq = EqProcessor::new(10, 48000.0, 2).unwrap();
+        eq.set_phase_mode(PhaseMode::Mixed(0.5)).unwrap();
+        
+        // Flat bands give a pure delay
+        let ir = impulse_response(&mut eq);
+        let latency = eq.latency_samples();
+        assert!((ir[latency * 2] - 1.0).abs() < 1e-4);
+        assert!((ir[latency * 2 + 1] - 1.0).abs() < 1e-4);
+        
+        eq.flatten_bands();
+        eq.set_band_gain(6, 9.0).unwrap();
+        let ir = impulse_response(&mut eq);
+        let left: Vec<f64> = ir.iter().step_by(2).take(FIR_LENGTH).map(|&x| x as f64).collect();
+        let frequency = design_band(&eq, 6).frequency;
+        let gain_db = 20.0 * fir_design::magnitude(&left, frequency as f64 / 48000.0).log10();
+        assert!((gain_db - 9.0).abs() < 0.05, "{} dB", gain_db);
+        assert_eq!(ir.iter().step_by(2).collect::<Vec<_>>(), ir.iter().skip(1).step_by(2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_bypass_keeps_latency_in_chain() {
+        use crate::audio::filters::FilterChain;
+
+        let mut eq = EqProcessor::new(10, 48000.0, 2).unwrap();
+        eq.set_phase_mode(PhaseMode::Linear).unwrap();
+        let mut chain = FilterChain::new();
+        let id = chain.add_filter(Box::new(eq)).unwrap();
+        chain.add_filter(Box::new(BiquadFilter::peaking(1000.0, 48000.0, 1.0, 0.0))).unwrap();
+        chain.prepare(ChannelLayout::STEREO, 1024);
+        assert_eq!(chain.latency_samples(), FIR_LENGTH / 2);
+
+        // Flat bands delay an impulse by the latency, bypassed or not
+        let latency = chain.latency_samples();
+        let run = |chain: &mut FilterChain| {
+            let mut signal = vec![0.0; (latency + 512) * 2];
+            signal[0] = 1.0;
+            signal[1] = 1.0;
+            for block in signal.chunks_mut(1024) {
+                chain.process_in_place(block, ChannelLayout::STEREO);
+            }
+            signal
+        };
+        let active = run(&mut chain);
+        assert!((active[latency * 2] - 1.0).abs() < 1e-4);
+        chain.reset_all();
+        chain.set_filter_bypass(&id, true).unwrap();
+        assert_eq!(chain.latency_samples(), FIR_LENGTH / 2);
+        let bypassed = run(&mut chain);
+        assert_eq!(bypassed[latency * 2..latency * 2 + 2], [1.0, 1.0]);
+        assert!(bypassed.iter().enumerate().all(|(i, &x)| x == 0.0 || i / 2 == latency));
     }
 }
@@ -0,0 +1,115 @@
+//! Kaiser-window FIR design helpers
+//!
+//! Shared by the decimators and resamplers. Frequencies are normalized to the
+//! sample rate (0.5 = Nyquist) and coefficients are computed in f64.
+
+use std::f64::consts::PI;
+
+/// Zeroth-order modified Bessel function of the first kind
+pub fn bessel_i0(x: f64) -> f64 {
+    let half_x = x / 2.0;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..64 {
+        term *= (half_x / k as f64) * (half_x / k as f64);
+        sum += term;
+        if term < sum * 1e-17 {
+            break;
+        }
+    }
+    sum
+}
+
+/// Kaiser window shape parameter for a stopband attenuation in dB
+pub fn kaiser_beta(attenuation_db: f64) -> f64 {
+    if attenuation_db > 50.0 {
+        0.1102 * (attenuation_db - 8.7)
+    } else if attenuation_db >= 21.0 {
+        0.5842 * (attenuation_db - 21.0).powf(0.4) + 0.07886 * (attenuation_db - 21.0)
+    } else {
+        0.0
+    }
+}
+
+/// Estimated number of taps for an attenuation and normalized transition width
+pub fn kaiser_length(attenuation_db: f64, transition_width: f64) -> usize {
+    ((attenuation_db - 7.95) / (14.36 * transition_width)).ceil() as usize + 1
+}
+
+/// Kaiser window of `length` points
+pub fn kaiser_window(length: usize, beta: f64) -> Vec<f64> {
+    let center = (length - 1) as f64 / 2.0;
+    (0..length)
+        .map(|n| {
+            let ratio = if center > 0.0 { (n as f64 - center) / center } else { 0.0 };
+            kaiser(ratio, beta)
+        })
+        .collect()
+}
+
+/// Kaiser window value at `ratio` in [-1, 1] from its centre
+pub fn kaiser(ratio: f64, beta: f64) -> f64 {
+    bessel_i0(beta * (1.0 - ratio * ratio).max(0.0).sqrt()) / bessel_i0(beta)
+}
+
+/// Normalized sinc, sin(pi x) / (pi x)
+pub fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-12 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Windowed-sinc lowpass with unity DC gain
+///
+/// `cutoff` is the -6 dB point, normalized to the sample rate.
+pub fn lowpass(length: usize, cutoff: f64, beta: f64) -> Vec<f64> {
+    let center = (length - 1) as f64 / 2.0;
+    let mut taps: Vec<f64> = kaiser_window(length, beta)
+        .into_iter()
+        .enumerate()
+        .map(|(n, w)| 2.0 * cutoff * sinc(2.0 * cutoff * (n as f64 - center)) * w)
+        .collect();
+
+    let dc_gain: f64 = taps.iter().sum();
+    taps.iter_mut().for_each(|t| *t /= dc_gain);
+    taps
+}
+
+/// Magnitude response of an FIR at a normalized frequency
+pub fn magnitude(taps: &[f64], frequency: f64) -> f64 {
+    let (re, im) = taps.iter().enumerate().fold((0.0, 0.0), |(re, im), (n, &t)| {
+        let phase = -2.0 * PI * frequency * n as f64;
+        (re + t * phase.cos(), im + t * phase.sin())
+    });
+    (re * re + im * im).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bessel_i0() {
+        assert!((bessel_i0(0.0) - 1.0).abs() < 1e-15);
+        assert!((bessel_i0(1.0) - 1.266_065_877_752_008_4).abs() < 1e-12);
+        assert!((bessel_i0(10.0) - 2_815.716_628_466_254).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_lowpass_meets_specification() {
+        // 100 dB stopband, transition 0.2 - 0.3
+        let attenuation = 100.0;
+        let length = kaiser_length(attenuation, 0.1) | 1;
+        let taps = lowpass(length, 0.25, kaiser_beta(attenuation));
+
+        assert!((taps.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        for i in 0..=40 {
+            let pass = magnitude(&taps, 0.2 * i as f64 / 40.0);
+            assert!((pass - 1.0).abs() < 1e-4, "passband ripple {}", pass);
+            let stop = magnitude(&taps, 0.3 + 0.2 * i as f64 / 40.0);
+            assert!(20.0 * stop.log10() < -attenuation + 1.0, "stopband leak {}", stop);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Combined response of many EQ bands, realized as one FIR
+//!
+//! Cascading hundreds of biquads costs one filter per band per sample and
+//! loses precision in f32. Instead the bands' responses are sampled on the
+//! DFT bin grid of a `fir_length`-tap FIR and summed there as log magnitude
+//! and phase, so the whole curve costs a single convolution and changing one
+//! band costs one pass over the bins, with no trigonometry beyond one
+//! `atan2` per bin.
+
+use super::fir_design;
+use crate::audio::filters::BiquadCoefficients;
+use rustfft::num_complex::Complex64;
+use rustfft::{Fft, FftPlanner};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// Kaiser beta tapering the designed FIR
+const WINDOW_BETA: f64 = 6.0;
+
+/// Band responses accumulated on the bins of an FIR
+pub struct GraphicEq {
+    fir_length: usize,
+    // Per bin 0..=fir_length / 2: sum of ln|H| and of arg(H) over the bands
+    log_magnitude: Vec<f64>,
+    phase: Vec<f64>,
+    // z^-1 on the unit circle at each bin, for evaluating band responses
+    unit_delays: Vec<Complex64>,
+    // Kaiser window around `window_delay`, kept until the delay changes
+    window: Vec<f64>,
+    window_delay: Option<usize>,
+    inverse_fft: Arc<dyn Fft<f64>>,
+    spectrum: Vec<Complex64>,
+    fft_scratch: Vec<Complex64>,
+}
+
+impl GraphicEq {
+    /// Flat response for an FIR of `fir_length` taps (a power of two)
+    pub fn new(fir_length: usize) -> Self {
+        let bins = fir_length / 2 + 1;
+        let inverse_fft = FftPlanner::new().plan_fft_inverse(fir_length);
+        let unit_delays = (0..bins)
+            .map(|k| Complex64::from_polar(1.0, -2.0 * PI * k as f64 / fir_length as f64))
+            .collect();
+        Self {
+            fir_length,
+            log_magnitude: vec![0.0; bins],
+            phase: vec![0.0; bins],
+            unit_delays,
+            window: vec![0.0; fir_length],
+            window_delay: None,
+            fft_scratch: vec![Complex64::default(); inverse_fft.get_inplace_scratch_len()],
+            inverse_fft,
+            spectrum: vec![Complex64::default(); fir_length],
+        }
+    }
+
+    /// Add a band's response to the curve
+    pub fn add_band(&mut self, coeffs: &BiquadCoefficients) {
+        self.accumulate(coeffs, 1.0);
+    }
+
+    /// Take back a response previously passed to `add_band`
+    pub fn remove_band(&mut self, coeffs: &BiquadCoefficients) {
+        self.accumulate(coeffs, -1.0);
+    }
+
+    /// Return to a flat response
+    pub fn clear(&mut self) {
+        self.log_magnitude.fill(0.0);
+        self.phase.fill(0.0);
+    }
+
+    /// FIR length in taps
+    pub fn fir_length(&self) -> usize {
+        self.fir_length
+    }
+
+    /// Design the FIR for the current curve
+    ///
+    /// The taps have the summed magnitude, `minimum_share` (0 to 1) of the
+    /// summed band phase and a bulk delay of `delay` samples. Biquad bands are
+    /// minimum phase, so a share of 1 with no delay reproduces the cascade
+    /// itself and a share of 0 is linear phase. The inverse transform is
+    /// Kaiser windowed around the delay, spanning the whole FIR either side;
+    /// band responses longer than that are truncated.
+    pub fn design(&mut self, minimum_share: f32, delay: usize) -> Vec<f32> {
+        let mut taps = vec![0.0; self.fir_length];
+        self.design_into(minimum_share, delay, &mut taps);
+        taps
+    }
+
+    /// `design` into the first `fir_length` slots of `taps`, without allocating
+    pub fn design_into(&mut self, minimum_share: f32, delay: usize, taps: &mut [f32]) {
+        let n = self.fir_length;
+        let share = minimum_share as f64;
+        for (k, (&log_magnitude, &phase)) in self.log_magnitude.iter().zip(&self.phase).enumerate() {
+            let phase = share * phase - 2.0 * PI * k as f64 * delay as f64 / n as f64;
+            self.spectrum[k] = Complex64::from_polar(log_magnitude.exp(), phase);
+        }
+        // A real FIR needs a real Nyquist bin and a conjugate-symmetric upper half
+        self.spectrum[n / 2] = Complex64::new(self.spectrum[n / 2].re, 0.0);
+        for k in 1..n / 2 {
+            self.spectrum[n - k] = self.spectrum[k].conj();
+        }
+        self.inverse_fft.process_with_scratch(&mut self.spectrum, &mut self.fft_scratch);
+
+        if self.window_delay != Some(delay) {
+            for (i, window) in self.window.iter_mut().enumerate() {
+                let offset = i as f64 - delay as f64;
+                let span = if offset < 0.0 { delay } else { n - delay };
+                *window = fir_design::kaiser(offset / span as f64, WINDOW_BETA);
+            }
+            self.window_delay = Some(delay);
+        }
+        for ((tap, value), &window) in taps.iter_mut().zip(&self.spectrum).zip(&self.window) {
+            *tap = (value.re / n as f64 * window) as f32;
+        }
+    }
+
+    fn accumulate(&mut self, coeffs: &BiquadCoefficients, sign: f64) {
+        for ((log_magnitude, phase), &z1) in self.log_magnitude.iter_mut().zip(&mut self.phase).zip(&self.unit_delays) {
+            let z2 = z1 * z1;
+            let numerator = coeffs.b0 + coeffs.b1 * z1 + coeffs.b2 * z2;
+            let denominator = 1.0 + coeffs.a1 * z1 + coeffs.a2 * z2;
+            // ln|N / D| and arg(N / D), without dividing or taking roots
+            *log_magnitude += sign * 0.5 * (numerator.norm_sqr() / denominator.norm_sqr()).ln();
+            // Summing per-band phase sidesteps unwrapping the total
+            *phase += sign * (numerator * denominator.conj()).arg();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flat_curve_is_a_delay() {
+        let mut eq = GraphicEq::new(1024);
+        let taps = eq.design(0.0, 512);
+        for (n, &tap) in taps.iter().enumerate() {
+            let expected = if n == 512 { 1.0 } else { 0.0 };
+            assert!((tap - expected).abs() < 1e-9, "tap {}: {}", n, tap);
+        }
+    }
+
+    #[test]
+    fn test_minimum_phase_matches_cascade() {
+        let bands = [
+            BiquadCoefficients::peaking(200.0, 48000.0, 1.0, 6.0),
+            BiquadCoefficients::peaking(3000.0, 48000.0, 2.0, -8.0),
+        ];
+        let mut eq = GraphicEq::new(4096);
+        bands.iter().for_each(|coeffs| eq.add_band(coeffs));
+        let taps: Vec<f64> = eq.design(1.0, 0).into_iter().map(f64::from).collect();
+
+        for frequency in [50.0f32, 200.0, 1000.0, 3000.0, 12000.0] {
+            let expected: f64 = bands.iter().map(|c| c.response(frequency, 48000.0).norm()).product();
+            let actual = fir_design::magnitude(&taps, frequency as f64 / 48000.0);
+            assert!((actual / expected - 1.0).abs() < 5e-3, "{} Hz: {} vs {}", frequency, actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_remove_band_restores_curve() {
+        let mut eq = GraphicEq::new(1024);
+        let kept = BiquadCoefficients::peaking(500.0, 48000.0, 1.0, 4.0);
+        let removed = BiquadCoefficients::peaking(5000.0, 48000.0, 1.0, -6.0);
+        eq.add_band(&kept);
+        let before = eq.design(0.5, 256);
+        eq.add_band(&removed);
+        eq.remove_band(&removed);
+        for (a, b) in before.iter().zip(eq.design(0.5, 256)) {
+            assert!((a - b).abs() < 1e-7);
+        }
+    }
+}
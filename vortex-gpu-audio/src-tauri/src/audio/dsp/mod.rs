@@ -1,10 +1,18 @@
 // DSP algorithm implementations
+pub mod channels;
 pub mod eq_processor;
 pub mod dsd_processor;
+pub mod dop;
+pub mod sigma_delta;
+pub mod fir_design;
 pub mod convolver;
+pub mod graphic_eq;
 pub mod resampler;
 
-pub use eq_processor::EqProcessor;
-pub use dsd_processor::DsdProcessor;
-pub use convolver::Convolver;
+pub use eq_processor::{EqController, EqProcessor, PhaseMode};
+pub use dsd_processor::{DecimationProfile, DsdProcessor, DsdRate};
+pub use dop::{DopDecoder, DopEncoder};
+pub use sigma_delta::{DsdOutputStage, SigmaDeltaModulator};
+pub use convolver::{Convolver, PartitionScheme, PartitionedIr};
+pub use graphic_eq::GraphicEq;
 pub use resampler::Resampler;
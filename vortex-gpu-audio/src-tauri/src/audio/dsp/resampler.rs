@@ -1,4 +1,6 @@
 use crate::error::VortexError;
+use crate::lockfree::AudioRingBuffer;
+use super::{channels, fir_design};
 
 /// Resampler quality presets
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -10,6 +12,7 @@ pub enum ResamplerQuality {
 }
 
 impl ResamplerQuality {
+    /// Filter taps, counted at the lower of the two sample rates
     pub fn filter_length(&self) -> usize {
         match self {
             ResamplerQuality::Draft => 16,
@@ -18,120 +21,596 @@ impl ResamplerQuality {
             ResamplerQuality::Maximum => 1024,
         }
     }
+
+    /// Guaranteed rejection of images and aliases
+    pub fn stopband_attenuation_db(&self) -> f64 {
+        match self {
+            ResamplerQuality::Draft => 60.0,
+            ResamplerQuality::Standard => 96.0,
+            ResamplerQuality::High => 120.0,
+            ResamplerQuality::Maximum => 150.0,
+        }
+    }
+}
+
+/// Headroom over the quality attenuation; Kaiser estimates run slightly short
+const DESIGN_MARGIN_DB: f64 = 6.0;
+
+/// Largest interpolation factor after reducing the rate ratio
+const MAX_PHASES: usize = 2048;
+
+/// Polyphase branches per input sample in asynchronous mode
+///
+/// Outputs between branches are cubic (Lagrange) interpolated, which keeps the
+/// interpolation error below -180 dB.
+const ASYNC_PHASES: usize = 128;
+
+/// Furthest the asynchronous ratio may move from nominal (2%)
+pub const MAX_RATIO_DEVIATION: f64 = 0.02;
+
+/// Damping of the drift control loop (slightly underdamped, no ringing)
+const DRIFT_DAMPING: f64 = 0.7;
+
+/// Shortest sweep of the drift correction across its range, as a fraction of
+/// the loop's response time
+const DRIFT_MIN_SWEEP: f64 = 0.25;
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Settings for the buffer-fill control loop of an asynchronous `Resampler`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DriftControl {
+    /// Fill level to hold the steered buffer at (0.0 to 1.0)
+    pub target_fill: f32,
+    /// Closed-loop time constant in seconds; longer is smoother but slower
+    pub response_secs: f64,
+    /// Largest correction the loop may apply, as a fraction of the nominal ratio
+    pub max_deviation: f64,
+}
+
+impl Default for DriftControl {
+    fn default() -> Self {
+        Self {
+            target_fill: 0.5,
+            response_secs: 10.0,
+            // 5000 ppm, far beyond real clock tolerances
+            max_deviation: 0.005,
+        }
+    }
+}
+
+/// State of the drift control loop
+#[derive(Debug, Clone, Default)]
+struct DriftLoop {
+    control: DriftControl,
+    // Low-passed fill level; None until the first measurement
+    smoothed_fill: Option<f64>,
+    integral: f64,
+    correction: f64,
+    // Output samples since the last update, the loop's time base
+    produced: usize,
+}
+
+/// How output sample times are generated
+enum Clock {
+    /// Fixed rational ratio; next output time in 1/L input samples
+    Synchronous { position: u64 },
+    /// Adjustable ratio; next output time in 1/ASYNC_PHASES input samples
+    Asynchronous { position: f64, drift: DriftLoop },
 }
 
 /// Polyphase FIR resampler
+///
+/// Resamples by the exact rational ratio `output_rate / input_rate` = L / M:
+/// conceptually upsampling by L, filtering with a Kaiser-windowed sinc and
+/// keeping every Mth sample, computed directly from the L polyphase branches.
+/// The stopband starts at the lower Nyquist frequency, so both images and
+/// aliases are rejected by the quality's stated attenuation.
+///
+/// In asynchronous mode (`new_async`) the ratio can be changed while running,
+/// either directly with `set_ratio` or by the built-in drift loop (`steer`),
+/// which holds an `AudioRingBuffer` at a target fill to bridge two clocks.
+///
+/// All channels share one clock but have separate filter state; buffers are
+/// interleaved for `process` and planar for `process_planar` (see `channels`).
 pub struct Resampler {
     input_rate: u32,
     output_rate: u32,
+    channels: usize,
     quality: ResamplerQuality,
     ratio: f64,
-    // Filter state
-    buffer: Vec<f32>,
-    position: f64,
+    nominal_ratio: f64,
+    // Branches (L, or ASYNC_PHASES) and input advance per output in 1/L samples
+    phases: usize,
+    step: usize,
+    taps_per_phase: usize,
+    // Branch coefficients, phase-major, each branch ordered oldest input first
+    coefficients: Vec<f64>,
+    // Passband edge in Hz
+    passband: f64,
+    // Filter state per channel: the history the next output needs, then unconsumed input
+    buffers: Vec<Vec<f32>>,
+    clock: Clock,
 }
 
 impl Resampler {
     /// Create a new resampler
-    pub fn new(input_rate: u32, output_rate: u32, quality: ResamplerQuality) -> Result<Self, VortexError> {
+    pub fn new(input_rate: u32, output_rate: u32, quality: ResamplerQuality, channels: u16) -> Result<Self, VortexError> {
+        Self::validate_rates(input_rate, output_rate)?;
+        let channels = channels::validate_channels(channels)?;
+
+        let divisor = gcd(input_rate, output_rate);
+        let phases = (output_rate / divisor) as usize;
+        let step = (input_rate / divisor) as usize;
+        if phases > MAX_PHASES {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "Ratio {}:{} needs {} polyphase branches, at most {} supported",
+                output_rate, input_rate, phases, MAX_PHASES
+            )).into());
+        }
+
+        let mut resampler = Self::with_phases(input_rate, output_rate, channels, quality, phases, step);
+        resampler.reset();
+        Ok(resampler)
+    }
+
+    /// Create an asynchronous resampler whose ratio can be adjusted at runtime
+    ///
+    /// Any pair of rates is accepted; `output_rate / input_rate` is the
+    /// nominal ratio.
+    pub fn new_async(input_rate: u32, output_rate: u32, quality: ResamplerQuality, channels: u16) -> Result<Self, VortexError> {
+        Self::validate_rates(input_rate, output_rate)?;
+        let channels = channels::validate_channels(channels)?;
+
+        let mut resampler = Self::with_phases(input_rate, output_rate, channels, quality, ASYNC_PHASES, 0);
+        resampler.clock = Clock::Asynchronous {
+            position: 0.0,
+            drift: DriftLoop::default(),
+        };
+        resampler.reset();
+        Ok(resampler)
+    }
+
+    fn validate_rates(input_rate: u32, output_rate: u32) -> Result<(), VortexError> {
         if input_rate == 0 || output_rate == 0 {
             return Err(crate::error::AudioError::InvalidParameter(
                 "Sample rates must be > 0".to_string()
             ).into());
         }
-        
+        Ok(())
+    }
+
+    /// Design the `phases`-branch filter bank for a rate pair
+    fn with_phases(
+        input_rate: u32,
+        output_rate: u32,
+        channels: usize,
+        quality: ResamplerQuality,
+        phases: usize,
+        step: usize,
+    ) -> Self {
         let ratio = output_rate as f64 / input_rate as f64;
+
+        // Taps are counted at the lower rate, so downsampling spans more inputs
         let filter_length = quality.filter_length();
-        
-        Ok(Self {
+        let taps_per_phase = filter_length * (input_rate as usize).div_ceil(input_rate.min(output_rate) as usize);
+        let length = taps_per_phase * phases;
+
+        // Normalized to the upsampled rate; the stopband begins at the lower Nyquist
+        let attenuation = quality.stopband_attenuation_db() + DESIGN_MARGIN_DB;
+        let upsampled_rate = input_rate as f64 * phases as f64;
+        let stopband = input_rate.min(output_rate) as f64 / 2.0 / upsampled_rate;
+        let transition = (attenuation - 7.95) / (14.36 * (length - 1) as f64);
+        let prototype = fir_design::lowpass(
+            length,
+            stopband - transition / 2.0,
+            fir_design::kaiser_beta(attenuation),
+        );
+
+        // Branch p holds taps p, p + L, p + 2L, ..., scaled by L for unity gain
+        let mut coefficients = Vec::with_capacity(length);
+        for phase in 0..phases {
+            let branch = (0..taps_per_phase).rev().map(|k| prototype[phase + k * phases] * phases as f64);
+            coefficients.extend(branch);
+        }
+
+        Self {
             input_rate,
             output_rate,
+            channels,
             quality,
             ratio,
-            buffer: vec![0.0; filter_length],
-            position: 0.0,
-        })
+            nominal_ratio: ratio,
+            phases,
+            step,
+            taps_per_phase,
+            coefficients,
+            passband: (stopband - transition) * upsampled_rate,
+            buffers: vec![Vec::new(); channels],
+            clock: Clock::Synchronous { position: 0 },
+        }
+    }
+
+    /// Output of one polyphase branch for `channel` at `time` in 1/phases input samples
+    ///
+    /// The newest input used is `buffers[channel][time / phases]`.
+    fn branch_output(&self, channel: usize, time: u64) -> f64 {
+        let index = (time / self.phases as u64) as usize;
+        let phase = (time % self.phases as u64) as usize;
+        let branch = &self.coefficients[phase * self.taps_per_phase..(phase + 1) * self.taps_per_phase];
+        let window = &self.buffers[channel][index + 1 - self.taps_per_phase..=index];
+        branch.iter().zip(window).map(|(&c, &x)| c * x as f64).sum()
+    }
+
+    /// Input frames held as filter state, history included
+    fn buffered_frames(&self) -> usize {
+        self.buffers[0].len()
+    }
+
+    /// Advance per output in asynchronous mode, in 1/ASYNC_PHASES input samples
+    fn async_step(&self) -> f64 {
+        self.phases as f64 / self.ratio
     }
-    
-    /// Process audio with resampling
+
+    /// Number of output frames the next `process` call yields for `input_frames` frames
+    ///
+    /// Exact in synchronous mode; asynchronous mode may differ by one frame.
+    pub fn output_len(&self, input_frames: usize) -> usize {
+        let end = ((self.buffered_frames() + input_frames) * self.phases) as u64;
+        match &self.clock {
+            Clock::Synchronous { position } => {
+                end.saturating_sub(*position).div_ceil(self.step as u64) as usize
+            }
+            Clock::Asynchronous { position, .. } => {
+                // Cubic interpolation reads two branches past the output time
+                let available = end as f64 - 2.0 - position;
+                (available.max(0.0) / self.async_step()).ceil() as usize
+            }
+        }
+    }
+
+    /// Most output frames one call can yield for `input_frames` frames, at
+    /// any ratio the resampler allows, when earlier calls had room for all
+    /// their output
+    pub fn max_output_len(&self, input_frames: usize) -> usize {
+        let ratio = match self.clock {
+            Clock::Synchronous { .. } => self.nominal_ratio,
+            Clock::Asynchronous { .. } => self.nominal_ratio * (1.0 + MAX_RATIO_DEVIATION),
+        };
+        (input_frames as f64 * ratio).ceil() as usize + 1
+    }
+
+    /// Reserve filter state for calls of up to `max_frames` input frames, so
+    /// that processing them into output sized by `max_output_len` does not
+    /// allocate
+    pub fn prepare(&mut self, max_frames: usize) {
+        // The history, the frames interpolation holds back, then the input
+        let frames = self.taps_per_phase + 2 + max_frames;
+        for buffer in &mut self.buffers {
+            buffer.reserve(frames.saturating_sub(buffer.len()));
+        }
+    }
+
+    /// Resample interleaved audio
+    ///
+    /// Returns the number of frames written. Input that does not fit in
+    /// `output` is kept and resampled by the next call; size `output` for
+    /// `output_len` frames to consume everything.
     pub fn process(&mut self, input: &[f32], output: &mut [f32]) -> Result<usize, VortexError> {
-        let mut output_count = 0;
-        let mut input_index = 0;
-        
-        while input_index < input.len() && output_count < output.len() {
-            // Simplified linear interpolation (full version would use polyphase FIR)
-            let index_floor = self.position.floor() as usize;
-            let fraction = self.position - self.position.floor();
-            
-            if index_floor + 1 < input.len() {
-                let sample1 = input[index_floor];
-                let sample2 = input[index_floor + 1];
-                output[output_count] = sample1 + (sample2 - sample1) * fraction as f32;
-                output_count += 1;
+        channels::interleaved_frames(input.len(), self.channels)?;
+        let max_frames = channels::interleaved_frames(output.len(), self.channels)?;
+
+        channels::deinterleave(input, &mut self.buffers);
+        let stride = self.channels;
+        Ok(self.render(max_frames, |frame, channel, value| output[frame * stride + channel] = value))
+    }
+
+    /// Resample planar audio, one slice per channel
+    ///
+    /// Same contract as `process`, with every channel's output slice sized
+    /// for `output_len` frames.
+    pub fn process_planar(&mut self, input: &[&[f32]], output: &mut [&mut [f32]]) -> Result<usize, VortexError> {
+        channels::planar_frames(input, self.channels)?;
+        let max_frames = channels::planar_frames(output, self.channels)?;
+
+        for (buffer, plane) in self.buffers.iter_mut().zip(input) {
+            buffer.extend_from_slice(plane);
+        }
+        Ok(self.render(max_frames, |frame, channel, value| output[channel][frame] = value))
+    }
+
+    /// Produce up to `max_frames` frames from the buffered input, passing each
+    /// sample to `write(frame, channel, value)`, then drop input no longer needed
+    fn render(&mut self, max_frames: usize, mut write: impl FnMut(usize, usize, f32)) -> usize {
+        let phases = self.phases as u64;
+        let available = self.buffered_frames();
+        let mut frames = 0;
+        let oldest_needed = match self.clock {
+            Clock::Synchronous { mut position } => {
+                while frames < max_frames && position / phases < available as u64 {
+                    for channel in 0..self.channels {
+                        write(frames, channel, self.branch_output(channel, position) as f32);
+                    }
+                    frames += 1;
+                    position += self.step as u64;
+                }
+                self.clock = Clock::Synchronous { position };
+                position
             }
-            
-            self.position += 1.0 / self.ratio;
-            
-            if self.position >= input.len() as f64 {
-                break;
+            Clock::Asynchronous { mut position, .. } => {
+                let step = self.async_step();
+                while frames < max_frames {
+                    let time = position as u64;
+                    if (time + 2) / phases >= available as u64 {
+                        break;
+                    }
+
+                    // 4-point Lagrange interpolation between adjacent branches
+                    let t = position - time as f64;
+                    let weights = [
+                        -t * (t - 1.0) * (t - 2.0) / 6.0,
+                        (t + 1.0) * (t - 1.0) * (t - 2.0) / 2.0,
+                        -(t + 1.0) * t * (t - 2.0) / 2.0,
+                        (t + 1.0) * t * (t - 1.0) / 6.0,
+                    ];
+                    for channel in 0..self.channels {
+                        let value: f64 = weights
+                            .iter()
+                            .zip(time - 1..)
+                            .map(|(&w, branch_time)| w * self.branch_output(channel, branch_time))
+                            .sum();
+                        write(frames, channel, value as f32);
+                    }
+                    frames += 1;
+                    position += step;
+                }
+                if let Clock::Asynchronous { position: p, drift } = &mut self.clock {
+                    *p = position;
+                    drift.produced += frames;
+                }
+                position as u64 - 1
             }
+        };
+
+        // Keep the history the next output needs
+        let next_index = (oldest_needed / phases) as usize;
+        let consumed = (next_index + 1).saturating_sub(self.taps_per_phase).min(available + 1 - self.taps_per_phase);
+        for buffer in &mut self.buffers {
+            buffer.drain(..consumed);
         }
-        
-        // Reset position for next block
-        self.position -= input.len() as f64;
-        if self.position < 0.0 {
-            self.position = 0.0;
+        let shift = (consumed * self.phases) as u64;
+        match &mut self.clock {
+            Clock::Synchronous { position } => *position -= shift,
+            Clock::Asynchronous { position, .. } => *position -= shift as f64,
         }
-        
-        Ok(output_count)
+
+        frames
     }
-    
+
     /// Reset resampler state
     pub fn reset(&mut self) {
-        self.buffer.fill(0.0);
-        self.position = 0.0;
+        // One extra sample of history for the cubic interpolation in asynchronous mode
+        let history = match self.clock {
+            Clock::Synchronous { .. } => self.taps_per_phase - 1,
+            Clock::Asynchronous { .. } => self.taps_per_phase,
+        };
+        for buffer in &mut self.buffers {
+            buffer.clear();
+            buffer.resize(history, 0.0);
+        }
+
+        let start = (history * self.phases) as u64;
+        match &mut self.clock {
+            Clock::Synchronous { position } => *position = start,
+            Clock::Asynchronous { position, drift } => {
+                *position = start as f64;
+                *drift = DriftLoop { control: drift.control, ..DriftLoop::default() };
+                self.ratio = self.nominal_ratio;
+            }
+        }
     }
-    
+
     /// Get resampling ratio
     pub fn ratio(&self) -> f64 {
         self.ratio
     }
+
+    /// Ratio implied by the sample rates
+    pub fn nominal_ratio(&self) -> f64 {
+        self.nominal_ratio
+    }
+
+    /// Number of channels resampled together
+    pub fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    pub fn quality(&self) -> ResamplerQuality {
+        self.quality
+    }
+
+    /// Whether the ratio can be adjusted at runtime
+    pub fn is_async(&self) -> bool {
+        matches!(self.clock, Clock::Asynchronous { .. })
+    }
+
+    /// Upper edge of the flat passband in Hz
+    pub fn passband_edge(&self) -> f64 {
+        self.passband
+    }
+
+    /// Group delay in output frames
+    pub fn latency_samples(&self) -> f64 {
+        (self.taps_per_phase * self.phases - 1) as f64 / 2.0 / self.phases as f64 * self.ratio
+    }
+
+    /// Change the ratio of an asynchronous resampler, effective from the next output
+    ///
+    /// The ratio must stay within `MAX_RATIO_DEVIATION` of nominal.
+    pub fn set_ratio(&mut self, ratio: f64) -> Result<(), VortexError> {
+        if !self.is_async() {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "Ratio is fixed in synchronous mode".to_string()
+            ).into());
+        }
+        if !ratio.is_finite() || (ratio / self.nominal_ratio - 1.0).abs() > MAX_RATIO_DEVIATION {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "Ratio {} is more than {}% from nominal {}",
+                ratio,
+                MAX_RATIO_DEVIATION * 100.0,
+                self.nominal_ratio
+            )).into());
+        }
+
+        self.ratio = ratio;
+        Ok(())
+    }
+
+    /// Configure the drift control loop used by `steer`
+    pub fn set_drift_control(&mut self, control: DriftControl) -> Result<(), VortexError> {
+        let Clock::Asynchronous { drift, .. } = &mut self.clock else {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "Drift control needs an asynchronous resampler".to_string()
+            ).into());
+        };
+        if !(0.0..=1.0).contains(&control.target_fill)
+            || !control.response_secs.is_finite()
+            || control.response_secs <= 0.0
+            || !(0.0..=MAX_RATIO_DEVIATION).contains(&control.max_deviation)
+        {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "Invalid drift control {:?}",
+                control
+            )).into());
+        }
+
+        drift.control = control;
+        Ok(())
+    }
+
+    /// Run one iteration of the drift loop against `buffer`, returning the new ratio
+    ///
+    /// Call once per processed block with the buffer that absorbs the clock
+    /// mismatch: the one this resampler writes into, or the one it reads from
+    /// when its output is pulled at a fixed rate. In both cases a fill above
+    /// target lowers the ratio. The loop is a PI controller on the smoothed
+    /// fill level, with gains scaled to the buffer duration so it settles in
+    /// about `response_secs` whatever the buffer size, and with the correction
+    /// slew-limited so pitch never moves audibly.
+    pub fn steer(&mut self, buffer: &AudioRingBuffer) -> Result<f64, VortexError> {
+        let output_rate = self.output_rate as f64;
+        let Clock::Asynchronous { drift, .. } = &mut self.clock else {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "Drift control needs an asynchronous resampler".to_string()
+            ).into());
+        };
+
+        let fill = buffer.fill_percentage() as f64;
+        let dt = drift.produced as f64 / output_rate;
+        drift.produced = 0;
+        let control = drift.control;
+
+        let smoothed = match drift.smoothed_fill {
+            // Smooth out the steps of block-wise reads and writes
+            Some(previous) => previous + (fill - previous) * (1.0 - (-8.0 * dt / control.response_secs).exp()),
+            None => fill,
+        };
+        drift.smoothed_fill = Some(smoothed);
+
+        if dt > 0.0 {
+            let duration = buffer.capacity_ms() / 1000.0;
+            let omega = 1.0 / control.response_secs;
+            let proportional = 2.0 * DRIFT_DAMPING * omega * duration;
+            let integral_gain = omega * omega * duration;
+
+            let error = smoothed - control.target_fill as f64;
+            let integral = drift.integral + error * dt;
+            let wanted = -(proportional * error + integral_gain * integral);
+            let limited = wanted.clamp(-control.max_deviation, control.max_deviation);
+            // Anti-windup: stop integrating while the correction is saturated
+            if limited == wanted {
+                drift.integral = integral;
+            }
+
+            let max_step = control.max_deviation * dt / (DRIFT_MIN_SWEEP * control.response_secs);
+            drift.correction += (limited - drift.correction).clamp(-max_step, max_step);
+        }
+
+        self.ratio = self.nominal_ratio * (1.0 + drift.correction);
+        Ok(self.ratio)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+    use std::f64::consts::PI;
+
+    /// Least-squares amplitude of a sinusoid at `frequency` Hz
+    fn tone_amplitude(signal: &[f32], frequency: f64, sample_rate: f64) -> f64 {
+        let omega = 2.0 * PI * frequency / sample_rate;
+        let (mut ss, mut sc, mut cc, mut xs, mut xc) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (i, &x) in signal.iter().enumerate() {
+            let (s, c) = (omega * i as f64).sin_cos();
+            ss += s * s;
+            sc += s * c;
+            cc += c * c;
+            xs += x as f64 * s;
+            xc += x as f64 * c;
+        }
+        let det = ss * cc - sc * sc;
+        ((xs * cc - xc * sc) / det).hypot((xc * ss - xs * sc) / det)
+    }
+
+    /// Resample a unit tone and measure the output at `measure_hz`
+    fn resampled_tone(resampler: &mut Resampler, frequency: f64, measure_hz: f64) -> f64 {
+        let input_rate = resampler.input_rate as f64;
+        let input: Vec<f32> = (0..8192)
+            .map(|i| (2.0 * PI * frequency * i as f64 / input_rate).sin() as f32)
+            .collect();
+        let mut output = vec![0.0f32; resampler.output_len(input.len())];
+        let written = resampler.process(&input, &mut output).unwrap();
+
+        // Skip the filter's start-up transient
+        let settle = 2 * resampler.latency_samples().ceil() as usize;
+        tone_amplitude(&output[settle..written], measure_hz, resampler.output_rate as f64)
+    }
+
     #[test]
     fn test_resampler_creation() {
-        let resampler = Resampler::new(44100, 48000, ResamplerQuality::Standard);
+        let resampler = Resampler::new(44100, 48000, ResamplerQuality::Standard, 1);
         assert!(resampler.is_ok());
     }
-    
+
     #[test]
     fn test_invalid_rates() {
-        let resampler = Resampler::new(0, 48000, ResamplerQuality::Standard);
+        let resampler = Resampler::new(0, 48000, ResamplerQuality::Standard, 1);
         assert!(resampler.is_err());
+
+        // 44101 and 48000 share no factor
+        assert!(Resampler::new(44101, 48000, ResamplerQuality::Standard, 1).is_err());
     }
-    
+
     #[test]
     fn test_ratio_calculation() {
-        let resampler = Resampler::new(44100, 48000, ResamplerQuality::Standard).unwrap();
+        let resampler = Resampler::new(44100, 48000, ResamplerQuality::Standard, 1).unwrap();
         let expected_ratio = 48000.0 / 44100.0;
         assert!((resampler.ratio() - expected_ratio).abs() < 0.0001);
     }
-    
+
     #[test]
     fn test_basic_resampling() {
-        let mut resampler = Resampler::new(44100, 48000, ResamplerQuality::Standard).unwrap();
+        let mut resampler = Resampler::new(44100, 48000, ResamplerQuality::Standard, 1).unwrap();
         let input = vec![1.0; 1024];
         let mut output = vec![0.0; 2048];
-        
+
         let result = resampler.process(&input, &mut output);
         assert!(result.is_ok());
         assert!(result.unwrap() > 0);
     }
-    
+
     #[test]
     fn test_quality_levels() {
         assert_eq!(ResamplerQuality::Draft.filter_length(), 16);
@@ -139,4 +618,283 @@ mod tests {
         assert_eq!(ResamplerQuality::High.filter_length(), 256);
         assert_eq!(ResamplerQuality::Maximum.filter_length(), 1024);
     }
+
+    #[test]
+    fn test_output_count_tracks_ratio() {
+        let mut resampler = Resampler::new(44100, 48000, ResamplerQuality::Draft, 1).unwrap();
+        let mut output = vec![0.0f32; 1024];
+        let mut total = 0;
+        for _ in 0..100 {
+            let expected = resampler.output_len(441);
+            let written = resampler.process(&[0.5; 441], &mut output).unwrap();
+            assert_eq!(written, expected);
+            total += written;
+        }
+        assert_eq!(total, 48000);
+
+        // Output that does not fit is produced by the next call
+        let written = resampler.process(&[0.5; 441], &mut output[..100]).unwrap();
+        assert_eq!(written, 100);
+        assert_eq!(resampler.output_len(0), 380);
+        assert_eq!(resampler.process(&[], &mut output).unwrap(), 380);
+    }
+
+    #[test]
+    fn test_block_continuity() {
+        let input: Vec<f32> = (0..10_000).map(|i| (i as f32 * 0.05).sin()).collect();
+
+        let mut whole = Resampler::new(48000, 44100, ResamplerQuality::High, 1).unwrap();
+        let mut expected = vec![0.0f32; whole.output_len(input.len())];
+        whole.process(&input, &mut expected).unwrap();
+
+        let mut chunked = Resampler::new(48000, 44100, ResamplerQuality::High, 1).unwrap();
+        let mut actual = Vec::new();
+        for chunk in input.chunks(97) {
+            let mut block = vec![0.0f32; chunked.output_len(chunk.len())];
+            let written = chunked.process(chunk, &mut block).unwrap();
+            actual.extend_from_slice(&block[..written]);
+        }
+        assert_eq!(actual, expected);
+
+        chunked.reset();
+        let mut again = vec![0.0f32; chunked.output_len(input.len())];
+        chunked.process(&input, &mut again).unwrap();
+        assert_eq!(again, expected);
+    }
+
+    #[test]
+    fn test_stereo_matches_independent_channels() {
+        let left: Vec<f32> = (0..4410).map(|i| (i as f32 * 0.05).sin()).collect();
+        let right: Vec<f32> = (0..4410).map(|i| (i as f32 * 0.31).cos() * 0.5).collect();
+        let interleaved: Vec<f32> = left.iter().zip(&right).flat_map(|(&l, &r)| [l, r]).collect();
+
+        for asynchronous in [false, true] {
+            let create = |channels| if asynchronous {
+                Resampler::new_async(44100, 48000, ResamplerQuality::Standard, channels).unwrap()
+            } else {
+                Resampler::new(44100, 48000, ResamplerQuality::Standard, channels).unwrap()
+            };
+
+            let mono: Vec<Vec<f32>> = [&left, &right]
+                .iter()
+                .map(|channel| {
+                    let mut resampler = create(1);
+                    let mut output = vec![0.0f32; resampler.output_len(channel.len())];
+                    let written = resampler.process(channel, &mut output).unwrap();
+                    output.truncate(written);
+                    output
+                })
+                .collect();
+
+            let mut stereo = create(2);
+            let frames = stereo.output_len(left.len());
+            let mut output = vec![0.0f32; frames * 2];
+            assert_eq!(stereo.process(&interleaved, &mut output).unwrap(), mono[0].len());
+            let (l, r): (Vec<f32>, Vec<f32>) = output.chunks(2).map(|frame| (frame[0], frame[1])).unzip();
+            assert_eq!(l, mono[0]);
+            assert_eq!(r, mono[1]);
+
+            let mut planar = create(2);
+            let (mut l, mut r) = (vec![0.0f32; frames], vec![0.0f32; frames]);
+            planar.process_planar(&[&left, &right], &mut [&mut l, &mut r]).unwrap();
+            assert_eq!([l, r], [mono[0].clone(), mono[1].clone()]);
+
+            assert!(stereo.process(&interleaved[..3], &mut output).is_err());
+            assert!(planar.process_planar(&[&left], &mut [&mut output]).is_err());
+        }
+        assert!(Resampler::new(44100, 48000, ResamplerQuality::Draft, 0).is_err());
+    }
+
+    #[test]
+    fn test_prototype_meets_specification() {
+        let qualities = [
+            ResamplerQuality::Draft,
+            ResamplerQuality::Standard,
+            ResamplerQuality::High,
+            ResamplerQuality::Maximum,
+        ];
+        for quality in qualities {
+            for (input_rate, output_rate) in [(44100, 88200), (88200, 44100), (48000, 44100)] {
+                let resampler = Resampler::new(input_rate, output_rate, quality, 1).unwrap();
+                let upsampled_rate = input_rate as f64 * resampler.phases as f64;
+                let prototype: Vec<f64> = resampler.coefficients.clone();
+                let dc = prototype.iter().sum::<f64>();
+                // The branches interleave back into the prototype (reversed per branch)
+                let mut taps = vec![0.0; prototype.len()];
+                for (phase, branch) in prototype.chunks(resampler.taps_per_phase).enumerate() {
+                    for (k, &c) in branch.iter().rev().enumerate() {
+                        taps[phase + k * resampler.phases] = c / dc;
+                    }
+                }
+
+                let passband = resampler.passband_edge();
+                for i in 0..=20 {
+                    let gain = fir_design::magnitude(&taps, passband * i as f64 / 20.0 / upsampled_rate);
+                    assert!((20.0 * gain.log10()).abs() < 0.01, "{:?}: passband ripple {}", quality, gain);
+                }
+
+                // Stopband from the lower Nyquist to the Nyquist of the upsampled rate
+                let stopband = input_rate.min(output_rate) as f64 / 2.0;
+                let points = 150;
+                for i in 0..=points {
+                    let frequency = stopband + (upsampled_rate / 2.0 - stopband) * i as f64 / points as f64;
+                    let gain_db = 20.0 * fir_design::magnitude(&taps, frequency / upsampled_rate).log10();
+                    assert!(
+                        gain_db < -quality.stopband_attenuation_db(),
+                        "{:?} {} -> {}: {:.1} dB at {:.0} Hz", quality, input_rate, output_rate, gain_db, frequency
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_measured_passband_flatness() {
+        for quality in [ResamplerQuality::Standard, ResamplerQuality::High, ResamplerQuality::Maximum] {
+            let probe = Resampler::new(44100, 48000, quality, 1).unwrap();
+            for fraction in [0.05, 0.3, 0.6, 0.95] {
+                let frequency = probe.passband_edge() * fraction;
+                let mut resampler = Resampler::new(44100, 48000, quality, 1).unwrap();
+                let gain_db = 20.0 * resampled_tone(&mut resampler, frequency, frequency).log10();
+                assert!(gain_db.abs() < 0.01, "{:?}: {:.4} dB at {:.0} Hz", quality, gain_db, frequency);
+            }
+        }
+    }
+
+    #[test]
+    fn test_measured_alias_rejection() {
+        let qualities = [
+            ResamplerQuality::Draft,
+            ResamplerQuality::Standard,
+            ResamplerQuality::High,
+            ResamplerQuality::Maximum,
+        ];
+        for quality in qualities {
+            // 23 kHz is above the 22.05 kHz output Nyquist and folds to 21.1 kHz
+            let mut resampler = Resampler::new(48000, 44100, quality, 1).unwrap();
+            let alias = resampled_tone(&mut resampler, 23_000.0, 44100.0 - 23_000.0);
+            let rejection_db = -20.0 * alias.log10();
+            assert!(
+                rejection_db > quality.stopband_attenuation_db(),
+                "{:?}: alias only {:.1} dB down", quality, rejection_db
+            );
+        }
+    }
+
+    #[test]
+    fn test_async_meets_specification() {
+        let qualities = [
+            ResamplerQuality::Draft,
+            ResamplerQuality::Standard,
+            ResamplerQuality::High,
+            ResamplerQuality::Maximum,
+        ];
+        for quality in qualities {
+            let mut resampler = Resampler::new_async(48000, 44100, quality, 1).unwrap();
+            assert!(resampler.is_async());
+            let alias = resampled_tone(&mut resampler, 23_000.0, 44100.0 - 23_000.0);
+            assert!(
+                -20.0 * alias.log10() > quality.stopband_attenuation_db(),
+                "{:?}: alias {:.1} dB", quality, 20.0 * alias.log10()
+            );
+
+            // Tones between branches must not be colored by the interpolation
+            let frequency = resampler.passband_edge() * 0.9;
+            resampler.reset();
+            let gain_db = 20.0 * resampled_tone(&mut resampler, frequency, frequency).log10();
+            assert!(gain_db.abs() < 0.01, "{:?}: {:.4} dB at {:.0} Hz", quality, gain_db, frequency);
+        }
+    }
+
+    #[test]
+    fn test_async_ratio_changes() {
+        let mut synchronous = Resampler::new(44100, 48000, ResamplerQuality::Draft, 1).unwrap();
+        assert!(synchronous.set_ratio(1.1).is_err());
+        assert!(synchronous.set_drift_control(DriftControl::default()).is_err());
+
+        let mut resampler = Resampler::new_async(44100, 48000, ResamplerQuality::Standard, 1).unwrap();
+        let nominal = resampler.nominal_ratio();
+        assert!(resampler.set_ratio(nominal * 1.03).is_err());
+        assert!(resampler.set_ratio(f64::NAN).is_err());
+
+        // Output count follows the ratio, continuing seamlessly across changes
+        let mut output = vec![0.0f32; 2048];
+        let mut produced = 0;
+        for block in 0..200 {
+            let ratio = nominal * (1.0 + 0.01 * (block as f64 / 200.0));
+            resampler.set_ratio(ratio).unwrap();
+            let expected = resampler.output_len(441);
+            let written = resampler.process(&[0.25; 441], &mut output).unwrap();
+            assert!(written.abs_diff(expected) <= 1);
+            assert!(output[..written].iter().all(|&s| (s - 0.25).abs() < 1e-4) || block == 0);
+            produced += written;
+        }
+        let expected = 88200.0 * nominal * 1.005;
+        assert!((produced as f64 - expected).abs() < 200.0, "{} outputs", produced);
+    }
+
+    #[test]
+    fn test_prepared_processing_does_not_allocate() {
+        use crate::audio::alloc_check::heap_operations;
+
+        let mut resampler = Resampler::new_async(44100, 48000, ResamplerQuality::Standard, 2).unwrap();
+        resampler.prepare(512);
+        let input: Vec<f32> = (0..1024).map(|i| (i as f32 * 0.1).sin()).collect();
+        let mut output = vec![0.0; resampler.max_output_len(512) * 2];
+
+        for block in 0..100 {
+            // Swing the ratio to both ends of its range
+            let deviation = if block % 2 == 0 { 0.999 } else { -0.999 } * MAX_RATIO_DEVIATION;
+            resampler.set_ratio(resampler.nominal_ratio() * (1.0 + deviation)).unwrap();
+            let (result, operations) = heap_operations(|| resampler.process(&input, &mut output));
+            result.unwrap();
+            assert_eq!(operations, 0, "block {}", block);
+        }
+    }
+
+    #[test]
+    fn test_drift_loop_steers_buffer_fill() {
+        // Source clocked at 44.1 kHz, device pulling at 48 kHz + 300 ppm
+        let mut resampler = Resampler::new_async(44100, 48000, ResamplerQuality::Draft, 1).unwrap();
+        resampler.set_drift_control(DriftControl {
+            target_fill: 0.5,
+            response_secs: 2.0,
+            ..DriftControl::default()
+        }).unwrap();
+        let buffer = AudioRingBuffer::new(200, 48000, 1);
+        let device_rate = 48000.0 * 1.0003;
+
+        // Playback starts with the buffer partly primed
+        let primed = (0.4 * buffer.capacity_ms() * 48.0) as usize;
+        buffer.write_samples(&vec![0.0; primed]);
+
+        let input = vec![0.1f32; 441];
+        let mut output = vec![0.0f32; 1024];
+        let mut pulled = vec![0.0f32; 1024];
+        let mut device_clock = 0.0;
+        let mut previous_ratio = resampler.ratio();
+        let mut fills = Vec::new();
+        for _ in 0..3000 {
+            let written = resampler.process(&input, &mut output).unwrap();
+            buffer.write_samples(&output[..written]);
+
+            // The device consumes its own clock's worth of samples per 10 ms
+            device_clock += device_rate / 100.0;
+            let wanted = device_clock as usize;
+            device_clock -= wanted as f64;
+            buffer.read_samples(&mut pulled[..wanted]);
+
+            let ratio = resampler.steer(&buffer).unwrap();
+            // Corrections are gradual: at most 100 ppm per 10 ms block
+            assert!((ratio / previous_ratio - 1.0).abs() < 1.01e-4);
+            previous_ratio = ratio;
+            fills.push(buffer.fill_percentage());
+        }
+
+        // After 30 s the buffer sits at target and the ratio matches the device
+        let settled = &fills[fills.len() - 500..];
+        assert!(settled.iter().all(|&f| (f - 0.5).abs() < 0.02), "fill {:?}", settled.last());
+        let drift_ppm = (resampler.ratio() / resampler.nominal_ratio() - 1.0) * 1e6;
+        assert!((drift_ppm - 300.0).abs() < 20.0, "ratio off by {:.1} ppm", drift_ppm);
+    }
 }
@@ -0,0 +1,678 @@
+//! PCM to DSD conversion
+//!
+//! The reverse of `DsdProcessor`: PCM is interpolated to the DSD rate by a
+//! half-band cascade followed by linear interpolation, then quantized to one
+//! bit by a high-order sigma-delta modulator. The modulator uses an
+//! error-feedback structure whose noise transfer function (NTF) places its
+//! zeros optimally across the audio band and its poles on a Butterworth
+//! alignment, tuned so the out-of-band gain stays at 1.5 (Lee's criterion).
+
+use crate::error::VortexError;
+use super::dop::{self, DopEncoder};
+use super::dsd_processor::{self, DsdRate};
+use std::f64::consts::PI;
+
+/// Lowest supported modulator order
+pub const MIN_ORDER: usize = 5;
+
+/// Highest supported modulator order
+pub const MAX_ORDER: usize = 7;
+
+/// Modulation depth at PCM full scale (the SACD 0 dB reference level)
+pub const DSD_MODULATION_INDEX: f64 = 0.5;
+
+/// Peak out-of-band NTF gain; higher shapes harder but is less stable
+const MAX_NTF_GAIN: f64 = 1.5;
+
+/// Stopband attenuation of the interpolation half-bands
+const INTERPOLATION_ATTENUATION_DB: f64 = 120.0;
+
+/// Half-band stages before linear interpolation takes over (8x)
+const HALF_BAND_STAGES: u32 = 3;
+
+/// Quantization error fed back is clipped to this magnitude
+const ERROR_LIMIT: f64 = 2.0;
+
+/// Quantizer input beyond this means the loop has gone unstable
+const OVERLOAD_LIMIT: f64 = 16.0;
+
+/// Optimal NTF zero positions relative to the band edge, for orders 5, 6 and 7
+///
+/// These are the non-negative roots of the Legendre polynomial of that order,
+/// which minimize integrated in-band noise. Zero is a real zero at DC.
+const OPTIMAL_ZEROS: [&[f64]; 3] = [
+    &[0.0, 0.538_469_310_105_683, 0.906_179_845_938_664],
+    &[0.238_619_186_083_197, 0.661_209_386_466_265, 0.932_469_514_203_152],
+    &[0.0, 0.405_845_151_377_397, 0.741_531_185_599_394, 0.949_107_912_342_759],
+];
+
+/// One factor of the NTF, with b0 = a0 = 1
+#[derive(Debug, Clone, Copy)]
+struct NtfSection {
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+}
+
+impl NtfSection {
+    fn magnitude(&self, omega: f64) -> f64 {
+        let (c1, s1, c2, s2) = (omega.cos(), omega.sin(), (2.0 * omega).cos(), (2.0 * omega).sin());
+        let num = (1.0 + self.b1 * c1 + self.b2 * c2).hypot(self.b1 * s1 + self.b2 * s2);
+        let den = (1.0 + self.a1 * c1 + self.a2 * c2).hypot(self.a1 * s1 + self.a2 * s2);
+        num / den
+    }
+}
+
+/// |NTF| at `omega` radians per sample
+fn ntf_magnitude(sections: &[NtfSection], omega: f64) -> f64 {
+    sections.iter().map(|s| s.magnitude(omega)).product()
+}
+
+/// Largest |NTF| over the whole band
+fn ntf_peak_gain(sections: &[NtfSection]) -> f64 {
+    (0..=1024)
+        .map(|i| ntf_magnitude(sections, PI * i as f64 / 1024.0))
+        .fold(0.0, f64::max)
+}
+
+/// NTF with the given zeros and Butterworth poles at `cutoff` radians per sample
+fn ntf_sections(order: usize, zeros: &[f64], cutoff: f64) -> Vec<NtfSection> {
+    // Bilinear transform of analog Butterworth poles, prewarped to `cutoff`
+    let analog_cutoff = 2.0 * (cutoff / 2.0).tan();
+    let pole = |k: usize| {
+        let theta = PI / 2.0 + PI * (2 * k + 1) as f64 / (2 * order) as f64;
+        let (sr, si) = (analog_cutoff * theta.cos() / 2.0, analog_cutoff * theta.sin() / 2.0);
+        let den = (1.0 - sr) * (1.0 - sr) + si * si;
+        ((1.0 - sr * sr - si * si) / den, 2.0 * si / den)
+    };
+
+    let mut pair_zeros = zeros.iter().filter(|&&z| z > 0.0);
+    let mut sections: Vec<NtfSection> = (0..order / 2)
+        .map(|k| {
+            let (re, im) = pole(k);
+            let zero = *pair_zeros.next().unwrap();
+            NtfSection { b1: -2.0 * zero.cos(), b2: 1.0, a1: -2.0 * re, a2: re * re + im * im }
+        })
+        .collect();
+    if order % 2 == 1 {
+        let (re, _) = pole(order / 2);
+        sections.push(NtfSection { b1: -1.0, b2: 0.0, a1: -re, a2: 0.0 });
+    }
+    sections
+}
+
+/// Design the NTF for `order` at `dsd_rate` Hz
+fn design_ntf(order: usize, dsd_rate: f64) -> Vec<NtfSection> {
+    let band_edge = 2.0 * PI * dsd_processor::PASSBAND_HZ / dsd_rate;
+    let zeros: Vec<f64> = OPTIMAL_ZEROS[order - MIN_ORDER].iter().map(|z| z * band_edge).collect();
+
+    // Out-of-band gain rises with the pole cutoff; bisect for MAX_NTF_GAIN
+    let (mut low, mut high) = (1e-4, 0.9 * PI);
+    for _ in 0..50 {
+        let mid = (low + high) / 2.0;
+        if ntf_peak_gain(&ntf_sections(order, &zeros, mid)) > MAX_NTF_GAIN {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    ntf_sections(order, &zeros, low)
+}
+
+/// Interpolate by two with a half-band filter, one input sample at a time
+struct HalfBandInterpolator {
+    // Non-zero taps at offsets 1, 3, 5, ... from the centre, doubled for unity gain
+    side_taps: Vec<f64>,
+    // Input history mirrored at `len` so the window is always contiguous
+    history: Vec<f64>,
+    position: usize,
+}
+
+impl HalfBandInterpolator {
+    fn new(taps: &[f64]) -> Self {
+        let center = taps.len() / 2;
+        let side_taps: Vec<f64> = taps[center + 1..].iter().step_by(2).map(|t| 2.0 * t).collect();
+        let len = 2 * side_taps.len();
+        Self {
+            side_taps,
+            history: vec![0.0; 2 * len],
+            position: 0,
+        }
+    }
+
+    /// Consume one sample and produce the next two
+    fn push(&mut self, sample: f64) -> [f64; 2] {
+        let len = 2 * self.side_taps.len();
+        self.history[self.position] = sample;
+        self.history[self.position + len] = sample;
+        self.position = (self.position + 1) % len;
+
+        // Oldest sample first; the even phase is symmetric about the middle
+        let window = &self.history[self.position..self.position + len];
+        let middle = self.side_taps.len() - 1;
+        let even = self
+            .side_taps
+            .iter()
+            .enumerate()
+            .map(|(i, tap)| tap * (window[middle - i] + window[middle + 1 + i]))
+            .sum();
+        [even, window[middle + 1]]
+    }
+
+    fn reset(&mut self) {
+        self.history.fill(0.0);
+        self.position = 0;
+    }
+}
+
+/// Interpolation and modulator state for one channel
+struct ChannelPath {
+    interpolators: Vec<HalfBandInterpolator>,
+    // Last half-band output, the start point of linear interpolation
+    previous: f64,
+    // Transposed direct form II state of each NTF section
+    states: Vec<[f64; 2]>,
+    overloads: u64,
+}
+
+impl ChannelPath {
+    /// Run one output-rate sample through the error-feedback loop, returning the bit
+    fn modulate(&mut self, input: f64, sections: &[NtfSection]) -> bool {
+        // (NTF - 1) e depends only on past errors: the sum of the first states
+        let feedback: f64 = self.states.iter().map(|s| s[0]).sum();
+        let v = input + feedback;
+        let bit = v >= 0.0;
+        if v.abs() > OVERLOAD_LIMIT {
+            self.states.iter_mut().for_each(|s| *s = [0.0; 2]);
+            self.overloads += 1;
+            return bit;
+        }
+
+        let output = if bit { 1.0 } else { -1.0 };
+        let mut x = (output - v).clamp(-ERROR_LIMIT, ERROR_LIMIT);
+        for (section, state) in sections.iter().zip(&mut self.states) {
+            let y = x + state[0];
+            state[0] = section.b1 * x - section.a1 * y + state[1];
+            state[1] = section.b2 * x - section.a2 * y;
+            x = y;
+        }
+        bit
+    }
+
+    fn reset(&mut self) {
+        self.interpolators.iter_mut().for_each(HalfBandInterpolator::reset);
+        self.previous = 0.0;
+        self.states.iter_mut().for_each(|s| *s = [0.0; 2]);
+    }
+}
+
+/// Sigma-delta modulator converting PCM to a DSD bitstream
+///
+/// PCM full scale maps to `DSD_MODULATION_INDEX`; louder input is clipped.
+/// The output is per-channel LSB-first bytes, the layout `DsdProcessor` and
+/// `DopEncoder` consume.
+pub struct SigmaDeltaModulator {
+    input_rate: u32,
+    dsd_rate: DsdRate,
+    order: usize,
+    interpolation_factor: u32,
+    sections: Vec<NtfSection>,
+    paths: Vec<ChannelPath>,
+}
+
+impl SigmaDeltaModulator {
+    /// Create a modulator of `order` (5 to 7) from `input_rate` PCM
+    ///
+    /// The DSD rate must be a power-of-two multiple, at least 8x, of the
+    /// input rate (44.1 kHz family).
+    pub fn new(input_rate: u32, dsd_rate: DsdRate, order: usize, channels: u16) -> Result<Self, VortexError> {
+        if !(MIN_ORDER..=MAX_ORDER).contains(&order) {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "Modulator order must be {} to {}, got {}",
+                MIN_ORDER, MAX_ORDER, order
+            )).into());
+        }
+        if channels == 0 {
+            return Err(crate::error::AudioError::InvalidParameter(
+                "Modulator needs at least one channel".to_string()
+            ).into());
+        }
+
+        let interpolation_factor = if input_rate > 0 { dsd_rate.decimation_factor(input_rate) } else { 0 };
+        let exact = interpolation_factor * input_rate == dsd_rate.sample_rate();
+        if !exact || !interpolation_factor.is_power_of_two() || interpolation_factor < 8 {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "Cannot modulate {} Hz PCM to {:?}: rate must divide the DSD rate by 8, 16, 32, ...",
+                input_rate, dsd_rate
+            )).into());
+        }
+
+        let dsd_hz = dsd_rate.sample_rate() as f64;
+        let passband = dsd_processor::MAX_PASSBAND_RATIO * input_rate as f64;
+        let interpolator_taps: Vec<Vec<f64>> = (1..=HALF_BAND_STAGES)
+            .map(|stage| {
+                let rate = input_rate as f64 * (1 << stage) as f64;
+                dsd_processor::design_half_band(rate, passband, INTERPOLATION_ATTENUATION_DB)
+            })
+            .collect();
+        let sections = design_ntf(order, dsd_hz);
+
+        let paths = (0..channels)
+            .map(|_| ChannelPath {
+                interpolators: interpolator_taps.iter().map(|t| HalfBandInterpolator::new(t)).collect(),
+                previous: 0.0,
+                states: vec![[0.0; 2]; sections.len()],
+                overloads: 0,
+            })
+            .collect();
+
+        Ok(Self {
+            input_rate,
+            dsd_rate,
+            order,
+            interpolation_factor,
+            sections,
+            paths,
+        })
+    }
+
+    pub fn input_rate(&self) -> u32 {
+        self.input_rate
+    }
+
+    pub fn dsd_rate(&self) -> DsdRate {
+        self.dsd_rate
+    }
+
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.paths.len() as u16
+    }
+
+    /// DSD samples produced per input frame
+    pub fn interpolation_factor(&self) -> u32 {
+        self.interpolation_factor
+    }
+
+    /// Bytes appended to each channel for `frames` input frames
+    pub fn output_bytes(&self, frames: usize) -> usize {
+        frames * self.interpolation_factor as usize / 8
+    }
+
+    /// Noise transfer function magnitude at `frequency` Hz
+    pub fn noise_transfer_gain(&self, frequency: f64) -> f64 {
+        ntf_magnitude(&self.sections, 2.0 * PI * frequency / self.dsd_rate.sample_rate() as f64)
+    }
+
+    /// Times the loop went unstable and was reset since creation or `reset`
+    pub fn overload_count(&self) -> u64 {
+        self.paths.iter().map(|p| p.overloads).sum()
+    }
+
+    /// Modulate interleaved PCM, appending LSB-first bytes to each channel
+    ///
+    /// Returns the number of input frames consumed.
+    pub fn process(&mut self, input: &[f32], output: &mut [Vec<u8>]) -> Result<usize, VortexError> {
+        let channels = self.paths.len();
+        if output.len() != channels || !input.len().is_multiple_of(channels) {
+            return Err(crate::error::AudioError::InvalidParameter(format!(
+                "Expected whole frames of {} channels",
+                channels
+            )).into());
+        }
+
+        let linear_factor = self.interpolation_factor >> HALF_BAND_STAGES;
+        for (ch, (path, bytes)) in self.paths.iter_mut().zip(output.iter_mut()).enumerate() {
+            bytes.reserve(input.len() / channels * self.interpolation_factor as usize / 8);
+            let (mut packed, mut bit_index) = (0u8, 0);
+            for frame in input.chunks_exact(channels) {
+                let sample = (frame[ch] as f64).clamp(-1.0, 1.0) * DSD_MODULATION_INDEX;
+
+                // Half-band cascade: 1 -> 2 -> 4 -> 8 samples
+                let mut block = [0.0f64; 1 << HALF_BAND_STAGES];
+                block[0] = sample;
+                let mut len = 1;
+                for interpolator in &mut path.interpolators {
+                    let mut next = [0.0f64; 1 << HALF_BAND_STAGES];
+                    for i in 0..len {
+                        let [even, odd] = interpolator.push(block[i]);
+                        next[2 * i] = even;
+                        next[2 * i + 1] = odd;
+                    }
+                    block = next;
+                    len *= 2;
+                }
+
+                for &target in &block {
+                    for step in 1..=linear_factor {
+                        let t = step as f64 / linear_factor as f64;
+                        let value = path.previous + (target - path.previous) * t;
+                        if path.modulate(value, &self.sections) {
+                            packed |= 1 << bit_index;
+                        }
+                        bit_index += 1;
+                        if bit_index == 8 {
+                            bytes.push(packed);
+                            packed = 0;
+                            bit_index = 0;
+                        }
+                    }
+                    path.previous = target;
+                }
+            }
+        }
+
+        Ok(input.len() / channels)
+    }
+
+    /// Clear all filter and loop state
+    pub fn reset(&mut self) {
+        self.paths.iter_mut().for_each(|p| {
+            p.reset();
+            p.overloads = 0;
+        });
+    }
+}
+
+/// Streaming PCM to DoP output stage
+///
+/// Modulates processed PCM to DSD and packs it as DoP, so a DSD-only DAC can
+/// be fed through an ordinary PCM output at `carrier_rate`.
+pub struct DsdOutputStage {
+    modulator: SigmaDeltaModulator,
+    encoder: DopEncoder,
+    // Per-channel bytes for the current block
+    bytes: Vec<Vec<u8>>,
+    // DoP words for the current block
+    words: Vec<i32>,
+}
+
+impl DsdOutputStage {
+    pub fn new(input_rate: u32, dsd_rate: DsdRate, order: usize, channels: u16) -> Result<Self, VortexError> {
+        let modulator = SigmaDeltaModulator::new(input_rate, dsd_rate, order, channels)?;
+        let encoder = DopEncoder::new(dsd_rate, channels)?;
+
+        Ok(Self {
+            modulator,
+            encoder,
+            bytes: vec![Vec::new(); channels as usize],
+            words: Vec::new(),
+        })
+    }
+
+    pub fn modulator(&self) -> &SigmaDeltaModulator {
+        &self.modulator
+    }
+
+    /// PCM rate of the DoP output
+    pub fn carrier_rate(&self) -> u32 {
+        self.encoder.carrier_rate()
+    }
+
+    /// Most output samples produced for `input_samples` interleaved input samples
+    ///
+    /// At one DSD byte per frame, a block with an odd frame count leaves a
+    /// byte for the next one, which then yields one extra frame.
+    pub fn output_len(&self, input_samples: usize) -> usize {
+        let channels = self.bytes.len();
+        self.modulator.output_bytes(input_samples / channels).div_ceil(2) * channels
+    }
+
+    /// Reserve room for blocks of up to `max_input_samples` interleaved samples
+    ///
+    /// `process` then does not allocate for such blocks, provided `output`
+    /// has capacity for `output_len` samples.
+    pub fn prepare(&mut self, max_input_samples: usize) {
+        let frames = max_input_samples / self.bytes.len();
+        let bytes = self.modulator.output_bytes(frames);
+        for channel in &mut self.bytes {
+            channel.reserve(bytes.saturating_sub(channel.len()));
+        }
+        let words = self.output_len(max_input_samples);
+        self.words.reserve(words.saturating_sub(self.words.len()));
+    }
+
+    /// Convert a block of interleaved PCM to interleaved DoP samples in `output`
+    ///
+    /// Returns the number of output frames.
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) -> Result<usize, VortexError> {
+        self.bytes.iter_mut().for_each(Vec::clear);
+        self.modulator.process(input, &mut self.bytes)?;
+
+        let len = self.encoder.output_frames(self.bytes[0].len()) * self.bytes.len();
+        self.words.resize(len, 0);
+        let frames = self.encoder.encode(&self.bytes, &mut self.words)?;
+
+        output.clear();
+        output.extend(self.words[..frames * self.bytes.len()].iter().map(|&word| dop::sample_to_f32(word)));
+        Ok(frames)
+    }
+
+    pub fn reset(&mut self) {
+        self.modulator.reset();
+        self.encoder.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::dsp::{DopDecoder, DsdProcessor};
+
+    fn sine(frequency: f64, amplitude: f64, sample_rate: u32, frames: usize, channels: usize) -> Vec<f32> {
+        (0..frames * channels)
+            .map(|i| {
+                let t = (i / channels) as f64 / sample_rate as f64;
+                (amplitude * (2.0 * PI * frequency * t).sin()) as f32
+            })
+            .collect()
+    }
+
+    /// Amplitude of `frequency` and the RMS of everything else (least-squares sine fit)
+    fn fit_sine(signal: &[f32], frequency: f64, sample_rate: f64) -> (f64, f64) {
+        let omega = 2.0 * PI * frequency / sample_rate;
+        let basis = |i: usize| ((omega * i as f64).sin(), (omega * i as f64).cos());
+        let (mut ss, mut sc, mut cc, mut xs, mut xc) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (i, &x) in signal.iter().enumerate() {
+            let (s, c) = basis(i);
+            ss += s * s;
+            sc += s * c;
+            cc += c * c;
+            xs += x as f64 * s;
+            xc += x as f64 * c;
+        }
+        let det = ss * cc - sc * sc;
+        let (a, b) = ((xs * cc - xc * sc) / det, (xc * ss - xs * sc) / det);
+
+        let residual: f64 = signal
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                let (s, c) = basis(i);
+                (x as f64 - a * s - b * c).powi(2)
+            })
+            .sum();
+        (a.hypot(b), (residual / signal.len() as f64).sqrt())
+    }
+
+    /// Modulate then decode back to 44.1 kHz, skipping filter settling
+    fn round_trip(input: &[f32], dsd_rate: DsdRate, order: usize) -> Vec<f32> {
+        let mut modulator = SigmaDeltaModulator::new(44100, dsd_rate, order, 1).unwrap();
+        let mut bytes = vec![Vec::new()];
+        modulator.process(input, &mut bytes).unwrap();
+        assert_eq!(modulator.overload_count(), 0);
+
+        let mut decimator = DsdProcessor::new(dsd_rate, 44100, 1).unwrap();
+        let mut pcm = vec![0.0f32; decimator.output_len(bytes[0].len())];
+        let written = decimator.process(&bytes, &mut pcm).unwrap();
+        pcm.truncate(written);
+        pcm.split_off(2048)
+    }
+
+    #[test]
+    fn test_ntf_design() {
+        for order in MIN_ORDER..=MAX_ORDER {
+            for dsd_rate in [DsdRate::Dsd64, DsdRate::Dsd128, DsdRate::Dsd256] {
+                let modulator = SigmaDeltaModulator::new(44100, dsd_rate, order, 1).unwrap();
+                let sections = &modulator.sections;
+
+                let peak = ntf_peak_gain(sections);
+                assert!((peak - MAX_NTF_GAIN).abs() < 0.01, "order {} peak gain {}", order, peak);
+
+                // The audio band is shaped far below the quantizer noise floor
+                let in_band = (1..=100)
+                    .map(|i| modulator.noise_transfer_gain(200.0 * i as f64))
+                    .fold(0.0, f64::max);
+                assert!(20.0 * in_band.log10() < -60.0, "order {} {:?}: in-band {}", order, dsd_rate, in_band);
+                assert_eq!(modulator.noise_transfer_gain(0.0) == 0.0, order % 2 == 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_configuration() {
+        assert!(SigmaDeltaModulator::new(44100, DsdRate::Dsd64, 4, 2).is_err());
+        assert!(SigmaDeltaModulator::new(44100, DsdRate::Dsd64, 8, 2).is_err());
+        assert!(SigmaDeltaModulator::new(44100, DsdRate::Dsd64, 5, 0).is_err());
+        assert!(SigmaDeltaModulator::new(48000, DsdRate::Dsd64, 5, 2).is_err());
+        assert!(SigmaDeltaModulator::new(705_600, DsdRate::Dsd64, 5, 2).is_err());
+        assert!(SigmaDeltaModulator::new(0, DsdRate::Dsd64, 5, 2).is_err());
+
+        let modulator = SigmaDeltaModulator::new(352_800, DsdRate::Dsd64, 7, 2).unwrap();
+        assert_eq!(modulator.interpolation_factor(), 8);
+        assert_eq!(modulator.output_bytes(10), 10);
+        let modulator = SigmaDeltaModulator::new(44100, DsdRate::Dsd256, 6, 2).unwrap();
+        assert_eq!(modulator.interpolation_factor(), 256);
+    }
+
+    #[test]
+    fn test_dc_sets_bit_density() {
+        let mut modulator = SigmaDeltaModulator::new(44100, DsdRate::Dsd64, 5, 1).unwrap();
+        let mut bytes = vec![Vec::new()];
+        modulator.process(&vec![0.5f32; 4410], &mut bytes).unwrap();
+        assert_eq!(bytes[0].len(), 4410 * 8);
+
+        // Skip interpolator settling; density of ones is (1 + 0.5 * 0.5) / 2
+        let settled = &bytes[0][8000..];
+        let ones: u32 = settled.iter().map(|b| b.count_ones()).sum();
+        let density = ones as f64 / (settled.len() * 8) as f64;
+        assert!((density - 0.625).abs() < 1e-3, "density {}", density);
+    }
+
+    #[test]
+    fn test_tone_round_trip() {
+        for order in MIN_ORDER..=MAX_ORDER {
+            for dsd_rate in [DsdRate::Dsd64, DsdRate::Dsd128] {
+                let input = sine(1000.0, 0.5, 44100, 16384, 1);
+                let output = round_trip(&input, dsd_rate, order);
+
+                // Full scale maps to 50% modulation
+                let (amplitude, residual) = fit_sine(&output, 1000.0, 44100.0);
+                let expected = 0.5 * DSD_MODULATION_INDEX;
+                assert!((amplitude / expected - 1.0).abs() < 0.01, "order {}: amplitude {}", order, amplitude);
+
+                let snr_db = 20.0 * (amplitude / std::f64::consts::SQRT_2 / residual).log10();
+                assert!(snr_db > 100.0, "order {} {:?}: SNR {:.1} dB", order, dsd_rate, snr_db);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stable_at_full_scale() {
+        for order in MIN_ORDER..=MAX_ORDER {
+            let mut modulator = SigmaDeltaModulator::new(44100, DsdRate::Dsd64, order, 1).unwrap();
+            let mut bytes = vec![Vec::new()];
+
+            // Clipped square wave: full modulation depth with steep edges
+            let square: Vec<f32> = (0..8820).map(|i| if (i / 50) % 2 == 0 { 1.5 } else { -1.5 }).collect();
+            modulator.process(&square, &mut bytes).unwrap();
+            modulator.process(&sine(5000.0, 1.0, 44100, 8820, 1), &mut bytes).unwrap();
+            assert_eq!(modulator.overload_count(), 0, "order {} went unstable", order);
+        }
+    }
+
+    #[test]
+    fn test_recovers_from_overload() {
+        let mut modulator = SigmaDeltaModulator::new(44100, DsdRate::Dsd64, 7, 1).unwrap();
+        // Force the loop far outside its stable region
+        modulator.paths[0].states[0] = [1e3, 0.0];
+        let mut bytes = vec![Vec::new()];
+        modulator.process(&sine(1000.0, 0.5, 44100, 8192, 1), &mut bytes).unwrap();
+        assert_eq!(modulator.overload_count(), 1);
+
+        let mut decimator = DsdProcessor::new(DsdRate::Dsd64, 44100, 1).unwrap();
+        let mut pcm = vec![0.0f32; 8192];
+        decimator.process(&bytes, &mut pcm).unwrap();
+        let (amplitude, residual) = fit_sine(&pcm[4096..], 1000.0, 44100.0);
+        assert!((amplitude / 0.25 - 1.0).abs() < 0.01);
+        assert!(residual < 1e-4);
+    }
+
+    #[test]
+    fn test_block_size_independence() {
+        let mut input = sine(440.0, 0.8, 88200, 3000, 2);
+        input.iter_mut().skip(1).step_by(2).for_each(|x| *x *= -0.5);
+        let mut whole = SigmaDeltaModulator::new(88200, DsdRate::Dsd128, 6, 2).unwrap();
+        let mut expected = vec![Vec::new(); 2];
+        whole.process(&input, &mut expected).unwrap();
+
+        let mut chunked = SigmaDeltaModulator::new(88200, DsdRate::Dsd128, 6, 2).unwrap();
+        let mut actual = vec![Vec::new(); 2];
+        for chunk in input.chunks(2 * 173) {
+            chunked.process(chunk, &mut actual).unwrap();
+        }
+        assert_eq!(actual, expected);
+        assert_eq!(actual[0].len(), chunked.output_bytes(3000));
+        assert_ne!(actual[0], actual[1], "channels are modulated independently");
+
+        assert!(chunked.process(&input[..3], &mut actual).is_err());
+    }
+
+    #[test]
+    fn test_output_stage_emits_dop() {
+        let input = sine(1000.0, 0.5, 44100, 1024, 2);
+        let mut stage = DsdOutputStage::new(44100, DsdRate::Dsd64, 5, 2).unwrap();
+        assert_eq!(stage.carrier_rate(), 176_400);
+
+        let mut dop = Vec::new();
+        assert_eq!(stage.process(&input, &mut dop).unwrap(), 4096);
+        assert_eq!(dop.len(), stage.output_len(input.len()));
+        assert!(DopDecoder::detect_f32(&dop, 2));
+
+        // The DoP payload is exactly the modulator's bitstream
+        let mut modulator = SigmaDeltaModulator::new(44100, DsdRate::Dsd64, 5, 2).unwrap();
+        let mut expected = vec![Vec::new(); 2];
+        modulator.process(&input, &mut expected).unwrap();
+        let mut decoded = vec![Vec::new(); 2];
+        DopDecoder::new(2).unwrap().decode_f32(&dop, &mut decoded).unwrap();
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_output_stage_carries_odd_bytes() {
+        // One DSD byte per frame, so odd blocks leave a byte for the next
+        let input = sine(1000.0, 0.5, 352_800, 3 * 7, 2);
+        let mut stage = DsdOutputStage::new(352_800, DsdRate::Dsd64, 5, 2).unwrap();
+        stage.prepare(6);
+        let mut dop = Vec::with_capacity(stage.output_len(6));
+        let mut decoder = DopDecoder::new(2).unwrap();
+        let mut decoded = vec![Vec::new(); 2];
+        for (i, block) in input.chunks(6).enumerate() {
+            let frames = stage.process(block, &mut dop).unwrap();
+            assert_eq!(frames, if i.is_multiple_of(2) { 1 } else { 2 });
+            assert!(dop.len() <= stage.output_len(block.len()));
+            decoder.decode_f32(&dop, &mut decoded).unwrap();
+        }
+
+        let mut modulator = SigmaDeltaModulator::new(352_800, DsdRate::Dsd64, 5, 2).unwrap();
+        let mut expected = vec![Vec::new(); 2];
+        modulator.process(&input, &mut expected).unwrap();
+        for (decoded, expected) in decoded.iter().zip(&expected) {
+            assert_eq!(decoded[..], expected[..20]);
+        }
+    }
+}
@@ -1,11 +1,26 @@
 use crate::error::{AudioError, VortexError};
+use crate::fileio::StreamingDecoder;
 use crate::gpu::GpuProcessor;
 use crate::lockfree::AudioRingBuffer;
+use crate::validation::{ResourceLimitEnforcer, ResourceLimits};
 use super::processor::AudioProcessor;
-use super::filters::FilterChain;
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use super::filters::{ChainBatch, ChannelLayout, Filter, FilterChain};
+use super::dsp::{DsdOutputStage, DsdRate, Resampler};
+use super::dsp::resampler::{DriftControl, ResamplerQuality, MAX_RATIO_DEVIATION};
+use crossbeam_queue::ArrayQueue;
+use std::sync::{Arc, atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering}};
 use std::thread::{self, JoinHandle};
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+
+/// Length of the input and output ring buffers
+const RING_BUFFER_MS: usize = 5000;
+
+/// Graph edits that can wait for the audio thread at once
+const COMMAND_CAPACITY: usize = 256;
+
+/// Objects the audio thread can hand back at once; each command retires at
+/// most one, and the control thread collects them before sending the next
+const RETIRED_CAPACITY: usize = COMMAND_CAPACITY + 1;
 
 /// Audio engine configuration
 #[derive(Debug, Clone)]
@@ -42,45 +57,191 @@ pub enum AudioEngineError {
     GpuInitFailed(String),
 }
 
+impl From<AudioEngineError> for VortexError {
+    fn from(error: AudioEngineError) -> Self {
+        AudioError::ProcessingError(error.to_string()).into()
+    }
+}
+
+/// Edit to the processing graph, applied by the audio thread between blocks
+enum Command {
+    /// Validated against the control-side mirror of the chain
+    EditChain(ChainBatch),
+    SetDriftCorrection(Option<DriftCorrection>),
+    SetDsdOutput(Option<DsdOutput>),
+}
+
+/// Something the audio thread is done with, dropped by the control thread
+///
+/// Freeing memory is as unsafe on the audio thread as allocating it.
+enum Retired {
+    /// Committed, holding the filters it removed
+    ChainBatch(ChainBatch),
+    DriftCorrection(DriftCorrection),
+    DsdOutput(DsdOutput),
+}
+
+/// Asynchronous resampler steering the output buffer's fill, with its
+/// output block
+struct DriftCorrection {
+    resampler: Resampler,
+    output: Vec<f32>,
+}
+
+/// DSD output stage with its DoP buffer, sized for the engine's blocks
+struct DsdOutput {
+    stage: DsdOutputStage,
+    dop: Vec<f32>,
+}
+
+/// Everything the audio thread works on
+///
+/// The processing thread owns it while running and hands it back when it
+/// stops, so nothing on the audio path sits behind a lock. All buffers are
+/// sized for one engine block up front.
+struct RealtimeState {
+    chain: FilterChain,
+    drift_correction: Option<DriftCorrection>,
+    dsd_output: Option<DsdOutput>,
+    layout: ChannelLayout,
+    // Block read from the input buffer, processed in place
+    block: Vec<f32>,
+}
+
+impl RealtimeState {
+    fn new(buffer_size: usize, channels: usize) -> Self {
+        let layout = ChannelLayout::new(channels);
+        let mut chain = FilterChain::new();
+        chain.prepare(layout, buffer_size * channels);
+        Self {
+            chain,
+            drift_correction: None,
+            dsd_output: None,
+            layout,
+            block: vec![0.0; buffer_size * channels],
+        }
+    }
+
+    /// Apply queued edits, handing back whatever they replace
+    fn apply_commands(&mut self, commands: &ArrayQueue<Command>, retired: &ArrayQueue<Retired>) {
+        while let Some(command) = commands.pop() {
+            match command {
+                Command::EditChain(mut batch) => {
+                    self.chain.commit_batch(&mut batch);
+                    Self::retire(retired, Retired::ChainBatch(batch));
+                }
+                Command::SetDriftCorrection(correction) => {
+                    if let Some(previous) = std::mem::replace(&mut self.drift_correction, correction) {
+                        Self::retire(retired, Retired::DriftCorrection(previous));
+                    }
+                }
+                Command::SetDsdOutput(output) => {
+                    if let Some(previous) = std::mem::replace(&mut self.dsd_output, output) {
+                        Self::retire(retired, Retired::DsdOutput(previous));
+                    }
+                }
+            }
+        }
+    }
+
+    fn retire(retired: &ArrayQueue<Retired>, item: Retired) {
+        // Cannot fill up (see `RETIRED_CAPACITY`); dropping here is the fallback
+        let _ = retired.push(item);
+    }
+
+    /// Run the first `samples` of `block` through the chain, drift
+    /// correction and DSD stage
+    ///
+    /// Returns the samples to send to `output_buffer`.
+    fn process_block(&mut self, samples: usize, output_buffer: &AudioRingBuffer) -> &[f32] {
+        let Self { chain, drift_correction, dsd_output, layout, block } = self;
+        let mut output = &mut block[..samples];
+        chain.process_in_place(output, *layout);
+        
+        // Follow the output device's clock when drift correction is enabled
+        if let Some(DriftCorrection { resampler, output: resampled }) = drift_correction.as_mut() {
+            let steered = resampler.steer(output_buffer);
+            match steered.and_then(|_| resampler.process(output, resampled)) {
+                Ok(frames) => output = &mut resampled[..frames * layout.channels()],
+                Err(e) => log::error!("Drift correction failed: {}", e),
+            }
+        }
+        
+        // Modulate to DSD when enabled; the device then receives DoP
+        match dsd_output.as_mut() {
+            Some(DsdOutput { stage, dop }) => {
+                if let Err(e) = stage.process(output, dop) {
+                    log::error!("DSD modulation failed: {}", e);
+                    dop.clear();
+                }
+                dop
+            }
+            None => output,
+        }
+    }
+}
+
 /// Main audio processing engine
+///
+/// Processing runs on a dedicated `audio-processing` thread that neither
+/// locks nor touches the heap. Filter, drift correction and DSD changes are queued to it as
+/// commands through a lock-free queue and take effect at the next block;
+/// anything they replace comes back through a second queue and is dropped
+/// here, on the control side.
 pub struct AudioEngine {
     config: AudioConfig,
-    processor: Arc<RwLock<Option<AudioProcessor>>>,
-    filter_chain: Arc<RwLock<FilterChain>>,
+    processor: Option<Arc<AudioProcessor>>,
     gpu_processor: Arc<RwLock<Option<GpuProcessor>>>,
     input_buffer: Arc<AudioRingBuffer>,
     output_buffer: Arc<AudioRingBuffer>,
-    processing_thread: Option<JoinHandle<()>>,
+    commands: Arc<ArrayQueue<Command>>,
+    retired: Arc<ArrayQueue<Retired>>,
+    // Ids and latencies of the filters in the chain, in order, once all
+    // queued commands are applied
+    filters: Mutex<Vec<(String, usize)>>,
+    limits: ResourceLimitEnforcer,
+    // Audio thread state while stopped; the processing thread owns it while running
+    realtime: Option<RealtimeState>,
+    processing_thread: Option<JoinHandle<RealtimeState>>,
     running: Arc<AtomicBool>,
+    decoder_thread: Option<JoinHandle<Box<dyn StreamingDecoder>>>,
+    decoding: Arc<AtomicBool>,
+    output_rate: AtomicU32,
+    // Group delay of the drift correction resampler, in frames
+    drift_latency: AtomicUsize,
 }
 
 impl AudioEngine {
     /// Create a new audio engine with the given configuration
     pub fn new(config: AudioConfig) -> Result<Self, VortexError> {
-        // Calculate buffer capacity (5 seconds of audio)
-        let buffer_capacity = (config.sample_rate * 5) as usize;
-        
-        let input_buffer = Arc::new(AudioRingBuffer::new(
-            buffer_capacity,
-            config.sample_rate,
-        )?);
-        
-        let output_buffer = Arc::new(AudioRingBuffer::new(
-            buffer_capacity,
-            config.sample_rate,
-        )?);
+        let channels = config.channels as usize;
+        if channels == 0 || config.buffer_size == 0 {
+            return Err(AudioError::InvalidConfig {
+                reason: "engine needs at least one channel and a non-empty buffer".to_string(),
+            }.into());
+        }
         
-        let filter_chain = Arc::new(RwLock::new(FilterChain::new()));
+        let input_buffer = Arc::new(AudioRingBuffer::new(RING_BUFFER_MS, config.sample_rate, channels));
+        let output_buffer = Arc::new(AudioRingBuffer::new(RING_BUFFER_MS, config.sample_rate, channels));
+        let realtime = RealtimeState::new(config.buffer_size, channels);
         
         Ok(Self {
-            config,
-            processor: Arc::new(RwLock::new(None)),
-            filter_chain,
+            processor: None,
             gpu_processor: Arc::new(RwLock::new(None)),
             input_buffer,
             output_buffer,
+            commands: Arc::new(ArrayQueue::new(COMMAND_CAPACITY)),
+            retired: Arc::new(ArrayQueue::new(RETIRED_CAPACITY)),
+            filters: Mutex::new(Vec::new()),
+            limits: ResourceLimitEnforcer::new(ResourceLimits::default()),
+            realtime: Some(realtime),
             processing_thread: None,
             running: Arc::new(AtomicBool::new(false)),
+            decoder_thread: None,
+            decoding: Arc::new(AtomicBool::new(false)),
+            output_rate: AtomicU32::new(config.sample_rate),
+            drift_latency: AtomicUsize::new(0),
+            config,
         })
     }
     
@@ -93,7 +254,7 @@ impl AudioEngine {
             self.config.channels,
         )?;
         
-        *self.processor.write() = Some(processor);
+        self.processor = Some(Arc::new(processor));
         
         // Initialize GPU if enabled
         if self.config.enable_gpu {
@@ -117,37 +278,32 @@ impl AudioEngine {
             return Err(AudioEngineError::AlreadyRunning.into());
         }
         
-        if self.processor.read().is_none() {
-            return Err(AudioEngineError::NotInitialized.into());
-        }
+        let processor = self.processor.clone().ok_or(AudioEngineError::NotInitialized)?;
+        let state = self.realtime.take().ok_or(AudioEngineError::AlreadyRunning)?;
         
         self.running.store(true, Ordering::Release);
         
         let running = Arc::clone(&self.running);
         let input_buffer = Arc::clone(&self.input_buffer);
         let output_buffer = Arc::clone(&self.output_buffer);
-        let processor = Arc::clone(&self.processor);
-        let filter_chain = Arc::clone(&self.filter_chain);
-        let gpu_processor = Arc::clone(&self.gpu_processor);
-        let buffer_size = self.config.buffer_size;
-        let channels = self.config.channels as usize;
+        let commands = Arc::clone(&self.commands);
+        let retired = Arc::clone(&self.retired);
         
         // Spawn processing thread
         let handle = thread::Builder::new()
             .name("audio-processing".to_string())
             .spawn(move || {
-                Self::processing_loop(
-                    running,
-                    input_buffer,
-                    output_buffer,
-                    processor,
-                    filter_chain,
-                    gpu_processor,
-                    buffer_size,
-                    channels,
-                );
-            })
-            .map_err(|e| AudioError::ProcessingError(format!("Failed to spawn thread: {}", e)))?;
+                Self::processing_loop(running, input_buffer, output_buffer, processor, commands, retired, state)
+            });
+        let handle = match handle {
+            Ok(handle) => handle,
+            Err(e) => {
+                // The closure and the state with it are gone; start over empty
+                self.running.store(false, Ordering::Release);
+                self.reset_realtime();
+                return Err(AudioError::ProcessingError(format!("Failed to spawn thread: {}", e)).into());
+            }
+        };
         
         self.processing_thread = Some(handle);
         
@@ -164,24 +320,253 @@ impl AudioEngine {
         self.running.store(false, Ordering::Release);
         
         if let Some(handle) = self.processing_thread.take() {
-            handle.join().map_err(|_| {
-                AudioError::ProcessingError("Processing thread panicked".to_string())
-            })?;
+            match handle.join() {
+                Ok(state) => self.realtime = Some(state),
+                Err(_) => {
+                    self.reset_realtime();
+                    return Err(AudioError::ProcessingError("Processing thread panicked".to_string()).into());
+                }
+            }
         }
+        self.collect_retired();
         
         log::info!("Audio processing stopped");
         Ok(())
     }
     
-    /// Add a filter to the processing chain
-    pub fn add_filter(&self, filter: Box<dyn crate::audio::filters::Filter>) -> String {
-        self.filter_chain.write().add_filter(filter)
+    /// Start again from an empty graph after the audio thread state was lost
+    fn reset_realtime(&mut self) {
+        self.realtime = Some(RealtimeState::new(self.config.buffer_size, self.config.channels as usize));
+        self.filters.lock().clear();
+        while self.commands.pop().is_some() {}
+        self.output_rate.store(self.config.sample_rate, Ordering::Release);
+        self.drift_latency.store(0, Ordering::Release);
     }
     
-    /// Remove a filter from the processing chain
+    /// Feed the input buffer from a streaming decoder on a dedicated thread
+    ///
+    /// Any previous stream is stopped first. The decoder must match the engine's
+    /// sample rate and channel count.
+    pub fn start_stream(&mut self, decoder: Box<dyn StreamingDecoder>) -> Result<(), VortexError> {
+        if decoder.sample_rate() != self.config.sample_rate || decoder.channels() != self.config.channels {
+            return Err(AudioError::InvalidConfig {
+                reason: format!(
+                    "stream is {} Hz / {} channels, engine expects {} Hz / {} channels",
+                    decoder.sample_rate(),
+                    decoder.channels(),
+                    self.config.sample_rate,
+                    self.config.channels,
+                ),
+            }.into());
+        }
+        
+        self.stop_stream()?;
+        self.decoding.store(true, Ordering::Release);
+        
+        let decoding = Arc::clone(&self.decoding);
+        let input_buffer = Arc::clone(&self.input_buffer);
+        
+        let handle = thread::Builder::new()
+            .name("audio-decoder".to_string())
+            .spawn(move || Self::decoding_loop(decoder, decoding, input_buffer))
+            .map_err(|e| AudioError::DriverRuntimeError {
+                driver: "decoder".to_string(),
+                reason: format!("Failed to spawn thread: {}", e),
+            })?;
+        
+        self.decoder_thread = Some(handle);
+        Ok(())
+    }
+    
+    /// Stop the decoder thread and hand back its decoder (e.g. to seek and restart)
+    ///
+    /// Samples already queued in the input buffer are left in place.
+    pub fn stop_stream(&mut self) -> Result<Option<Box<dyn StreamingDecoder>>, VortexError> {
+        self.decoding.store(false, Ordering::Release);
+        
+        match self.decoder_thread.take() {
+            Some(handle) => handle.join().map(Some).map_err(|_| {
+                AudioError::DriverRuntimeError {
+                    driver: "decoder".to_string(),
+                    reason: "Decoder thread panicked".to_string(),
+                }.into()
+            }),
+            None => Ok(None),
+        }
+    }
+    
+    /// Check if a decoder thread is still producing samples
+    pub fn is_streaming(&self) -> bool {
+        self.decoding.load(Ordering::Acquire)
+    }
+    
+    /// Add a filter to the end of the processing chain
+    ///
+    /// The filter is prepared for the engine's layout here and joins the
+    /// chain at the audio thread's next block. A full chain rejects it with
+    /// a `ConfigError`.
+    pub fn add_filter(&self, filter: Box<dyn Filter>) -> Result<String, VortexError> {
+        let id = filter.metadata().id.clone();
+        let mut batch = ChainBatch::new();
+        batch.push(filter);
+        self.apply_batch(batch)?;
+        
+        log::info!("Added filter: {}", id);
+        Ok(id)
+    }
+    
+    /// Insert a filter at `index` in the processing chain, as `add_filter` does
+    pub fn insert_filter(&self, index: usize, filter: Box<dyn Filter>) -> Result<String, VortexError> {
+        let id = filter.metadata().id.clone();
+        let mut batch = ChainBatch::new();
+        batch.insert(index, filter);
+        self.apply_batch(batch)?;
+        
+        log::info!("Inserted filter: {} at index {}", id, index);
+        Ok(id)
+    }
+    
+    /// Remove a filter from the processing chain at the audio thread's next block
     pub fn remove_filter(&self, filter_id: &str) -> Result<(), VortexError> {
-        self.filter_chain.write().remove_filter(filter_id)
-            .map_err(|e| AudioError::InvalidParameter(format!("Filter removal failed: {}", e)).into())
+        let mut batch = ChainBatch::new();
+        batch.remove(filter_id);
+        self.apply_batch(batch)?;
+        
+        log::info!("Removed filter: {}", filter_id);
+        Ok(())
+    }
+    
+    /// Move a filter to `index` in the processing chain
+    pub fn move_filter(&self, filter_id: &str, index: usize) -> Result<(), VortexError> {
+        let mut batch = ChainBatch::new();
+        batch.move_filter(filter_id, index);
+        self.apply_batch(batch)
+    }
+    
+    /// Swap the positions of two filters in the processing chain
+    pub fn swap_filters(&self, first: &str, second: &str) -> Result<(), VortexError> {
+        let mut batch = ChainBatch::new();
+        batch.swap(first, second);
+        self.apply_batch(batch)
+    }
+    
+    /// Apply every edit in `batch` to the processing chain within one block
+    /// boundary, or none of them
+    ///
+    /// The batch is checked here against the chain as the queued commands
+    /// will leave it, and its filters are prepared for the engine's layout,
+    /// so the audio thread only has to commit it.
+    pub fn apply_batch(&self, mut batch: ChainBatch) -> Result<(), VortexError> {
+        let channels = self.config.channels as usize;
+        batch.prepare(ChannelLayout::new(channels), self.config.buffer_size * channels);
+        
+        let mut filters = self.filters.lock();
+        let ids = filters.iter().map(|(id, _)| id.as_str()).collect();
+        let order = batch.validate(ids, &self.limits)?;
+        let mirror: Vec<(String, usize)> = order
+            .into_iter()
+            .map(|id| {
+                let latency = match filters.iter().find(|(existing, _)| existing == id) {
+                    Some(&(_, latency)) => latency,
+                    None => batch.filter(id).map_or(0, |filter| filter.latency_samples()),
+                };
+                (id.to_string(), latency)
+            })
+            .collect();
+        
+        self.send(Command::EditChain(batch))?;
+        *filters = mirror;
+        Ok(())
+    }
+    
+    /// Absorb the drift between the clock feeding the engine and the output
+    /// device's clock
+    ///
+    /// The processed audio goes through an asynchronous resampler whose
+    /// ratio `Resampler::steer` adjusts every block to hold the output buffer
+    /// at `control.target_fill`, ahead of any DSD modulation. The resampler's
+    /// group delay adds to `latency_samples`.
+    pub fn enable_drift_correction(&self, quality: ResamplerQuality, control: DriftControl) -> Result<(), VortexError> {
+        let rate = self.config.sample_rate;
+        let mut resampler = Resampler::new_async(rate, rate, quality, self.config.channels)?;
+        resampler.set_drift_control(control)?;
+        resampler.prepare(self.config.buffer_size);
+        let output = vec![0.0; resampler.max_output_len(self.config.buffer_size) * self.config.channels as usize];
+        let latency = resampler.latency_samples().round() as usize;
+        
+        self.send(Command::SetDriftCorrection(Some(DriftCorrection { resampler, output })))?;
+        self.drift_latency.store(latency, Ordering::Release);
+        log::info!("Drift correction enabled, holding the output buffer at {:.0}%", control.target_fill * 100.0);
+        Ok(())
+    }
+    
+    /// Pass the processed audio to the output buffer at the nominal rate again
+    pub fn disable_drift_correction(&self) -> Result<(), VortexError> {
+        self.send(Command::SetDriftCorrection(None))?;
+        self.drift_latency.store(0, Ordering::Release);
+        Ok(())
+    }
+    
+    /// Largest block the output stages receive, in samples: one engine
+    /// block, stretched by drift correction at its furthest
+    fn output_block_samples(&self) -> usize {
+        let frames = (self.config.buffer_size as f64 * (1.0 + MAX_RATIO_DEVIATION)).ceil() as usize + 1;
+        frames * self.config.channels as usize
+    }
+    
+    /// Modulate the processed output to DSD and deliver it as DoP
+    ///
+    /// Returns the DoP carrier rate the output device must run at. The engine
+    /// sample rate must divide the DSD rate by a power of two of at least 8.
+    pub fn enable_dsd_output(&self, dsd_rate: DsdRate, order: usize) -> Result<u32, VortexError> {
+        let block_samples = self.output_block_samples();
+        let mut stage = DsdOutputStage::new(self.config.sample_rate, dsd_rate, order, self.config.channels)?;
+        stage.prepare(block_samples);
+        let carrier_rate = stage.carrier_rate();
+        let dop = Vec::with_capacity(stage.output_len(block_samples));
+        
+        self.send(Command::SetDsdOutput(Some(DsdOutput { stage, dop })))?;
+        self.output_rate.store(carrier_rate, Ordering::Release);
+        log::info!("DSD output enabled: {:?} as DoP at {} Hz", dsd_rate, carrier_rate);
+        Ok(carrier_rate)
+    }
+    
+    /// Return to PCM output
+    pub fn disable_dsd_output(&self) -> Result<(), VortexError> {
+        self.send(Command::SetDsdOutput(None))?;
+        self.output_rate.store(self.config.sample_rate, Ordering::Release);
+        Ok(())
+    }
+    
+    /// Sample rate of the output buffer: the DoP carrier rate while DSD output is enabled
+    pub fn output_sample_rate(&self) -> u32 {
+        self.output_rate.load(Ordering::Acquire)
+    }
+    
+    /// Delay from input to output, in frames: one processing block plus
+    /// the latency of every filter in the chain, bypassed ones included,
+    /// and of drift correction when enabled
+    pub fn latency_samples(&self) -> usize {
+        let filters: usize = self.filters.lock().iter().map(|(_, latency)| latency).sum();
+        self.config.buffer_size + filters + self.drift_latency.load(Ordering::Acquire)
+    }
+    
+    /// Delay from input to output, in milliseconds
+    pub fn latency_ms(&self) -> f64 {
+        self.latency_samples() as f64 * 1000.0 / self.config.sample_rate as f64
+    }
+    
+    /// Queue a command for the audio thread
+    fn send(&self, command: Command) -> Result<(), VortexError> {
+        self.collect_retired();
+        self.commands.push(command).map_err(|_| {
+            AudioError::ProcessingError("Audio thread command queue is full".to_string()).into()
+        })
+    }
+    
+    /// Drop whatever the audio thread has handed back
+    fn collect_retired(&self) {
+        while self.retired.pop().is_some() {}
     }
     
     /// Get current configuration
@@ -195,63 +580,99 @@ impl AudioEngine {
     }
     
     /// Main processing loop (runs in dedicated thread)
+    ///
+    /// Returns the audio thread state when stopped.
     fn processing_loop(
         running: Arc<AtomicBool>,
         input_buffer: Arc<AudioRingBuffer>,
         output_buffer: Arc<AudioRingBuffer>,
-        processor: Arc<RwLock<Option<AudioProcessor>>>,
-        filter_chain: Arc<RwLock<FilterChain>>,
-        gpu_processor: Arc<RwLock<Option<GpuProcessor>>>,
-        buffer_size: usize,
-        channels: usize,
-    ) {
-        let mut temp_input = vec![0.0f32; buffer_size * channels];
-        let mut temp_output = vec![0.0f32; buffer_size * channels];
-        
+        processor: Arc<AudioProcessor>,
+        commands: Arc<ArrayQueue<Command>>,
+        retired: Arc<ArrayQueue<Retired>>,
+        mut state: RealtimeState,
+    ) -> RealtimeState {
         while running.load(Ordering::Acquire) {
-            // Read from input buffer
-            let samples_read = match input_buffer.read_slice(&mut temp_input) {
-                Ok(n) => n,
-                Err(_) => {
-                    // Buffer underrun, use silence
-                    temp_input.fill(0.0);
-                    buffer_size * channels
-                }
-            };
-            
-            if samples_read == 0 {
+            let processed = Self::process_available(&mut state, &input_buffer, &output_buffer, &processor, &commands, &retired);
+            if !processed {
                 // No data available, sleep briefly
                 thread::sleep(std::time::Duration::from_micros(100));
-                continue;
             }
-            
-            // Process audio through filter chain
-            {
-                let chain = filter_chain.read();
-                chain.process(&temp_input, &mut temp_output);
-            }
-            
-            // Apply GPU processing if available
-            if let Some(gpu) = gpu_processor.read().as_ref() {
-                // GPU processing would go here
-                // For now, just copy the output
-            }
-            
-            // Write to output buffer
-            if let Err(e) = output_buffer.write_slice(&temp_output[..samples_read]) {
-                log::error!("Output buffer write failed: {}", e);
+        }
+        state
+    }
+    
+    /// The audio callback: apply pending commands and process up to one
+    /// block of whole frames from the input buffer
+    ///
+    /// Returns false if there was no input. Never locks or allocates; test
+    /// builds fail the thread if it touches the heap.
+    fn process_available(
+        state: &mut RealtimeState,
+        input_buffer: &AudioRingBuffer,
+        output_buffer: &AudioRingBuffer,
+        processor: &AudioProcessor,
+        commands: &ArrayQueue<Command>,
+        retired: &ArrayQueue<Retired>,
+    ) -> bool {
+        #[cfg(test)]
+        let _scope = super::alloc_check::NoAllocScope::enter("audio callback");
+        
+        state.apply_commands(commands, retired);
+        
+        let channels = state.layout.channels();
+        let frames = input_buffer.available_frames().min(state.block.len() / channels);
+        if frames == 0 {
+            return false;
+        }
+        
+        let samples_read = input_buffer.read_samples(&mut state.block[..frames * channels]);
+        let block = state.process_block(samples_read, output_buffer);
+        if output_buffer.write_samples(block) < block.len() {
+            processor.record_overrun();
+        }
+        
+        processor.update_stats(samples_read);
+        true
+    }
+    
+    /// Decoder loop (runs in dedicated thread until end of stream or stop)
+    fn decoding_loop(
+        mut decoder: Box<dyn StreamingDecoder>,
+        decoding: Arc<AtomicBool>,
+        input_buffer: Arc<AudioRingBuffer>,
+    ) -> Box<dyn StreamingDecoder> {
+        let channels = decoder.channels() as usize;
+        let mut block = vec![0.0f32; decoder.block_frames() * channels];
+        let mut queued = 0..0;
+        
+        while decoding.load(Ordering::Acquire) {
+            if queued.is_empty() {
+                match decoder.next_block(&mut block) {
+                    Ok(0) => break,
+                    Ok(frames) => queued = 0..frames * channels,
+                    Err(e) => {
+                        log::error!("Stream decoding failed: {}", e);
+                        break;
+                    }
+                }
             }
             
-            // Update processor stats
-            if let Some(proc) = processor.write().as_mut() {
-                proc.update_stats(samples_read);
+            // Push as much of the block as fits; wait for the consumer otherwise
+            let written = input_buffer.write_samples(&block[queued.clone()]);
+            queued.start += written;
+            if !queued.is_empty() {
+                thread::sleep(std::time::Duration::from_millis(1));
             }
         }
+        
+        decoding.store(false, Ordering::Release);
+        decoder
     }
 }
 
 impl Drop for AudioEngine {
     fn drop(&mut self) {
+        let _ = self.stop_stream();
         let _ = self.stop_processing();
     }
 }
@@ -306,4 +727,287 @@ mod tests {
         
         engine.stop_processing().unwrap();
     }
+    
+    #[test]
+    fn test_stream_feeds_input_buffer() {
+        use crate::fileio::test_fixtures::{test_tone, write_wav, WavEncoding};
+        use crate::fileio::FileStream;
+        
+        let dir = tempfile::TempDir::new().unwrap();
+        let tone = test_tone(20_000, 2, 48000);
+        let path = write_wav(&dir.path().join("tone.wav"), 48000, 2, WavEncoding::Float32, &tone);
+        
+        let mut engine = AudioEngine::new(AudioConfig {
+            enable_gpu: false,
+            ..Default::default()
+        }).unwrap();
+        engine.start_stream(Box::new(FileStream::open(&path, 512).unwrap())).unwrap();
+        
+        let mut queued = vec![0.0f32; tone.len()];
+        let mut read = 0;
+        while read < queued.len() {
+            read += engine.input_buffer.read_samples(&mut queued[read..]);
+            if !engine.is_streaming() && engine.input_buffer.available_frames() == 0 {
+                break;
+            }
+        }
+        assert_eq!(read, tone.len());
+        assert_eq!(queued, tone);
+        
+        // The decoder comes back at the end of the stream
+        let decoder = engine.stop_stream().unwrap().unwrap();
+        assert_eq!(decoder.position(), 20_000);
+        assert!(engine.stop_stream().unwrap().is_none());
+    }
+    
+    #[test]
+    fn test_dsd_output_emits_dop() {
+        use crate::audio::dsp::DopDecoder;
+        use crate::fileio::test_fixtures::test_tone;
+        
+        let mut engine = AudioEngine::new(AudioConfig {
+            sample_rate: 44100,
+            enable_gpu: false,
+            ..Default::default()
+        }).unwrap();
+        engine.initialize().unwrap();
+        assert_eq!(engine.output_sample_rate(), 44100);
+        assert!(engine.enable_dsd_output(DsdRate::Dsd64, 4).is_err());
+        assert_eq!(engine.enable_dsd_output(DsdRate::Dsd64, 5).unwrap(), 176_400);
+        assert_eq!(engine.output_sample_rate(), 176_400);
+        
+        engine.input_buffer.write_samples(&test_tone(1024, 2, 44100));
+        engine.start_processing().unwrap();
+        
+        // 1024 input frames become 4096 DoP frames
+        let mut dop = vec![0.0f32; 4096 * 2];
+        let mut read = 0;
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while read < dop.len() && std::time::Instant::now() < deadline {
+            read += engine.output_buffer.read_samples(&mut dop[read..]);
+        }
+        engine.stop_processing().unwrap();
+        assert_eq!(read, dop.len());
+        assert!(DopDecoder::detect_f32(&dop, 2));
+        
+        engine.disable_dsd_output().unwrap();
+        assert_eq!(engine.output_sample_rate(), 44100);
+    }
+    
+    #[test]
+    fn test_realtime_state_does_not_allocate() {
+        use crate::audio::alloc_check::heap_operations;
+        use crate::audio::filters::{BiquadCoefficients, BiquadFilter};
+        
+        let (block, channels) = (256, 2);
+        let mut state = RealtimeState::new(block, channels);
+        let commands = ArrayQueue::new(COMMAND_CAPACITY);
+        let retired = ArrayQueue::new(RETIRED_CAPACITY);
+        let output_buffer = AudioRingBuffer::new(100, 48000, channels);
+        
+        let coeffs = BiquadCoefficients::peaking(1000.0, 48000.0, 1.0, 6.0);
+        let mut filter: Box<dyn Filter> = Box::new(BiquadFilter::new("peak".to_string(), coeffs));
+        filter.prepare(state.layout, block * channels);
+        let id = filter.metadata().id.clone();
+        let mut stage = DsdOutputStage::new(44100, DsdRate::Dsd64, 5, channels as u16).unwrap();
+        stage.prepare(block * channels);
+        let dop = Vec::with_capacity(stage.output_len(block * channels));
+        
+        for (i, sample) in state.block.iter_mut().enumerate() {
+            *sample = (i as f32 * 0.05).sin() * 0.5;
+        }
+        
+        let mut add = ChainBatch::new();
+        add.push(filter);
+        let _ = commands.push(Command::EditChain(add));
+        let ((), operations) = heap_operations(|| {
+            state.apply_commands(&commands, &retired);
+            state.process_block(block * channels, &output_buffer);
+        });
+        assert_eq!(operations, 0);
+        assert_eq!(state.chain.len(), 1);
+        
+        let _ = commands.push(Command::SetDsdOutput(Some(DsdOutput { stage, dop })));
+        let ((), operations) = heap_operations(|| {
+            state.apply_commands(&commands, &retired);
+            assert_eq!(state.process_block(block * channels, &output_buffer).len(), 4 * block * channels);
+        });
+        assert_eq!(operations, 0);
+        
+        // At one DSD byte per frame, odd blocks carry a byte over to the next
+        let mut stage = DsdOutputStage::new(352_800, DsdRate::Dsd64, 5, channels as u16).unwrap();
+        stage.prepare(block * channels);
+        let dop = Vec::with_capacity(stage.output_len(block * channels));
+        let _ = commands.push(Command::SetDsdOutput(Some(DsdOutput { stage, dop })));
+        let ((), operations) = heap_operations(|| {
+            state.apply_commands(&commands, &retired);
+            for _ in 0..3 {
+                assert!(!state.process_block((block - 1) * channels, &output_buffer).is_empty());
+            }
+        });
+        assert_eq!(operations, 0);
+        
+        let mut remove = ChainBatch::new();
+        remove.remove(&id);
+        let _ = commands.push(Command::EditChain(remove));
+        let _ = commands.push(Command::SetDsdOutput(None));
+        let mut resampler = Resampler::new_async(48000, 48000, ResamplerQuality::Draft, channels as u16).unwrap();
+        resampler.prepare(block);
+        let output = vec![0.0; resampler.max_output_len(block) * channels];
+        let _ = commands.push(Command::SetDriftCorrection(Some(DriftCorrection { resampler, output })));
+        let ((), operations) = heap_operations(|| {
+            state.apply_commands(&commands, &retired);
+            for _ in 0..3 {
+                state.process_block(block * channels, &output_buffer);
+            }
+        });
+        assert_eq!(operations, 0);
+        assert!(state.chain.is_empty());
+        
+        let _ = commands.push(Command::SetDriftCorrection(None));
+        let ((), operations) = heap_operations(|| {
+            state.apply_commands(&commands, &retired);
+            state.process_block(block * channels, &output_buffer);
+        });
+        assert_eq!(operations, 0);
+        
+        // Both batches, one holding the filter, both DSD stages and the
+        // drift correction come back to be freed
+        assert_eq!(retired.len(), 5);
+    }
+    
+    #[test]
+    fn test_drift_correction_follows_output_clock() {
+        let mut engine = AudioEngine::new(AudioConfig {
+            channels: 1,
+            buffer_size: 480,
+            enable_gpu: false,
+            ..Default::default()
+        }).unwrap();
+        let latency = engine.latency_samples();
+        let control = DriftControl { target_fill: 0.5, response_secs: 2.0, ..DriftControl::default() };
+        engine.enable_drift_correction(ResamplerQuality::Draft, control).unwrap();
+        assert!(engine.latency_samples() > latency);
+        
+        // Blocks of 10 ms at 48 kHz, pulled by a device running 300 ppm fast
+        let mut state = engine.realtime.take().unwrap();
+        state.apply_commands(&engine.commands, &engine.retired);
+        let output_buffer = AudioRingBuffer::new(200, 48000, 1);
+        output_buffer.write_samples(&vec![0.0; (0.4 * output_buffer.capacity_ms() * 48.0) as usize]);
+        let mut pulled = vec![0.0; 1024];
+        let mut device_clock = 0.0;
+        let mut fills = Vec::new();
+        for _ in 0..3000 {
+            state.block.fill(0.1);
+            let block = state.process_block(480, &output_buffer);
+            output_buffer.write_samples(block);
+            
+            device_clock += 480.0 * 1.0003;
+            let wanted = device_clock as usize;
+            device_clock -= wanted as f64;
+            output_buffer.read_samples(&mut pulled[..wanted]);
+            fills.push(output_buffer.fill_percentage());
+        }
+        
+        // Uncorrected, the buffer would have drained by 432 frames (4.5%)
+        let settled = &fills[fills.len() - 500..];
+        assert!(settled.iter().all(|&f| (f - 0.5).abs() < 0.02), "fill {:?}", settled.last());
+        
+        engine.realtime = Some(state);
+        engine.disable_drift_correction().unwrap();
+        assert_eq!(engine.latency_samples(), latency);
+    }
+    
+    #[test]
+    fn test_filter_changes_while_running() {
+        use crate::audio::filters::{BiquadCoefficients, BiquadFilter};
+        use crate::fileio::test_fixtures::test_tone;
+        
+        let mut engine = AudioEngine::new(AudioConfig {
+            enable_gpu: false,
+            ..Default::default()
+        }).unwrap();
+        engine.initialize().unwrap();
+        engine.start_processing().unwrap();
+        
+        let tone = test_tone(512, 2, 48000);
+        let mut sink = vec![0.0f32; tone.len()];
+        let mut ids = Vec::new();
+        for i in 0..20 {
+            let coeffs = BiquadCoefficients::peaking(200.0 * (i + 1) as f32, 48000.0, 1.0, 3.0);
+            ids.push(engine.add_filter(Box::new(BiquadFilter::new(format!("peak{}", i), coeffs))).unwrap());
+            engine.input_buffer.write_samples(&tone);
+            if i % 3 == 2 {
+                engine.remove_filter(&ids.remove(0)).unwrap();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            engine.output_buffer.read_samples(&mut sink);
+        }
+        assert!(engine.remove_filter("missing").is_err());
+        assert_eq!(engine.latency_samples(), engine.config().buffer_size);
+        
+        // Reorder and replace as one edit while audio flows
+        let replacement = BiquadFilter::peaking(5000.0, 48000.0, 1.0, -3.0);
+        let mut batch = ChainBatch::new();
+        batch
+            .swap(&ids[0], &ids[1])
+            .move_filter(&ids[2], 0)
+            .remove(&ids[3])
+            .insert(1, Box::new(replacement));
+        engine.apply_batch(batch).unwrap();
+        engine.input_buffer.write_samples(&tone);
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        engine.output_buffer.read_samples(&mut sink);
+        let removed = ids.remove(3);
+        let expected: Vec<String> = engine.filters.lock().iter().map(|(id, _)| id.clone()).collect();
+        assert_eq!(expected[0], ids[2]);
+        assert_eq!(expected[2..5], [ids[1].clone(), ids[0].clone(), ids[3].clone()]);
+        
+        // The audio thread panics in test builds if it ever touches the heap
+        assert!(engine.stop_processing().is_ok());
+        let state = engine.realtime.as_ref().unwrap();
+        assert_eq!(state.chain.ids().collect::<Vec<_>>(), expected);
+        assert!(state.chain.get_filter(&removed).is_none());
+    }
+    
+    #[test]
+    fn test_full_chain_rejects_filters() {
+        use crate::audio::filters::{BiquadCoefficients, BiquadFilter};
+        use crate::error::VortexError;
+        
+        let engine = AudioEngine::new(AudioConfig::default()).unwrap();
+        let filter = |i: usize| Box::new(BiquadFilter::new(format!("f{}", i), BiquadCoefficients { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 }));
+        let ids: Vec<String> = (0..ResourceLimits::default().max_filter_chain_length)
+            .map(|i| engine.add_filter(filter(i)).unwrap())
+            .collect();
+        
+        assert!(matches!(engine.add_filter(filter(99)), Err(VortexError::Config(_))));
+        assert!(engine.insert_filter(0, filter(99)).is_err());
+        assert_eq!(engine.filters.lock().len(), ids.len());
+        assert_eq!(engine.filters.lock()[0].0, ids[0]);
+        
+        // A failing batch changes nothing, and room can be made in the same batch
+        let mut batch = ChainBatch::new();
+        batch.remove(&ids[0]).push(filter(100)).push(filter(101));
+        assert!(engine.apply_batch(batch).is_err());
+        assert_eq!(engine.filters.lock()[0].0, ids[0]);
+        let mut batch = ChainBatch::new();
+        batch.remove(&ids[0]).insert(0, filter(100));
+        engine.apply_batch(batch).unwrap();
+        assert_eq!(engine.filters.lock().len(), ids.len());
+        assert_ne!(engine.filters.lock()[0].0, ids[0]);
+    }
+    
+    #[test]
+    fn test_stream_format_mismatch() {
+        use crate::fileio::test_fixtures::{test_tone, write_wav, WavEncoding};
+        use crate::fileio::FileStream;
+        
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = write_wav(&dir.path().join("mono.wav"), 44100, 1, WavEncoding::Pcm16, &test_tone(100, 1, 44100));
+        
+        let mut engine = AudioEngine::new(AudioConfig::default()).unwrap();
+        assert!(engine.start_stream(Box::new(FileStream::open(&path, 64).unwrap())).is_err());
+        assert!(!engine.is_streaming());
+    }
 }
@@ -1,8 +1,14 @@
-use super::filter_chain::{Filter, FilterMetadata};
+use super::filter_chain::{ChannelLayout, ChannelMask, Filter, FilterMetadata};
+use super::preset::FilterPreset;
+use crossbeam_queue::ArrayQueue;
+use rustfft::num_complex::Complex64;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 use uuid::Uuid;
 
 /// Filter types for biquad filter
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FilterType {
     Lowpass,
     Highpass,
@@ -15,31 +21,71 @@ pub enum FilterType {
 }
 
 /// Biquad filter coefficients
-#[derive(Debug, Clone, Copy)]
+///
+/// Designed and stored in f64; each `BiquadTopology` rounds them to its own
+/// working precision.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct BiquadCoefficients {
-    pub b0: f32,
-    pub b1: f32,
-    pub b2: f32,
-    pub a1: f32,
-    pub a2: f32,
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+/// Bandwidth of a shelf filter's transition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShelfWidth {
+    /// Resonance of the transition, as for the other filter types
+    Q(f32),
+    /// Shelf slope S; 1.0 is the steepest slope without overshoot
+    ///
+    /// Steeper slopes are limited to the steepest the gain allows.
+    Slope(f32),
+}
+
+/// Angular frequency terms shared by every cookbook design
+struct Prototype {
+    sin_omega: f64,
+    cos_omega: f64,
+}
+
+impl Prototype {
+    fn new(frequency: f32, sample_rate: f32) -> Self {
+        let omega = 2.0 * std::f64::consts::PI * frequency as f64 / sample_rate as f64;
+        Self {
+            sin_omega: omega.sin(),
+            cos_omega: omega.cos(),
+        }
+    }
+
+    /// Bandwidth term for a resonance `q`
+    fn alpha(&self, q: f32) -> f64 {
+        self.sin_omega / (2.0 * q as f64)
+    }
+
+    /// Bandwidth term for a shelf of linear amplitude `a`
+    fn shelf_alpha(&self, width: ShelfWidth, a: f64) -> f64 {
+        match width {
+            ShelfWidth::Q(q) => self.alpha(q),
+            ShelfWidth::Slope(slope) => {
+                // The term under the root turns negative past S = k / (k - 2)
+                let k = a + 1.0 / a;
+                let slope = if k > 2.0 { (slope as f64).min(k / (k - 2.0)) } else { slope as f64 };
+                self.sin_omega / 2.0 * (k * (1.0 / slope - 1.0) + 2.0).max(0.0).sqrt()
+            }
+        }
+    }
+}
+
+/// Linear amplitude A of a peaking or shelf gain
+fn amplitude(gain_db: f32) -> f64 {
+    10.0_f64.powf(gain_db as f64 / 40.0)
 }
 
 impl BiquadCoefficients {
-    /// Calculate coefficients for a peaking EQ filter
-    pub fn peaking(frequency: f32, sample_rate: f32, q: f32, gain_db: f32) -> Self {
-        let omega = 2.0 * std::f32::consts::PI * frequency / sample_rate;
-        let sin_omega = omega.sin();
-        let cos_omega = omega.cos();
-        let alpha = sin_omega / (2.0 * q);
-        let a = 10.0_f32.powf(gain_db / 40.0);
-        
-        let b0 = 1.0 + alpha * a;
-        let b1 = -2.0 * cos_omega;
-        let b2 = 1.0 - alpha * a;
-        let a0 = 1.0 + alpha / a;
-        let a1 = -2.0 * cos_omega;
-        let a2 = 1.0 - alpha / a;
-        
+    /// Normalize a transfer function by `a0`
+    fn normalized(b0: f64, b1: f64, b2: f64, a0: f64, a1: f64, a2: f64) -> Self {
         Self {
             b0: b0 / a0,
             b1: b1 / a0,
@@ -48,80 +94,513 @@ impl BiquadCoefficients {
             a2: a2 / a0,
         }
     }
+
+    /// Design any filter type from the Audio EQ Cookbook
+    ///
+    /// `gain_db` only affects peaking and shelf filters; shelves take `q` as
+    /// `ShelfWidth::Q` and bandpass uses the constant 0 dB peak form.
+    pub fn design(filter_type: FilterType, frequency: f32, sample_rate: f32, q: f32, gain_db: f32) -> Self {
+        match filter_type {
+            FilterType::Lowpass => Self::lowpass(frequency, sample_rate, q),
+            FilterType::Highpass => Self::highpass(frequency, sample_rate, q),
+            FilterType::Bandpass => Self::bandpass(frequency, sample_rate, q),
+            FilterType::Notch => Self::notch(frequency, sample_rate, q),
+            FilterType::Allpass => Self::allpass(frequency, sample_rate, q),
+            FilterType::Peaking => Self::peaking(frequency, sample_rate, q, gain_db),
+            FilterType::LowShelf => Self::low_shelf(frequency, sample_rate, q, gain_db),
+            FilterType::HighShelf => Self::high_shelf(frequency, sample_rate, q, gain_db),
+        }
+    }
+
+    /// Calculate coefficients for a peaking EQ filter
+    pub fn peaking(frequency: f32, sample_rate: f32, q: f32, gain_db: f32) -> Self {
+        let p = Prototype::new(frequency, sample_rate);
+        let alpha = p.alpha(q);
+        let a = amplitude(gain_db);
+        
+        Self::normalized(
+            1.0 + alpha * a,
+            -2.0 * p.cos_omega,
+            1.0 - alpha * a,
+            1.0 + alpha / a,
+            -2.0 * p.cos_omega,
+            1.0 - alpha / a,
+        )
+    }
     
     /// Calculate coefficients for a lowpass filter
     pub fn lowpass(frequency: f32, sample_rate: f32, q: f32) -> Self {
-        let omega = 2.0 * std::f32::consts::PI * frequency / sample_rate;
-        let sin_omega = omega.sin();
-        let cos_omega = omega.cos();
-        let alpha = sin_omega / (2.0 * q);
-        
-        let b0 = (1.0 - cos_omega) / 2.0;
-        let b1 = 1.0 - cos_omega;
-        let b2 = (1.0 - cos_omega) / 2.0;
-        let a0 = 1.0 + alpha;
-        let a1 = -2.0 * cos_omega;
-        let a2 = 1.0 - alpha;
+        let p = Prototype::new(frequency, sample_rate);
+        let alpha = p.alpha(q);
         
-        Self {
-            b0: b0 / a0,
-            b1: b1 / a0,
-            b2: b2 / a0,
-            a1: a1 / a0,
-            a2: a2 / a0,
-        }
+        Self::normalized(
+            (1.0 - p.cos_omega) / 2.0,
+            1.0 - p.cos_omega,
+            (1.0 - p.cos_omega) / 2.0,
+            1.0 + alpha,
+            -2.0 * p.cos_omega,
+            1.0 - alpha,
+        )
     }
     
     /// Calculate coefficients for a highpass filter
     pub fn highpass(frequency: f32, sample_rate: f32, q: f32) -> Self {
-        let omega = 2.0 * std::f32::consts::PI * frequency / sample_rate;
-        let sin_omega = omega.sin();
-        let cos_omega = omega.cos();
-        let alpha = sin_omega / (2.0 * q);
-        
-        let b0 = (1.0 + cos_omega) / 2.0;
-        let b1 = -(1.0 + cos_omega);
-        let b2 = (1.0 + cos_omega) / 2.0;
-        let a0 = 1.0 + alpha;
-        let a1 = -2.0 * cos_omega;
-        let a2 = 1.0 - alpha;
+        let p = Prototype::new(frequency, sample_rate);
+        let alpha = p.alpha(q);
+        
+        Self::normalized(
+            (1.0 + p.cos_omega) / 2.0,
+            -(1.0 + p.cos_omega),
+            (1.0 + p.cos_omega) / 2.0,
+            1.0 + alpha,
+            -2.0 * p.cos_omega,
+            1.0 - alpha,
+        )
+    }
+
+    /// Calculate coefficients for a bandpass filter with 0 dB peak gain
+    pub fn bandpass(frequency: f32, sample_rate: f32, q: f32) -> Self {
+        let p = Prototype::new(frequency, sample_rate);
+        let alpha = p.alpha(q);
+        
+        Self::normalized(
+            alpha,
+            0.0,
+            -alpha,
+            1.0 + alpha,
+            -2.0 * p.cos_omega,
+            1.0 - alpha,
+        )
+    }
+
+    /// Calculate coefficients for a constant skirt gain bandpass filter
+    ///
+    /// The skirts are fixed and the peak gain equals `q`.
+    pub fn bandpass_constant_skirt(frequency: f32, sample_rate: f32, q: f32) -> Self {
+        let p = Prototype::new(frequency, sample_rate);
+        let alpha = p.alpha(q);
+        
+        Self::normalized(
+            p.sin_omega / 2.0,
+            0.0,
+            -p.sin_omega / 2.0,
+            1.0 + alpha,
+            -2.0 * p.cos_omega,
+            1.0 - alpha,
+        )
+    }
+
+    /// Calculate coefficients for a notch filter
+    pub fn notch(frequency: f32, sample_rate: f32, q: f32) -> Self {
+        let p = Prototype::new(frequency, sample_rate);
+        let alpha = p.alpha(q);
+        
+        Self::normalized(
+            1.0,
+            -2.0 * p.cos_omega,
+            1.0,
+            1.0 + alpha,
+            -2.0 * p.cos_omega,
+            1.0 - alpha,
+        )
+    }
+
+    /// Calculate coefficients for an allpass filter
+    ///
+    /// The phase passes through -180 degrees at `frequency`.
+    pub fn allpass(frequency: f32, sample_rate: f32, q: f32) -> Self {
+        let p = Prototype::new(frequency, sample_rate);
+        let alpha = p.alpha(q);
+        
+        Self::normalized(
+            1.0 - alpha,
+            -2.0 * p.cos_omega,
+            1.0 + alpha,
+            1.0 + alpha,
+            -2.0 * p.cos_omega,
+            1.0 - alpha,
+        )
+    }
+
+    /// Calculate coefficients for a low shelf filter
+    pub fn low_shelf(frequency: f32, sample_rate: f32, q: f32, gain_db: f32) -> Self {
+        Self::low_shelf_with(frequency, sample_rate, ShelfWidth::Q(q), gain_db)
+    }
+    
+    /// Calculate coefficients for a low shelf filter with an explicit transition width
+    pub fn low_shelf_with(frequency: f32, sample_rate: f32, width: ShelfWidth, gain_db: f32) -> Self {
+        let p = Prototype::new(frequency, sample_rate);
+        let a = amplitude(gain_db);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * p.shelf_alpha(width, a);
+        let cos_omega = p.cos_omega;
+        
+        Self::normalized(
+            a * ((a + 1.0) - (a - 1.0) * cos_omega + sqrt_a_alpha),
+            2.0 * a * ((a - 1.0) - (a + 1.0) * cos_omega),
+            a * ((a + 1.0) - (a - 1.0) * cos_omega - sqrt_a_alpha),
+            (a + 1.0) + (a - 1.0) * cos_omega + sqrt_a_alpha,
+            -2.0 * ((a - 1.0) + (a + 1.0) * cos_omega),
+            (a + 1.0) + (a - 1.0) * cos_omega - sqrt_a_alpha,
+        )
+    }
+    
+    /// Calculate coefficients for a high shelf filter
+    pub fn high_shelf(frequency: f32, sample_rate: f32, q: f32, gain_db: f32) -> Self {
+        Self::high_shelf_with(frequency, sample_rate, ShelfWidth::Q(q), gain_db)
+    }
+
+    /// Calculate coefficients for a high shelf filter with an explicit transition width
+    pub fn high_shelf_with(frequency: f32, sample_rate: f32, width: ShelfWidth, gain_db: f32) -> Self {
+        let p = Prototype::new(frequency, sample_rate);
+        let a = amplitude(gain_db);
+        let sqrt_a_alpha = 2.0 * a.sqrt() * p.shelf_alpha(width, a);
+        let cos_omega = p.cos_omega;
         
+        Self::normalized(
+            a * ((a + 1.0) + (a - 1.0) * cos_omega + sqrt_a_alpha),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos_omega),
+            a * ((a + 1.0) + (a - 1.0) * cos_omega - sqrt_a_alpha),
+            (a + 1.0) - (a - 1.0) * cos_omega + sqrt_a_alpha,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos_omega),
+            (a + 1.0) - (a - 1.0) * cos_omega - sqrt_a_alpha,
+        )
+    }
+
+    /// Coefficients `t` (0 to 1) of the way from `self` to `target`
+    ///
+    /// Stable biquads form a convex set in (a1, a2), so every step of a
+    /// ramp between two stable filters is stable too.
+    fn lerp(&self, target: &Self, t: f64) -> Self {
         Self {
-            b0: b0 / a0,
-            b1: b1 / a0,
-            b2: b2 / a0,
-            a1: a1 / a0,
-            a2: a2 / a0,
+            b0: self.b0 + (target.b0 - self.b0) * t,
+            b1: self.b1 + (target.b1 - self.b1) * t,
+            b2: self.b2 + (target.b2 - self.b2) * t,
+            a1: self.a1 + (target.a1 - self.a1) * t,
+            a2: self.a2 + (target.a2 - self.a2) * t,
+        }
+    }
+
+    /// Complex response H(e^jω) at `frequency` for a stream at `sample_rate`
+    pub fn response(&self, frequency: f32, sample_rate: f32) -> Complex64 {
+        let omega = 2.0 * std::f64::consts::PI * frequency as f64 / sample_rate as f64;
+        let z1 = Complex64::from_polar(1.0, -omega);
+        let z2 = z1 * z1;
+        let numerator = self.b0 + self.b1 * z1 + self.b2 * z2;
+        let denominator = 1.0 + self.a1 * z1 + self.a2 * z2;
+        numerator / denominator
+    }
+}
+
+/// Realization of the difference equation used by `BiquadFilter`
+///
+/// Direct form I in f32 is cheapest, but a filter whose corner is a tiny
+/// fraction of the sample rate (20 Hz at 192 kHz) has poles within ~1e-3 of
+/// z = 1. In f32 its `a1`/`a2` coefficients then round to a noticeably
+/// different filter and the recursion amplifies rounding noise. The other
+/// topologies trade a little speed for precision there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BiquadTopology {
+    /// Direct form I in f32
+    DirectForm1,
+    /// Transposed direct form II in f32
+    TransposedDirectForm2,
+    /// Trapezoidal state-variable filter in f32, well conditioned at low corners
+    StateVariable,
+    /// Direct form I with f64 coefficients and state
+    DoublePrecision,
+}
+
+impl BiquadTopology {
+    /// Topology suited to a filter at `frequency` for a stream at `sample_rate`
+    ///
+    /// Direct form I down to 1% of the sample rate, the state-variable form
+    /// down to 0.01% (20 Hz at 192 kHz), and f64 below that.
+    pub fn for_frequency(frequency: f32, sample_rate: f32) -> Self {
+        let ratio = frequency / sample_rate;
+        if ratio >= 1e-2 {
+            BiquadTopology::DirectForm1
+        } else if ratio >= 1e-4 {
+            BiquadTopology::StateVariable
+        } else {
+            BiquadTopology::DoublePrecision
+        }
+    }
+}
+
+/// Coefficients of a biquad rounded to f32
+#[derive(Debug, Clone, Copy)]
+struct SinglePrecision {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl From<BiquadCoefficients> for SinglePrecision {
+    fn from(c: BiquadCoefficients) -> Self {
+        Self {
+            b0: c.b0 as f32,
+            b1: c.b1 as f32,
+            b2: c.b2 as f32,
+            a1: c.a1 as f32,
+            a2: c.a2 as f32,
+        }
+    }
+}
+
+impl SinglePrecision {
+    fn lerp(&self, target: &Self, t: f32) -> Self {
+        Self {
+            b0: self.b0 + (target.b0 - self.b0) * t,
+            b1: self.b1 + (target.b1 - self.b1) * t,
+            b2: self.b2 + (target.b2 - self.b2) * t,
+            a1: self.a1 + (target.a1 - self.a1) * t,
+            a2: self.a2 + (target.a2 - self.a2) * t,
+        }
+    }
+}
+
+/// Trapezoidal SVF equivalent of a biquad (Simper's "linear trap" form)
+///
+/// The integrator gain `g` and damping `k` come from the poles, and the
+/// output mixes input, bandpass and lowpass with `m0`, `m1` and `m2` to
+/// reproduce the zeros.
+#[derive(Debug, Clone, Copy)]
+struct StateVariable {
+    g: f64,
+    k: f64,
+    mix: [f64; 3],
+    a1: f32,
+    a2: f32,
+    a3: f32,
+    m0: f32,
+    m1: f32,
+    m2: f32,
+}
+
+impl From<BiquadCoefficients> for StateVariable {
+    fn from(c: BiquadCoefficients) -> Self {
+        // Denominator of the SVF: (1 + gk + g²) + 2(g² - 1)z⁻¹ + (1 - gk + g²)z⁻²
+        let d = 4.0 / (1.0 - c.a1 + c.a2);
+        let g = ((1.0 + c.a1 + c.a2) / (1.0 - c.a1 + c.a2)).max(0.0).sqrt();
+        let k = (1.0 - c.a2) * d / (2.0 * g);
+
+        // Numerator: m0 * denominator + m1 * g(1 - z⁻²) + m2 * g²(1 + z⁻¹)²
+        let (b0, b1, b2) = (c.b0 * d, c.b1 * d, c.b2 * d);
+        let m0 = (b0 - b1 + b2) / 4.0;
+        let m1 = (b0 - b2 - 2.0 * m0 * g * k) / (2.0 * g);
+        let m2 = (b1 - 2.0 * m0 * (g * g - 1.0)) / (2.0 * g * g);
+
+        Self::new(g, k, [m0, m1, m2])
+    }
+}
+
+impl StateVariable {
+    fn new(g: f64, k: f64, mix: [f64; 3]) -> Self {
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        Self {
+            g,
+            k,
+            mix,
+            a1: a1 as f32,
+            a2: (g * a1) as f32,
+            a3: (g * g * a1) as f32,
+            m0: mix[0] as f32,
+            m1: mix[1] as f32,
+            m2: mix[2] as f32,
+        }
+    }
+
+    /// Interpolates g, k and the mix rather than the biquad coefficients
+    ///
+    /// At low frequencies the mix grows as 1/g², so small steps in the
+    /// biquad coefficients swing it widely; in its own parameters the SVF
+    /// modulates smoothly.
+    fn lerp(&self, target: &Self, t: f64) -> Self {
+        let lerp = |from: f64, to: f64| from + (to - from) * t;
+        Self::new(
+            lerp(self.g, target.g),
+            lerp(self.k, target.k),
+            [0, 1, 2].map(|i| lerp(self.mix[i], target.mix[i])),
+        )
+    }
+}
+
+/// Coefficients prepared for the selected topology
+#[derive(Debug, Clone, Copy)]
+enum Kernel {
+    DirectForm1(SinglePrecision),
+    TransposedDirectForm2(SinglePrecision),
+    StateVariable(StateVariable),
+    DoublePrecision(BiquadCoefficients),
+}
+
+impl Kernel {
+    fn new(topology: BiquadTopology, coeffs: BiquadCoefficients) -> Self {
+        match topology {
+            BiquadTopology::DirectForm1 => Kernel::DirectForm1(coeffs.into()),
+            BiquadTopology::TransposedDirectForm2 => Kernel::TransposedDirectForm2(coeffs.into()),
+            BiquadTopology::StateVariable => Kernel::StateVariable(coeffs.into()),
+            BiquadTopology::DoublePrecision => Kernel::DoublePrecision(coeffs),
+        }
+    }
+
+    /// Kernel `t` (0 to 1) of the way from `self` to `target`
+    ///
+    /// Kernels of different topologies do not interpolate; the result is
+    /// then `target`.
+    fn lerp(&self, target: &Self, t: f64) -> Self {
+        match (self, target) {
+            (Kernel::DirectForm1(from), Kernel::DirectForm1(to)) => Kernel::DirectForm1(from.lerp(to, t as f32)),
+            (Kernel::TransposedDirectForm2(from), Kernel::TransposedDirectForm2(to)) => {
+                Kernel::TransposedDirectForm2(from.lerp(to, t as f32))
+            }
+            (Kernel::StateVariable(from), Kernel::StateVariable(to)) => Kernel::StateVariable(from.lerp(to, t)),
+            (Kernel::DoublePrecision(from), Kernel::DoublePrecision(to)) => Kernel::DoublePrecision(from.lerp(to, t)),
+            _ => *target,
         }
     }
+
+    /// Filter `samples` into `outputs`, carrying `state` across calls
+    ///
+    /// The state slots mean different things per topology: x1, x2, y1, y2
+    /// for direct form I, s1, s2 for transposed direct form II and ic1eq,
+    /// ic2eq for the SVF. f32 topologies keep f32 values in them, which f64
+    /// holds exactly.
+    fn run<'a>(
+        &self,
+        state: &mut BiquadState,
+        samples: impl Iterator<Item = &'a f32>,
+        outputs: impl Iterator<Item = &'a mut f32>,
+    ) {
+        let s = &mut state.0;
+        match *self {
+            Kernel::DirectForm1(c) => {
+                let [mut x1, mut x2, mut y1, mut y2] = s.map(|v| v as f32);
+                for (out, &x) in outputs.zip(samples) {
+                    let y = c.b0 * x + c.b1 * x1 + c.b2 * x2 - c.a1 * y1 - c.a2 * y2;
+                    x2 = x1;
+                    x1 = x;
+                    y2 = y1;
+                    y1 = y;
+                    *out = y;
+                }
+                *s = [x1, x2, y1, y2].map(f64::from);
+            }
+            Kernel::TransposedDirectForm2(c) => {
+                let (mut s1, mut s2) = (s[0] as f32, s[1] as f32);
+                for (out, &x) in outputs.zip(samples) {
+                    let y = c.b0 * x + s1;
+                    s1 = c.b1 * x - c.a1 * y + s2;
+                    s2 = c.b2 * x - c.a2 * y;
+                    *out = y;
+                }
+                s[0] = s1 as f64;
+                s[1] = s2 as f64;
+            }
+            Kernel::StateVariable(c) => {
+                let (mut ic1eq, mut ic2eq) = (s[0] as f32, s[1] as f32);
+                for (out, &x) in outputs.zip(samples) {
+                    let v3 = x - ic2eq;
+                    let v1 = c.a1 * ic1eq + c.a2 * v3;
+                    let v2 = ic2eq + c.a2 * ic1eq + c.a3 * v3;
+                    ic1eq = 2.0 * v1 - ic1eq;
+                    ic2eq = 2.0 * v2 - ic2eq;
+                    *out = c.m0 * x + c.m1 * v1 + c.m2 * v2;
+                }
+                s[0] = ic1eq as f64;
+                s[1] = ic2eq as f64;
+            }
+            Kernel::DoublePrecision(c) => {
+                let [mut x1, mut x2, mut y1, mut y2] = *s;
+                for (out, &x) in outputs.zip(samples) {
+                    let x = x as f64;
+                    let y = c.b0 * x + c.b1 * x1 + c.b2 * x2 - c.a1 * y1 - c.a2 * y2;
+                    x2 = x1;
+                    x1 = x;
+                    y2 = y1;
+                    y1 = y;
+                    *out = y as f32;
+                }
+                *s = [x1, x2, y1, y2];
+            }
+        }
+    }
+}
+
+/// Filter state of one channel, interpreted by the topology's `Kernel`
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadState([f64; 4]);
+
+/// Coefficient ramp in progress between two kernels
+#[derive(Debug, Clone, Copy)]
+struct Ramp {
+    from: Kernel,
+    to: Kernel,
+    elapsed: usize,
+    length: usize,
+}
+
+/// Handle for changing a `BiquadFilter`'s coefficients from another thread
+///
+/// Updates pass through a single-slot lock-free mailbox: the filter takes the
+/// latest one at its next `process` call and ramps to it, and an update it
+/// has not taken yet is replaced by the next one.
+#[derive(Clone)]
+pub struct BiquadController {
+    updates: Arc<ArrayQueue<BiquadCoefficients>>,
+}
+
+impl BiquadController {
+    /// Queue new coefficients for the filter
+    pub fn set_coefficients(&self, coeffs: BiquadCoefficients) {
+        self.updates.force_push(coeffs);
+    }
 }
 
 /// Biquad filter implementation
+///
+/// State is kept per channel and sized to the layout of the first buffer
+/// processed; a layout with a different channel count starts from silence.
+/// Processing runs in direct form I unless another `BiquadTopology` is set.
+///
+/// New coefficients take effect immediately unless a smoothing time is set,
+/// in which case the kernel ramps to them frame by frame, so dragging a
+/// control does not click.
 pub struct BiquadFilter {
     metadata: FilterMetadata,
+    // Target coefficients; `kernel` lags behind them while ramping
     coeffs: BiquadCoefficients,
-    // State variables (Direct Form I)
-    x1: f32,
-    x2: f32,
-    y1: f32,
-    y2: f32,
+    topology: BiquadTopology,
+    kernel: Kernel,
+    smoothing: usize,
+    ramp: Option<Ramp>,
+    updates: Arc<ArrayQueue<BiquadCoefficients>>,
+    // Filter state, one set per channel
+    states: Vec<BiquadState>,
 }
 
 impl BiquadFilter {
     /// Create a new biquad filter
     pub fn new(name: String, coeffs: BiquadCoefficients) -> Self {
+        let topology = BiquadTopology::DirectForm1;
         Self {
             metadata: FilterMetadata {
                 id: Uuid::new_v4().to_string(),
                 name,
                 enabled: true,
                 bypass: false,
+                channel_mask: ChannelMask::ALL,
             },
             coeffs,
-            x1: 0.0,
-            x2: 0.0,
-            y1: 0.0,
-            y2: 0.0,
+            topology,
+            kernel: Kernel::new(topology, coeffs),
+            smoothing: 0,
+            ramp: None,
+            updates: Arc::new(ArrayQueue::new(1)),
+            states: vec![BiquadState::default()],
         }
     }
     
@@ -131,29 +610,120 @@ impl BiquadFilter {
         Self::new(format!("Peaking EQ {:.0}Hz", frequency), coeffs)
     }
     
-    /// Update filter coefficients
+    /// Update filter coefficients, ramping to them over the smoothing time
     pub fn set_coefficients(&mut self, coeffs: BiquadCoefficients) {
         self.coeffs = coeffs;
+        let target = Kernel::new(self.topology, coeffs);
+        if self.smoothing == 0 {
+            self.kernel = target;
+            self.ramp = None;
+        } else {
+            self.ramp = Some(Ramp {
+                from: self.kernel,
+                to: target,
+                elapsed: 0,
+                length: self.smoothing,
+            });
+        }
+    }
+
+    /// Ramp length in frames for coefficient changes; 0 switches instantly
+    pub fn set_smoothing(&mut self, frames: usize) {
+        self.smoothing = frames;
+    }
+
+    /// Ramp length in frames for coefficient changes
+    pub fn smoothing(&self) -> usize {
+        self.smoothing
+    }
+
+    /// Whether a coefficient ramp is still in progress
+    pub fn is_ramping(&self) -> bool {
+        self.ramp.is_some()
+    }
+
+    /// Handle for updating the coefficients from another thread
+    pub fn controller(&self) -> BiquadController {
+        BiquadController {
+            updates: Arc::clone(&self.updates),
+        }
+    }
+
+    /// Change the realization; state restarts from silence
+    ///
+    /// Any ramp in progress completes immediately.
+    pub fn set_topology(&mut self, topology: BiquadTopology) {
+        self.topology = topology;
+        self.ramp = None;
+        self.kernel = Kernel::new(topology, self.coeffs);
+        self.states.fill(BiquadState::default());
+    }
+
+    /// Current realization
+    pub fn topology(&self) -> BiquadTopology {
+        self.topology
+    }
+
+    /// Move a running ramp on by `frames`
+    fn advance_ramp(&mut self, frames: usize) {
+        if let Some(ramp) = &mut self.ramp {
+            ramp.elapsed += frames;
+            if ramp.elapsed >= ramp.length {
+                self.kernel = ramp.to;
+                self.ramp = None;
+            } else {
+                self.kernel = ramp.from.lerp(&ramp.to, ramp.elapsed as f64 / ramp.length as f64);
+            }
+        }
+    }
+
+    /// Filter whole frames with the current kernel
+    fn process_frames(&mut self, input: &[f32], output: &mut [f32], channels: usize) {
+        for (channel, state) in self.states.iter_mut().enumerate() {
+            let samples = input.iter().skip(channel).step_by(channels);
+            let outputs = output.iter_mut().skip(channel).step_by(channels);
+            
+            if self.metadata.channel_mask.contains(channel) {
+                self.kernel.run(state, samples, outputs);
+            } else {
+                // Masked-off channels restart from silence when re-enabled
+                *state = BiquadState::default();
+                for (out, &x) in outputs.zip(samples) {
+                    *out = x;
+                }
+            }
+        }
     }
 }
 
 impl Filter for BiquadFilter {
-    fn process(&mut self, input: &[f32], output: &mut [f32]) {
-        for (i, &x) in input.iter().enumerate() {
-            // Direct Form I implementation
-            let y = self.coeffs.b0 * x
-                + self.coeffs.b1 * self.x1
-                + self.coeffs.b2 * self.x2
-                - self.coeffs.a1 * self.y1
-                - self.coeffs.a2 * self.y2;
-            
-            // Update state
-            self.x2 = self.x1;
-            self.x1 = x;
-            self.y2 = self.y1;
-            self.y1 = y;
-            
-            output[i] = y;
+    fn process(&mut self, input: &[f32], output: &mut [f32], layout: ChannelLayout) {
+        debug_assert_eq!(input.len() % layout.channels(), 0, "buffer must hold whole frames");
+        self.prepare(layout, input.len());
+        let channels = layout.channels();
+        if let Some(coeffs) = self.updates.pop() {
+            self.set_coefficients(coeffs);
+        }
+        
+        // Whole buffer at once, or one frame at a time while ramping
+        let frames = input.len() / channels;
+        let mut start = 0;
+        while start < frames {
+            let end = match self.ramp {
+                Some(_) => start + 1,
+                None => frames,
+            };
+            let block = start * channels..end * channels;
+            self.process_frames(&input[block.clone()], &mut output[block], channels);
+            self.advance_ramp(end - start);
+            start = end;
+        }
+    }
+    
+    fn prepare(&mut self, layout: ChannelLayout, _max_samples: usize) {
+        if self.states.len() != layout.channels() {
+            self.states.clear();
+            self.states.resize(layout.channels(), BiquadState::default());
         }
     }
     
@@ -165,25 +735,46 @@ impl Filter for BiquadFilter {
         self.metadata.bypass = bypass;
     }
     
+    fn set_channel_mask(&mut self, mask: ChannelMask) {
+        self.metadata.channel_mask = mask;
+    }
+    
     fn is_bypassed(&self) -> bool {
         self.metadata.bypass
     }
     
+    fn frequency_response(&self, frequencies: &[f32], sample_rate: f32) -> Vec<Complex64> {
+        frequencies
+            .iter()
+            .map(|&frequency| self.coeffs.response(frequency, sample_rate))
+            .collect()
+    }
+    
     fn reset(&mut self) {
-        self.x1 = 0.0;
-        self.x2 = 0.0;
-        self.y1 = 0.0;
-        self.y2 = 0.0;
+        self.states.fill(BiquadState::default());
+    }
+    
+    fn to_preset(&self) -> Option<FilterPreset> {
+        Some(FilterPreset::Biquad {
+            name: self.metadata.name.clone(),
+            coefficients: self.coeffs,
+            topology: self.topology,
+            smoothing: self.smoothing,
+            bypass: self.metadata.bypass,
+            channel_mask: self.metadata.channel_mask,
+        })
     }
     
     fn clone_box(&self) -> Box<dyn Filter> {
         Box::new(BiquadFilter {
             metadata: self.metadata.clone(),
             coeffs: self.coeffs,
-            x1: self.x1,
-            x2: self.x2,
-            y1: self.y1,
-            y2: self.y2,
+            topology: self.topology,
+            kernel: self.kernel,
+            smoothing: self.smoothing,
+            ramp: self.ramp,
+            updates: Arc::new(ArrayQueue::new(1)),
+            states: self.states.clone(),
         })
     }
 }
@@ -192,6 +783,199 @@ impl Filter for BiquadFilter {
 mod tests {
     use super::*;
     
+    fn gain_db(coeffs: &BiquadCoefficients, frequency: f32, sample_rate: f32) -> f64 {
+        20.0 * coeffs.response(frequency, sample_rate).norm().log10()
+    }
+    
+    #[test]
+    fn test_cookbook_corner_frequencies() {
+        let (fs, fc) = (48000.0, 1000.0);
+        let q_db = |q: f32| 20.0 * (q as f64).log10();
+        
+        for q in [0.5, 0.707, 2.0] {
+            // Lowpass and highpass pass through the corner with gain Q
+            assert!((gain_db(&BiquadCoefficients::lowpass(fc, fs, q), fc, fs) - q_db(q)).abs() < 0.01);
+            assert!((gain_db(&BiquadCoefficients::highpass(fc, fs, q), fc, fs) - q_db(q)).abs() < 0.01);
+            assert!(gain_db(&BiquadCoefficients::lowpass(fc, fs, q), 20.0, fs).abs() < 0.01);
+            assert!(gain_db(&BiquadCoefficients::highpass(fc, fs, q), 20000.0, fs).abs() < 0.1);
+            
+            // Bandpass peaks at 0 dB, or at Q for constant skirt gain
+            assert!(gain_db(&BiquadCoefficients::bandpass(fc, fs, q), fc, fs).abs() < 0.01);
+            let skirt = BiquadCoefficients::bandpass_constant_skirt(fc, fs, q);
+            assert!((gain_db(&skirt, fc, fs) - q_db(q)).abs() < 0.01);
+            
+            // Notch removes the centre and leaves the passbands
+            assert!(gain_db(&BiquadCoefficients::notch(fc, fs, q), fc, fs) < -60.0);
+            assert!(gain_db(&BiquadCoefficients::notch(fc, fs, q), 20.0, fs).abs() < 0.01);
+            
+            // Allpass keeps unity gain and crosses -180 degrees at the centre
+            let allpass = BiquadCoefficients::allpass(fc, fs, q);
+            for frequency in [20.0, fc, 15000.0] {
+                assert!(gain_db(&allpass, frequency, fs).abs() < 0.01);
+            }
+            let phase = allpass.response(fc, fs).arg();
+            assert!((phase.rem_euclid(2.0 * std::f64::consts::PI) - std::f64::consts::PI).abs() < 1e-3);
+            
+            for gain in [-6.0, 9.0] {
+                let peaking = BiquadCoefficients::peaking(fc, fs, q, gain);
+                assert!((gain_db(&peaking, fc, fs) - gain as f64).abs() < 0.01);
+                
+                // Shelves sit at half their gain at the corner
+                let low = BiquadCoefficients::low_shelf(fc, fs, q, gain);
+                assert!((gain_db(&low, fc, fs) - gain as f64 / 2.0).abs() < 0.01);
+                assert!((gain_db(&low, 1.0, fs) - gain as f64).abs() < 0.01);
+                let high = BiquadCoefficients::high_shelf(fc, fs, q, gain);
+                assert!((gain_db(&high, fc, fs) - gain as f64 / 2.0).abs() < 0.01);
+                assert!((gain_db(&high, fs / 2.0, fs) - gain as f64).abs() < 0.01);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_shelf_slope() {
+        let (fs, fc) = (48000.0, 500.0);
+        for gain in [-12.0, 6.0] {
+            // S = 1 is the Butterworth-like Q = 1/sqrt(2) shelf
+            let slope = BiquadCoefficients::low_shelf_with(fc, fs, ShelfWidth::Slope(1.0), gain);
+            let q = BiquadCoefficients::low_shelf(fc, fs, std::f32::consts::FRAC_1_SQRT_2, gain);
+            for frequency in [50.0, fc, 5000.0] {
+                assert!((gain_db(&slope, frequency, fs) - gain_db(&q, frequency, fs)).abs() < 1e-3);
+            }
+            
+            // A gentler slope still reaches half gain at the corner, but later
+            let gentle = BiquadCoefficients::high_shelf_with(fc, fs, ShelfWidth::Slope(0.5), gain);
+            let steep = BiquadCoefficients::high_shelf_with(fc, fs, ShelfWidth::Slope(1.0), gain);
+            assert!((gain_db(&gentle, fc, fs) - gain as f64 / 2.0).abs() < 0.01);
+            assert!(gain_db(&gentle, 2.0 * fc, fs).abs() < gain_db(&steep, 2.0 * fc, fs).abs());
+        }
+        
+        // Too steep for the gain: limited to the steepest valid slope, not NaN
+        let a = 10.0_f64.powf(24.0 / 40.0);
+        let limit = (a + 1.0 / a) / (a + 1.0 / a - 2.0);
+        let steepest = BiquadCoefficients::low_shelf_with(fc, fs, ShelfWidth::Slope(limit as f32), 24.0);
+        for width in [ShelfWidth::Slope(2.0), ShelfWidth::Slope(100.0)] {
+            let low = BiquadCoefficients::low_shelf_with(fc, fs, width, 24.0);
+            let high = BiquadCoefficients::high_shelf_with(fc, fs, width, 24.0);
+            for coeffs in [low, high] {
+                assert!([coeffs.b0, coeffs.b1, coeffs.b2, coeffs.a1, coeffs.a2].iter().all(|c| c.is_finite()));
+            }
+            assert!((gain_db(&low, 1.0, fs) - 24.0).abs() < 0.01);
+            for frequency in [50.0, fc, 5000.0] {
+                assert!((gain_db(&low, frequency, fs) - gain_db(&steepest, frequency, fs)).abs() < 1e-3);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_design_dispatch() {
+        let types = [
+            FilterType::Lowpass,
+            FilterType::Highpass,
+            FilterType::Bandpass,
+            FilterType::Notch,
+            FilterType::Allpass,
+            FilterType::Peaking,
+            FilterType::LowShelf,
+            FilterType::HighShelf,
+        ];
+        for filter_type in types {
+            let designed = BiquadCoefficients::design(filter_type, 2000.0, 44100.0, 1.2, 4.0);
+            for frequency in [100.0, 10000.0] {
+                assert!(gain_db(&designed, frequency, 44100.0).is_finite(), "{:?}", filter_type);
+            }
+        }
+        let designed = BiquadCoefficients::design(FilterType::Notch, 2000.0, 44100.0, 1.2, 4.0);
+        assert!(gain_db(&designed, 2000.0, 44100.0) < -60.0);
+    }
+    
+    /// Error of `topology` against an f64 reference, relative to the output, in dB
+    fn noise_floor_db(topology: BiquadTopology, coeffs: BiquadCoefficients, input: &[f32]) -> f64 {
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0f64, 0.0, 0.0, 0.0);
+        let reference: Vec<f64> = input
+            .iter()
+            .map(|&x| {
+                let x = x as f64;
+                let y = coeffs.b0 * x + coeffs.b1 * x1 + coeffs.b2 * x2 - coeffs.a1 * y1 - coeffs.a2 * y2;
+                x2 = x1;
+                x1 = x;
+                y2 = y1;
+                y1 = y;
+                y
+            })
+            .collect();
+        
+        let mut filter = BiquadFilter::new("Probe".to_string(), coeffs);
+        filter.set_topology(topology);
+        let mut output = vec![0.0; input.len()];
+        filter.process(input, &mut output, ChannelLayout::MONO);
+        
+        let error: f64 = output.iter().zip(&reference).map(|(&y, &r)| (y as f64 - r).powi(2)).sum();
+        let power: f64 = reference.iter().map(|r| r * r).sum();
+        10.0 * (error / power).log10()
+    }
+    
+    fn noise(len: usize) -> Vec<f32> {
+        let mut state = 1u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                ((state >> 8) as f32 / (1u32 << 23) as f32 - 1.0) * 0.1
+            })
+            .collect()
+    }
+    
+    #[test]
+    fn test_low_frequency_noise_floor() {
+        // 20 Hz at 192 kHz: poles within 1e-3 of z = 1
+        let coeffs = BiquadCoefficients::peaking(20.0, 192000.0, 0.7, 6.0);
+        let input = noise(192000);
+        let direct = noise_floor_db(BiquadTopology::DirectForm1, coeffs, &input);
+        let transposed = noise_floor_db(BiquadTopology::TransposedDirectForm2, coeffs, &input);
+        let svf = noise_floor_db(BiquadTopology::StateVariable, coeffs, &input);
+        let double = noise_floor_db(BiquadTopology::DoublePrecision, coeffs, &input);
+        
+        assert!(direct > -60.0 && transposed > -60.0, "{} {}", direct, transposed);
+        assert!(svf < direct - 50.0, "{}", svf);
+        // f64 is limited only by rounding the output to f32
+        assert!(double < -140.0, "{}", double);
+    }
+    
+    #[test]
+    fn test_topologies_agree_at_mid_frequencies() {
+        let input = noise(4800);
+        let topologies = [
+            BiquadTopology::DirectForm1,
+            BiquadTopology::TransposedDirectForm2,
+            BiquadTopology::StateVariable,
+            BiquadTopology::DoublePrecision,
+        ];
+        let types = [
+            FilterType::Lowpass,
+            FilterType::Highpass,
+            FilterType::Bandpass,
+            FilterType::Notch,
+            FilterType::Allpass,
+            FilterType::Peaking,
+            FilterType::LowShelf,
+            FilterType::HighShelf,
+        ];
+        for filter_type in types {
+            let coeffs = BiquadCoefficients::design(filter_type, 2000.0, 48000.0, 0.9, -5.0);
+            for topology in topologies {
+                let floor = noise_floor_db(topology, coeffs, &input);
+                assert!(floor < -100.0, "{:?} {:?}: {} dB", filter_type, topology, floor);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_topology_for_frequency() {
+        assert_eq!(BiquadTopology::for_frequency(1000.0, 48000.0), BiquadTopology::DirectForm1);
+        assert_eq!(BiquadTopology::for_frequency(20.0, 48000.0), BiquadTopology::StateVariable);
+        assert_eq!(BiquadTopology::for_frequency(20.0, 192000.0), BiquadTopology::StateVariable);
+        assert_eq!(BiquadTopology::for_frequency(5.0, 192000.0), BiquadTopology::DoublePrecision);
+    }
+    
     #[test]
     fn test_biquad_creation() {
         let filter = BiquadFilter::peaking(1000.0, 48000.0, 1.0, 6.0);
@@ -204,7 +988,7 @@ mod tests {
         let input = vec![1.0, 0.0, 0.0, 0.0];
         let mut output = vec![0.0; 4];
         
-        filter.process(&input, &mut output);
+        filter.process(&input, &mut output, ChannelLayout::MONO);
         
         // Output should have some response
         assert!(output[0].abs() > 0.0);
@@ -216,10 +1000,102 @@ mod tests {
         let input = vec![1.0; 10];
         let mut output = vec![0.0; 10];
         
-        filter.process(&input, &mut output);
+        filter.process(&input, &mut output, ChannelLayout::MONO);
         filter.reset();
         
-        assert_eq!(filter.x1, 0.0);
-        assert_eq!(filter.y1, 0.0);
+        assert_eq!(filter.states[0].0, [0.0; 4]);
+    }
+    
+    #[test]
+    fn test_smoothing_removes_clicks() {
+        // Jumps show up as spikes in the second difference of a slow sine
+        let largest_curvature = |topology, smoothing| {
+            let mut filter = BiquadFilter::peaking(1000.0, 48000.0, 4.0, -12.0);
+            filter.set_topology(topology);
+            filter.set_smoothing(smoothing);
+            let input: Vec<f32> = (0..4800)
+                .map(|n| (2.0 * std::f32::consts::PI * 100.0 * n as f32 / 48000.0).sin())
+                .collect();
+            let mut output = vec![0.0; 4800];
+            filter.process(&input[..2410], &mut output[..2410], ChannelLayout::MONO);
+            filter.set_coefficients(BiquadCoefficients::peaking(150.0, 48000.0, 4.0, 12.0));
+            filter.process(&input[2410..], &mut output[2410..], ChannelLayout::MONO);
+            output.windows(3).map(|w| (w[2] - 2.0 * w[1] + w[0]).abs()).fold(0.0f32, f32::max)
+        };
+        
+        // The sine itself curves by under 1e-3 per sample at either gain
+        for topology in [BiquadTopology::TransposedDirectForm2, BiquadTopology::StateVariable] {
+            assert!(largest_curvature(topology, 0) > 3e-3, "{:?}", topology);
+            assert!(largest_curvature(topology, 960) < 1e-3, "{:?}", topology);
+        }
+    }
+    
+    #[test]
+    fn test_controller_updates_from_another_thread() {
+        let mut filter = BiquadFilter::peaking(1000.0, 48000.0, 1.0, 0.0);
+        filter.set_smoothing(64);
+        let controller = filter.controller();
+        std::thread::spawn(move || {
+            controller.set_coefficients(BiquadCoefficients::peaking(1000.0, 48000.0, 1.0, 3.0));
+            controller.set_coefficients(BiquadCoefficients::peaking(1000.0, 48000.0, 1.0, 6.0));
+        })
+        .join()
+        .unwrap();
+        
+        let input = vec![0.0; 256];
+        let mut output = vec![0.0; 256];
+        filter.process(&input, &mut output, ChannelLayout::MONO);
+        
+        // Only the latest update is applied, and the ramp has finished
+        let gain = 20.0 * filter.frequency_response(&[1000.0], 48000.0)[0].norm().log10();
+        assert!((gain - 6.0).abs() < 0.01);
+        assert!(filter.ramp.is_none());
+        assert!(filter.updates.is_empty());
+    }
+    
+    #[test]
+    fn test_stereo_matches_independent_channels() {
+        let left: Vec<f32> = (0..256).map(|i| (i as f32 * 0.05).sin()).collect();
+        let right: Vec<f32> = (0..256).map(|i| (i as f32 * 0.7).cos() * 0.5).collect();
+        let interleaved: Vec<f32> = left.iter().zip(&right).flat_map(|(&l, &r)| [l, r]).collect();
+        
+        let mono: Vec<Vec<f32>> = [&left, &right]
+            .iter()
+            .map(|channel| {
+                let mut filter = BiquadFilter::peaking(1000.0, 48000.0, 1.0, 6.0);
+                let mut output = vec![0.0; channel.len()];
+                filter.process(channel, &mut output, ChannelLayout::MONO);
+                output
+            })
+            .collect();
+        
+        let mut stereo = BiquadFilter::peaking(1000.0, 48000.0, 1.0, 6.0);
+        let mut output = vec![0.0; interleaved.len()];
+        for (inp, out) in interleaved.chunks(64).zip(output.chunks_mut(64)) {
+            stereo.process(inp, out, ChannelLayout::STEREO);
+        }
+        let (l, r): (Vec<f32>, Vec<f32>) = output.chunks(2).map(|frame| (frame[0], frame[1])).unzip();
+        assert_eq!(l, mono[0]);
+        assert_eq!(r, mono[1]);
+    }
+    
+    #[test]
+    fn test_channel_mask_passes_other_channels() {
+        let mut filter = BiquadFilter::peaking(1000.0, 48000.0, 1.0, 6.0);
+        filter.set_channel_mask(ChannelMask::only(0));
+        
+        let input: Vec<f32> = (0..64).map(|i| (i as f32 * 0.3).sin()).collect();
+        let mut output = vec![0.0; 64];
+        filter.process(&input, &mut output, ChannelLayout::STEREO);
+        
+        let mut left_only = BiquadFilter::peaking(1000.0, 48000.0, 1.0, 6.0);
+        let left: Vec<f32> = input.iter().step_by(2).copied().collect();
+        let mut expected = vec![0.0; 32];
+        left_only.process(&left, &mut expected, ChannelLayout::MONO);
+        
+        for (frame, (input, expected)) in output.chunks(2).zip(input.chunks(2).zip(&expected)) {
+            assert_eq!(frame[0], *expected);
+            assert_eq!(frame[1], input[1]);
+        }
     }
 }
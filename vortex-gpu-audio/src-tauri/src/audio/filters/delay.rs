@@ -0,0 +1,89 @@
+//! Fixed delays for latency compensation
+
+/// Fixed delay on an interleaved signal
+///
+/// Lines up signals that took paths of different latency, and stands in for
+/// a bypassed filter with latency. The length is in samples, so a delay of
+/// `frames` on interleaved audio is `frames * channels` long.
+#[derive(Debug, Clone, Default)]
+pub struct DelayLine {
+    samples: Vec<f32>,
+    position: usize,
+}
+
+impl DelayLine {
+    /// Create a delay of `len` samples
+    pub fn new(len: usize) -> Self {
+        let mut delay = Self::default();
+        delay.set_len(len);
+        delay
+    }
+
+    /// Delay by `len` samples, starting from silence if the length changes
+    ///
+    /// Allocates when the length changes.
+    pub fn set_len(&mut self, len: usize) {
+        if self.samples.len() != len {
+            self.samples = vec![0.0; len];
+            self.position = 0;
+        }
+    }
+
+    /// Length in samples
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Check if the delay is zero
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Push `input` and return the sample from `len` samples ago
+    #[inline]
+    pub fn tick(&mut self, input: f32) -> f32 {
+        if self.samples.is_empty() {
+            return input;
+        }
+        let output = std::mem::replace(&mut self.samples[self.position], input);
+        self.position = (self.position + 1) % self.samples.len();
+        output
+    }
+
+    /// Delay `input` into `output`
+    pub fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        for (out, &x) in output.iter_mut().zip(input) {
+            *out = self.tick(x);
+        }
+    }
+
+    /// Clear the delayed signal
+    pub fn reset(&mut self) {
+        self.samples.fill(0.0);
+        self.position = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_line() {
+        let mut delay = DelayLine::new(3);
+        let mut output = [0.0; 5];
+        delay.process(&[1.0, 2.0, 3.0, 4.0, 5.0], &mut output);
+        assert_eq!(output, [0.0, 0.0, 0.0, 1.0, 2.0]);
+        delay.process(&[6.0, 7.0], &mut output[..2]);
+        assert_eq!(output[..2], [3.0, 4.0]);
+
+        // Zero length passes through; a new length starts from silence
+        let mut through = DelayLine::new(0);
+        assert!(through.is_empty());
+        assert_eq!(through.tick(9.0), 9.0);
+        delay.set_len(2);
+        assert_eq!((delay.len(), delay.tick(1.0)), (2, 0.0));
+        delay.reset();
+        assert_eq!(delay.tick(0.0), 0.0);
+    }
+}
@@ -1,7 +1,81 @@
-use crate::error::VortexError;
+use super::preset::FilterPreset;
+use crate::audio::memory_pool::{AudioMemoryPool, PooledBuffer};
+use crate::error::{AudioError, VortexError};
+use crate::validation::{ResourceLimitEnforcer, ResourceLimits};
+use parking_lot::Mutex;
+use rustfft::num_complex::Complex64;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
+/// Channel layout of the interleaved buffers passed to `Filter::process`
+///
+/// Buffers hold whole frames, `[L0, R0, L1, R1, ...]` for stereo, so their
+/// length is a multiple of `channels()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelLayout {
+    channels: usize,
+}
+
+impl ChannelLayout {
+    pub const MONO: Self = Self { channels: 1 };
+    pub const STEREO: Self = Self { channels: 2 };
+
+    /// Create a layout of `channels` interleaved channels
+    pub fn new(channels: usize) -> Self {
+        assert!(channels > 0, "a channel layout needs at least one channel");
+        Self { channels }
+    }
+
+    /// Number of interleaved channels
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Number of frames in an interleaved buffer of `len` samples
+    pub fn frames(&self, len: usize) -> usize {
+        len / self.channels
+    }
+}
+
+/// Set of channels a filter is applied to
+///
+/// Channels outside the mask pass through the filter unchanged. Bit `n`
+/// selects channel `n`, so up to 64 channels can be addressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ChannelMask(u64);
+
+impl ChannelMask {
+    /// Every channel
+    pub const ALL: Self = Self(u64::MAX);
+    /// No channel; the filter passes everything through
+    pub const NONE: Self = Self(0);
+
+    /// Only `channel`
+    pub fn only(channel: usize) -> Self {
+        Self::NONE.with(channel)
+    }
+
+    /// This mask plus `channel`
+    pub fn with(self, channel: usize) -> Self {
+        assert!(channel < 64, "channel masks address channels 0..64");
+        Self(self.0 | 1 << channel)
+    }
+
+    /// Check whether the filter applies to `channel`
+    pub fn contains(&self, channel: usize) -> bool {
+        channel < 64 && self.0 & (1 << channel) != 0
+    }
+}
+
+impl Default for ChannelMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
 /// Filter metadata
 #[derive(Debug, Clone)]
 pub struct FilterMetadata {
@@ -9,12 +83,53 @@ pub struct FilterMetadata {
     pub name: String,
     pub enabled: bool,
     pub bypass: bool,
+    pub channel_mask: ChannelMask,
 }
 
 /// Base trait for all audio filters
+///
+/// Filters keep independent state for every channel of the layout they are
+/// given, so channels never bleed into each other. `process` runs on the
+/// audio thread and must not allocate or block once the filter has been
+/// prepared for the layout.
 pub trait Filter: Send + Sync {
-    /// Process audio samples
-    fn process(&mut self, input: &[f32], output: &mut [f32]);
+    /// Process interleaved audio samples
+    ///
+    /// Channels outside `metadata().channel_mask` are copied from `input`
+    /// to `output` unchanged.
+    fn process(&mut self, input: &[f32], output: &mut [f32], layout: ChannelLayout);
+    
+    /// Size per-channel state for `layout` and scratch for blocks of up to
+    /// `max_samples` ahead of processing
+    ///
+    /// Called off the audio thread before a filter is handed to it, so the
+    /// first `process` call does not have to allocate.
+    fn prepare(&mut self, _layout: ChannelLayout, _max_samples: usize) {}
+    
+    /// Pass `input` through to `output` while the filter is bypassed
+    ///
+    /// Filters with latency must override this to delay the signal by
+    /// `latency_samples()`, so toggling bypass does not shift the audio in
+    /// time.
+    fn process_bypassed(&mut self, input: &[f32], output: &mut [f32], _layout: ChannelLayout) {
+        output.copy_from_slice(input);
+    }
+    
+    /// Delay the filter adds to the signal, in frames
+    ///
+    /// Reported whether or not the filter is bypassed. Filter graphs delay
+    /// parallel branches by the difference so they line up where they are
+    /// mixed.
+    fn latency_samples(&self) -> usize {
+        0
+    }
+    
+    /// Describe the filter for saving in a preset
+    ///
+    /// `None` for filters that cannot be rebuilt from a description.
+    fn to_preset(&self) -> Option<FilterPreset> {
+        None
+    }
     
     /// Get filter metadata
     fn metadata(&self) -> &FilterMetadata;
@@ -22,6 +137,17 @@ pub trait Filter: Send + Sync {
     /// Set bypass state
     fn set_bypass(&mut self, bypass: bool);
     
+    /// Select the channels the filter applies to
+    fn set_channel_mask(&mut self, mask: ChannelMask);
+    
+    /// Complex response at `frequencies` (Hz) for a stream at `sample_rate`
+    ///
+    /// Describes the channels the filter applies to. The default measures
+    /// the impulse response; filters with a closed form should override it.
+    fn frequency_response(&self, frequencies: &[f32], sample_rate: f32) -> Vec<Complex64> {
+        super::response::impulse_response_spectrum(self.clone_box(), frequencies, sample_rate)
+    }
+    
     /// Check if filter is bypassed
     fn is_bypassed(&self) -> bool;
     
@@ -32,61 +158,348 @@ pub trait Filter: Send + Sync {
     fn clone_box(&self) -> Box<dyn Filter>;
 }
 
+/// Buffer holding the signal between two filters of a chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cursor {
+    /// Still the caller's input: no filter has run yet
+    Source,
+    /// The caller's output buffer
+    Signal,
+    /// The chain's scratch buffer
+    Scratch,
+}
+
+/// Single edit in a `ChainBatch`
+enum Edit {
+    /// Insert at an index, or at the end for `None`; the filter is taken
+    /// when the batch is committed
+    Insert {
+        index: Option<usize>,
+        id: String,
+        filter: Option<Box<dyn Filter>>,
+    },
+    Remove(String),
+    Move { id: String, index: usize },
+    Swap(String, String),
+}
+
+/// Edits to a filter chain that take effect together or not at all
+///
+/// Edits apply in the order they were added, each to the chain as the
+/// previous ones left it. `FilterChain::apply_batch` checks the whole batch
+/// before changing anything, so a batch that would fail part way leaves the
+/// chain untouched.
+#[derive(Default)]
+pub struct ChainBatch {
+    edits: Vec<Edit>,
+    // Room for everything the batch takes out of the chain, so committing
+    // does not allocate
+    removed: Vec<(String, Box<dyn Filter>)>,
+}
+
+impl ChainBatch {
+    /// Create an empty batch
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Append a filter to the end of the chain
+    pub fn push(&mut self, filter: Box<dyn Filter>) -> &mut Self {
+        self.add_insert(None, filter)
+    }
+    
+    /// Insert a filter at `index`, moving the filters from there on back
+    pub fn insert(&mut self, index: usize, filter: Box<dyn Filter>) -> &mut Self {
+        self.add_insert(Some(index), filter)
+    }
+    
+    fn add_insert(&mut self, index: Option<usize>, filter: Box<dyn Filter>) -> &mut Self {
+        let id = filter.metadata().id.clone();
+        self.edits.push(Edit::Insert { index, id, filter: Some(filter) });
+        self.reserve_removed();
+        self
+    }
+    
+    /// Remove the filter with `id`
+    pub fn remove(&mut self, id: &str) -> &mut Self {
+        self.edits.push(Edit::Remove(id.to_string()));
+        self.reserve_removed();
+        self
+    }
+    
+    /// Move the filter with `id` to `index`
+    pub fn move_filter(&mut self, id: &str, index: usize) -> &mut Self {
+        self.edits.push(Edit::Move { id: id.to_string(), index });
+        self
+    }
+    
+    /// Swap the positions of two filters
+    pub fn swap(&mut self, first: &str, second: &str) -> &mut Self {
+        self.edits.push(Edit::Swap(first.to_string(), second.to_string()));
+        self
+    }
+    
+    fn reserve_removed(&mut self) {
+        let takes = self.edits.iter().filter(|edit| matches!(edit, Edit::Insert { .. } | Edit::Remove(_))).count();
+        self.removed.reserve(takes.saturating_sub(self.removed.len()));
+    }
+    
+    /// Number of edits
+    pub fn len(&self) -> usize {
+        self.edits.len()
+    }
+    
+    /// Check if the batch has no edits
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+    
+    /// Filter the batch inserts under `id`, until it is committed
+    pub fn filter(&self, id: &str) -> Option<&dyn Filter> {
+        self.edits.iter().find_map(|edit| match edit {
+            Edit::Insert { id: inserted, filter: Some(filter), .. } if inserted == id => Some(filter.as_ref()),
+            _ => None,
+        })
+    }
+    
+    /// Prepare the filters the batch inserts, as `FilterChain::prepare` does
+    pub fn prepare(&mut self, layout: ChannelLayout, max_samples: usize) {
+        for edit in &mut self.edits {
+            if let Edit::Insert { filter: Some(filter), .. } = edit {
+                filter.prepare(layout, max_samples);
+            }
+        }
+    }
+    
+    /// Check every edit against a chain holding the filters `ids`, in order
+    ///
+    /// Returns the ids in the order the batch leaves them. Inserting into a
+    /// chain `limits` say is full fails with a `ConfigError`; unknown ids,
+    /// duplicate ids and positions past the end fail with `InvalidParameter`.
+    pub fn validate<'a>(&'a self, mut ids: Vec<&'a str>, limits: &ResourceLimitEnforcer) -> Result<Vec<&'a str>, VortexError> {
+        let position = |ids: &[&str], id: &str| {
+            ids.iter().position(|&existing| existing == id).ok_or_else(|| -> VortexError {
+                AudioError::InvalidParameter(format!("Filter not found: {}", id)).into()
+            })
+        };
+        let past_end = |index: usize, len: usize| -> VortexError {
+            AudioError::InvalidParameter(format!("Position {} is past the end of a chain of {} filters", index, len)).into()
+        };
+        
+        for edit in &self.edits {
+            match edit {
+                Edit::Insert { index, id, .. } => {
+                    limits.can_add_filter(ids.len())?;
+                    if ids.contains(&id.as_str()) {
+                        return Err(AudioError::InvalidParameter(format!("Filter already in chain: {}", id)).into());
+                    }
+                    let index = index.unwrap_or(ids.len());
+                    if index > ids.len() {
+                        return Err(past_end(index, ids.len()));
+                    }
+                    ids.insert(index, id);
+                }
+                Edit::Remove(id) => {
+                    let from = position(&ids, id)?;
+                    ids.remove(from);
+                }
+                Edit::Move { id, index } => {
+                    let from = position(&ids, id)?;
+                    if *index >= ids.len() {
+                        return Err(past_end(*index, ids.len()));
+                    }
+                    let id = ids.remove(from);
+                    ids.insert(*index, id);
+                }
+                Edit::Swap(first, second) => {
+                    let (first, second) = (position(&ids, first)?, position(&ids, second)?);
+                    ids.swap(first, second);
+                }
+            }
+        }
+        Ok(ids)
+    }
+    
+    /// Take the filters the committed batch removed from the chain, with
+    /// their ids
+    pub fn into_removed(self) -> Vec<(String, Box<dyn Filter>)> {
+        self.removed
+    }
+}
+
 /// Chain of filters for sequential processing
+///
+/// Every active filter reads the signal from one buffer and writes it to the
+/// other of the output buffer and a single scratch buffer drawn from an
+/// `AudioMemoryPool`. Room for `max_filters` is reserved up front, so once
+/// `prepare` has drawn scratch for the block size, `process`,
+/// `process_in_place`, `commit_batch` and `take_filter` neither allocate nor
+/// lock and can run on the audio thread.
+///
+/// Adding to a full chain fails with the `ConfigError` of
+/// `ResourceLimitEnforcer::can_add_filter`; existing filters are never
+/// dropped to make room.
 pub struct FilterChain {
     filters: Vec<Box<dyn Filter>>,
     filter_map: HashMap<String, usize>,
     max_filters: usize,
+    limits: ResourceLimitEnforcer,
+    pool: Arc<Mutex<AudioMemoryPool>>,
+    scratch: PooledBuffer,
 }
 
 impl FilterChain {
-    /// Create a new filter chain
+    /// Create a new filter chain, as long as `ResourceLimits` allow by default
     pub fn new() -> Self {
-        Self::with_capacity(32) // Default max 32 filters
+        Self::with_capacity(ResourceLimits::default().max_filter_chain_length)
     }
     
     /// Create a filter chain with specified capacity
     pub fn with_capacity(max_filters: usize) -> Self {
+        Self::with_pool(max_filters, AudioMemoryPool::shared())
+    }
+    
+    /// Create a filter chain drawing its scratch buffer from `pool`
+    pub fn with_pool(max_filters: usize, pool: Arc<Mutex<AudioMemoryPool>>) -> Self {
+        let scratch = AudioMemoryPool::allocate(Arc::clone(&pool), 0);
+        let limits = ResourceLimitEnforcer::new(ResourceLimits {
+            max_filter_chain_length: max_filters,
+            ..ResourceLimits::default()
+        });
         Self {
-            filters: Vec::new(),
-            filter_map: HashMap::new(),
+            filters: Vec::with_capacity(max_filters),
+            // Twice the room, so removals leave the map space to rehash in place
+            filter_map: HashMap::with_capacity(2 * max_filters),
             max_filters,
+            limits,
+            pool,
+            scratch,
+        }
+    }
+    
+    /// Draw scratch for blocks of up to `max_samples` and prepare every
+    /// filter for `layout`
+    pub fn prepare(&mut self, layout: ChannelLayout, max_samples: usize) {
+        self.reserve_scratch(max_samples);
+        for filter in &mut self.filters {
+            filter.prepare(layout, max_samples);
+        }
+    }
+    
+    /// Swap the scratch buffer for a pooled one of at least `len` samples
+    fn reserve_scratch(&mut self, len: usize) {
+        if self.scratch.len() < len {
+            self.scratch = AudioMemoryPool::allocate(Arc::clone(&self.pool), len);
         }
     }
     
-    /// Add a filter to the chain
-    pub fn add_filter(&mut self, filter: Box<dyn Filter>) -> String {
+    /// Add a filter to the end of the chain
+    pub fn add_filter(&mut self, filter: Box<dyn Filter>) -> Result<String, VortexError> {
         let id = filter.metadata().id.clone();
+        let mut batch = ChainBatch::new();
+        batch.push(filter);
+        self.apply_batch(batch)?;
         
-        if self.filters.len() >= self.max_filters {
-            log::warn!("Filter chain at maximum capacity, removing oldest filter");
-            if let Some(removed) = self.filters.first() {
-                let removed_id = removed.metadata().id.clone();
-                self.filter_map.remove(&removed_id);
+        log::info!("Added filter: {} at index {}", id, self.filters.len() - 1);
+        Ok(id)
+    }
+    
+    /// Insert a filter at `index`, moving the filters from there on back
+    pub fn insert_filter(&mut self, index: usize, filter: Box<dyn Filter>) -> Result<String, VortexError> {
+        let id = filter.metadata().id.clone();
+        let mut batch = ChainBatch::new();
+        batch.insert(index, filter);
+        self.apply_batch(batch)?;
+        
+        log::info!("Inserted filter: {} at index {}", id, index);
+        Ok(id)
+    }
+    
+    /// Move a filter to `index`, shifting the filters in between
+    pub fn move_filter(&mut self, filter_id: &str, index: usize) -> Result<(), VortexError> {
+        let mut batch = ChainBatch::new();
+        batch.move_filter(filter_id, index);
+        self.apply_batch(batch).map(drop)
+    }
+    
+    /// Swap the positions of two filters
+    pub fn swap_filters(&mut self, first: &str, second: &str) -> Result<(), VortexError> {
+        let mut batch = ChainBatch::new();
+        batch.swap(first, second);
+        self.apply_batch(batch).map(drop)
+    }
+    
+    /// Apply every edit in `batch`, or none if any would fail
+    ///
+    /// Returns the committed batch, holding the filters it removed.
+    pub fn apply_batch(&mut self, mut batch: ChainBatch) -> Result<ChainBatch, VortexError> {
+        batch.validate(self.ids().collect(), &self.limits)?;
+        self.commit_batch(&mut batch);
+        Ok(batch)
+    }
+    
+    /// Apply a batch that `ChainBatch::validate` accepted for this chain,
+    /// without allocating
+    ///
+    /// The batch keeps what it removed from the chain, for the caller to
+    /// drop off the audio thread. Edits that no longer fit the chain are
+    /// skipped.
+    pub fn commit_batch(&mut self, batch: &mut ChainBatch) {
+        let ChainBatch { edits, removed } = batch;
+        for edit in edits.iter_mut() {
+            match edit {
+                Edit::Insert { index, id, filter } => {
+                    if let Some(filter) = filter.take() {
+                        let index = index.unwrap_or(self.filters.len());
+                        removed.extend(self.place_filter(index, std::mem::take(id), filter));
+                    }
+                }
+                Edit::Remove(id) => removed.extend(self.take_filter(id)),
+                Edit::Move { id, index } => {
+                    let to = *index;
+                    match self.filter_map.get(id.as_str()).copied() {
+                        Some(from) if to < self.filters.len() && from < to => {
+                            self.filters[from..=to].rotate_left(1);
+                            self.reindex(from..to + 1);
+                        }
+                        Some(from) if to < from => {
+                            self.filters[to..=from].rotate_right(1);
+                            self.reindex(to..from + 1);
+                        }
+                        _ => {}
+                    }
+                }
+                Edit::Swap(first, second) => {
+                    let first = self.filter_map.get(first.as_str()).copied();
+                    let second = self.filter_map.get(second.as_str()).copied();
+                    if let (Some(first), Some(second)) = (first, second) {
+                        self.filters.swap(first, second);
+                        self.reindex(first..first + 1);
+                        self.reindex(second..second + 1);
+                    }
+                }
             }
-            self.filters.remove(0);
         }
-        
-        let index = self.filters.len();
-        self.filters.push(filter);
-        self.filter_map.insert(id.clone(), index);
-        
-        log::info!("Added filter: {} at index {}", id, index);
-        id
+    }
+    
+    /// Insert a filter under `id` (its metadata id) at `index`, without
+    /// allocating
+    ///
+    /// When the chain is full, `index` is past the end or `id` is taken,
+    /// the filter is handed back with its id.
+    fn place_filter(&mut self, index: usize, id: String, filter: Box<dyn Filter>) -> Option<(String, Box<dyn Filter>)> {
+        if self.filters.len() >= self.max_filters || index > self.filters.len() || self.filter_map.contains_key(&id) {
+            return Some((id, filter));
+        }
+        self.filters.insert(index, filter);
+        self.filter_map.insert(id, index);
+        self.reindex(index + 1..self.filters.len());
+        None
     }
     
     /// Remove a filter by ID
     pub fn remove_filter(&mut self, filter_id: &str) -> Result<(), String> {
-        if let Some(&index) = self.filter_map.get(filter_id) {
-            self.filters.remove(index);
-            self.filter_map.remove(filter_id);
-            
-            // Update indices in map
-            self.filter_map.clear();
-            for (i, filter) in self.filters.iter().enumerate() {
-                self.filter_map.insert(filter.metadata().id.clone(), i);
-            }
-            
+        if self.take_filter(filter_id).is_some() {
             log::info!("Removed filter: {}", filter_id);
             Ok(())
         } else {
@@ -94,9 +507,48 @@ impl FilterChain {
         }
     }
     
+    /// Remove a filter by ID without allocating
+    ///
+    /// Returns the filter and its id, for the caller to drop off the audio
+    /// thread.
+    pub fn take_filter(&mut self, filter_id: &str) -> Option<(String, Box<dyn Filter>)> {
+        let (key, index) = self.filter_map.remove_entry(filter_id)?;
+        let filter = self.filters.remove(index);
+        self.shift_indices(index);
+        Some((key, filter))
+    }
+    
+    /// Close the gap left in the map by removing the filter at `removed`
+    fn shift_indices(&mut self, removed: usize) {
+        for index in self.filter_map.values_mut() {
+            if *index > removed {
+                *index -= 1;
+            }
+        }
+    }
+    
+    /// Point the map at the filters now at `positions`
+    fn reindex(&mut self, positions: std::ops::Range<usize>) {
+        for position in positions {
+            if let Some(index) = self.filter_map.get_mut(self.filters[position].metadata().id.as_str()) {
+                *index = position;
+            }
+        }
+    }
+    
+    /// Ids of the filters, in processing order
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.filters.iter().map(|filter| filter.metadata().id.as_str())
+    }
+    
+    /// Position of a filter in the chain
+    pub fn position(&self, filter_id: &str) -> Option<usize> {
+        self.filter_map.get(filter_id).copied()
+    }
+    
     /// Get a filter by ID
-    pub fn get_filter(&self, filter_id: &str) -> Option<&Box<dyn Filter>> {
-        self.filter_map.get(filter_id).and_then(|&index| self.filters.get(index))
+    pub fn get_filter(&self, filter_id: &str) -> Option<&dyn Filter> {
+        self.filter_map.get(filter_id).and_then(|&index| self.filters.get(index)).map(|f| f.as_ref())
     }
     
     /// Get a mutable filter by ID
@@ -114,45 +566,97 @@ impl FilterChain {
         }
     }
     
-    /// Process audio through the filter chain
-    pub fn process(&self, input: &[f32], output: &mut [f32]) {
-        if self.filters.is_empty() {
-            // No filters, just copy input to output
-            output.copy_from_slice(input);
-            return;
+    /// Select the channels a specific filter applies to
+    pub fn set_filter_channel_mask(&mut self, filter_id: &str, mask: ChannelMask) -> Result<(), String> {
+        if let Some(filter) = self.get_filter_mut(filter_id) {
+            filter.set_channel_mask(mask);
+            Ok(())
+        } else {
+            Err(format!("Filter not found: {}", filter_id))
         }
+    }
+    
+    /// Process interleaved audio through the filter chain
+    ///
+    /// Bypassed filters are skipped, unless they have latency, in which case
+    /// they delay the signal as if they were active. Locks the pool, and may
+    /// allocate, only if the block is longer than `prepare` allowed for.
+    pub fn process(&mut self, input: &[f32], output: &mut [f32], layout: ChannelLayout) {
+        self.run(Some(input), output, layout);
+    }
+    
+    /// Process interleaved audio through the filter chain in place
+    pub fn process_in_place(&mut self, buffer: &mut [f32], layout: ChannelLayout) {
+        self.run(None, buffer, layout);
+    }
+    
+    /// Run the active filters, leaving the result in `signal`
+    ///
+    /// The cursor follows the buffer that holds the signal so far: `source`
+    /// until the first active filter runs (or `signal` itself when there is
+    /// no source), then alternately `signal` and the scratch buffer.
+    fn run(&mut self, source: Option<&[f32]>, signal: &mut [f32], layout: ChannelLayout) {
+        let len = signal.len();
+        self.reserve_scratch(len);
+        let scratch = &mut self.scratch.as_mut_slice()[..len];
         
-        // Use two buffers for ping-pong processing
-        let mut buffer_a = input.to_vec();
-        let mut buffer_b = vec![0.0f32; input.len()];
-        
-        for (i, filter) in self.filters.iter().enumerate() {
-            if filter.is_bypassed() {
+        let mut cursor = if source.is_some() { Cursor::Source } else { Cursor::Signal };
+        for filter in self.filters.iter_mut() {
+            let bypassed = filter.is_bypassed();
+            if bypassed && filter.latency_samples() == 0 {
                 continue;
             }
-            
-            if i % 2 == 0 {
-                // Process from buffer_a to buffer_b
-                unsafe {
-                    let filter_mut = &mut *(filter.as_ref() as *const dyn Filter as *mut dyn Filter);
-                    filter_mut.process(&buffer_a, &mut buffer_b);
+            let mut stage = |input: &[f32], output: &mut [f32]| {
+                if bypassed {
+                    filter.process_bypassed(input, output, layout);
+                } else {
+                    filter.process(input, output, layout);
                 }
-            } else {
-                // Process from buffer_b to buffer_a
-                unsafe {
-                    let filter_mut = &mut *(filter.as_ref() as *const dyn Filter as *mut dyn Filter);
-                    filter_mut.process(&buffer_b, &mut buffer_a);
+            };
+            cursor = match (cursor, source) {
+                (Cursor::Source, Some(source)) => {
+                    stage(source, signal);
+                    Cursor::Signal
                 }
-            }
+                (Cursor::Scratch, _) => {
+                    stage(scratch, signal);
+                    Cursor::Signal
+                }
+                _ => {
+                    stage(signal, scratch);
+                    Cursor::Scratch
+                }
+            };
         }
         
-        // Copy final result to output
-        let final_buffer = if self.filters.len() % 2 == 0 {
-            &buffer_a
-        } else {
-            &buffer_b
-        };
-        output.copy_from_slice(final_buffer);
+        match (cursor, source) {
+            (Cursor::Source, Some(source)) => signal.copy_from_slice(source),
+            (Cursor::Scratch, _) => signal.copy_from_slice(scratch),
+            _ => {}
+        }
+    }
+    
+    /// Complex response of the chain on `channel` at `frequencies` (Hz)
+    ///
+    /// Bypassed filters and filters masked off `channel` contribute unity.
+    pub fn frequency_response(&self, frequencies: &[f32], sample_rate: f32, channel: usize) -> Vec<Complex64> {
+        let mut response = vec![Complex64::new(1.0, 0.0); frequencies.len()];
+        for filter in &self.filters {
+            if filter.is_bypassed() || !filter.metadata().channel_mask.contains(channel) {
+                continue;
+            }
+            for (total, h) in response.iter_mut().zip(filter.frequency_response(frequencies, sample_rate)) {
+                *total *= h;
+            }
+        }
+        response
+    }
+    
+    /// Total delay of the chain, in frames
+    ///
+    /// Includes bypassed filters, which keep their delay.
+    pub fn latency_samples(&self) -> usize {
+        self.filters.iter().map(|filter| filter.latency_samples()).sum()
     }
     
     /// Get the number of filters in the chain
@@ -164,7 +668,12 @@ impl FilterChain {
     pub fn is_empty(&self) -> bool {
         self.filters.is_empty()
     }
-    
+
+    /// Get the capacity limit: the most filters the chain accepts
+    pub fn max_filters(&self) -> usize {
+        self.max_filters
+    }
+
     /// Clear all filters
     pub fn clear(&mut self) {
         self.filters.clear();
@@ -180,10 +689,7 @@ impl FilterChain {
     /// Reset all filters
     pub fn reset_all(&mut self) {
         for filter in &mut self.filters {
-            unsafe {
-                let filter_mut = &mut **(filter as *mut Box<dyn Filter>);
-                filter_mut.reset();
-            }
+            filter.reset();
         }
     }
 }
@@ -212,6 +718,7 @@ mod tests {
                     name: name.to_string(),
                     enabled: true,
                     bypass: false,
+                    channel_mask: ChannelMask::ALL,
                 },
                 gain,
             }
@@ -219,9 +726,10 @@ mod tests {
     }
     
     impl Filter for MockFilter {
-        fn process(&mut self, input: &[f32], output: &mut [f32]) {
+        fn process(&mut self, input: &[f32], output: &mut [f32], layout: ChannelLayout) {
             for (i, &sample) in input.iter().enumerate() {
-                output[i] = sample * self.gain;
+                let applied = self.metadata.channel_mask.contains(i % layout.channels());
+                output[i] = if applied { sample * self.gain } else { sample };
             }
         }
         
@@ -233,6 +741,10 @@ mod tests {
             self.metadata.bypass = bypass;
         }
         
+        fn set_channel_mask(&mut self, mask: ChannelMask) {
+            self.metadata.channel_mask = mask;
+        }
+        
         fn is_bypassed(&self) -> bool {
             self.metadata.bypass
         }
@@ -260,7 +772,7 @@ mod tests {
     fn test_add_filter() {
         let mut chain = FilterChain::new();
         let filter = Box::new(MockFilter::new("Test", 1.0));
-        let id = chain.add_filter(filter);
+        let id = chain.add_filter(filter).unwrap();
         
         assert_eq!(chain.len(), 1);
         assert!(chain.get_filter(&id).is_some());
@@ -270,7 +782,7 @@ mod tests {
     fn test_remove_filter() {
         let mut chain = FilterChain::new();
         let filter = Box::new(MockFilter::new("Test", 1.0));
-        let id = chain.add_filter(filter);
+        let id = chain.add_filter(filter).unwrap();
         
         assert!(chain.remove_filter(&id).is_ok());
         assert_eq!(chain.len(), 0);
@@ -281,12 +793,12 @@ mod tests {
     fn test_process_single_filter() {
         let mut chain = FilterChain::new();
         let filter = Box::new(MockFilter::new("Gain", 2.0));
-        chain.add_filter(filter);
+        chain.add_filter(filter).unwrap();
         
         let input = vec![1.0, 2.0, 3.0, 4.0];
         let mut output = vec![0.0; 4];
         
-        chain.process(&input, &mut output);
+        chain.process(&input, &mut output, ChannelLayout::MONO);
         
         assert_eq!(output, vec![2.0, 4.0, 6.0, 8.0]);
     }
@@ -294,13 +806,13 @@ mod tests {
     #[test]
     fn test_process_multiple_filters() {
         let mut chain = FilterChain::new();
-        chain.add_filter(Box::new(MockFilter::new("Gain1", 2.0)));
-        chain.add_filter(Box::new(MockFilter::new("Gain2", 3.0)));
+        chain.add_filter(Box::new(MockFilter::new("Gain1", 2.0))).unwrap();
+        chain.add_filter(Box::new(MockFilter::new("Gain2", 3.0))).unwrap();
         
         let input = vec![1.0, 2.0];
         let mut output = vec![0.0; 2];
         
-        chain.process(&input, &mut output);
+        chain.process(&input, &mut output, ChannelLayout::MONO);
         
         // 1.0 * 2.0 * 3.0 = 6.0
         // 2.0 * 2.0 * 3.0 = 12.0
@@ -310,38 +822,268 @@ mod tests {
     #[test]
     fn test_bypass_filter() {
         let mut chain = FilterChain::new();
-        let filter_id = chain.add_filter(Box::new(MockFilter::new("Gain", 2.0)));
+        let filter_id = chain.add_filter(Box::new(MockFilter::new("Gain", 2.0))).unwrap();
         
         chain.set_filter_bypass(&filter_id, true).unwrap();
         
         let input = vec![1.0, 2.0, 3.0, 4.0];
         let mut output = vec![0.0; 4];
         
-        chain.process(&input, &mut output);
+        chain.process(&input, &mut output, ChannelLayout::MONO);
         
         // Should be unchanged (bypassed)
         assert_eq!(output, input);
     }
     
+    #[test]
+    fn test_any_bypass_pattern_matches_sequential_reference() {
+        use crate::audio::filters::BiquadFilter;
+
+        // Stateful filters over several blocks, so a stale buffer or a
+        // filter run twice or skipped shows up in the output
+        let make_filter = |i: usize| -> Box<dyn Filter> {
+            if i.is_multiple_of(2) {
+                Box::new(BiquadFilter::peaking(300.0 * (i + 1) as f32, 48000.0, 0.7, 4.0 - i as f32))
+            } else {
+                Box::new(MockFilter::new("Gain", 0.5 + i as f32 * 0.25))
+            }
+        };
+        let layout = ChannelLayout::STEREO;
+        let blocks: Vec<Vec<f32>> = (0..3)
+            .map(|block| (0..64).map(|i| ((block * 64 + i) as f32 * 0.37).sin()).collect())
+            .collect();
+
+        for count in 1..=6 {
+            for pattern in 0..1u32 << count {
+                let bypassed = |i: usize| pattern & (1 << i) != 0;
+                let mut chain = FilterChain::new();
+                let mut in_place = FilterChain::new();
+                let mut reference = Vec::new();
+                for i in 0..count {
+                    let filter = make_filter(i);
+                    reference.push(filter.clone_box());
+                    let (id, id_in_place) = (chain.add_filter(filter).unwrap(), in_place.add_filter(make_filter(i)).unwrap());
+                    chain.set_filter_bypass(&id, bypassed(i)).unwrap();
+                    in_place.set_filter_bypass(&id_in_place, bypassed(i)).unwrap();
+                }
+
+                for input in &blocks {
+                    let mut expected = input.clone();
+                    for (_, filter) in reference.iter_mut().enumerate().filter(|(i, _)| !bypassed(*i)) {
+                        let mut next = vec![0.0; expected.len()];
+                        filter.process(&expected, &mut next, layout);
+                        expected = next;
+                    }
+
+                    let mut output = vec![f32::NAN; input.len()];
+                    chain.process(input, &mut output, layout);
+                    assert_eq!(output, expected, "{} filters, bypass pattern {:b}", count, pattern);
+
+                    let mut buffer = input.clone();
+                    in_place.process_in_place(&mut buffer, layout);
+                    assert_eq!(buffer, expected, "{} filters in place, bypass pattern {:b}", count, pattern);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_prepared_chain_uses_pool_scratch() {
+        use crate::audio::alloc_check::heap_operations;
+        use crate::audio::memory_pool::PoolTier;
+
+        let pool = Arc::new(Mutex::new(AudioMemoryPool::new()));
+        let mut chain = FilterChain::with_pool(4, Arc::clone(&pool));
+        for gain in [2.0, 3.0, 0.5] {
+            chain.add_filter(Box::new(MockFilter::new("Gain", gain))).unwrap();
+        }
+        chain.prepare(ChannelLayout::STEREO, 1024);
+        let available = pool.lock().stats().medium_available;
+        assert_eq!(available, PoolTier::Medium.count() - 1);
+
+        let input = vec![1.0; 1024];
+        let mut output = vec![0.0; 1024];
+        let ((), operations) = heap_operations(|| {
+            chain.process(&input, &mut output, ChannelLayout::STEREO);
+            chain.process_in_place(&mut output, ChannelLayout::STEREO);
+        });
+        assert_eq!(operations, 0);
+        assert_eq!(output, vec![9.0; 1024]);
+
+        // The scratch goes back to the pool with the chain
+        drop(chain);
+        assert_eq!(pool.lock().stats().medium_available, PoolTier::Medium.count());
+    }
+
+    #[test]
+    fn test_channel_mask() {
+        let mut chain = FilterChain::new();
+        let filter_id = chain.add_filter(Box::new(MockFilter::new("Gain", 2.0))).unwrap();
+        chain.set_filter_channel_mask(&filter_id, ChannelMask::only(1)).unwrap();
+        assert!(chain.set_filter_channel_mask("missing", ChannelMask::ALL).is_err());
+        
+        let input = vec![1.0, 1.0, 2.0, 2.0];
+        let mut output = vec![0.0; 4];
+        
+        chain.process(&input, &mut output, ChannelLayout::STEREO);
+        
+        // Only the right channel is filtered
+        assert_eq!(output, vec![1.0, 2.0, 2.0, 4.0]);
+    }
+    
+    #[test]
+    fn test_channel_mask_bits() {
+        let mask = ChannelMask::only(0).with(3);
+        assert!(mask.contains(0) && mask.contains(3));
+        assert!(!mask.contains(1) && !mask.contains(64));
+        assert!(!ChannelMask::NONE.contains(0));
+        assert_eq!(ChannelMask::default(), ChannelMask::ALL);
+        assert_eq!(ChannelLayout::new(2), ChannelLayout::STEREO);
+        assert_eq!(ChannelLayout::STEREO.frames(8), 4);
+    }
+    
+    #[test]
+    fn test_chain_frequency_response() {
+        use crate::audio::filters::BiquadFilter;
+        
+        let mut chain = FilterChain::new();
+        let low = BiquadFilter::peaking(100.0, 48000.0, 1.0, 6.0);
+        let high = BiquadFilter::peaking(5000.0, 48000.0, 1.0, -3.0);
+        let frequencies = [100.0, 1000.0, 5000.0];
+        let expected: Vec<Complex64> = low
+            .frequency_response(&frequencies, 48000.0)
+            .iter()
+            .zip(high.frequency_response(&frequencies, 48000.0))
+            .map(|(a, b)| a * b)
+            .collect();
+        let low_only = low.frequency_response(&frequencies, 48000.0);
+        
+        chain.add_filter(Box::new(low)).unwrap();
+        let high_id = chain.add_filter(Box::new(high)).unwrap();
+        let bypassed = chain.add_filter(Box::new(BiquadFilter::peaking(1000.0, 48000.0, 1.0, 12.0))).unwrap();
+        chain.set_filter_bypass(&bypassed, true).unwrap();
+        
+        let response = chain.frequency_response(&frequencies, 48000.0, 0);
+        for (a, b) in response.iter().zip(&expected) {
+            assert!((a - b).norm() < 1e-9);
+        }
+        
+        // A filter masked off the channel drops out of that channel's response
+        chain.set_filter_channel_mask(&high_id, ChannelMask::only(1)).unwrap();
+        assert_eq!(chain.frequency_response(&frequencies, 48000.0, 0), low_only);
+    }
+    
     #[test]
     fn test_max_capacity() {
         let mut chain = FilterChain::with_capacity(2);
         
-        let id1 = chain.add_filter(Box::new(MockFilter::new("Filter1", 1.0)));
-        let id2 = chain.add_filter(Box::new(MockFilter::new("Filter2", 1.0)));
-        let _id3 = chain.add_filter(Box::new(MockFilter::new("Filter3", 1.0)));
+        let id1 = chain.add_filter(Box::new(MockFilter::new("Filter1", 1.0))).unwrap();
+        let id2 = chain.add_filter(Box::new(MockFilter::new("Filter2", 1.0))).unwrap();
+        let result = chain.add_filter(Box::new(MockFilter::new("Filter3", 1.0)));
         
-        // Should have removed the first filter
+        // Rejected as a configuration error, keeping the existing filters
+        assert!(matches!(result, Err(VortexError::Config(_))));
         assert_eq!(chain.len(), 2);
-        assert!(chain.get_filter(&id1).is_none());
+        assert!(chain.get_filter(&id1).is_some());
         assert!(chain.get_filter(&id2).is_some());
+        assert!(chain.insert_filter(0, Box::new(MockFilter::new("Filter3", 1.0))).is_err());
+    }
+    
+    fn names(chain: &FilterChain) -> Vec<String> {
+        chain.list_filters().into_iter().map(|metadata| metadata.name).collect()
+    }
+    
+    #[test]
+    fn test_insert_move_and_swap() {
+        let mut chain = FilterChain::new();
+        let a = chain.add_filter(Box::new(MockFilter::new("A", 1.0))).unwrap();
+        let b = chain.add_filter(Box::new(MockFilter::new("B", 1.0))).unwrap();
+        let c = chain.insert_filter(0, Box::new(MockFilter::new("C", 1.0))).unwrap();
+        chain.insert_filter(3, Box::new(MockFilter::new("D", 1.0))).unwrap();
+        assert_eq!(names(&chain), ["C", "A", "B", "D"]);
+        assert!(chain.insert_filter(5, Box::new(MockFilter::new("E", 1.0))).is_err());
+        
+        chain.move_filter(&c, 2).unwrap();
+        assert_eq!(names(&chain), ["A", "B", "C", "D"]);
+        chain.move_filter(&c, 0).unwrap();
+        assert_eq!(names(&chain), ["C", "A", "B", "D"]);
+        chain.swap_filters(&a, &c).unwrap();
+        assert_eq!(names(&chain), ["A", "C", "B", "D"]);
+        assert!(chain.move_filter(&b, 4).is_err());
+        assert!(chain.swap_filters(&b, "missing").is_err());
+        
+        // Lookups follow the filters to their new positions
+        for (position, id) in chain.ids().map(str::to_string).collect::<Vec<_>>().iter().enumerate() {
+            assert_eq!(chain.position(id), Some(position));
+            assert_eq!(&chain.get_filter(id).unwrap().metadata().id, id);
+        }
+        chain.remove_filter(&c).unwrap();
+        assert_eq!(chain.position(&b), Some(1));
+    }
+    
+    #[test]
+    fn test_batch_applies_all_or_nothing() {
+        let mut chain = FilterChain::with_capacity(3);
+        let a = chain.add_filter(Box::new(MockFilter::new("A", 2.0))).unwrap();
+        let b = chain.add_filter(Box::new(MockFilter::new("B", 3.0))).unwrap();
+        
+        // The last edit fails, so none of them happen
+        let mut batch = ChainBatch::new();
+        batch.swap(&a, &b).remove(&a).move_filter(&b, 1);
+        assert!(chain.apply_batch(batch).is_err());
+        assert_eq!(names(&chain), ["A", "B"]);
+        
+        // Room made by a removal can be used later in the same batch
+        let mut batch = ChainBatch::new();
+        batch
+            .push(Box::new(MockFilter::new("C", 5.0)))
+            .remove(&a)
+            .insert(0, Box::new(MockFilter::new("D", 7.0)))
+            .swap(&b, &a);
+        assert!(chain.apply_batch(batch).is_err());
+        let mut batch = ChainBatch::new();
+        batch
+            .push(Box::new(MockFilter::new("C", 5.0)))
+            .remove(&a)
+            .insert(0, Box::new(MockFilter::new("D", 7.0)))
+            .move_filter(&b, 2);
+        let removed = chain.apply_batch(batch).unwrap().into_removed();
+        assert_eq!(names(&chain), ["D", "C", "B"]);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].0, a);
+        
+        let mut output = [0.0];
+        chain.process(&[1.0], &mut output, ChannelLayout::MONO);
+        assert_eq!(output, [105.0]);
+    }
+    
+    #[test]
+    fn test_commit_batch_does_not_allocate() {
+        use crate::audio::alloc_check::heap_operations;
+        
+        let mut chain = FilterChain::new();
+        let ids: Vec<String> = (0..4)
+            .map(|i| chain.add_filter(Box::new(MockFilter::new(&format!("F{}", i), 1.0))).unwrap())
+            .collect();
+        let mut batch = ChainBatch::new();
+        batch
+            .insert(1, Box::new(MockFilter::new("New", 1.0)))
+            .remove(&ids[2])
+            .move_filter(&ids[0], 3)
+            .swap(&ids[1], &ids[3]);
+        batch.validate(chain.ids().collect(), &ResourceLimitEnforcer::new(ResourceLimits::default())).unwrap();
+        
+        let ((), operations) = heap_operations(|| chain.commit_batch(&mut batch));
+        assert_eq!(operations, 0);
+        assert_eq!(names(&chain), ["New", "F3", "F1", "F0"]);
+        assert_eq!(batch.into_removed().len(), 1);
     }
     
     #[test]
     fn test_clear_chain() {
         let mut chain = FilterChain::new();
-        chain.add_filter(Box::new(MockFilter::new("Filter1", 1.0)));
-        chain.add_filter(Box::new(MockFilter::new("Filter2", 1.0)));
+        chain.add_filter(Box::new(MockFilter::new("Filter1", 1.0))).unwrap();
+        chain.add_filter(Box::new(MockFilter::new("Filter2", 1.0))).unwrap();
         
         chain.clear();
         
@@ -352,8 +1094,8 @@ mod tests {
     #[test]
     fn test_list_filters() {
         let mut chain = FilterChain::new();
-        chain.add_filter(Box::new(MockFilter::new("Filter1", 1.0)));
-        chain.add_filter(Box::new(MockFilter::new("Filter2", 1.0)));
+        chain.add_filter(Box::new(MockFilter::new("Filter1", 1.0))).unwrap();
+        chain.add_filter(Box::new(MockFilter::new("Filter2", 1.0))).unwrap();
         
         let list = chain.list_filters();
         assert_eq!(list.len(), 2);
@@ -0,0 +1,1042 @@
+//! Filters wired as a directed acyclic graph
+//!
+//! A `FilterGraph` routes audio from its input node to its output node
+//! through filters, splits into parallel branches, mixes and mid/side
+//! conversions. Branches that reach a mix with different latencies are
+//! delayed to line up. The graph is itself a `Filter`, so it can be added to
+//! a `FilterChain` or the engine like any other filter.
+
+use super::delay::DelayLine;
+use super::filter_chain::{ChannelLayout, ChannelMask, Filter, FilterMetadata};
+use super::preset::FilterPreset;
+use crate::error::{AudioError, VortexError};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use uuid::Uuid;
+
+/// Handle of a node in a `FilterGraph`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NodeId(usize);
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {}", self.0)
+    }
+}
+
+/// Direction of a mid/side node
+///
+/// Converts between left/right in channels 0 and 1 and mid/side in the
+/// same channels; other channels pass through. Encoding halves the sum and
+/// difference so that decoding restores the input exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MidSideMode {
+    Encode,
+    Decode,
+}
+
+/// What a saved node does, see `NodePreset`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NodeKindPreset {
+    Input,
+    Output,
+    Split,
+    /// Gains in the order of the node's inputs
+    Mix { gains: Vec<f32> },
+    MidSide { mode: MidSideMode },
+    Filter { filter: FilterPreset },
+}
+
+/// Saved node of a `GraphPreset`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodePreset {
+    pub id: NodeId,
+    pub inputs: Vec<NodeId>,
+    #[serde(flatten)]
+    pub kind: NodeKindPreset,
+}
+
+/// Serializable description of a `FilterGraph`
+///
+/// Node ids run from 0 to one less than the number of nodes, with the
+/// graph input and output at 0 and 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphPreset {
+    pub name: String,
+    pub bypass: bool,
+    pub channel_mask: ChannelMask,
+    pub nodes: Vec<NodePreset>,
+}
+
+enum NodeKind {
+    Input,
+    Output,
+    Split,
+    // Compensation delays line every input up with the latest one
+    Mix { gains: Vec<f32>, delays: Vec<DelayLine> },
+    MidSide(MidSideMode),
+    Filter(Box<dyn Filter>),
+}
+
+struct Node {
+    kind: NodeKind,
+    // Nodes feeding this one; only mixes take more than one
+    inputs: Vec<NodeId>,
+    // Output for the current block
+    buffer: Vec<f32>,
+    // Frames from the graph input to this node's output
+    latency: usize,
+}
+
+impl Node {
+    fn new(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            inputs: Vec::new(),
+            buffer: Vec::new(),
+            latency: 0,
+        }
+    }
+
+    fn is_mix(&self) -> bool {
+        matches!(self.kind, NodeKind::Mix { .. })
+    }
+
+    fn clone_node(&self) -> Self {
+        let kind = match &self.kind {
+            NodeKind::Input => NodeKind::Input,
+            NodeKind::Output => NodeKind::Output,
+            NodeKind::Split => NodeKind::Split,
+            NodeKind::Mix { gains, delays } => NodeKind::Mix {
+                gains: gains.clone(),
+                delays: delays.clone(),
+            },
+            NodeKind::MidSide(mode) => NodeKind::MidSide(*mode),
+            NodeKind::Filter(filter) => NodeKind::Filter(filter.clone_box()),
+        };
+        Self {
+            kind,
+            inputs: self.inputs.clone(),
+            buffer: self.buffer.clone(),
+            latency: self.latency,
+        }
+    }
+}
+
+/// Copy `source` into `output`, or fill it with silence if there is none
+fn copy_or_silence(source: Option<&[f32]>, output: &mut [f32]) {
+    match source {
+        Some(source) => output.copy_from_slice(source),
+        None => output.fill(0.0),
+    }
+}
+
+/// Convert channels 0 and 1 of every frame between left/right and mid/side
+fn mid_side(mode: MidSideMode, buffer: &mut [f32], layout: ChannelLayout) {
+    if layout.channels() < 2 {
+        return;
+    }
+    for frame in buffer.chunks_exact_mut(layout.channels()) {
+        let (a, b) = (frame[0], frame[1]);
+        (frame[0], frame[1]) = match mode {
+            MidSideMode::Encode => (0.5 * (a + b), 0.5 * (a - b)),
+            MidSideMode::Decode => (a + b, a - b),
+        };
+    }
+}
+
+/// Directed acyclic graph of filters
+///
+/// Every graph has an input and an output node (`INPUT` and `OUTPUT`),
+/// connected directly when it is created. `add_filter` appends filters in
+/// series in front of the output, like `FilterChain::add_filter`; parallel
+/// routing is built from `add_split`, `add_mix` and `connect`. An
+/// unconnected input reads silence, and nodes the output does not depend on
+/// are not run.
+///
+/// Every edit reschedules the graph: nodes are ordered so each runs after
+/// its inputs, and each mix input is delayed by the difference between its
+/// latency and the mix's latest input. Bypassed filters keep their latency
+/// and delay the signal instead, so bypassing one does not move the branches
+/// out of line. Once `prepare` has sized the node buffers, processing does
+/// not allocate.
+pub struct FilterGraph {
+    metadata: FilterMetadata,
+    // Slots indexed by `NodeId`; removed nodes leave `None`
+    nodes: Vec<Option<Node>>,
+    // Nodes the output depends on, each after its inputs
+    order: Vec<NodeId>,
+    layout: ChannelLayout,
+    max_samples: usize,
+    // The input delayed by the graph's latency, for masked channels and bypass
+    dry: DelayLine,
+}
+
+impl FilterGraph {
+    /// Node that receives the graph's input
+    pub const INPUT: NodeId = NodeId(0);
+    /// Node whose output is the graph's output
+    pub const OUTPUT: NodeId = NodeId(1);
+
+    /// Create a graph passing its input straight to its output
+    pub fn new(name: String) -> Self {
+        let mut output = Node::new(NodeKind::Output);
+        output.inputs.push(Self::INPUT);
+        let mut graph = Self {
+            metadata: FilterMetadata {
+                id: Uuid::new_v4().to_string(),
+                name,
+                enabled: true,
+                bypass: false,
+                channel_mask: ChannelMask::ALL,
+            },
+            nodes: vec![Some(Node::new(NodeKind::Input)), Some(output)],
+            order: Vec::new(),
+            layout: ChannelLayout::STEREO,
+            max_samples: 0,
+            dry: DelayLine::default(),
+        };
+        graph.schedule();
+        graph
+    }
+
+    fn node(&self, id: NodeId) -> Result<&Node, VortexError> {
+        self.nodes
+            .get(id.0)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| AudioError::InvalidParameter(format!("Graph has no {}", id)).into())
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Result<&mut Node, VortexError> {
+        self.nodes
+            .get_mut(id.0)
+            .and_then(Option::as_mut)
+            .ok_or_else(|| AudioError::InvalidParameter(format!("Graph has no {}", id)).into())
+    }
+
+    fn add_node(&mut self, kind: NodeKind) -> NodeId {
+        self.nodes.push(Some(Node::new(kind)));
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Append a filter in series in front of the output
+    pub fn add_filter(&mut self, filter: Box<dyn Filter>) -> NodeId {
+        let id = self.add_node(NodeKind::Filter(filter));
+        let output = self.nodes[Self::OUTPUT.0].as_mut().expect("the output node is never removed");
+        let source = std::mem::replace(&mut output.inputs, vec![id]);
+        self.nodes[id.0].as_mut().expect("node was just added").inputs = source;
+        self.schedule();
+        id
+    }
+
+    /// Add an unconnected filter node
+    pub fn add_filter_node(&mut self, filter: Box<dyn Filter>) -> NodeId {
+        self.add_node(NodeKind::Filter(filter))
+    }
+
+    /// Add an unconnected split node, which passes its input to every node
+    /// connected to it
+    ///
+    /// Any node can feed several others; a split just gives the branch point
+    /// a name.
+    pub fn add_split(&mut self) -> NodeId {
+        self.add_node(NodeKind::Split)
+    }
+
+    /// Add an unconnected mix node, which sums its inputs with a gain each
+    pub fn add_mix(&mut self) -> NodeId {
+        self.add_node(NodeKind::Mix {
+            gains: Vec::new(),
+            delays: Vec::new(),
+        })
+    }
+
+    /// Add an unconnected mid/side encode or decode node
+    pub fn add_mid_side(&mut self, mode: MidSideMode) -> NodeId {
+        self.add_node(NodeKind::MidSide(mode))
+    }
+
+    /// Feed the output of `from` into `to`
+    ///
+    /// Mixes take any number of inputs, at unity gain until `set_mix_gain`
+    /// changes it; other nodes take one.
+    pub fn connect(&mut self, from: NodeId, to: NodeId) -> Result<(), VortexError> {
+        self.node(from)?;
+        let target = self.node(to)?;
+        if from == Self::OUTPUT || to == Self::INPUT {
+            return Err(AudioError::InvalidParameter(
+                "The graph output feeds no node and the graph input takes no input".to_string()
+            ).into());
+        }
+        if !target.is_mix() && !target.inputs.is_empty() {
+            return Err(AudioError::InvalidParameter(format!("{} already has an input", to)).into());
+        }
+        if self.depends_on(from, to) {
+            return Err(AudioError::InvalidParameter(
+                format!("Connecting {} to {} would form a cycle", from, to)
+            ).into());
+        }
+
+        let target = self.node_mut(to)?;
+        target.inputs.push(from);
+        if let NodeKind::Mix { gains, .. } = &mut target.kind {
+            gains.push(1.0);
+        }
+        self.schedule();
+        Ok(())
+    }
+
+    /// Stop feeding the output of `from` into `to`
+    pub fn disconnect(&mut self, from: NodeId, to: NodeId) -> Result<(), VortexError> {
+        let target = self.node_mut(to)?;
+        let Some(index) = target.inputs.iter().position(|&input| input == from) else {
+            return Err(AudioError::InvalidParameter(format!("{} does not feed {}", from, to)).into());
+        };
+        target.inputs.remove(index);
+        if let NodeKind::Mix { gains, delays } = &mut target.kind {
+            gains.remove(index);
+            if index < delays.len() {
+                delays.remove(index);
+            }
+        }
+        self.schedule();
+        Ok(())
+    }
+
+    /// Set the gain a mix applies to the input it takes from `from`
+    pub fn set_mix_gain(&mut self, mix: NodeId, from: NodeId, gain: f32) -> Result<(), VortexError> {
+        let node = self.node_mut(mix)?;
+        let index = node.inputs.iter().position(|&input| input == from);
+        match (&mut node.kind, index) {
+            (NodeKind::Mix { gains, .. }, Some(index)) => {
+                gains[index] = gain;
+                Ok(())
+            }
+            (NodeKind::Mix { .. }, None) => {
+                Err(AudioError::InvalidParameter(format!("{} does not feed {}", from, mix)).into())
+            }
+            _ => Err(AudioError::InvalidParameter(format!("{} is not a mix", mix)).into()),
+        }
+    }
+
+    /// Remove a node and its connections
+    ///
+    /// Nodes it fed take its input instead if it had exactly one, so
+    /// removing a filter from a series keeps the series connected.
+    pub fn remove_node(&mut self, id: NodeId) -> Result<(), VortexError> {
+        if id == Self::INPUT || id == Self::OUTPUT {
+            return Err(AudioError::InvalidParameter(
+                "The graph input and output cannot be removed".to_string()
+            ).into());
+        }
+        self.node(id)?;
+        let removed = self.nodes[id.0].take().expect("node exists");
+        let replacement = match removed.inputs.as_slice() {
+            &[input] => Some(input),
+            _ => None,
+        };
+
+        for node in self.nodes.iter_mut().flatten() {
+            let mut index = 0;
+            while index < node.inputs.len() {
+                if node.inputs[index] != id {
+                    index += 1;
+                } else if let Some(replacement) = replacement {
+                    node.inputs[index] = replacement;
+                    index += 1;
+                } else {
+                    node.inputs.remove(index);
+                    if let NodeKind::Mix { gains, delays } = &mut node.kind {
+                        gains.remove(index);
+                        if index < delays.len() {
+                            delays.remove(index);
+                        }
+                    }
+                }
+            }
+        }
+        self.schedule();
+        Ok(())
+    }
+
+    /// Find the node holding the filter with metadata id `filter_id`
+    pub fn find_filter(&self, filter_id: &str) -> Option<NodeId> {
+        self.nodes.iter().enumerate().find_map(|(index, node)| match node {
+            Some(Node { kind: NodeKind::Filter(filter), .. }) if filter.metadata().id == filter_id => {
+                Some(NodeId(index))
+            }
+            _ => None,
+        })
+    }
+
+    /// The filter held by `id`, if it is a filter node
+    pub fn filter(&self, id: NodeId) -> Option<&dyn Filter> {
+        match self.node(id).ok()?.kind {
+            NodeKind::Filter(ref filter) => Some(filter.as_ref()),
+            _ => None,
+        }
+    }
+
+    fn filter_mut(&mut self, id: NodeId) -> Result<&mut Box<dyn Filter>, VortexError> {
+        match self.node_mut(id)?.kind {
+            NodeKind::Filter(ref mut filter) => Ok(filter),
+            _ => Err(AudioError::InvalidParameter(format!("{} is not a filter", id)).into()),
+        }
+    }
+
+    /// Set bypass state for the filter at `id`
+    pub fn set_filter_bypass(&mut self, id: NodeId, bypass: bool) -> Result<(), VortexError> {
+        self.filter_mut(id)?.set_bypass(bypass);
+        Ok(())
+    }
+
+    /// Select the channels the filter at `id` applies to
+    pub fn set_filter_channel_mask(&mut self, id: NodeId, mask: ChannelMask) -> Result<(), VortexError> {
+        self.filter_mut(id)?.set_channel_mask(mask);
+        Ok(())
+    }
+
+    /// Nodes feeding `id`, in order
+    pub fn inputs(&self, id: NodeId) -> Option<&[NodeId]> {
+        self.node(id).ok().map(|node| node.inputs.as_slice())
+    }
+
+    /// Frames from the graph input to the output of `id`
+    pub fn node_latency(&self, id: NodeId) -> Option<usize> {
+        self.node(id).ok().map(|node| node.latency)
+    }
+
+    /// Number of nodes, including input and output
+    pub fn node_count(&self) -> usize {
+        self.nodes.iter().flatten().count()
+    }
+
+    /// Whether `node` is `source` or is fed by it, directly or not
+    fn depends_on(&self, node: NodeId, source: NodeId) -> bool {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            if id == source {
+                return true;
+            }
+            if std::mem::replace(&mut visited[id.0], true) {
+                continue;
+            }
+            if let Some(node) = &self.nodes[id.0] {
+                stack.extend(&node.inputs);
+            }
+        }
+        false
+    }
+
+    /// Order the nodes the output depends on, work out their latencies and
+    /// the mix compensation delays, and size everything for the prepared
+    /// layout and block size
+    fn schedule(&mut self) {
+        // Depth-first from the output; a node is ordered once its inputs are
+        let mut order = Vec::new();
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![(Self::OUTPUT, false)];
+        while let Some((id, inputs_done)) = stack.pop() {
+            if inputs_done {
+                order.push(id);
+                continue;
+            }
+            if std::mem::replace(&mut visited[id.0], true) {
+                continue;
+            }
+            stack.push((id, true));
+            let node = self.nodes[id.0].as_ref().expect("connected nodes exist");
+            stack.extend(node.inputs.iter().rev().filter(|input| !visited[input.0]).map(|&input| (input, false)));
+        }
+
+        let channels = self.layout.channels();
+        for &id in &order {
+            let mut node = self.nodes[id.0].take().expect("scheduled nodes exist");
+            let arrivals: Vec<usize> = node
+                .inputs
+                .iter()
+                .map(|input| self.nodes[input.0].as_ref().expect("inputs are scheduled first").latency)
+                .collect();
+            let latest = arrivals.iter().copied().max().unwrap_or(0);
+
+            node.latency = match &mut node.kind {
+                NodeKind::Mix { delays, .. } => {
+                    delays.resize_with(arrivals.len(), DelayLine::default);
+                    for (delay, arrival) in delays.iter_mut().zip(&arrivals) {
+                        delay.set_len((latest - arrival) * channels);
+                    }
+                    latest
+                }
+                NodeKind::Filter(filter) => {
+                    filter.prepare(self.layout, self.max_samples);
+                    latest + filter.latency_samples()
+                }
+                _ => latest,
+            };
+            node.buffer.resize(self.max_samples, 0.0);
+            self.nodes[id.0] = Some(node);
+        }
+        self.order = order;
+        self.dry.set_len(self.latency_samples() * channels);
+    }
+
+    /// This node's output for the current block
+    fn buffer(&self, id: NodeId, len: usize) -> &[f32] {
+        &self.nodes[id.0].as_ref().expect("inputs are scheduled first").buffer[..len]
+    }
+
+    /// Describe the graph for saving in a preset
+    ///
+    /// Nodes are renumbered to close the gaps removed nodes left. Fails if a
+    /// filter in it cannot be described.
+    pub fn preset(&self) -> Result<GraphPreset, VortexError> {
+        let mut renumbered = vec![None; self.nodes.len()];
+        for (dense, (index, _)) in self.nodes.iter().enumerate().filter(|(_, node)| node.is_some()).enumerate() {
+            renumbered[index] = Some(NodeId(dense));
+        }
+        let renumber = |id: NodeId| renumbered[id.0].expect("inputs are live nodes");
+
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        for (index, node) in self.nodes.iter().enumerate() {
+            let Some(node) = node else { continue };
+            let kind = match &node.kind {
+                NodeKind::Input => NodeKindPreset::Input,
+                NodeKind::Output => NodeKindPreset::Output,
+                NodeKind::Split => NodeKindPreset::Split,
+                NodeKind::Mix { gains, .. } => NodeKindPreset::Mix { gains: gains.clone() },
+                NodeKind::MidSide(mode) => NodeKindPreset::MidSide { mode: *mode },
+                NodeKind::Filter(filter) => NodeKindPreset::Filter {
+                    filter: filter.to_preset().ok_or_else(|| {
+                        AudioError::InvalidParameter(format!(
+                            "Filter '{}' cannot be saved in a preset",
+                            filter.metadata().name
+                        ))
+                    })?,
+                },
+            };
+            nodes.push(NodePreset {
+                id: renumber(NodeId(index)),
+                inputs: node.inputs.iter().map(|&input| renumber(input)).collect(),
+                kind,
+            });
+        }
+
+        Ok(GraphPreset {
+            name: self.metadata.name.clone(),
+            bypass: self.metadata.bypass,
+            channel_mask: self.metadata.channel_mask,
+            nodes,
+        })
+    }
+
+    /// Rebuild a graph from a preset, checking its connections as `connect` does
+    pub fn from_preset(preset: &GraphPreset) -> Result<Self, VortexError> {
+        let invalid = |reason: String| -> VortexError { AudioError::InvalidParameter(reason).into() };
+        // Ids come from a file, so check them before sizing anything by them
+        if let Some(node) = preset.nodes.iter().find(|node| node.id.0 >= preset.nodes.len()) {
+            return Err(invalid(format!("Preset of {} nodes has {}", preset.nodes.len(), node.id)));
+        }
+        let mut graph = Self::new(preset.name.clone());
+        graph.metadata.bypass = preset.bypass;
+        graph.metadata.channel_mask = preset.channel_mask;
+        graph.nodes = (0..preset.nodes.len().max(2)).map(|_| None).collect();
+
+        for node in &preset.nodes {
+            let kind = match &node.kind {
+                NodeKindPreset::Input => NodeKind::Input,
+                NodeKindPreset::Output => NodeKind::Output,
+                NodeKindPreset::Split => NodeKind::Split,
+                NodeKindPreset::Mix { gains } => {
+                    if gains.len() != node.inputs.len() {
+                        return Err(invalid(format!("Mix {} needs one gain per input", node.id)));
+                    }
+                    NodeKind::Mix { gains: Vec::new(), delays: Vec::new() }
+                }
+                NodeKindPreset::MidSide { mode } => NodeKind::MidSide(*mode),
+                NodeKindPreset::Filter { filter } => NodeKind::Filter(filter.build()?),
+            };
+            let expected = match node.id {
+                Self::INPUT => matches!(kind, NodeKind::Input),
+                Self::OUTPUT => matches!(kind, NodeKind::Output),
+                _ => !matches!(kind, NodeKind::Input | NodeKind::Output),
+            };
+            if !expected {
+                return Err(invalid(format!("Input and output must be nodes 0 and 1, not {}", node.id)));
+            }
+            if graph.nodes[node.id.0].replace(Node::new(kind)).is_some() {
+                return Err(invalid(format!("Preset has {} twice", node.id)));
+            }
+        }
+        if graph.nodes[Self::INPUT.0].is_none() || graph.nodes[Self::OUTPUT.0].is_none() {
+            return Err(invalid("Preset lacks the graph input or output".to_string()));
+        }
+
+        for node in &preset.nodes {
+            for &input in &node.inputs {
+                graph.connect(input, node.id)?;
+            }
+            if let NodeKindPreset::Mix { gains: saved } = &node.kind {
+                if let NodeKind::Mix { gains, .. } = &mut graph.node_mut(node.id)?.kind {
+                    gains.copy_from_slice(saved);
+                }
+            }
+        }
+        graph.schedule();
+        Ok(graph)
+    }
+}
+
+impl Filter for FilterGraph {
+    fn process(&mut self, input: &[f32], output: &mut [f32], layout: ChannelLayout) {
+        debug_assert_eq!(input.len() % layout.channels(), 0, "buffer must hold whole frames");
+        if layout != self.layout || input.len() > self.max_samples {
+            self.prepare(layout, input.len().max(self.max_samples));
+        }
+
+        let len = input.len();
+        for step in 0..self.order.len() {
+            let id = self.order[step];
+            let mut node = self.nodes[id.0].take().expect("scheduled nodes exist");
+            let out = &mut node.buffer[..len];
+            let source = node.inputs.first().map(|&input| self.buffer(input, len));
+
+            match &mut node.kind {
+                NodeKind::Input => out.copy_from_slice(input),
+                NodeKind::Output | NodeKind::Split => copy_or_silence(source, out),
+                NodeKind::Filter(filter) => match source {
+                    Some(source) if filter.is_bypassed() => filter.process_bypassed(source, out, layout),
+                    Some(source) => filter.process(source, out, layout),
+                    None => out.fill(0.0),
+                },
+                NodeKind::MidSide(mode) => {
+                    copy_or_silence(source, out);
+                    mid_side(*mode, out, layout);
+                }
+                NodeKind::Mix { gains, delays } => {
+                    out.fill(0.0);
+                    for ((&input, &gain), delay) in node.inputs.iter().zip(gains.iter()).zip(delays.iter_mut()) {
+                        for (sample, &x) in out.iter_mut().zip(self.buffer(input, len)) {
+                            *sample += gain * delay.tick(x);
+                        }
+                    }
+                }
+            }
+            self.nodes[id.0] = Some(node);
+        }
+
+        output.copy_from_slice(self.buffer(Self::OUTPUT, len));
+        // Keep the dry delay running so masked channels stay in line and a
+        // switch to bypass picks up where processing left off
+        let mask = self.metadata.channel_mask;
+        if mask != ChannelMask::ALL || !self.dry.is_empty() {
+            for (i, (out, &x)) in output.iter_mut().zip(input).enumerate() {
+                let dry = self.dry.tick(x);
+                if !mask.contains(i % layout.channels()) {
+                    *out = dry;
+                }
+            }
+        }
+    }
+
+    fn process_bypassed(&mut self, input: &[f32], output: &mut [f32], layout: ChannelLayout) {
+        if layout != self.layout {
+            self.prepare(layout, input.len().max(self.max_samples));
+        }
+        self.dry.process(input, output);
+    }
+
+    fn prepare(&mut self, layout: ChannelLayout, max_samples: usize) {
+        self.layout = layout;
+        self.max_samples = max_samples;
+        self.schedule();
+    }
+
+    fn latency_samples(&self) -> usize {
+        self.node(Self::OUTPUT).map_or(0, |node| node.latency)
+    }
+
+    fn to_preset(&self) -> Option<FilterPreset> {
+        self.preset().ok().map(FilterPreset::Graph)
+    }
+
+    fn metadata(&self) -> &FilterMetadata {
+        &self.metadata
+    }
+
+    fn set_bypass(&mut self, bypass: bool) {
+        self.metadata.bypass = bypass;
+    }
+
+    fn set_channel_mask(&mut self, mask: ChannelMask) {
+        self.metadata.channel_mask = mask;
+    }
+
+    fn is_bypassed(&self) -> bool {
+        self.metadata.bypass
+    }
+
+    fn reset(&mut self) {
+        for node in self.nodes.iter_mut().flatten() {
+            node.buffer.fill(0.0);
+            match &mut node.kind {
+                NodeKind::Filter(filter) => filter.reset(),
+                NodeKind::Mix { delays, .. } => delays.iter_mut().for_each(DelayLine::reset),
+                _ => {}
+            }
+        }
+        self.dry.reset();
+    }
+
+    fn clone_box(&self) -> Box<dyn Filter> {
+        Box::new(FilterGraph {
+            metadata: self.metadata.clone(),
+            nodes: self.nodes.iter().map(|node| node.as_ref().map(Node::clone_node)).collect(),
+            order: self.order.clone(),
+            layout: self.layout,
+            max_samples: self.max_samples,
+            dry: DelayLine::new(self.dry.len()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::filters::{BiquadCoefficients, BiquadFilter, FilterChain};
+
+    /// Filter delaying its input by a whole number of frames
+    struct Delay {
+        metadata: FilterMetadata,
+        frames: usize,
+        line: DelayLine,
+    }
+
+    impl Delay {
+        fn new(frames: usize) -> Self {
+            Self {
+                metadata: FilterMetadata {
+                    id: Uuid::new_v4().to_string(),
+                    name: "Delay".to_string(),
+                    enabled: true,
+                    bypass: false,
+                    channel_mask: ChannelMask::ALL,
+                },
+                frames,
+                line: DelayLine::default(),
+            }
+        }
+    }
+
+    impl Filter for Delay {
+        fn process(&mut self, input: &[f32], output: &mut [f32], layout: ChannelLayout) {
+            self.line.set_len(self.frames * layout.channels());
+            for (out, &x) in output.iter_mut().zip(input) {
+                *out = self.line.tick(x);
+            }
+        }
+
+        fn process_bypassed(&mut self, input: &[f32], output: &mut [f32], layout: ChannelLayout) {
+            self.process(input, output, layout);
+        }
+
+        fn latency_samples(&self) -> usize {
+            self.frames
+        }
+
+        fn metadata(&self) -> &FilterMetadata {
+            &self.metadata
+        }
+
+        fn set_bypass(&mut self, bypass: bool) {
+            self.metadata.bypass = bypass;
+        }
+
+        fn set_channel_mask(&mut self, mask: ChannelMask) {
+            self.metadata.channel_mask = mask;
+        }
+
+        fn is_bypassed(&self) -> bool {
+            self.metadata.bypass
+        }
+
+        fn reset(&mut self) {
+            self.line.reset();
+        }
+
+        fn clone_box(&self) -> Box<dyn Filter> {
+            Box::new(Delay::new(self.frames))
+        }
+    }
+
+    fn noise(len: usize) -> Vec<f32> {
+        let mut state = 0x1234_5678u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+            })
+            .collect()
+    }
+
+    fn run(filter: &mut dyn Filter, input: &[f32], layout: ChannelLayout) -> Vec<f32> {
+        let mut output = vec![0.0; input.len()];
+        filter.process(input, &mut output, layout);
+        output
+    }
+
+    /// Input -> split -> lowpass and highpass at 1 kHz -> mix -> output
+    fn crossover(low_gain: f32, high_gain: f32) -> (FilterGraph, NodeId, NodeId, NodeId) {
+        let mut graph = FilterGraph::new("Crossover".to_string());
+        let split = graph.add_split();
+        let low = graph.add_filter_node(Box::new(BiquadFilter::new(
+            "Low".to_string(),
+            BiquadCoefficients::lowpass(1000.0, 48000.0, 0.707),
+        )));
+        let high = graph.add_filter_node(Box::new(BiquadFilter::new(
+            "High".to_string(),
+            BiquadCoefficients::highpass(1000.0, 48000.0, 0.707),
+        )));
+        let mix = graph.add_mix();
+        graph.disconnect(FilterGraph::INPUT, FilterGraph::OUTPUT).unwrap();
+        for (from, to) in [(FilterGraph::INPUT, split), (split, low), (split, high), (low, mix), (high, mix), (mix, FilterGraph::OUTPUT)] {
+            graph.connect(from, to).unwrap();
+        }
+        graph.set_mix_gain(mix, low, low_gain).unwrap();
+        graph.set_mix_gain(mix, high, high_gain).unwrap();
+        (graph, low, high, mix)
+    }
+
+    #[test]
+    fn test_new_graph_passes_through() {
+        let mut graph = FilterGraph::new("Empty".to_string());
+        let input = noise(256);
+        assert_eq!(run(&mut graph, &input, ChannelLayout::STEREO), input);
+        assert_eq!(graph.node_count(), 2);
+        assert_eq!(graph.latency_samples(), 0);
+    }
+
+    #[test]
+    fn test_series_matches_filter_chain() {
+        let designs = [(100.0, 6.0), (1000.0, -3.0), (8000.0, 2.0)];
+        let mut graph = FilterGraph::new("Series".to_string());
+        let mut chain = FilterChain::new();
+        let mut ids = Vec::new();
+        for (frequency, gain) in designs {
+            ids.push(graph.add_filter(Box::new(BiquadFilter::peaking(frequency, 48000.0, 1.0, gain))));
+            chain.add_filter(Box::new(BiquadFilter::peaking(frequency, 48000.0, 1.0, gain))).unwrap();
+        }
+        let input = noise(512);
+        let mut expected = vec![0.0; input.len()];
+        chain.process(&input, &mut expected, ChannelLayout::STEREO);
+        assert_eq!(run(&mut graph, &input, ChannelLayout::STEREO), expected);
+
+        // Removing a filter from the series keeps the rest connected
+        graph.remove_node(ids[1]).unwrap();
+        assert_eq!(graph.inputs(ids[2]), Some(&[ids[0]][..]));
+        assert!(graph.filter(ids[1]).is_none());
+    }
+
+    #[test]
+    fn test_crossover_sums_bands_with_gains() {
+        let (mut graph, ..) = crossover(0.5, 2.0);
+        let mut low = BiquadFilter::new("Low".to_string(), BiquadCoefficients::lowpass(1000.0, 48000.0, 0.707));
+        let mut high = BiquadFilter::new("High".to_string(), BiquadCoefficients::highpass(1000.0, 48000.0, 0.707));
+
+        let layout = ChannelLayout::STEREO;
+        for block in 0..3 {
+            let input: Vec<f32> = noise(512 * (block + 1))[512 * block..].to_vec();
+            let (low, high) = (run(&mut low, &input, layout), run(&mut high, &input, layout));
+            let expected: Vec<f32> = low.iter().zip(&high).map(|(l, h)| 0.5 * l + 2.0 * h).collect();
+            assert_eq!(run(&mut graph, &input, layout), expected);
+        }
+    }
+
+    #[test]
+    fn test_latency_compensation_aligns_branches() {
+        let mut graph = FilterGraph::new("Delayed".to_string());
+        let split = graph.add_split();
+        let delayed = graph.add_filter_node(Box::new(Delay::new(5)));
+        let mix = graph.add_mix();
+        graph.disconnect(FilterGraph::INPUT, FilterGraph::OUTPUT).unwrap();
+        for (from, to) in [(FilterGraph::INPUT, split), (split, delayed), (split, mix), (delayed, mix), (mix, FilterGraph::OUTPUT)] {
+            graph.connect(from, to).unwrap();
+        }
+        assert_eq!(graph.latency_samples(), 5);
+
+        // The direct branch is delayed to meet the other: one impulse of 2
+        let mut impulse = vec![0.0; 32];
+        impulse[0] = 1.0;
+        let output = run(&mut graph, &impulse, ChannelLayout::MONO);
+        let mut expected = vec![0.0; 32];
+        expected[5] = 2.0;
+        assert_eq!(output, expected);
+
+        // Series latencies add up, and a bypassed filter keeps its delay
+        let second = graph.add_filter(Box::new(Delay::new(3)));
+        assert_eq!(graph.latency_samples(), 8);
+        graph.set_filter_bypass(second, true).unwrap();
+        assert_eq!(graph.latency_samples(), 8);
+        assert_eq!(graph.node_latency(split), Some(0));
+        graph.reset();
+        expected.fill(0.0);
+        expected[8] = 2.0;
+        assert_eq!(run(&mut graph, &impulse, ChannelLayout::MONO), expected);
+
+        // Masked channels and the bypassed graph are delayed to match
+        graph.reset();
+        graph.set_channel_mask(ChannelMask::only(1));
+        let stereo: Vec<f32> = impulse.iter().flat_map(|&x| [x, x]).collect();
+        let delayed: Vec<f32> = expected.iter().flat_map(|&x| [x / 2.0, x]).collect();
+        assert_eq!(run(&mut graph, &stereo, ChannelLayout::STEREO), delayed);
+        graph.reset();
+        graph.set_bypass(true);
+        let mut bypassed = vec![0.0; stereo.len()];
+        graph.process_bypassed(&stereo, &mut bypassed, ChannelLayout::STEREO);
+        assert_eq!(bypassed, expected.iter().flat_map(|&x| [x / 2.0, x / 2.0]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_mid_side_processing() {
+        // Silence the side channel between encode and decode: both outputs become the mid
+        let mut graph = FilterGraph::new("M/S".to_string());
+        let encode = graph.add_mid_side(MidSideMode::Encode);
+        let side = graph.add_filter_node(Box::new(BiquadFilter::new(
+            "Side".to_string(),
+            BiquadCoefficients { b0: 0.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 },
+        )));
+        let decode = graph.add_mid_side(MidSideMode::Decode);
+        graph.disconnect(FilterGraph::INPUT, FilterGraph::OUTPUT).unwrap();
+        for (from, to) in [(FilterGraph::INPUT, encode), (encode, side), (side, decode), (decode, FilterGraph::OUTPUT)] {
+            graph.connect(from, to).unwrap();
+        }
+        graph.set_filter_channel_mask(side, ChannelMask::only(1)).unwrap();
+
+        let input = [1.0, 0.5, -0.25, 0.75, 0.0, 1.0];
+        let output = run(&mut graph, &input, ChannelLayout::STEREO);
+        assert_eq!(output, [0.75, 0.75, 0.25, 0.25, 0.5, 0.5]);
+
+        // Encode then decode restores the input
+        graph.set_filter_bypass(side, true).unwrap();
+        assert_eq!(run(&mut graph, &input, ChannelLayout::STEREO), input);
+    }
+
+    #[test]
+    fn test_invalid_connections() {
+        let (mut graph, low, high, mix) = crossover(1.0, 1.0);
+        let missing = NodeId(99);
+        assert!(graph.connect(missing, mix).is_err());
+        assert!(graph.connect(FilterGraph::OUTPUT, mix).is_err());
+        assert!(graph.connect(low, FilterGraph::INPUT).is_err());
+        // A filter takes one input, and edges must not close a loop
+        assert!(graph.connect(high, low).is_err());
+        assert!(graph.connect(mix, mix).is_err());
+        let extra = graph.add_mix();
+        graph.connect(mix, extra).unwrap();
+        assert!(graph.connect(extra, mix).is_err());
+        assert!(graph.disconnect(low, high).is_err());
+        assert!(graph.set_mix_gain(low, high, 1.0).is_err());
+        assert!(graph.remove_node(FilterGraph::OUTPUT).is_err());
+
+        // Removing a filter feeds its input through, into a mix too
+        let split = graph.inputs(high).unwrap()[0];
+        graph.remove_node(high).unwrap();
+        assert_eq!(graph.inputs(mix), Some(&[low, split][..]));
+        graph.remove_node(split).unwrap();
+        assert_eq!(graph.inputs(low), Some(&[FilterGraph::INPUT][..]));
+    }
+
+    #[test]
+    fn test_unconnected_output_is_silent() {
+        let mut graph = FilterGraph::new("Open".to_string());
+        let node = graph.add_filter(Box::new(BiquadFilter::peaking(1000.0, 48000.0, 1.0, 6.0)));
+        graph.disconnect(FilterGraph::INPUT, node).unwrap();
+        assert!(run(&mut graph, &noise(64), ChannelLayout::STEREO).iter().all(|&x| x == 0.0));
+    }
+
+    #[test]
+    fn test_preset_round_trip() {
+        let (mut graph, low, ..) = crossover(0.5, 1.5);
+        graph.set_filter_channel_mask(low, ChannelMask::only(0)).unwrap();
+        let spare = graph.add_split();
+        let ms = graph.add_mid_side(MidSideMode::Encode);
+        let source = graph.inputs(FilterGraph::OUTPUT).unwrap()[0];
+        graph.disconnect(source, FilterGraph::OUTPUT).unwrap();
+        graph.connect(source, ms).unwrap();
+        graph.connect(ms, FilterGraph::OUTPUT).unwrap();
+        // The gap the removed node leaves is closed in the preset
+        graph.remove_node(spare).unwrap();
+        let saved = graph.preset().unwrap();
+        assert!(saved.nodes.iter().all(|node| node.id.0 < saved.nodes.len()));
+
+        let json = serde_json::to_string(&graph.to_preset().unwrap()).unwrap();
+        let preset: FilterPreset = serde_json::from_str(&json).unwrap();
+        let mut restored = preset.build().unwrap();
+        assert_eq!(restored.to_preset(), graph.to_preset());
+
+        let input = noise(1024);
+        let expected = run(&mut graph, &input, ChannelLayout::STEREO);
+        assert_eq!(run(restored.as_mut(), &input, ChannelLayout::STEREO), expected);
+
+        // Graphs nest, and filters without a description cannot be saved
+        let mut outer = FilterGraph::new("Outer".to_string());
+        outer.add_filter(Box::new(graph));
+        assert!(outer.preset().is_ok());
+        outer.add_filter(Box::new(Delay::new(1)));
+        assert!(outer.preset().is_err());
+    }
+
+    #[test]
+    fn test_invalid_presets() {
+        let (graph, _, _, mix) = crossover(1.0, 1.0);
+        let preset = graph.preset().unwrap();
+
+        let mut cyclic = preset.clone();
+        let split = cyclic.nodes.iter_mut().find(|node| node.kind == NodeKindPreset::Split).unwrap();
+        split.inputs.push(mix);
+        assert!(FilterGraph::from_preset(&cyclic).is_err());
+
+        let mut no_output = preset.clone();
+        no_output.nodes.retain(|node| node.id != FilterGraph::OUTPUT);
+        assert!(FilterGraph::from_preset(&no_output).is_err());
+
+        let mut gains = preset.clone();
+        for node in &mut gains.nodes {
+            if let NodeKindPreset::Mix { gains } = &mut node.kind {
+                gains.pop();
+            }
+        }
+        assert!(FilterGraph::from_preset(&gains).is_err());
+
+        // Ids past the node count are rejected before anything is sized by them
+        for id in [usize::MAX, 1 << 40, preset.nodes.len()] {
+            let mut bad_id = preset.clone();
+            bad_id.nodes.last_mut().unwrap().id = NodeId(id);
+            assert!(FilterGraph::from_preset(&bad_id).is_err(), "{}", id);
+        }
+        let json = serde_json::to_string(&preset).unwrap().replacen("\"id\":2,", "\"id\":18446744073709551615,", 1);
+        assert!(FilterGraph::from_preset(&serde_json::from_str(&json).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_graph_in_chain_and_allocation_free() {
+        use crate::audio::alloc_check::heap_operations;
+
+        let (graph, ..) = crossover(1.0, 1.0);
+        let mut chain = FilterChain::new();
+        chain.add_filter(Box::new(graph)).unwrap();
+        chain.prepare(ChannelLayout::STEREO, 512);
+
+        let input = noise(512);
+        let mut output = vec![0.0; 512];
+        let ((), operations) = heap_operations(|| {
+            for _ in 0..4 {
+                chain.process(&input, &mut output, ChannelLayout::STEREO);
+            }
+        });
+        assert_eq!(operations, 0);
+    }
+}
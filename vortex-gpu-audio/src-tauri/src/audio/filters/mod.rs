@@ -1,5 +1,13 @@
 pub mod filter_chain;
 pub mod biquad;
+pub mod delay;
+pub mod graph;
+pub mod preset;
+pub mod response;
 
-pub use filter_chain::{Filter, FilterChain, FilterMetadata};
-pub use biquad::{BiquadFilter, BiquadCoefficients, FilterType};
+pub use filter_chain::{ChainBatch, ChannelLayout, ChannelMask, Filter, FilterChain, FilterMetadata};
+pub use biquad::{BiquadController, BiquadFilter, BiquadCoefficients, BiquadTopology, FilterType, ShelfWidth};
+pub use delay::DelayLine;
+pub use graph::{FilterGraph, GraphPreset, MidSideMode, NodeId, NodeKindPreset, NodePreset};
+pub use preset::FilterPreset;
+pub use response::{log_frequency_grid, ResponseCurve};
@@ -0,0 +1,42 @@
+//! Serializable filter descriptions for presets
+
+use super::biquad::{BiquadCoefficients, BiquadFilter, BiquadTopology};
+use super::filter_chain::{ChannelMask, Filter};
+use super::graph::{FilterGraph, GraphPreset};
+use crate::error::VortexError;
+use serde::{Deserialize, Serialize};
+
+/// Description of a filter that can be saved and rebuilt
+///
+/// Produced by `Filter::to_preset`. Coefficients are stored as designed, so
+/// a preset rebuilds the same filter at the sample rate it was saved for.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FilterPreset {
+    Biquad {
+        name: String,
+        coefficients: BiquadCoefficients,
+        topology: BiquadTopology,
+        smoothing: usize,
+        bypass: bool,
+        channel_mask: ChannelMask,
+    },
+    Graph(GraphPreset),
+}
+
+impl FilterPreset {
+    /// Build the described filter, with fresh state and a new id
+    pub fn build(&self) -> Result<Box<dyn Filter>, VortexError> {
+        match self {
+            FilterPreset::Biquad { name, coefficients, topology, smoothing, bypass, channel_mask } => {
+                let mut filter = BiquadFilter::new(name.clone(), *coefficients);
+                filter.set_topology(*topology);
+                filter.set_smoothing(*smoothing);
+                filter.set_bypass(*bypass);
+                filter.set_channel_mask(*channel_mask);
+                Ok(Box::new(filter))
+            }
+            FilterPreset::Graph(preset) => Ok(Box::new(FilterGraph::from_preset(preset)?)),
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Frequency response evaluation for filters and chains
+//!
+//! Responses are complex values H(e^jω) at frequencies in Hz. Biquads
+//! evaluate their transfer function directly; any other `Filter` falls back
+//! to the spectrum of its impulse response (`impulse_response_spectrum`).
+
+use super::filter_chain::{ChannelLayout, ChannelMask, Filter};
+use rustfft::num_complex::Complex64;
+use rustfft::FftPlanner;
+use serde::Serialize;
+
+/// Samples of impulse response captured by the FFT fallback
+pub const IMPULSE_LENGTH: usize = 16384;
+
+/// Zero padding factor applied before the fallback FFT
+const ZERO_PADDING: usize = 4;
+
+/// Relative step used to differentiate phase for group delay
+const GROUP_DELAY_STEP: f64 = 1e-5;
+
+/// Magnitude, phase and group delay of a response, ready for display
+#[derive(Debug, Clone, Serialize)]
+pub struct ResponseCurve {
+    pub frequencies: Vec<f32>,
+    pub magnitude_db: Vec<f32>,
+    /// Wrapped to (-180, 180]
+    pub phase_degrees: Vec<f32>,
+    pub group_delay_ms: Vec<f32>,
+}
+
+impl ResponseCurve {
+    /// Evaluate `response` at `frequencies` and derive the display curves
+    ///
+    /// `response` receives every frequency at which H is needed, in Hz, and
+    /// returns H at each. Group delay comes from a central difference of the
+    /// phase, so `response` is called once with three points per frequency.
+    pub fn evaluate<F>(frequencies: &[f32], sample_rate: f32, response: F) -> Self
+    where
+        F: FnOnce(&[f32]) -> Vec<Complex64>,
+    {
+        let step = (sample_rate as f64 * GROUP_DELAY_STEP) as f32;
+        let probes: Vec<f32> = frequencies
+            .iter()
+            .copied()
+            .chain(frequencies.iter().map(|&f| f - step))
+            .chain(frequencies.iter().map(|&f| f + step))
+            .collect();
+        let values = response(&probes);
+        let count = frequencies.len();
+        let (centre, rest) = values.split_at(count);
+        let (below, above) = rest.split_at(count);
+
+        let mut curve = Self {
+            frequencies: frequencies.to_vec(),
+            magnitude_db: Vec::with_capacity(count),
+            phase_degrees: Vec::with_capacity(count),
+            group_delay_ms: Vec::with_capacity(count),
+        };
+        for i in 0..count {
+            curve.magnitude_db.push((20.0 * centre[i].norm().log10()) as f32);
+            curve.phase_degrees.push(centre[i].arg().to_degrees() as f32);
+
+            // τ = -dφ/dω; the phase step is taken as arg(H+ conj(H-)) to avoid unwrapping
+            let phase_step = (above[i] * below[i].conj()).arg();
+            let omega_step = 2.0 * std::f64::consts::PI * 2.0 * step as f64;
+            curve.group_delay_ms.push((-phase_step / omega_step * 1000.0) as f32);
+        }
+        curve
+    }
+}
+
+/// `points` frequencies spaced logarithmically from `min_hz` to `max_hz`
+pub fn log_frequency_grid(min_hz: f32, max_hz: f32, points: usize) -> Vec<f32> {
+    match points {
+        0 => Vec::new(),
+        1 => vec![min_hz],
+        _ => {
+            let ratio = (max_hz as f64 / min_hz as f64).ln() / (points - 1) as f64;
+            (0..points)
+                .map(|i| (min_hz as f64 * (ratio * i as f64).exp()) as f32)
+                .collect()
+        }
+    }
+}
+
+/// Response of any filter from the FFT of its impulse response
+///
+/// `probe` (a copy of the filter) is reset, driven with a unit impulse on
+/// every channel, and the first `IMPULSE_LENGTH` output samples are transformed, so tails
+/// longer than that are truncated. Bins of the zero-padded spectrum are
+/// interpolated to reach arbitrary frequencies.
+pub fn impulse_response_spectrum(mut probe: Box<dyn Filter>, frequencies: &[f32], sample_rate: f32) -> Vec<Complex64> {
+    probe.reset();
+    probe.set_channel_mask(ChannelMask::ALL);
+
+    let mut impulse = vec![0.0f32; IMPULSE_LENGTH];
+    impulse[0] = 1.0;
+    let mut output = vec![0.0f32; IMPULSE_LENGTH];
+    probe.process(&impulse, &mut output, ChannelLayout::MONO);
+
+    let size = IMPULSE_LENGTH * ZERO_PADDING;
+    let mut spectrum: Vec<Complex64> = output
+        .iter()
+        .map(|&x| Complex64::new(x as f64, 0.0))
+        .chain(std::iter::repeat(Complex64::default()))
+        .take(size)
+        .collect();
+    FftPlanner::new().plan_fft_forward(size).process(&mut spectrum);
+
+    frequencies
+        .iter()
+        .map(|&frequency| {
+            let position = (frequency as f64 / sample_rate as f64).rem_euclid(1.0) * size as f64;
+            let index = position.floor() as usize;
+            let t = position - index as f64;
+            spectrum[index % size] * (1.0 - t) + spectrum[(index + 1) % size] * t
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::filters::{BiquadCoefficients, BiquadFilter, FilterMetadata};
+
+    /// Pure delay, which has no analytic response of its own
+    #[derive(Clone)]
+    struct Delay {
+        metadata: FilterMetadata,
+        samples: usize,
+        history: Vec<f32>,
+    }
+
+    impl Filter for Delay {
+        fn process(&mut self, input: &[f32], output: &mut [f32], _layout: ChannelLayout) {
+            for (out, &x) in output.iter_mut().zip(input) {
+                self.history.push(x);
+                *out = self.history.remove(0);
+            }
+        }
+
+        fn metadata(&self) -> &FilterMetadata {
+            &self.metadata
+        }
+
+        fn set_bypass(&mut self, bypass: bool) {
+            self.metadata.bypass = bypass;
+        }
+
+        fn set_channel_mask(&mut self, mask: ChannelMask) {
+            self.metadata.channel_mask = mask;
+        }
+
+        fn is_bypassed(&self) -> bool {
+            self.metadata.bypass
+        }
+
+        fn reset(&mut self) {
+            self.history = vec![0.0; self.samples];
+        }
+
+        fn clone_box(&self) -> Box<dyn Filter> {
+            Box::new(self.clone())
+        }
+    }
+
+    fn delay(samples: usize) -> Delay {
+        Delay {
+            metadata: FilterMetadata {
+                id: "delay".to_string(),
+                name: "Delay".to_string(),
+                enabled: true,
+                bypass: false,
+                channel_mask: ChannelMask::ALL,
+            },
+            samples,
+            history: vec![0.0; samples],
+        }
+    }
+
+    #[test]
+    fn test_log_grid() {
+        let grid = log_frequency_grid(20.0, 20000.0, 4);
+        let expected = [20.0, 200.0, 2000.0, 20000.0];
+        for (actual, expected) in grid.iter().zip(expected) {
+            assert!((actual / expected - 1.0).abs() < 1e-5);
+        }
+        assert!(log_frequency_grid(20.0, 20000.0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_fallback_matches_analytic_biquad() {
+        let filter = BiquadFilter::peaking(1000.0, 48000.0, 2.0, 9.0);
+        let frequencies = log_frequency_grid(20.0, 20000.0, 64);
+        let analytic = filter.frequency_response(&frequencies, 48000.0);
+        let measured = impulse_response_spectrum(filter.clone_box(), &frequencies, 48000.0);
+        for (a, m) in analytic.iter().zip(&measured) {
+            assert!((a - m).norm() < 1e-3, "{} vs {}", a, m);
+        }
+    }
+
+    #[test]
+    fn test_curve_of_delay() {
+        let filter = delay(48);
+        let frequencies = log_frequency_grid(50.0, 10000.0, 16);
+        let curve = ResponseCurve::evaluate(&frequencies, 48000.0, |f| filter.frequency_response(f, 48000.0));
+        for i in 0..frequencies.len() {
+            assert!(curve.magnitude_db[i].abs() < 0.01);
+            assert!((curve.group_delay_ms[i] - 1.0).abs() < 1e-3, "{}", curve.group_delay_ms[i]);
+        }
+    }
+
+    #[test]
+    fn test_biquad_group_delay_at_dc() {
+        // A second-order Butterworth lowpass delays DC by sqrt(2) / ωc
+        let coeffs = BiquadCoefficients::lowpass(1000.0, 48000.0, std::f32::consts::FRAC_1_SQRT_2);
+        let filter = BiquadFilter::new("Lowpass".to_string(), coeffs);
+        let curve = ResponseCurve::evaluate(&[10.0], 48000.0, |f| filter.frequency_response(f, 48000.0));
+        let expected_ms = std::f32::consts::SQRT_2 / (2.0 * std::f32::consts::PI * 1000.0) * 1000.0;
+        assert!((curve.group_delay_ms[0] / expected_ms - 1.0).abs() < 0.02, "{}", curve.group_delay_ms[0]);
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use parking_lot::Mutex;
 use std::collections::VecDeque;
 
@@ -49,6 +49,16 @@ impl PooledBuffer {
         &self.data
     }
     
+    /// Get the number of samples in the buffer
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    
+    /// Check if the buffer holds no samples
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    
     /// Get buffer capacity
     pub fn capacity(&self) -> usize {
         self.data.capacity()
@@ -100,6 +110,12 @@ impl AudioMemoryPool {
         pool
     }
     
+    /// Pool shared by the whole process, created on first use
+    pub fn shared() -> Arc<Mutex<Self>> {
+        static SHARED: OnceLock<Arc<Mutex<AudioMemoryPool>>> = OnceLock::new();
+        Arc::clone(SHARED.get_or_init(|| Arc::new(Mutex::new(AudioMemoryPool::new()))))
+    }
+    
     /// Pre-allocate all pool buffers
     fn preallocate(&mut self) {
         // Small buffers (512 samples)
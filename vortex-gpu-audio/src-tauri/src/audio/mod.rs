@@ -4,6 +4,8 @@ pub mod processor;
 pub mod dsp;
 pub mod filters;
 pub mod memory_pool;
+#[cfg(test)]
+pub mod alloc_check;
 
 pub use engine::{AudioEngine, AudioConfig, AudioEngineError};
 pub use processor::{AudioProcessor, ProcessingStats};
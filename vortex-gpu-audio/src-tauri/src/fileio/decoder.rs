@@ -0,0 +1,289 @@
+use super::opus::OpusDecoder;
+use crate::error::{FileIoError, VortexError};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{
+    CodecParameters, CodecRegistry, Decoder, DecoderOptions, CODEC_TYPE_NULL, CODEC_TYPE_OPUS,
+    CODEC_TYPE_PCM_F32BE, CODEC_TYPE_PCM_F32LE, CODEC_TYPE_PCM_F64BE, CODEC_TYPE_PCM_F64LE,
+};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Symphonia's built-in codecs plus the libopus adapter
+fn codec_registry() -> &'static CodecRegistry {
+    static REGISTRY: OnceLock<CodecRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = CodecRegistry::new();
+        symphonia::default::register_enabled_codecs(&mut registry);
+        registry.register_all::<OpusDecoder>();
+        registry
+    })
+}
+
+/// Convert a symphonia error into the file I/O error space
+fn map_error(error: SymphoniaError, path: &Path) -> VortexError {
+    match error {
+        SymphoniaError::IoError(e) => FileIoError::Io(e).into(),
+        SymphoniaError::Unsupported(what) => FileIoError::UnsupportedFormat {
+            format: what.to_string(),
+            path: path.display().to_string(),
+        }.into(),
+        other => FileIoError::FileCorrupted {
+            path: path.display().to_string(),
+            reason: other.to_string(),
+        }.into(),
+    }
+}
+
+/// Frames Opus needs to decode before a seek target to converge (RFC 7845 4.6)
+const OPUS_SEEK_PREROLL: u64 = 3840;
+
+/// Packet-level decoder producing interleaved f32 samples
+///
+/// Wraps a symphonia format reader and codec for the first audio track of a
+/// file. Encoder delay and padding are trimmed (gapless) where the container
+/// signals them.
+pub struct AudioDecoder {
+    path: PathBuf,
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    sample_rate: u32,
+    channels: u16,
+    bit_depth: u8,
+    total_frames: Option<u64>,
+    // Container timestamp of output frame 0 (the Opus pre-skip)
+    ts_offset: u64,
+    // Frames decoded ahead of a seek target so the codec state settles
+    seek_preroll: u64,
+    // First frame to emit after a seek; earlier decoded frames are dropped
+    seek_target: Option<u64>,
+    end_of_stream: bool,
+    sample_buf: Option<SampleBuffer<f32>>,
+}
+
+impl AudioDecoder {
+    /// Open a file and prepare its first audio track for decoding
+    pub fn open(path: &Path) -> Result<Self, VortexError> {
+        let file = File::open(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FileIoError::FileNotFound {
+                path: path.display().to_string(),
+            }.into(),
+            _ => VortexError::from(FileIoError::Io(e)),
+        })?;
+
+        let mut hint = Hint::new();
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(ext);
+        }
+
+        let format_options = FormatOptions {
+            enable_gapless: true,
+            ..Default::default()
+        };
+        let probed = symphonia::default::get_probe()
+            .format(
+                &hint,
+                MediaSourceStream::new(Box::new(file), Default::default()),
+                &format_options,
+                &MetadataOptions::default(),
+            )
+            .map_err(|e| map_error(e, path))?;
+        let format = probed.format;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or_else(|| FileIoError::UnsupportedFormat {
+                format: "no audio track".to_string(),
+                path: path.display().to_string(),
+            })?;
+        let params = &track.codec_params;
+
+        let decoder = codec_registry()
+            .make(params, &DecoderOptions::default())
+            .map_err(|e| map_error(e, path))?;
+
+        let sample_rate = params.sample_rate.ok_or_else(|| FileIoError::FileCorrupted {
+            path: path.display().to_string(),
+            reason: "missing sample rate".to_string(),
+        })?;
+        let channels = params.channels.map(|c| c.count() as u16).ok_or_else(|| {
+            FileIoError::FileCorrupted {
+                path: path.display().to_string(),
+                reason: "missing channel layout".to_string(),
+            }
+        })?;
+
+        // Ogg Opus timestamps and frame counts include the pre-skip the decoder discards
+        let (ts_offset, seek_preroll) = if params.codec == CODEC_TYPE_OPUS {
+            (u64::from(params.delay.unwrap_or(0)), OPUS_SEEK_PREROLL)
+        } else {
+            (0, 0)
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            track_id: track.id,
+            sample_rate,
+            channels,
+            bit_depth: Self::source_bit_depth(params),
+            total_frames: params.n_frames.map(|frames| frames.saturating_sub(ts_offset)),
+            ts_offset,
+            seek_preroll,
+            seek_target: None,
+            end_of_stream: false,
+            format,
+            decoder,
+            sample_buf: None,
+        })
+    }
+
+    /// Bit depth of the stored samples, 0 when the codec has none
+    fn source_bit_depth(params: &CodecParameters) -> u8 {
+        if let Some(bits) = params.bits_per_sample {
+            return bits as u8;
+        }
+        // Float PCM declares its width through the codec type only
+        match params.codec {
+            CODEC_TYPE_PCM_F32LE | CODEC_TYPE_PCM_F32BE => 32,
+            CODEC_TYPE_PCM_F64LE | CODEC_TYPE_PCM_F64BE => 64,
+            _ => 0,
+        }
+    }
+
+    /// Stream sample rate in Hz
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Number of interleaved channels
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    /// Source bit depth, or 0 for lossy codecs without one
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    /// Total frames when the container declares them
+    pub fn total_frames(&self) -> Option<u64> {
+        self.total_frames
+    }
+
+    /// Decode the next packet and append its interleaved samples to `output`
+    ///
+    /// Returns the number of frames appended; `Ok(0)` marks the end of the stream.
+    /// Corrupt packets are skipped rather than aborting the decode.
+    pub fn decode_next(&mut self, output: &mut Vec<f32>) -> Result<usize, VortexError> {
+        if self.end_of_stream {
+            return Ok(0);
+        }
+
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    return Ok(0);
+                }
+                Err(SymphoniaError::ResetRequired) => return Ok(0),
+                Err(e) => return Err(map_error(e, &self.path)),
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let first_frame = packet.ts().saturating_sub(self.ts_offset);
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(SymphoniaError::DecodeError(reason)) => {
+                    log::warn!("Skipping corrupt packet in {}: {}", self.path.display(), reason);
+                    continue;
+                }
+                Err(e) => return Err(map_error(e, &self.path)),
+            };
+
+            let frames = decoded.frames();
+            if frames == 0 {
+                continue;
+            }
+
+            // Drop pre-roll and the part of the packet before a seek target
+            let skip = self
+                .seek_target
+                .map_or(0, |target| target.saturating_sub(first_frame) as usize);
+            if skip >= frames {
+                continue;
+            }
+            self.seek_target = None;
+
+            // Grow the conversion buffer if a packet is larger than any seen so far
+            let needed = decoded.capacity() * self.channels as usize;
+            if self.sample_buf.as_ref().is_some_and(|buf| buf.capacity() < needed) {
+                self.sample_buf = None;
+            }
+            let spec = *decoded.spec();
+            let sample_buf = self
+                .sample_buf
+                .get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, spec));
+            sample_buf.copy_interleaved_ref(decoded);
+            output.extend_from_slice(&sample_buf.samples()[skip * self.channels as usize..]);
+
+            return Ok(frames - skip);
+        }
+    }
+
+    /// Reposition so the next decoded frame is exactly `frame`
+    ///
+    /// The container seeks to the nearest packet at or before the target and
+    /// `decode_next` discards the frames in between. Targets past the end of a
+    /// stream of known length clamp to the end. Returns the new position.
+    pub fn seek(&mut self, frame: u64) -> Result<u64, VortexError> {
+        if let Some(total) = self.total_frames {
+            if frame >= total {
+                self.end_of_stream = true;
+                return Ok(total);
+            }
+        }
+
+        let ts = (frame + self.ts_offset).saturating_sub(self.seek_preroll);
+        self.format
+            .seek(SeekMode::Accurate, SeekTo::TimeStamp { ts, track_id: self.track_id })
+            .map_err(|e| map_error(e, &self.path))?;
+        self.decoder.reset();
+
+        self.seek_target = Some(frame);
+        self.end_of_stream = false;
+        Ok(frame)
+    }
+
+    /// Count the remaining frames by walking packets without decoding them
+    ///
+    /// Used for streams (e.g. MP3 without a Xing header) that do not declare
+    /// their length. Consumes the decoder.
+    pub fn count_frames(mut self) -> Result<u64, VortexError> {
+        let mut frames = 0u64;
+        loop {
+            match self.format.next_packet() {
+                Ok(packet) if packet.track_id() == self.track_id => {
+                    let trim = u64::from(packet.trim_start) + u64::from(packet.trim_end);
+                    frames += packet.dur.saturating_sub(trim);
+                }
+                Ok(_) => {}
+                Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    return Ok(frames);
+                }
+                Err(SymphoniaError::ResetRequired) => return Ok(frames),
+                Err(e) => return Err(map_error(e, &self.path)),
+            }
+        }
+    }
+}
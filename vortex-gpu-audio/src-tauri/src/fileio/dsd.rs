@@ -0,0 +1,862 @@
+//! DSF and DSDIFF (DFF) container readers
+//!
+//! Both containers carry raw 1-bit DSD. DSF stores each channel in fixed-size
+//! blocks, least significant bit first; DSDIFF interleaves the channels byte by
+//! byte, most significant bit first. `DsdReader` parses either header and
+//! `DsdBitstream` normalizes the sound data into per-channel, LSB-first byte
+//! blocks, the layout `DsdProcessor::process` consumes.
+//!
+//! `DsfWriter` goes the other way, and `render_dsf` converts a PCM stream to a
+//! DSF file through `SigmaDeltaModulator`.
+
+use super::stream::StreamingDecoder;
+use crate::audio::dsp::{DsdRate, SigmaDeltaModulator};
+use crate::error::{ConfigError, FileIoError, VortexError};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes per channel in each block yielded from byte-interleaved DSDIFF data
+pub const DFF_BLOCK_BYTES: usize = 4096;
+
+/// Bytes per channel in each block of a written DSF file
+pub const DSF_BLOCK_BYTES: usize = 4096;
+
+/// DSF channel type for 1 to 6 channels: mono, stereo, 3 channels, quad, 5 channels, 5.1
+const DSF_CHANNEL_TYPES: [u32; 6] = [1, 2, 3, 4, 6, 7];
+
+/// DSD header, fmt and data chunk header sizes
+const DSF_HEADER_BYTES: u64 = 28 + 52 + 12;
+
+/// DSD container formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DsdContainer {
+    Dsf,
+    Dff,
+}
+
+/// Stream parameters shared by both containers
+#[derive(Debug, Clone)]
+pub struct DsdInfo {
+    pub container: DsdContainer,
+    pub channels: u16,
+    pub dsd_rate: DsdRate,
+    /// 1-bit samples per channel
+    pub sample_count: u64,
+    /// Raw ID3v2 tag, if the file carries one
+    pub id3_tag: Option<Vec<u8>>,
+}
+
+impl DsdInfo {
+    /// Duration in seconds
+    pub fn duration_secs(&self) -> f64 {
+        self.sample_count as f64 / self.dsd_rate.sample_rate() as f64
+    }
+}
+
+/// How channels are laid out in the sound data
+#[derive(Debug, Clone, Copy)]
+enum Layout {
+    /// `block_size` bytes of channel 0, then channel 1, ... (DSF)
+    Block { block_size: usize },
+    /// One byte per channel in turn (DSDIFF)
+    Byte,
+}
+
+/// Reader for a DSF or DSDIFF file
+pub struct DsdReader {
+    path: PathBuf,
+    reader: BufReader<File>,
+    info: DsdInfo,
+    layout: Layout,
+    msb_first: bool,
+    data_offset: u64,
+}
+
+impl DsdReader {
+    /// Open a DSD file, detecting the container from its magic bytes
+    pub fn open(path: &Path) -> Result<Self, VortexError> {
+        let file = File::open(path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => FileIoError::FileNotFound {
+                path: path.display().to_string(),
+            }.into(),
+            _ => VortexError::from(FileIoError::Io(e)),
+        })?;
+        let mut parser = Parser {
+            path,
+            reader: BufReader::new(file),
+        };
+
+        let (info, layout, msb_first, data_offset) = match &parser.read_array::<4>()? {
+            b"DSD " => parser.parse_dsf()?,
+            b"FRM8" => parser.parse_dff()?,
+            _ => {
+                return Err(FileIoError::UnsupportedFormat {
+                    format: "not a DSF or DSDIFF file".to_string(),
+                    path: path.display().to_string(),
+                }.into())
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            reader: parser.reader,
+            info,
+            layout,
+            msb_first,
+            data_offset,
+        })
+    }
+
+    /// Stream parameters and metadata
+    pub fn info(&self) -> &DsdInfo {
+        &self.info
+    }
+
+    /// Number of channels
+    pub fn channels(&self) -> u16 {
+        self.info.channels
+    }
+
+    /// DSD rate of the bitstream
+    pub fn dsd_rate(&self) -> DsdRate {
+        self.info.dsd_rate
+    }
+
+    /// Iterate over the sound data from the start
+    pub fn into_bitstream(mut self) -> Result<DsdBitstream, VortexError> {
+        self.reader
+            .seek(SeekFrom::Start(self.data_offset))
+            .map_err(FileIoError::Io)?;
+
+        Ok(DsdBitstream {
+            path: self.path,
+            reader: self.reader,
+            channels: self.info.channels as usize,
+            layout: self.layout,
+            msb_first: self.msb_first,
+            remaining: self.info.sample_count.div_ceil(8),
+            raw: Vec::new(),
+        })
+    }
+}
+
+/// Iterator over DSD sound data
+///
+/// Each item holds one block per channel: equal-length byte vectors with the
+/// oldest sample in the least significant bit. DSF yields its native blocks,
+/// DSDIFF yields `DFF_BLOCK_BYTES` per channel; the last block may be shorter.
+pub struct DsdBitstream {
+    path: PathBuf,
+    reader: BufReader<File>,
+    channels: usize,
+    layout: Layout,
+    msb_first: bool,
+    // Bytes per channel not yet yielded
+    remaining: u64,
+    raw: Vec<u8>,
+}
+
+impl DsdBitstream {
+    fn read_block(&mut self) -> Result<Vec<Vec<u8>>, VortexError> {
+        let (per_channel, raw_len) = match self.layout {
+            // DSF pads the final block of every channel to the full block size
+            Layout::Block { block_size } => {
+                (self.remaining.min(block_size as u64) as usize, block_size * self.channels)
+            }
+            Layout::Byte => {
+                let bytes = self.remaining.min(DFF_BLOCK_BYTES as u64) as usize;
+                (bytes, bytes * self.channels)
+            }
+        };
+
+        self.raw.resize(raw_len, 0);
+        self.reader.read_exact(&mut self.raw).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => FileIoError::FileCorrupted {
+                path: self.path.display().to_string(),
+                reason: "sound data is truncated".to_string(),
+            }.into(),
+            _ => VortexError::from(FileIoError::Io(e)),
+        })?;
+
+        let mut block = Vec::with_capacity(self.channels);
+        for ch in 0..self.channels {
+            let mut bytes: Vec<u8> = match self.layout {
+                Layout::Block { block_size } => {
+                    self.raw[ch * block_size..ch * block_size + per_channel].to_vec()
+                }
+                Layout::Byte => self.raw[ch..].iter().step_by(self.channels).copied().collect(),
+            };
+            if self.msb_first {
+                bytes.iter_mut().for_each(|b| *b = b.reverse_bits());
+            }
+            block.push(bytes);
+        }
+
+        self.remaining -= per_channel as u64;
+        Ok(block)
+    }
+}
+
+impl Iterator for DsdBitstream {
+    type Item = Result<Vec<Vec<u8>>, VortexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let block = self.read_block();
+        if block.is_err() {
+            self.remaining = 0;
+        }
+        Some(block)
+    }
+}
+
+/// Writer for LSB-first DSF files
+///
+/// Channel bytes are buffered into `DSF_BLOCK_BYTES` blocks as they arrive;
+/// `finish` pads the final block and fills in the header sizes.
+pub struct DsfWriter {
+    writer: BufWriter<File>,
+    dsd_rate: DsdRate,
+    // Bytes per channel not yet written as a full block
+    pending: Vec<Vec<u8>>,
+    bytes_per_channel: u64,
+    blocks_written: u64,
+}
+
+impl DsfWriter {
+    /// Create `path` for a DSD stream of 1 to 6 channels
+    pub fn create(path: &Path, dsd_rate: DsdRate, channels: u16) -> Result<Self, VortexError> {
+        if !(1..=DSF_CHANNEL_TYPES.len()).contains(&(channels as usize)) {
+            return Err(ConfigError::InvalidValue {
+                key: "channels".to_string(),
+                reason: format!("DSF holds 1 to 6 channels, got {}", channels),
+            }.into());
+        }
+
+        let file = File::create(path).map_err(FileIoError::Io)?;
+        let mut writer = Self {
+            writer: BufWriter::new(file),
+            dsd_rate,
+            pending: vec![Vec::with_capacity(DSF_BLOCK_BYTES); channels as usize],
+            bytes_per_channel: 0,
+            blocks_written: 0,
+        };
+        // Placeholder sizes until `finish`
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    /// Append one equal-length block of LSB-first bytes per channel
+    pub fn write<B: AsRef<[u8]>>(&mut self, channels: &[B]) -> Result<(), VortexError> {
+        let bytes = channels.first().map_or(0, |ch| ch.as_ref().len());
+        if channels.len() != self.pending.len() || channels.iter().any(|ch| ch.as_ref().len() != bytes) {
+            return Err(ConfigError::InvalidValue {
+                key: "channels".to_string(),
+                reason: format!("expected {} equal-length channels", self.pending.len()),
+            }.into());
+        }
+
+        let mut offset = 0;
+        while offset < bytes {
+            let count = (DSF_BLOCK_BYTES - self.pending[0].len()).min(bytes - offset);
+            for (pending, ch) in self.pending.iter_mut().zip(channels) {
+                pending.extend_from_slice(&ch.as_ref()[offset..offset + count]);
+            }
+            offset += count;
+            if self.pending[0].len() == DSF_BLOCK_BYTES {
+                self.flush_block()?;
+            }
+        }
+
+        self.bytes_per_channel += bytes as u64;
+        Ok(())
+    }
+
+    /// Pad the final block, complete the header and close the file
+    pub fn finish(mut self) -> Result<DsdInfo, VortexError> {
+        if !self.pending[0].is_empty() {
+            // The final block of each channel is zero padded
+            self.pending.iter_mut().for_each(|p| p.resize(DSF_BLOCK_BYTES, 0));
+            self.flush_block()?;
+        }
+
+        self.writer.seek(SeekFrom::Start(0)).map_err(FileIoError::Io)?;
+        self.write_header()?;
+        self.writer.flush().map_err(FileIoError::Io)?;
+
+        Ok(DsdInfo {
+            container: DsdContainer::Dsf,
+            channels: self.pending.len() as u16,
+            dsd_rate: self.dsd_rate,
+            sample_count: self.bytes_per_channel * 8,
+            id3_tag: None,
+        })
+    }
+
+    fn flush_block(&mut self) -> Result<(), VortexError> {
+        for pending in &mut self.pending {
+            self.writer.write_all(pending).map_err(FileIoError::Io)?;
+            pending.clear();
+        }
+        self.blocks_written += 1;
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> Result<(), VortexError> {
+        let channels = self.pending.len();
+        let data_bytes = self.blocks_written * (DSF_BLOCK_BYTES * channels) as u64;
+
+        let mut header = Vec::with_capacity(DSF_HEADER_BYTES as usize);
+        header.extend_from_slice(b"DSD ");
+        header.extend_from_slice(&28u64.to_le_bytes());
+        header.extend_from_slice(&(DSF_HEADER_BYTES + data_bytes).to_le_bytes());
+        header.extend_from_slice(&0u64.to_le_bytes()); // No metadata
+
+        header.extend_from_slice(b"fmt ");
+        header.extend_from_slice(&52u64.to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes()); // Format version
+        header.extend_from_slice(&0u32.to_le_bytes()); // DSD raw
+        header.extend_from_slice(&DSF_CHANNEL_TYPES[channels - 1].to_le_bytes());
+        header.extend_from_slice(&(channels as u32).to_le_bytes());
+        header.extend_from_slice(&self.dsd_rate.sample_rate().to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes()); // LSB first
+        header.extend_from_slice(&(self.bytes_per_channel * 8).to_le_bytes());
+        header.extend_from_slice(&(DSF_BLOCK_BYTES as u32).to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes()); // Reserved
+
+        header.extend_from_slice(b"data");
+        header.extend_from_slice(&(12 + data_bytes).to_le_bytes());
+        self.writer.write_all(&header).map_err(FileIoError::Io)?;
+        Ok(())
+    }
+}
+
+/// Convert a PCM stream to a DSF file with a sigma-delta modulator of `order`
+///
+/// The stream rate must be a power-of-two fraction (1/8 or less) of the DSD
+/// rate, as `SigmaDeltaModulator::new` requires.
+pub fn render_dsf(
+    source: &mut dyn StreamingDecoder,
+    path: &Path,
+    dsd_rate: DsdRate,
+    order: usize,
+) -> Result<DsdInfo, VortexError> {
+    let channels = source.channels();
+    let mut modulator = SigmaDeltaModulator::new(source.sample_rate(), dsd_rate, order, channels)?;
+    let mut writer = DsfWriter::create(path, dsd_rate, channels)?;
+
+    let mut block = vec![0.0f32; source.block_frames() * channels as usize];
+    let mut bytes = vec![Vec::new(); channels as usize];
+    loop {
+        let frames = source.next_block(&mut block)?;
+        if frames == 0 {
+            break;
+        }
+        bytes.iter_mut().for_each(Vec::clear);
+        modulator.process(&block[..frames * channels as usize], &mut bytes)?;
+        writer.write(&bytes)?;
+    }
+
+    writer.finish()
+}
+
+/// Header parsing state for both containers
+struct Parser<'a> {
+    path: &'a Path,
+    reader: BufReader<File>,
+}
+
+impl Parser<'_> {
+    fn corrupted(&self, reason: impl Into<String>) -> VortexError {
+        FileIoError::FileCorrupted {
+            path: self.path.display().to_string(),
+            reason: reason.into(),
+        }.into()
+    }
+
+    fn unsupported(&self, format: impl Into<String>) -> VortexError {
+        FileIoError::UnsupportedFormat {
+            format: format.into(),
+            path: self.path.display().to_string(),
+        }.into()
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], VortexError> {
+        let mut bytes = [0u8; N];
+        self.reader.read_exact(&mut bytes).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => self.corrupted("header is truncated"),
+            _ => FileIoError::Io(e).into(),
+        })?;
+        Ok(bytes)
+    }
+
+    fn read_u16_be(&mut self) -> Result<u16, VortexError> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_u32_le(&mut self) -> Result<u32, VortexError> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u32_be(&mut self) -> Result<u32, VortexError> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_u64_le(&mut self) -> Result<u64, VortexError> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    fn read_u64_be(&mut self) -> Result<u64, VortexError> {
+        Ok(u64::from_be_bytes(self.read_array()?))
+    }
+
+    fn position(&mut self) -> Result<u64, VortexError> {
+        Ok(self.reader.stream_position().map_err(FileIoError::Io)?)
+    }
+
+    fn seek_to(&mut self, offset: u64) -> Result<(), VortexError> {
+        self.reader.seek(SeekFrom::Start(offset)).map_err(FileIoError::Io)?;
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, len: u64) -> Result<Vec<u8>, VortexError> {
+        let mut bytes = Vec::new();
+        (&mut self.reader)
+            .take(len)
+            .read_to_end(&mut bytes)
+            .map_err(FileIoError::Io)?;
+        if (bytes.len() as u64) < len {
+            return Err(self.corrupted("chunk is truncated"));
+        }
+        Ok(bytes)
+    }
+
+    /// `start + len` for offsets and sizes read from the file
+    fn offset_after(&self, start: u64, len: u64) -> Result<u64, VortexError> {
+        start.checked_add(len).ok_or_else(|| self.corrupted("chunk size overflows"))
+    }
+
+    fn dsd_rate(&self, sample_rate: u32) -> Result<DsdRate, VortexError> {
+        DsdRate::from_sample_rate(sample_rate)
+            .ok_or_else(|| self.unsupported(format!("DSD sample rate {} Hz", sample_rate)))
+    }
+
+    /// DSF: "DSD " header, "fmt " chunk, "data" chunk, optional trailing ID3v2 tag
+    fn parse_dsf(&mut self) -> Result<(DsdInfo, Layout, bool, u64), VortexError> {
+        let _header_size = self.read_u64_le()?;
+        let _file_size = self.read_u64_le()?;
+        let metadata_offset = self.read_u64_le()?;
+
+        if &self.read_array::<4>()? != b"fmt " {
+            return Err(self.corrupted("missing fmt chunk"));
+        }
+        let fmt_size = self.read_u64_le()?;
+        let fmt_start = self.position()?;
+        let _version = self.read_u32_le()?;
+        if self.read_u32_le()? != 0 {
+            return Err(self.unsupported("DSF format other than raw DSD"));
+        }
+        let _channel_type = self.read_u32_le()?;
+        let channels = self.read_u32_le()?;
+        let sample_rate = self.read_u32_le()?;
+        let bits_per_sample = self.read_u32_le()?;
+        let sample_count = self.read_u64_le()?;
+        let block_size = self.read_u32_le()? as usize;
+
+        if !(1..=6).contains(&channels) {
+            return Err(self.corrupted(format!("invalid channel count {}", channels)));
+        }
+        if block_size == 0 {
+            return Err(self.corrupted("zero block size"));
+        }
+        // 1 = LSB first, 8 = MSB first
+        let msb_first = match bits_per_sample {
+            1 => false,
+            8 => true,
+            other => return Err(self.corrupted(format!("invalid bits per sample {}", other))),
+        };
+        let dsd_rate = self.dsd_rate(sample_rate)?;
+
+        // fmt size counts its own 12-byte chunk header
+        self.seek_to(self.offset_after(fmt_start, fmt_size.saturating_sub(12))?)?;
+        if &self.read_array::<4>()? != b"data" {
+            return Err(self.corrupted("missing data chunk"));
+        }
+        let _data_size = self.read_u64_le()?;
+        let data_offset = self.position()?;
+
+        let id3_tag = if metadata_offset != 0 {
+            self.seek_to(metadata_offset)?;
+            Some(self.read_id3_tag()?)
+        } else {
+            None
+        };
+
+        let info = DsdInfo {
+            container: DsdContainer::Dsf,
+            channels: channels as u16,
+            dsd_rate,
+            sample_count,
+            id3_tag,
+        };
+        Ok((info, Layout::Block { block_size }, msb_first, data_offset))
+    }
+
+    /// ID3v2 tag: 10-byte header with a syncsafe body size
+    fn read_id3_tag(&mut self) -> Result<Vec<u8>, VortexError> {
+        let header = self.read_array::<10>()?;
+        if &header[..3] != b"ID3" {
+            return Err(self.corrupted("metadata pointer does not reference an ID3 tag"));
+        }
+        let body_size = header[6..10]
+            .iter()
+            .fold(0u64, |size, &b| (size << 7) | u64::from(b & 0x7F));
+
+        let mut tag = header.to_vec();
+        tag.extend(self.read_bytes(body_size)?);
+        Ok(tag)
+    }
+
+    /// DSDIFF: FRM8 form of big-endian chunks, with stream properties under PROP/SND
+    fn parse_dff(&mut self) -> Result<(DsdInfo, Layout, bool, u64), VortexError> {
+        let form_size = self.read_u64_be()?;
+        if &self.read_array::<4>()? != b"DSD " {
+            return Err(self.corrupted("FRM8 form type is not DSD"));
+        }
+        let form_end = self.offset_after(12, form_size)?;
+
+        let mut sample_rate = None;
+        let mut channels = None;
+        let mut data = None;
+        let mut id3_tag = None;
+
+        let mut offset = self.position()?;
+        while form_end.saturating_sub(offset) >= 12 {
+            self.seek_to(offset)?;
+            let id = self.read_array::<4>()?;
+            let size = self.read_u64_be()?;
+            let body = offset + 12;
+            let end = self.offset_after(body, size)?;
+
+            match &id {
+                b"PROP" => {
+                    if &self.read_array::<4>()? != b"SND " {
+                        return Err(self.corrupted("PROP chunk is not of type SND"));
+                    }
+                    self.parse_dff_properties(body.saturating_add(4), end, &mut sample_rate, &mut channels)?;
+                }
+                b"DSD " => data = Some((body, size)),
+                b"DST " => return Err(self.unsupported("DST-compressed DSDIFF")),
+                b"ID3 " => id3_tag = Some(self.read_bytes(size)?),
+                _ => {}
+            }
+
+            // Chunks are padded to an even length
+            offset = self.offset_after(end, size & 1)?;
+        }
+
+        let sample_rate = sample_rate.ok_or_else(|| self.corrupted("missing FS chunk"))?;
+        let channels: u16 = channels.ok_or_else(|| self.corrupted("missing CHNL chunk"))?;
+        let (data_offset, data_size) = data.ok_or_else(|| self.corrupted("missing DSD sound data chunk"))?;
+        if channels == 0 {
+            return Err(self.corrupted("zero channels"));
+        }
+
+        let info = DsdInfo {
+            container: DsdContainer::Dff,
+            channels,
+            dsd_rate: self.dsd_rate(sample_rate)?,
+            sample_count: data_size / u64::from(channels) * 8,
+            id3_tag,
+        };
+        Ok((info, Layout::Byte, true, data_offset))
+    }
+
+    /// Local chunks of the PROP/SND chunk: FS, CHNL and CMPR
+    fn parse_dff_properties(
+        &mut self,
+        start: u64,
+        end: u64,
+        sample_rate: &mut Option<u32>,
+        channels: &mut Option<u16>,
+    ) -> Result<(), VortexError> {
+        let mut offset = start;
+        while end.saturating_sub(offset) >= 12 {
+            self.seek_to(offset)?;
+            let id = self.read_array::<4>()?;
+            let size = self.read_u64_be()?;
+
+            match &id {
+                b"FS  " => *sample_rate = Some(self.read_u32_be()?),
+                b"CHNL" => *channels = Some(self.read_u16_be()?),
+                b"CMPR" => {
+                    let compression = self.read_array::<4>()?;
+                    if &compression != b"DSD " {
+                        return Err(self.unsupported(format!(
+                            "DSDIFF compression {}",
+                            String::from_utf8_lossy(&compression).trim_end()
+                        )));
+                    }
+                }
+                _ => {}
+            }
+
+            offset = self.offset_after(offset + 12, size)?;
+            offset = self.offset_after(offset, size & 1)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::dsp::DsdProcessor;
+    use crate::fileio::test_fixtures::{test_tone, write_dff, write_dsf, write_wav, WavEncoding};
+    use crate::fileio::FileStream;
+    use tempfile::TempDir;
+
+    /// Per-channel pseudo-random LSB-first bitstreams
+    fn test_bitstream(channels: usize, bytes: usize) -> Vec<Vec<u8>> {
+        let mut state = 0x9E37_79B9u32;
+        (0..channels)
+            .map(|_| {
+                (0..bytes)
+                    .map(|_| {
+                        state ^= state << 13;
+                        state ^= state >> 17;
+                        state ^= state << 5;
+                        state as u8
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn collect_channels(reader: DsdReader) -> Vec<Vec<u8>> {
+        let channels = reader.channels() as usize;
+        let mut output = vec![Vec::new(); channels];
+        for block in reader.into_bitstream().unwrap() {
+            let block = block.unwrap();
+            assert_eq!(block.len(), channels);
+            assert!(block.iter().all(|ch| ch.len() == block[0].len()));
+            for (out, ch) in output.iter_mut().zip(block) {
+                out.extend(ch);
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn test_dsf_info_and_bitstream() {
+        let dir = TempDir::new().unwrap();
+        let bits = test_bitstream(2, 10_000);
+        let id3 = b"ID3\x04\x00\x00\x00\x00\x00\x04TEST";
+        let path = write_dsf(&dir.path().join("test.dsf"), DsdRate::Dsd128, &bits, false, Some(id3));
+
+        let reader = DsdReader::open(&path).unwrap();
+        let info = reader.info().clone();
+        assert_eq!(info.container, DsdContainer::Dsf);
+        assert_eq!(reader.channels(), 2);
+        assert_eq!(reader.dsd_rate(), DsdRate::Dsd128);
+        assert_eq!(info.sample_count, 80_000);
+        assert_eq!(info.id3_tag.as_deref(), Some(&id3[..]));
+        assert!((info.duration_secs() - 80_000.0 / 5_644_800.0).abs() < 1e-12);
+
+        assert_eq!(collect_channels(reader), bits);
+    }
+
+    #[test]
+    fn test_dsf_msb_first_is_normalized() {
+        let dir = TempDir::new().unwrap();
+        let bits = test_bitstream(1, 5000);
+        let path = write_dsf(&dir.path().join("msb.dsf"), DsdRate::Dsd64, &bits, true, None);
+
+        let reader = DsdReader::open(&path).unwrap();
+        assert!(reader.info().id3_tag.is_none());
+        assert_eq!(collect_channels(reader), bits);
+    }
+
+    #[test]
+    fn test_dff_info_and_bitstream() {
+        let dir = TempDir::new().unwrap();
+        let bits = test_bitstream(6, 9_001);
+        let id3 = b"ID3\x03\x00\x00\x00\x00\x00\x03ABC";
+        let path = write_dff(&dir.path().join("test.dff"), DsdRate::Dsd256, &bits, b"DSD ", Some(id3));
+
+        let reader = DsdReader::open(&path).unwrap();
+        assert_eq!(reader.info().container, DsdContainer::Dff);
+        assert_eq!(reader.channels(), 6);
+        assert_eq!(reader.dsd_rate(), DsdRate::Dsd256);
+        assert_eq!(reader.info().sample_count, 9_001 * 8);
+        assert_eq!(reader.info().id3_tag.as_deref(), Some(&id3[..]));
+
+        assert_eq!(collect_channels(reader), bits);
+    }
+
+    #[test]
+    fn test_containers_agree() {
+        let dir = TempDir::new().unwrap();
+        let bits = test_bitstream(2, 3 * DFF_BLOCK_BYTES + 17);
+        let dsf = write_dsf(&dir.path().join("a.dsf"), DsdRate::Dsd64, &bits, false, None);
+        let dff = write_dff(&dir.path().join("a.dff"), DsdRate::Dsd64, &bits, b"DSD ", None);
+
+        assert_eq!(
+            collect_channels(DsdReader::open(&dsf).unwrap()),
+            collect_channels(DsdReader::open(&dff).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_bitstream_feeds_dsd_processor() {
+        let dir = TempDir::new().unwrap();
+        // Left channel all ones, right channel all zeros
+        let bits = vec![vec![0xFF; 8192], vec![0x00; 8192]];
+        let path = write_dff(&dir.path().join("dc.dff"), DsdRate::Dsd64, &bits, b"DSD ", None);
+
+        let reader = DsdReader::open(&path).unwrap();
+        let mut processor = DsdProcessor::new(reader.dsd_rate(), 44100, reader.channels()).unwrap();
+
+        let mut pcm = vec![0.0f32; DFF_BLOCK_BYTES * 8 / 64 * 2];
+        for block in reader.into_bitstream().unwrap() {
+            let frames = processor.process(&block.unwrap(), &mut pcm).unwrap();
+            assert!(frames > 0);
+            for frame in pcm[frames..2 * frames].chunks(2) {
+                assert!((frame[0] - 1.0).abs() < 1e-3 && (frame[1] + 1.0).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_invalid_files() {
+        let dir = TempDir::new().unwrap();
+        let bits = test_bitstream(2, 100);
+
+        let dst = write_dff(&dir.path().join("dst.dff"), DsdRate::Dsd64, &bits, b"DST ", None);
+        assert!(DsdReader::open(&dst).is_err());
+
+        let wav = dir.path().join("not_dsd.dsf");
+        std::fs::write(&wav, b"RIFF\x00\x00\x00\x00WAVE").unwrap();
+        assert!(DsdReader::open(&wav).is_err());
+
+        // Header intact but sound data cut short
+        let dsf = write_dsf(&dir.path().join("cut.dsf"), DsdRate::Dsd64, &bits, false, None);
+        let bytes = std::fs::read(&dsf).unwrap();
+        std::fs::write(&dsf, &bytes[..bytes.len() - 4096]).unwrap();
+        let mut blocks = DsdReader::open(&dsf).unwrap().into_bitstream().unwrap();
+        assert!(blocks.next().unwrap().is_err());
+        assert!(blocks.next().is_none());
+
+        assert!(DsdReader::open(&dir.path().join("missing.dsf")).is_err());
+    }
+
+    #[test]
+    fn test_rejects_overflowing_chunk_sizes() {
+        let dir = TempDir::new().unwrap();
+        let overflows = |path: &Path| match DsdReader::open(path) {
+            Err(e) => e.to_string().contains("chunk size overflows"),
+            Ok(_) => false,
+        };
+
+        // FRM8 form size
+        let form = dir.path().join("form.dff");
+        let mut bytes = b"FRM8".to_vec();
+        bytes.extend_from_slice(&u64::MAX.to_be_bytes());
+        bytes.extend_from_slice(b"DSD ");
+        std::fs::write(&form, &bytes).unwrap();
+        assert!(overflows(&form));
+
+        // Chunk inside a form, and a local chunk inside PROP
+        for (id, size) in [(b"COMT", u64::MAX - 8), (b"PROP", 84)] {
+            let chunk = dir.path().join("chunk.dff");
+            let mut bytes = b"FRM8".to_vec();
+            bytes.extend_from_slice(&100u64.to_be_bytes());
+            bytes.extend_from_slice(b"DSD ");
+            bytes.extend_from_slice(id);
+            bytes.extend_from_slice(&size.to_be_bytes());
+            bytes.extend_from_slice(b"SND FS  ");
+            bytes.extend_from_slice(&(u64::MAX - 40).to_be_bytes());
+            bytes.resize(112, 0);
+            std::fs::write(&chunk, &bytes).unwrap();
+            assert!(overflows(&chunk), "{}", String::from_utf8_lossy(id));
+        }
+
+        // DSF fmt chunk size
+        let bits = test_bitstream(2, 100);
+        let dsf = write_dsf(&dir.path().join("fmt.dsf"), DsdRate::Dsd64, &bits, false, None);
+        let mut bytes = std::fs::read(&dsf).unwrap();
+        bytes[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+        std::fs::write(&dsf, &bytes).unwrap();
+        assert!(overflows(&dsf));
+    }
+
+    #[test]
+    fn test_dsf_writer_matches_reference_layout() {
+        let dir = TempDir::new().unwrap();
+        let bits = test_bitstream(2, 2 * DSF_BLOCK_BYTES + 1001);
+
+        let path = dir.path().join("written.dsf");
+        let mut writer = DsfWriter::create(&path, DsdRate::Dsd128, 2).unwrap();
+        for chunk in (0..bits[0].len()).step_by(3000) {
+            let end = (chunk + 3000).min(bits[0].len());
+            writer.write(&[&bits[0][chunk..end], &bits[1][chunk..end]]).unwrap();
+        }
+        let info = writer.finish().unwrap();
+        assert_eq!(info.sample_count, bits[0].len() as u64 * 8);
+
+        let reference = write_dsf(&dir.path().join("reference.dsf"), DsdRate::Dsd128, &bits, false, None);
+        assert_eq!(std::fs::read(&path).unwrap(), std::fs::read(&reference).unwrap());
+
+        let reader = DsdReader::open(&path).unwrap();
+        assert_eq!(reader.info().sample_count, info.sample_count);
+        assert_eq!(collect_channels(reader), bits);
+    }
+
+    #[test]
+    fn test_dsf_writer_rejects_bad_input() {
+        let dir = TempDir::new().unwrap();
+        assert!(DsfWriter::create(&dir.path().join("a.dsf"), DsdRate::Dsd64, 0).is_err());
+        assert!(DsfWriter::create(&dir.path().join("b.dsf"), DsdRate::Dsd64, 7).is_err());
+
+        let mut writer = DsfWriter::create(&dir.path().join("c.dsf"), DsdRate::Dsd64, 2).unwrap();
+        assert!(writer.write(&[vec![0u8; 4]]).is_err());
+        assert!(writer.write(&[vec![0u8; 4], vec![0u8; 3]]).is_err());
+    }
+
+    #[test]
+    fn test_render_dsf_from_pcm() {
+        let dir = TempDir::new().unwrap();
+        let tone = test_tone(22_050, 2, 44100);
+        let wav = write_wav(&dir.path().join("tone.wav"), 44100, 2, WavEncoding::Float32, &tone);
+        let dsf = dir.path().join("tone.dsf");
+
+        let mut source = FileStream::open(&wav, 1000).unwrap();
+        let info = render_dsf(&mut source, &dsf, DsdRate::Dsd64, 5).unwrap();
+        assert_eq!(info.sample_count, 22_050 * 64);
+        assert_eq!(info.channels, 2);
+
+        // Decoding the file recovers both tones at the DSD reference level
+        let reader = DsdReader::open(&dsf).unwrap();
+        assert_eq!(reader.info().sample_count, info.sample_count);
+        let channels = collect_channels(reader);
+        for (ch, bytes) in channels.iter().enumerate() {
+            let mut processor = DsdProcessor::new(DsdRate::Dsd64, 44100, 1).unwrap();
+            let mut pcm = vec![0.0f32; processor.output_len(bytes.len())];
+            let frames = processor.process(&[bytes], &mut pcm).unwrap();
+
+            let frequency = 440.0 * (ch + 1) as f64;
+            let (mut re, mut im) = (0.0, 0.0);
+            for (n, &x) in pcm[4410..frames].iter().enumerate() {
+                let phase = 2.0 * std::f64::consts::PI * frequency * n as f64 / 44100.0;
+                re += x as f64 * phase.cos();
+                im += x as f64 * phase.sin();
+            }
+            let amplitude = 2.0 * re.hypot(im) / (frames - 4410) as f64;
+            assert!((amplitude - 0.25).abs() < 0.005, "channel {}: amplitude {}", ch, amplitude);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use crate::error::{FileIoError, VortexError};
 use std::path::Path;
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::io::Read;
 
 /// Supported audio formats
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -20,18 +20,22 @@ pub enum AudioFormat {
     Unknown,
 }
 
+/// Bytes read from the start of a file for magic number detection
+const MAGIC_PROBE_BYTES: usize = 36;
+
 /// Format detector using magic numbers
 pub struct FormatDetector;
 
 impl FormatDetector {
     /// Detect audio format from file
     pub fn detect_format(path: &Path) -> Result<AudioFormat, VortexError> {
-        let mut file = File::open(path)
-            .map_err(|e| FileIoError::ReadError(e.to_string()))?;
+        let file = File::open(path).map_err(FileIoError::Io)?;
         
-        let mut magic_bytes = [0u8; 12];
-        file.read_exact(&mut magic_bytes)
-            .map_err(|e| FileIoError::ReadError(e.to_string()))?;
+        // Enough for the first Ogg page header plus the codec signature
+        let mut magic_bytes = Vec::with_capacity(MAGIC_PROBE_BYTES);
+        file.take(MAGIC_PROBE_BYTES as u64)
+            .read_to_end(&mut magic_bytes)
+            .map_err(FileIoError::Io)?;
         
         // Check magic numbers
         let format = Self::detect_by_magic(&magic_bytes);
@@ -64,8 +68,11 @@ impl FormatDetector {
             return AudioFormat::Mp3;
         }
         
-        // OGG: "OggS"
+        // OGG: "OggS", Opus when the first packet is "OpusHead"
         if bytes.len() >= 4 && &bytes[0..4] == b"OggS" {
+            if bytes.len() >= 36 && &bytes[28..36] == b"OpusHead" {
+                return AudioFormat::Opus;
+            }
             return AudioFormat::Ogg;
         }
         
@@ -127,6 +134,16 @@ mod tests {
         assert_eq!(FormatDetector::detect_by_magic(magic), AudioFormat::Flac);
     }
     
+    #[test]
+    fn test_ogg_opus_magic() {
+        let mut page = b"OggS\x00\x02".to_vec();
+        page.resize(28, 0);
+        assert_eq!(FormatDetector::detect_by_magic(&page), AudioFormat::Ogg);
+        
+        page.extend_from_slice(b"OpusHead");
+        assert_eq!(FormatDetector::detect_by_magic(&page), AudioFormat::Opus);
+    }
+    
     #[test]
     fn test_dsd_dsf_magic() {
         let magic = b"DSD \x00\x00\x00\x00\x00\x00\x00\x00";
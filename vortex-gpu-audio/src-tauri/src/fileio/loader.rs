@@ -1,3 +1,5 @@
+use super::decoder::AudioDecoder;
+use super::stream::FileStream;
 use crate::error::{FileIoError, VortexError};
 use std::path::{Path, PathBuf};
 
@@ -8,6 +10,7 @@ pub struct AudioFileInfo {
     pub format: super::AudioFormat,
     pub sample_rate: u32,
     pub channels: u16,
+    /// Source bit depth; 0 for lossy codecs (MP3, Vorbis, Opus)
     pub bit_depth: u8,
     pub duration_secs: f64,
     pub size_bytes: u64,
@@ -16,11 +19,19 @@ pub struct AudioFileInfo {
 /// Decoded audio data
 #[derive(Debug)]
 pub struct AudioData {
+    /// Interleaved samples in [-1.0, 1.0]
     pub samples: Vec<f32>,
     pub sample_rate: u32,
     pub channels: u16,
 }
 
+impl AudioData {
+    /// Number of frames (samples per channel)
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1) as usize
+    }
+}
+
 /// Audio file loader with multi-format support
 pub struct AudioFileLoader {
     supported_formats: Vec<super::AudioFormat>,
@@ -35,56 +46,58 @@ impl AudioFileLoader {
                 super::AudioFormat::Flac,
                 super::AudioFormat::Mp3,
                 super::AudioFormat::Aac,
-                super::AudioFormat::DsdDsf,
-                super::AudioFormat::DsdDff,
+                super::AudioFormat::Ogg,
+                super::AudioFormat::Opus,
                 super::AudioFormat::Alac,
-                super::AudioFormat::Ape,
             ],
         }
     }
     
-    /// Load audio file from path
+    /// Load audio file from path, decoding it to interleaved f32
     pub fn load_file(&self, path: &Path) -> Result<AudioData, VortexError> {
-        if !path.exists() {
-            return Err(FileIoError::FileNotFound(path.display().to_string()).into());
-        }
-        
-        // Detect format
-        let format = super::FormatDetector::detect_format(path)?;
+        let (_, mut decoder) = self.open_decoder(path)?;
         
-        if !self.supported_formats.contains(&format) {
-            return Err(FileIoError::UnsupportedFormat(format!("{:?}", format)).into());
+        let channels = decoder.channels();
+        let mut samples = Vec::new();
+        if let Some(frames) = decoder.total_frames() {
+            samples.reserve(frames as usize * channels as usize);
         }
         
-        // TODO: Implement actual decoding
-        // For now, return empty data
-        log::warn!("File loading not yet implemented, returning empty data");
+        while decoder.decode_next(&mut samples)? > 0 {}
         
         Ok(AudioData {
-            samples: Vec::new(),
-            sample_rate: 48000,
-            channels: 2,
+            samples,
+            sample_rate: decoder.sample_rate(),
+            channels,
         })
     }
     
+    /// Open a file for block-wise streaming instead of loading it whole
+    pub fn open_stream(&self, path: &Path, block_frames: usize) -> Result<FileStream, VortexError> {
+        let (_, decoder) = self.open_decoder(path)?;
+        FileStream::from_decoder(decoder, block_frames)
+    }
+    
     /// Get file information without loading full file
     pub fn get_file_info(&self, path: &Path) -> Result<AudioFileInfo, VortexError> {
-        if !path.exists() {
-            return Err(FileIoError::FileNotFound(path.display().to_string()).into());
-        }
+        let (format, decoder) = self.open_decoder(path)?;
+        let metadata = std::fs::metadata(path).map_err(FileIoError::Io)?;
         
-        let format = super::FormatDetector::detect_format(path)?;
-        let metadata = std::fs::metadata(path)
-            .map_err(|e| FileIoError::ReadError(e.to_string()))?;
+        let sample_rate = decoder.sample_rate();
+        let channels = decoder.channels();
+        let bit_depth = decoder.bit_depth();
+        let total_frames = match decoder.total_frames() {
+            Some(frames) => frames,
+            None => decoder.count_frames()?,
+        };
         
-        // TODO: Extract actual audio info from file
         Ok(AudioFileInfo {
             path: path.to_path_buf(),
             format,
-            sample_rate: 48000,
-            channels: 2,
-            bit_depth: 16,
-            duration_secs: 0.0,
+            sample_rate,
+            channels,
+            bit_depth,
+            duration_secs: total_frames as f64 / sample_rate as f64,
             size_bytes: metadata.len(),
         })
     }
@@ -93,6 +106,26 @@ impl AudioFileLoader {
     pub fn is_format_supported(&self, format: &super::AudioFormat) -> bool {
         self.supported_formats.contains(format)
     }
+    
+    /// Validate the file and open a decoder for it
+    fn open_decoder(&self, path: &Path) -> Result<(super::AudioFormat, AudioDecoder), VortexError> {
+        if !path.exists() {
+            return Err(FileIoError::FileNotFound {
+                path: path.display().to_string(),
+            }.into());
+        }
+        
+        let format = super::FormatDetector::detect_format(path)?;
+        
+        if !self.supported_formats.contains(&format) {
+            return Err(FileIoError::UnsupportedFormat {
+                format: format!("{:?}", format),
+                path: path.display().to_string(),
+            }.into());
+        }
+        
+        Ok((format, AudioDecoder::open(path)?))
+    }
 }
 
 impl Default for AudioFileLoader {
@@ -104,12 +137,14 @@ impl Default for AudioFileLoader {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fileio::test_fixtures::{self, WavEncoding};
+    use crate::fileio::AudioFormat;
     
     #[test]
     fn test_loader_creation() {
         let loader = AudioFileLoader::new();
-        assert!(loader.is_format_supported(&super::AudioFormat::Wav));
-        assert!(loader.is_format_supported(&super::AudioFormat::Flac));
+        assert!(loader.is_format_supported(&AudioFormat::Wav));
+        assert!(loader.is_format_supported(&AudioFormat::Flac));
     }
     
     #[test]
@@ -118,4 +153,144 @@ mod tests {
         let result = loader.load_file(Path::new("nonexistent.wav"));
         assert!(result.is_err());
     }
+    
+    #[test]
+    fn test_load_wav_encodings() {
+        let dir = tempfile::tempdir().unwrap();
+        let loader = AudioFileLoader::new();
+        let source = test_fixtures::test_tone(1000, 2, 44100);
+        
+        for encoding in [WavEncoding::Pcm16, WavEncoding::Pcm24, WavEncoding::Pcm32, WavEncoding::Float32] {
+            let path = dir.path().join(format!("{:?}.wav", encoding));
+            test_fixtures::write_wav(&path, 44100, 2, encoding, &source);
+            
+            let data = loader.load_file(&path).unwrap();
+            assert_eq!(data.sample_rate, 44100);
+            assert_eq!(data.channels, 2);
+            assert_eq!(data.frames(), 1000);
+            
+            for (&decoded, &original) in data.samples.iter().zip(&source) {
+                let expected = match encoding {
+                    WavEncoding::Float32 => original,
+                    _ => test_fixtures::dequantize(
+                        test_fixtures::quantize(original, encoding.bits()),
+                        encoding.bits(),
+                    ),
+                };
+                assert!((decoded - expected).abs() < 1e-6, "{:?}", encoding);
+            }
+            
+            let info = loader.get_file_info(&path).unwrap();
+            assert_eq!(info.format, AudioFormat::Wav);
+            assert_eq!(info.bit_depth as u32, encoding.bits());
+            assert!((info.duration_secs - 1000.0 / 44100.0).abs() < 1e-9);
+        }
+    }
+    
+    #[test]
+    fn test_load_flac() {
+        let dir = tempfile::tempdir().unwrap();
+        let loader = AudioFileLoader::new();
+        // Spans several blocks with a short final block
+        let source = test_fixtures::test_tone(10_000, 3, 96000);
+        
+        for bits in [16, 24] {
+            let path = dir.path().join(format!("tone{}.flac", bits));
+            test_fixtures::write_flac(&path, 96000, 3, bits, &source);
+            
+            let data = loader.load_file(&path).unwrap();
+            assert_eq!(data.sample_rate, 96000);
+            assert_eq!(data.channels, 3);
+            assert_eq!(data.frames(), 10_000);
+            for (&decoded, &original) in data.samples.iter().zip(&source) {
+                let expected = test_fixtures::dequantize(test_fixtures::quantize(original, bits), bits);
+                assert!((decoded - expected).abs() < 1e-7);
+            }
+            
+            let info = loader.get_file_info(&path).unwrap();
+            assert_eq!(info.format, AudioFormat::Flac);
+            assert_eq!(info.bit_depth as u32, bits);
+            assert!((info.duration_secs - 10_000.0 / 96000.0).abs() < 1e-9);
+        }
+    }
+    
+    #[test]
+    fn test_load_mp3() {
+        let dir = tempfile::tempdir().unwrap();
+        let loader = AudioFileLoader::new();
+        let path = test_fixtures::write_silent_mp3(&dir.path().join("silence.mp3"), 40);
+        
+        let data = loader.load_file(&path).unwrap();
+        assert_eq!(data.sample_rate, 44100);
+        assert_eq!(data.channels, 2);
+        // Decoder delay may trim part of the first frame
+        assert!(data.frames() > 38 * 1152 && data.frames() <= 40 * 1152, "{}", data.frames());
+        assert!(data.samples.iter().all(|s| s.abs() < 1e-6));
+        
+        let info = loader.get_file_info(&path).unwrap();
+        assert_eq!(info.format, AudioFormat::Mp3);
+        assert_eq!(info.bit_depth, 0);
+        assert!((info.duration_secs - data.frames() as f64 / 44100.0).abs() < 0.03);
+    }
+    
+    #[test]
+    fn test_load_vorbis() {
+        let dir = tempfile::tempdir().unwrap();
+        let loader = AudioFileLoader::new();
+        let path = test_fixtures::write_silent_vorbis(&dir.path().join("silence.ogg"), 32000, 2, 50);
+        
+        let data = loader.load_file(&path).unwrap();
+        assert_eq!(data.sample_rate, 32000);
+        assert_eq!(data.channels, 2);
+        assert_eq!(data.frames() as u64, 49 * test_fixtures::VORBIS_FRAMES_PER_PACKET);
+        assert!(data.samples.iter().all(|&s| s == 0.0));
+        
+        let info = loader.get_file_info(&path).unwrap();
+        assert_eq!(info.format, AudioFormat::Ogg);
+        assert!((info.duration_secs - data.frames() as f64 / 32000.0).abs() < 1e-9);
+    }
+    
+    #[test]
+    fn test_load_opus() {
+        let dir = tempfile::tempdir().unwrap();
+        let loader = AudioFileLoader::new();
+        let source = test_fixtures::test_tone(24_000, 2, 48000);
+        let path = test_fixtures::write_opus(&dir.path().join("tone.opus"), 2, &source);
+        
+        let data = loader.load_file(&path).unwrap();
+        assert_eq!(data.sample_rate, 48000);
+        assert_eq!(data.channels, 2);
+        // Pre-skip and end trimming restore the exact source length
+        assert_eq!(data.frames(), 24_000);
+        
+        // Lossy, but pre-skip keeps the output time-aligned with the source
+        let skip = 2 * 960;
+        let signal: f32 = source[skip..].iter().map(|s| s * s).sum();
+        let noise: f32 = source[skip..]
+            .iter()
+            .zip(&data.samples[skip..])
+            .map(|(a, b)| (a - b) * (a - b))
+            .sum();
+        let snr_db = 10.0 * (signal / noise).log10();
+        assert!(snr_db > 15.0, "SNR {:.1} dB", snr_db);
+        
+        let info = loader.get_file_info(&path).unwrap();
+        assert_eq!(info.format, AudioFormat::Opus);
+        assert!((info.duration_secs - 0.5).abs() < 1e-9);
+    }
+    
+    #[test]
+    fn test_unsupported_and_corrupt_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let loader = AudioFileLoader::new();
+        
+        let ape = dir.path().join("track.ape");
+        std::fs::write(&ape, b"MAC \x96\x0f\x00\x00\x00\x00\x00\x00").unwrap();
+        assert!(loader.load_file(&ape).is_err());
+        
+        let truncated = dir.path().join("truncated.wav");
+        std::fs::write(&truncated, b"RIFF\x24\x00\x00\x00WAVEfmt ").unwrap();
+        assert!(loader.load_file(&truncated).is_err());
+        assert!(loader.get_file_info(&truncated).is_err());
+    }
 }
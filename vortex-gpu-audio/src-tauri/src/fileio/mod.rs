@@ -3,8 +3,18 @@ pub mod loader;
 pub mod format_detector;
 pub mod metadata_extractor;
 pub mod playlist_manager;
+pub mod decoder;
+pub mod stream;
+pub mod dsd;
+mod opus;
+
+#[cfg(test)]
+pub(crate) mod test_fixtures;
 
 pub use loader::{AudioFileLoader, AudioData, AudioFileInfo};
 pub use format_detector::{AudioFormat, FormatDetector};
 pub use metadata_extractor::{AudioMetadata, MetadataExtractor};
 pub use playlist_manager::{PlaylistManager, Playlist, PlaylistItem};
+pub use decoder::AudioDecoder;
+pub use dsd::{render_dsf, DsdBitstream, DsdContainer, DsdInfo, DsdReader, DsfWriter};
+pub use stream::{FileStream, StreamingDecoder, DEFAULT_BLOCK_FRAMES};
@@ -0,0 +1,151 @@
+use audiopus::coder::Decoder as LibOpusDecoder;
+use audiopus::packet::Packet as OpusPacket;
+use audiopus::{Channels as OpusChannels, MutSignals, SampleRate};
+use parking_lot::Mutex;
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Signal, SignalSpec};
+use symphonia::core::codecs::{
+    CodecDescriptor, CodecParameters, Decoder, DecoderOptions, FinalizeResult, CODEC_TYPE_OPUS,
+};
+use symphonia::core::errors::{decode_error, unsupported_error, Result};
+use symphonia::core::formats::Packet;
+use symphonia::core::support_codec;
+
+/// Opus always decodes at 48 kHz
+const OPUS_SAMPLE_RATE: u32 = 48_000;
+
+/// Longest Opus packet: 120 ms at 48 kHz
+const MAX_FRAMES_PER_PACKET: usize = 5760;
+
+/// Symphonia decoder adapter around libopus
+///
+/// Symphonia demuxes Ogg Opus but has no Opus codec of its own, so this plugs
+/// libopus into the same pipeline as every other format. Only channel mapping
+/// family 0 (mono/stereo) is supported.
+pub struct OpusDecoder {
+    params: CodecParameters,
+    // libopus decoders are Send but not Sync
+    decoder: Mutex<LibOpusDecoder>,
+    opus_channels: OpusChannels,
+    output_gain: i32,
+    // Frames at the start of the stream (by timestamp) that the decoder discards
+    pre_skip: u64,
+    interleaved: Vec<f32>,
+    buf: AudioBuffer<f32>,
+}
+
+impl OpusDecoder {
+    fn create_decoder(channels: OpusChannels, output_gain: i32) -> Result<LibOpusDecoder> {
+        let decoder = LibOpusDecoder::new(SampleRate::Hz48000, channels)
+            .or_else(|_| decode_error("opus: failed to create decoder"))?;
+
+        if output_gain != 0 {
+            decoder
+                .set_gain(output_gain)
+                .or_else(|_| decode_error("opus: invalid output gain"))?;
+        }
+
+        Ok(decoder)
+    }
+}
+
+impl Decoder for OpusDecoder {
+    fn try_new(params: &CodecParameters, _options: &DecoderOptions) -> Result<Self> {
+        if params.codec != CODEC_TYPE_OPUS {
+            return unsupported_error("opus: invalid codec type");
+        }
+
+        // OpusHead: magic(8) version(1) channels(1) pre-skip(2) rate(4) gain(2) family(1)
+        let head = match params.extra_data.as_deref() {
+            Some(head) if head.len() >= 19 => head,
+            _ => return unsupported_error("opus: missing identification header"),
+        };
+
+        if head[18] != 0 {
+            return unsupported_error("opus: multistream channel mappings are not supported");
+        }
+
+        let opus_channels = match head[9] {
+            1 => OpusChannels::Mono,
+            2 => OpusChannels::Stereo,
+            _ => return unsupported_error("opus: invalid channel count for mapping family 0"),
+        };
+        let pre_skip = u64::from(u16::from_le_bytes([head[10], head[11]]));
+        let output_gain = i32::from(i16::from_le_bytes([head[16], head[17]]));
+
+        let channels = match params.channels {
+            Some(channels) => channels,
+            None => return unsupported_error("opus: missing channel layout"),
+        };
+        let spec = SignalSpec::new(OPUS_SAMPLE_RATE, channels);
+
+        Ok(Self {
+            params: params.clone(),
+            decoder: Mutex::new(Self::create_decoder(opus_channels, output_gain)?),
+            opus_channels,
+            output_gain,
+            pre_skip,
+            interleaved: vec![0.0; MAX_FRAMES_PER_PACKET * spec.channels.count()],
+            buf: AudioBuffer::new(MAX_FRAMES_PER_PACKET as u64, spec),
+        })
+    }
+
+    fn supported_codecs() -> &'static [CodecDescriptor] {
+        &[support_codec!(CODEC_TYPE_OPUS, "opus", "Opus Interactive Audio Codec")]
+    }
+
+    fn reset(&mut self) {
+        // libopus has no reset through this binding; a fresh decoder is equivalent
+        if let Ok(decoder) = Self::create_decoder(self.opus_channels, self.output_gain) {
+            *self.decoder.lock() = decoder;
+        }
+    }
+
+    fn codec_params(&self) -> &CodecParameters {
+        &self.params
+    }
+
+    fn decode(&mut self, packet: &Packet) -> Result<AudioBufferRef<'_>> {
+        self.buf.clear();
+
+        // An empty packet signals loss; libopus conceals it
+        let input = match packet.buf() {
+            [] => None,
+            data => Some(OpusPacket::try_from(data).or_else(|_| decode_error("opus: invalid packet"))?),
+        };
+        let output = MutSignals::try_from(&mut self.interleaved[..])
+            .or_else(|_| decode_error("opus: invalid output buffer"))?;
+
+        let frames = self
+            .decoder
+            .lock()
+            .decode_float(input, output, false)
+            .or_else(|_| decode_error("opus: corrupt packet"))?;
+
+        let channels = self.buf.spec().channels.count();
+        self.buf.render_reserved(Some(frames));
+        for ch in 0..channels {
+            let plane = self.buf.chan_mut(ch);
+            for (frame, sample) in plane.iter_mut().enumerate() {
+                *sample = self.interleaved[frame * channels + ch];
+            }
+        }
+
+        // The container trims end padding; pre-skip is the decoder's job (RFC 7845 4.2).
+        // Deriving it from the packet timestamp keeps it correct after a seek.
+        let trim_start = packet.trim_start as usize;
+        let trim_end = packet.trim_end as usize;
+        let available = frames.saturating_sub(trim_start + trim_end);
+        let skip = (self.pre_skip.saturating_sub(packet.ts) as usize).min(available);
+        self.buf.trim(trim_start + skip, trim_end);
+
+        Ok(self.buf.as_audio_buffer_ref())
+    }
+
+    fn finalize(&mut self) -> FinalizeResult {
+        FinalizeResult::default()
+    }
+
+    fn last_decoded(&self) -> AudioBufferRef<'_> {
+        self.buf.as_audio_buffer_ref()
+    }
+}
@@ -0,0 +1,260 @@
+//! Pull-based streaming decoders
+//!
+//! `AudioData` holds a whole file in memory, which does not scale to long
+//! high-resolution recordings. A `StreamingDecoder` instead yields fixed-size
+//! blocks of interleaved samples on demand, so memory use is bounded by the
+//! block size regardless of file length.
+
+use super::decoder::AudioDecoder;
+use crate::error::{ConfigError, VortexError};
+use std::path::Path;
+
+/// Default block size in frames
+pub const DEFAULT_BLOCK_FRAMES: usize = 4096;
+
+/// Pull-based decoder yielding fixed-size blocks of interleaved f32 samples
+pub trait StreamingDecoder: Send {
+    /// Stream sample rate in Hz
+    fn sample_rate(&self) -> u32;
+
+    /// Number of interleaved channels
+    fn channels(&self) -> u16;
+
+    /// Frames produced by every block except the last
+    fn block_frames(&self) -> usize;
+
+    /// Total frames in the stream, when the container declares them
+    fn total_frames(&self) -> Option<u64>;
+
+    /// Index of the next frame `next_block` will produce
+    fn position(&self) -> u64;
+
+    /// Decode the next block into `output`
+    ///
+    /// `output` must hold `block_frames() * channels()` samples. Returns the
+    /// number of frames written, which is `block_frames()` except for the final
+    /// block of the stream; `Ok(0)` marks the end of the stream.
+    fn next_block(&mut self, output: &mut [f32]) -> Result<usize, VortexError>;
+
+    /// Reposition so the next block starts exactly at `frame`
+    ///
+    /// Returns the new position, which is clamped to the end of the stream
+    /// when the total length is known.
+    fn seek(&mut self, frame: u64) -> Result<u64, VortexError>;
+}
+
+/// File-backed `StreamingDecoder` built on `AudioDecoder`
+pub struct FileStream {
+    decoder: AudioDecoder,
+    block_frames: usize,
+    // Decoded samples not yet handed out, starting at `pending_pos`
+    pending: Vec<f32>,
+    pending_pos: usize,
+    position: u64,
+}
+
+impl FileStream {
+    /// Open a file for streaming in blocks of `block_frames` frames
+    pub fn open(path: &Path, block_frames: usize) -> Result<Self, VortexError> {
+        Self::from_decoder(AudioDecoder::open(path)?, block_frames)
+    }
+
+    /// Stream from an already opened decoder
+    pub fn from_decoder(decoder: AudioDecoder, block_frames: usize) -> Result<Self, VortexError> {
+        if block_frames == 0 {
+            return Err(ConfigError::InvalidValue {
+                key: "block_frames".to_string(),
+                reason: "block size must be at least one frame".to_string(),
+            }.into());
+        }
+
+        Ok(Self {
+            decoder,
+            block_frames,
+            pending: Vec::new(),
+            pending_pos: 0,
+            position: 0,
+        })
+    }
+}
+
+impl StreamingDecoder for FileStream {
+    fn sample_rate(&self) -> u32 {
+        self.decoder.sample_rate()
+    }
+
+    fn channels(&self) -> u16 {
+        self.decoder.channels()
+    }
+
+    fn block_frames(&self) -> usize {
+        self.block_frames
+    }
+
+    fn total_frames(&self) -> Option<u64> {
+        self.decoder.total_frames()
+    }
+
+    fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn next_block(&mut self, output: &mut [f32]) -> Result<usize, VortexError> {
+        let channels = self.channels() as usize;
+        let wanted = self.block_frames * channels;
+        if output.len() < wanted {
+            return Err(ConfigError::InvalidValue {
+                key: "output".to_string(),
+                reason: format!("block needs {} samples, got {}", wanted, output.len()),
+            }.into());
+        }
+
+        let mut filled = 0;
+        while filled < wanted {
+            if self.pending_pos == self.pending.len() {
+                self.pending.clear();
+                self.pending_pos = 0;
+                if self.decoder.decode_next(&mut self.pending)? == 0 {
+                    break;
+                }
+            }
+
+            let count = (wanted - filled).min(self.pending.len() - self.pending_pos);
+            output[filled..filled + count]
+                .copy_from_slice(&self.pending[self.pending_pos..self.pending_pos + count]);
+            filled += count;
+            self.pending_pos += count;
+        }
+
+        let frames = filled / channels;
+        self.position += frames as u64;
+        Ok(frames)
+    }
+
+    fn seek(&mut self, frame: u64) -> Result<u64, VortexError> {
+        self.pending.clear();
+        self.pending_pos = 0;
+        self.position = self.decoder.seek(frame)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fileio::test_fixtures::*;
+    use crate::fileio::AudioFileLoader;
+    use tempfile::TempDir;
+
+    /// Read a stream to the end, checking every block but the last is full
+    fn read_to_end(stream: &mut FileStream) -> Vec<f32> {
+        let channels = stream.channels() as usize;
+        let mut block = vec![0.0f32; stream.block_frames() * channels];
+        let mut samples = Vec::new();
+        let mut short_block = false;
+        loop {
+            let frames = stream.next_block(&mut block).unwrap();
+            if frames == 0 {
+                return samples;
+            }
+            assert!(!short_block, "only the final block may be short");
+            short_block = frames < stream.block_frames();
+            samples.extend_from_slice(&block[..frames * channels]);
+        }
+    }
+
+    #[test]
+    fn test_blocks_match_whole_file_decode() {
+        let dir = TempDir::new().unwrap();
+        let tone = test_tone(10_000, 2, 44100);
+        let path = write_wav(&dir.path().join("tone.wav"), 44100, 2, WavEncoding::Pcm16, &tone);
+
+        let mut stream = FileStream::open(&path, 1024).unwrap();
+        assert_eq!(stream.sample_rate(), 44100);
+        assert_eq!(stream.channels(), 2);
+        assert_eq!(stream.total_frames(), Some(10_000));
+
+        let streamed = read_to_end(&mut stream);
+        assert_eq!(stream.position(), 10_000);
+        assert_eq!(streamed, AudioFileLoader::new().load_file(&path).unwrap().samples);
+    }
+
+    #[test]
+    fn test_seek_is_sample_accurate() {
+        let dir = TempDir::new().unwrap();
+        let tone = test_tone(20_000, 2, 48000);
+        let files = [
+            write_wav(&dir.path().join("tone.wav"), 48000, 2, WavEncoding::Pcm24, &tone),
+            write_flac(&dir.path().join("tone.flac"), 48000, 2, 24, &tone),
+        ];
+
+        for path in &files {
+            let reference = AudioFileLoader::new().load_file(path).unwrap().samples;
+            let mut stream = FileStream::open(path, 256).unwrap();
+            let mut block = vec![0.0f32; 512];
+
+            // Forwards, backwards and across FLAC frame boundaries
+            for target in [7777u64, 1, 4095, 4096, 19_999, 0, 12_288] {
+                assert_eq!(stream.seek(target).unwrap(), target);
+                let frames = stream.next_block(&mut block).unwrap();
+                let start = target as usize * 2;
+                let end = (start + 512).min(reference.len());
+                assert_eq!(frames, (end - start) / 2, "{}: frames after seek to {}", path.display(), target);
+                assert_eq!(&block[..end - start], &reference[start..end], "{}: seek to {}", path.display(), target);
+                assert_eq!(stream.position(), target + frames as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_seek_past_end_clamps() {
+        let dir = TempDir::new().unwrap();
+        let tone = test_tone(1000, 1, 44100);
+        let path = write_wav(&dir.path().join("short.wav"), 44100, 1, WavEncoding::Pcm16, &tone);
+
+        let mut stream = FileStream::open(&path, 64).unwrap();
+        assert_eq!(stream.seek(5000).unwrap(), 1000);
+        let mut block = vec![0.0f32; 64];
+        assert_eq!(stream.next_block(&mut block).unwrap(), 0);
+
+        // Seeking back in range resumes decoding
+        assert_eq!(stream.seek(990).unwrap(), 990);
+        assert_eq!(stream.next_block(&mut block).unwrap(), 10);
+    }
+
+    #[test]
+    fn test_seek_lossy_streams() {
+        let dir = TempDir::new().unwrap();
+
+        // MP3 without a Xing header: length unknown until decoded
+        let mp3 = write_silent_mp3(&dir.path().join("silence.mp3"), 40);
+        let total = read_to_end(&mut FileStream::open(&mp3, 1000).unwrap()).len() as u64 / 2;
+        let mut stream = FileStream::open(&mp3, 1000).unwrap();
+        assert_eq!(stream.seek(10_000).unwrap(), 10_000);
+        assert_eq!(read_to_end(&mut stream).len() as u64 / 2, total - 10_000);
+
+        // Opus seeks land exactly and converge on the continuous decode
+        let tone = test_tone(24_000, 2, 48000);
+        let opus = write_opus(&dir.path().join("tone.opus"), 2, &tone);
+        let reference = AudioFileLoader::new().load_file(&opus).unwrap().samples;
+        let mut stream = FileStream::open(&opus, 960).unwrap();
+        assert_eq!(stream.seek(12_000).unwrap(), 12_000);
+        let tail = read_to_end(&mut stream);
+        assert_eq!(tail.len(), 12_000 * 2);
+        let reference = &reference[24_000..];
+        let signal: f32 = reference.iter().map(|x| x * x).sum();
+        let noise: f32 = tail.iter().zip(reference).map(|(a, b)| (a - b) * (a - b)).sum();
+        let snr_db = 10.0 * (signal / noise).log10();
+        assert!(snr_db > 25.0, "seeked decode diverges: SNR {:.1} dB", snr_db);
+    }
+
+    #[test]
+    fn test_invalid_block_sizes() {
+        let dir = TempDir::new().unwrap();
+        let path = write_wav(&dir.path().join("tone.wav"), 44100, 2, WavEncoding::Pcm16, &test_tone(100, 2, 44100));
+
+        assert!(FileStream::open(&path, 0).is_err());
+        let mut stream = FileStream::open(&path, 64).unwrap();
+        assert!(stream.next_block(&mut [0.0; 64]).is_err());
+    }
+}
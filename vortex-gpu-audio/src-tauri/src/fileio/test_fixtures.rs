@@ -0,0 +1,494 @@
+//! Minimal audio file writers for decoder tests
+//!
+//! Each writer produces a small but spec-conformant file so tests never depend
+//! on binary fixtures checked into the repository. Lossless writers store the
+//! exact quantized samples; MP3 and Vorbis fixtures are digital silence built
+//! from hand-assembled frames; Opus fixtures go through the real libopus encoder.
+//! DSF and DSDIFF writers wrap caller-supplied bitstreams.
+
+use crate::audio::dsp::DsdRate;
+use ogg::{PacketWriteEndInfo, PacketWriter};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Interleaved multi-tone test signal, one frequency per channel
+pub fn test_tone(frames: usize, channels: u16, sample_rate: u32) -> Vec<f32> {
+    let mut samples = Vec::with_capacity(frames * channels as usize);
+    for n in 0..frames {
+        for ch in 0..channels {
+            let freq = 440.0 * (ch + 1) as f32;
+            let phase = 2.0 * std::f32::consts::PI * freq * n as f32 / sample_rate as f32;
+            samples.push(0.5 * phase.sin());
+        }
+    }
+    samples
+}
+
+/// Quantize to a signed integer of `bits` bits, as stored in PCM files
+pub fn quantize(sample: f32, bits: u32) -> i32 {
+    let scale = (1i64 << (bits - 1)) as f64;
+    (sample as f64 * scale).round().clamp(-scale, scale - 1.0) as i32
+}
+
+/// Value a decoder produces for a quantized sample
+pub fn dequantize(value: i32, bits: u32) -> f32 {
+    (value as f64 / (1i64 << (bits - 1)) as f64) as f32
+}
+
+/// WAV sample encodings
+#[derive(Debug, Clone, Copy)]
+pub enum WavEncoding {
+    Pcm16,
+    Pcm24,
+    Pcm32,
+    Float32,
+}
+
+impl WavEncoding {
+    pub fn bits(self) -> u32 {
+        match self {
+            WavEncoding::Pcm16 => 16,
+            WavEncoding::Pcm24 => 24,
+            WavEncoding::Pcm32 | WavEncoding::Float32 => 32,
+        }
+    }
+}
+
+/// Write a canonical RIFF/WAVE file
+pub fn write_wav(
+    path: &Path,
+    sample_rate: u32,
+    channels: u16,
+    encoding: WavEncoding,
+    samples: &[f32],
+) -> PathBuf {
+    let bits = encoding.bits();
+    let bytes_per_sample = bits as usize / 8;
+    let block_align = channels as usize * bytes_per_sample;
+    let format_tag: u16 = match encoding {
+        WavEncoding::Float32 => 3,
+        _ => 1,
+    };
+
+    let mut data = Vec::with_capacity(samples.len() * bytes_per_sample);
+    for &sample in samples {
+        match encoding {
+            WavEncoding::Float32 => data.extend_from_slice(&sample.to_le_bytes()),
+            _ => {
+                let value = quantize(sample, bits).to_le_bytes();
+                data.extend_from_slice(&value[..bytes_per_sample]);
+            }
+        }
+    }
+
+    let mut out = Vec::with_capacity(44 + data.len());
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&format_tag.to_le_bytes());
+    out.extend_from_slice(&channels.to_le_bytes());
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    out.extend_from_slice(&(block_align as u16).to_le_bytes());
+    out.extend_from_slice(&(bits as u16).to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(&data);
+
+    write_file(path, &out)
+}
+
+/// Write a FLAC file using verbatim subframes (16 or 24 bits per sample)
+pub fn write_flac(path: &Path, sample_rate: u32, channels: u16, bits: u32, samples: &[f32]) -> PathBuf {
+    assert!(bits == 16 || bits == 24, "verbatim writer keeps samples byte-aligned");
+    const BLOCK_SIZE: usize = 4096;
+
+    let channels_usize = channels as usize;
+    let total_frames = samples.len() / channels_usize;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"fLaC");
+
+    // STREAMINFO, flagged as the last metadata block
+    out.push(0x80);
+    out.extend_from_slice(&34u32.to_be_bytes()[1..]);
+    out.extend_from_slice(&(BLOCK_SIZE.min(total_frames) as u16).to_be_bytes());
+    out.extend_from_slice(&(BLOCK_SIZE as u16).to_be_bytes());
+    out.extend_from_slice(&[0; 6]); // min/max frame size unknown
+    let packed: u64 = (u64::from(sample_rate) << 44)
+        | (u64::from(channels - 1) << 41)
+        | (u64::from(bits - 1) << 36)
+        | total_frames as u64;
+    out.extend_from_slice(&packed.to_be_bytes());
+    out.extend_from_slice(&[0; 16]); // MD5 unknown
+
+    for (frame_number, block) in samples.chunks(BLOCK_SIZE * channels_usize).enumerate() {
+        let block_frames = block.len() / channels_usize;
+        let start = out.len();
+
+        out.extend_from_slice(&[0xFF, 0xF8]);
+        // 16-bit block size at end of header, sample rate from STREAMINFO
+        out.push(0x70);
+        // Independent channels, sample size from STREAMINFO
+        out.push(((channels - 1) as u8) << 4);
+        push_utf8_number(&mut out, frame_number as u32);
+        out.extend_from_slice(&((block_frames - 1) as u16).to_be_bytes());
+        let header_crc = crc8(&out[start..]);
+        out.push(header_crc);
+
+        for ch in 0..channels_usize {
+            out.push(0x02); // Verbatim subframe, no wasted bits
+            for frame in 0..block_frames {
+                let value = quantize(block[frame * channels_usize + ch], bits).to_be_bytes();
+                out.extend_from_slice(&value[4 - bits as usize / 8..]);
+            }
+        }
+
+        let frame_crc = crc16(&out[start..]);
+        out.extend_from_slice(&frame_crc.to_be_bytes());
+    }
+
+    write_file(path, &out)
+}
+
+/// Write `frames` MPEG-1 Layer III frames of digital silence (44.1 kHz stereo, 128 kbps)
+pub fn write_silent_mp3(path: &Path, frames: usize) -> PathBuf {
+    const FRAME_BYTES: usize = 417; // 144 * 128000 / 44100, no padding
+    let mut out = Vec::with_capacity(frames * FRAME_BYTES);
+    for _ in 0..frames {
+        // Sync, MPEG-1, Layer III, no CRC | 128 kbps, 44.1 kHz | stereo, original
+        out.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x04]);
+        // Zeroed side info and main data: no Huffman bits, so every granule is silent
+        out.resize(out.len() + FRAME_BYTES - 4, 0);
+    }
+    write_file(path, &out)
+}
+
+/// Samples produced by each short-block packet of the Vorbis fixture
+pub const VORBIS_FRAMES_PER_PACKET: u64 = 128;
+
+/// Write an Ogg Vorbis stream of silent short-block packets
+///
+/// The setup header declares the smallest valid configuration (one codebook,
+/// one floor 1 with no partitions, one empty residue, one mapping, one mode)
+/// and every audio packet marks all channel floors unused.
+pub fn write_silent_vorbis(path: &Path, sample_rate: u32, channels: u8, packets: usize) -> PathBuf {
+    let mut ident = vec![0x01];
+    ident.extend_from_slice(b"vorbis");
+    ident.extend_from_slice(&0u32.to_le_bytes());
+    ident.push(channels);
+    ident.extend_from_slice(&sample_rate.to_le_bytes());
+    ident.extend_from_slice(&[0; 12]); // Bitrate hints
+    ident.push(0xB8); // blocksize_0 = 256, blocksize_1 = 2048
+    ident.push(0x01);
+
+    let mut comment = vec![0x03];
+    comment.extend_from_slice(b"vorbis");
+    comment.extend_from_slice(&4u32.to_le_bytes());
+    comment.extend_from_slice(b"test");
+    comment.extend_from_slice(&0u32.to_le_bytes());
+    comment.push(0x01);
+
+    let mut bits = LsbBitWriter::default();
+    // Codebooks: one, 1 dimension, 2 entries of length 1, no lookup
+    bits.write(0, 8);
+    bits.write(0x564342, 24);
+    bits.write(1, 16);
+    bits.write(2, 24);
+    bits.write(0, 1); // Not ordered
+    bits.write(0, 1); // Not sparse
+    bits.write(0, 5);
+    bits.write(0, 5);
+    bits.write(0, 4); // Lookup type
+    // Time domain transforms: one placeholder
+    bits.write(0, 6);
+    bits.write(0, 16);
+    // Floors: one type 1 floor, no partitions, multiplier 2, 8 range bits
+    bits.write(0, 6);
+    bits.write(1, 16);
+    bits.write(0, 5);
+    bits.write(1, 2);
+    bits.write(8, 4);
+    // Residues: one type 0 residue covering nothing
+    bits.write(0, 6);
+    bits.write(0, 16);
+    bits.write(0, 24);
+    bits.write(0, 24);
+    bits.write(0, 24);
+    bits.write(0, 6);
+    bits.write(0, 8);
+    bits.write(0, 3);
+    bits.write(0, 1);
+    // Mappings: one type 0 mapping with a single submap
+    bits.write(0, 6);
+    bits.write(0, 16);
+    bits.write(0, 1);
+    bits.write(0, 1);
+    bits.write(0, 2);
+    bits.write(0, 8);
+    bits.write(0, 8);
+    bits.write(0, 8);
+    // Modes: one short-block mode
+    bits.write(0, 6);
+    bits.write(0, 1);
+    bits.write(0, 16);
+    bits.write(0, 16);
+    bits.write(0, 8);
+    bits.write(1, 1); // Framing
+    let mut setup = vec![0x05];
+    setup.extend_from_slice(b"vorbis");
+    setup.extend_from_slice(&bits.finish());
+
+    // Audio packet type bit and one unused-floor bit per channel, all zero
+    let audio_packet = vec![0u8; 1];
+
+    let serial = 0x5652_4258;
+    let mut writer = PacketWriter::new(Vec::new());
+    writer.write_packet(ident.into_boxed_slice(), serial, PacketWriteEndInfo::EndPage, 0).unwrap();
+    writer.write_packet(comment.into_boxed_slice(), serial, PacketWriteEndInfo::NormalPacket, 0).unwrap();
+    writer.write_packet(setup.into_boxed_slice(), serial, PacketWriteEndInfo::EndPage, 0).unwrap();
+    for i in 0..packets {
+        let end = if i + 1 == packets {
+            PacketWriteEndInfo::EndStream
+        } else {
+            PacketWriteEndInfo::NormalPacket
+        };
+        let granule = i as u64 * VORBIS_FRAMES_PER_PACKET;
+        writer.write_packet(audio_packet.clone().into_boxed_slice(), serial, end, granule).unwrap();
+    }
+
+    write_file(path, &writer.into_inner())
+}
+
+/// Encode interleaved 48 kHz samples to Ogg Opus with libopus
+pub fn write_opus(path: &Path, channels: u16, samples: &[f32]) -> PathBuf {
+    use audiopus::coder::Encoder;
+    use audiopus::{Application, Bitrate, Channels, SampleRate};
+
+    const FRAME: usize = 960; // 20 ms
+
+    let opus_channels = if channels == 1 { Channels::Mono } else { Channels::Stereo };
+    let mut encoder = Encoder::new(SampleRate::Hz48000, opus_channels, Application::Audio).unwrap();
+    encoder.set_bitrate(Bitrate::BitsPerSecond(128_000)).unwrap();
+    let pre_skip = encoder.lookahead().unwrap() as u64;
+
+    let mut head = b"OpusHead".to_vec();
+    head.push(1);
+    head.push(channels as u8);
+    head.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+    head.extend_from_slice(&48_000u32.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes());
+    head.push(0); // Channel mapping family 0
+
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&4u32.to_le_bytes());
+    tags.extend_from_slice(b"test");
+    tags.extend_from_slice(&0u32.to_le_bytes());
+
+    let serial = 0x4F50_5553;
+    let mut writer = PacketWriter::new(Vec::new());
+    writer.write_packet(head.into_boxed_slice(), serial, PacketWriteEndInfo::EndPage, 0).unwrap();
+    writer.write_packet(tags.into_boxed_slice(), serial, PacketWriteEndInfo::EndPage, 0).unwrap();
+
+    // Pad to whole frames and flush the encoder lookahead
+    let channels_usize = channels as usize;
+    let total_frames = samples.len() / channels_usize;
+    let mut padded = samples.to_vec();
+    let padded_frames = (total_frames + pre_skip as usize).div_ceil(FRAME) * FRAME;
+    padded.resize(padded_frames * channels_usize, 0.0);
+
+    let mut packet = vec![0u8; 4000];
+    let blocks: Vec<&[f32]> = padded.chunks(FRAME * channels_usize).collect();
+    for (i, block) in blocks.iter().enumerate() {
+        let len = encoder.encode_float(block, &mut packet).unwrap();
+        let last = i + 1 == blocks.len();
+        let granule = if last {
+            pre_skip + total_frames as u64
+        } else {
+            ((i + 1) * FRAME) as u64
+        };
+        let end = if last { PacketWriteEndInfo::EndStream } else { PacketWriteEndInfo::EndPage };
+        writer.write_packet(packet[..len].to_vec().into_boxed_slice(), serial, end, granule).unwrap();
+    }
+
+    write_file(path, &writer.into_inner())
+}
+
+/// DSF block size per channel mandated by the specification
+const DSF_BLOCK_SIZE: usize = 4096;
+
+/// Write a DSF file from per-channel LSB-first bitstreams of equal length
+pub fn write_dsf(
+    path: &Path,
+    rate: DsdRate,
+    channels: &[Vec<u8>],
+    msb_first: bool,
+    id3: Option<&[u8]>,
+) -> PathBuf {
+    let bytes_per_channel = channels[0].len();
+    let blocks = bytes_per_channel.div_ceil(DSF_BLOCK_SIZE);
+
+    let mut data = Vec::with_capacity(blocks * DSF_BLOCK_SIZE * channels.len());
+    for block in 0..blocks {
+        for channel in channels {
+            let start = block * DSF_BLOCK_SIZE;
+            let end = (start + DSF_BLOCK_SIZE).min(bytes_per_channel);
+            let bytes = channel[start..end]
+                .iter()
+                .map(|&b| if msb_first { b.reverse_bits() } else { b });
+            data.extend(bytes);
+            // The final block of each channel is zero padded
+            data.resize(data.len() + DSF_BLOCK_SIZE - (end - start), 0);
+        }
+    }
+
+    let data_chunk_size = 12 + data.len() as u64;
+    let metadata_offset = if id3.is_some() { 28 + 52 + data_chunk_size } else { 0 };
+    let file_size = 28 + 52 + data_chunk_size + id3.map_or(0, |tag| tag.len() as u64);
+
+    let mut out = Vec::with_capacity(file_size as usize);
+    out.extend_from_slice(b"DSD ");
+    out.extend_from_slice(&28u64.to_le_bytes());
+    out.extend_from_slice(&file_size.to_le_bytes());
+    out.extend_from_slice(&metadata_offset.to_le_bytes());
+
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&52u64.to_le_bytes());
+    out.extend_from_slice(&1u32.to_le_bytes()); // Format version
+    out.extend_from_slice(&0u32.to_le_bytes()); // DSD raw
+    let channel_type: u32 = if channels.len() == 2 { 2 } else { 1 };
+    out.extend_from_slice(&channel_type.to_le_bytes());
+    out.extend_from_slice(&(channels.len() as u32).to_le_bytes());
+    out.extend_from_slice(&rate.sample_rate().to_le_bytes());
+    let bits_per_sample: u32 = if msb_first { 8 } else { 1 };
+    out.extend_from_slice(&bits_per_sample.to_le_bytes());
+    out.extend_from_slice(&(bytes_per_channel as u64 * 8).to_le_bytes());
+    out.extend_from_slice(&(DSF_BLOCK_SIZE as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes()); // Reserved
+
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_chunk_size.to_le_bytes());
+    out.extend_from_slice(&data);
+    if let Some(tag) = id3 {
+        out.extend_from_slice(tag);
+    }
+
+    write_file(path, &out)
+}
+
+/// Write a DSDIFF file from per-channel LSB-first bitstreams of equal length
+///
+/// `compression` is the CMPR type; anything but `DSD ` is only useful for
+/// rejection tests since the sound data is always stored uncompressed.
+pub fn write_dff(
+    path: &Path,
+    rate: DsdRate,
+    channels: &[Vec<u8>],
+    compression: &[u8; 4],
+    id3: Option<&[u8]>,
+) -> PathBuf {
+    fn chunk(out: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+        out.extend_from_slice(id);
+        out.extend_from_slice(&(body.len() as u64).to_be_bytes());
+        out.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            out.push(0);
+        }
+    }
+
+    let mut properties = b"SND ".to_vec();
+    chunk(&mut properties, b"FS  ", &rate.sample_rate().to_be_bytes());
+    let mut channel_ids = (channels.len() as u16).to_be_bytes().to_vec();
+    for ch in 0..channels.len() {
+        channel_ids.extend_from_slice(match ch {
+            0 if channels.len() == 2 => b"SLFT",
+            1 if channels.len() == 2 => b"SRGT",
+            _ => b"C000",
+        });
+    }
+    chunk(&mut properties, b"CHNL", &channel_ids);
+    let mut cmpr = compression.to_vec();
+    cmpr.extend_from_slice(b"\x0enot compressed\x00");
+    chunk(&mut properties, b"CMPR", &cmpr);
+
+    // Byte-interleaved, MSB first
+    let sound: Vec<u8> = (0..channels[0].len())
+        .flat_map(|i| channels.iter().map(move |ch| ch[i].reverse_bits()))
+        .collect();
+
+    let mut form = b"DSD ".to_vec();
+    chunk(&mut form, b"FVER", &0x0105_0000u32.to_be_bytes());
+    chunk(&mut form, b"PROP", &properties);
+    chunk(&mut form, b"DSD ", &sound);
+    if let Some(tag) = id3 {
+        chunk(&mut form, b"ID3 ", tag);
+    }
+
+    let mut out = Vec::with_capacity(12 + form.len());
+    chunk(&mut out, b"FRM8", &form);
+    write_file(path, &out)
+}
+
+fn write_file(path: &Path, bytes: &[u8]) -> PathBuf {
+    File::create(path).and_then(|mut f| f.write_all(bytes)).unwrap();
+    path.to_path_buf()
+}
+
+/// FLAC frame numbers use the extended UTF-8 style variable-length coding
+fn push_utf8_number(out: &mut Vec<u8>, value: u32) {
+    match char::from_u32(value) {
+        Some(c) if value < 0x110000 => {
+            let mut buf = [0; 4];
+            out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        _ => panic!("frame number {} out of fixture range", value),
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+/// Vorbis packs header fields least-significant bit first
+#[derive(Default)]
+struct LsbBitWriter {
+    bytes: Vec<u8>,
+    bit: u32,
+}
+
+impl LsbBitWriter {
+    fn write(&mut self, value: u32, bits: u32) {
+        for i in 0..bits {
+            if self.bit == 0 {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 != 0 {
+                *self.bytes.last_mut().unwrap() |= 1 << self.bit;
+            }
+            self.bit = (self.bit + 1) % 8;
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
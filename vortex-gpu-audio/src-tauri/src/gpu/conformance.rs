@@ -0,0 +1,307 @@
+//! Conformance suite for `GpuBackend` implementations
+//!
+//! Every check is generic over the backend and compares its results against
+//! host-side references (direct convolution, naive DFT, f64 `BiquadFilter`
+//! cascade). A backend proves conformance with a single line:
+//!
+//! ```ignore
+//! gpu_backend_conformance_tests!(cpu_fallback, CpuFallbackBackend);
+//! ```
+
+use super::{EqBand, EqFilterType, GpuBackend, GpuBuffer};
+use crate::audio::filters::{BiquadFilter, BiquadTopology, ChannelLayout, Filter};
+use crate::error::VortexResult;
+
+/// Maximum absolute error accepted for single-precision kernels
+pub const TOLERANCE: f32 = 1e-4;
+
+const MB: usize = 1024 * 1024;
+const F32_BYTES: usize = std::mem::size_of::<f32>();
+
+/// Deterministic pseudo-random signal in [-0.5, 0.5)
+fn test_signal(len: usize, seed: u32) -> Vec<f32> {
+    let mut state = seed.wrapping_mul(2_654_435_761).max(1);
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 - 0.5
+        })
+        .collect()
+}
+
+fn assert_close(actual: &[f32], expected: &[f32], tolerance: f32, context: &str) {
+    assert_eq!(actual.len(), expected.len(), "{}: length mismatch", context);
+    for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+        assert!(
+            (a - e).abs() <= tolerance,
+            "{}: sample {} is {}, expected {}",
+            context,
+            i,
+            a,
+            e
+        );
+    }
+}
+
+/// Upload samples into a freshly allocated buffer of `capacity` samples
+fn upload<B: GpuBackend>(backend: &B, samples: &[f32], capacity: usize) -> VortexResult<B::Buffer> {
+    let buffer = backend.allocate_buffer(capacity * F32_BYTES)?;
+    backend.copy_to_device(&buffer, samples)?;
+    Ok(buffer)
+}
+
+fn download<B: GpuBackend>(backend: &B, buffer: &B::Buffer, samples: usize) -> VortexResult<Vec<f32>> {
+    backend.synchronize()?;
+    let mut host = vec![0.0f32; samples];
+    backend.copy_from_device(buffer, &mut host)?;
+    Ok(host)
+}
+
+/// Allocations report their size and respect the advertised memory limit
+pub fn check_allocation_limits<B: GpuBackend>(backend: &B) -> VortexResult<()> {
+    for size in [F32_BYTES, 4096, MB] {
+        let buffer = backend.allocate_buffer(size)?;
+        assert_eq!(buffer.size(), size, "allocated buffer reports wrong size");
+        assert!(buffer.alignment().is_power_of_two(), "alignment must be a power of 2");
+        backend.free_buffer(buffer)?;
+    }
+
+    let over_limit = (backend.capabilities().max_memory_mb + 1) * MB;
+    assert!(
+        backend.allocate_buffer(over_limit).is_err(),
+        "allocation beyond max_memory_mb must fail"
+    );
+
+    Ok(())
+}
+
+/// Host to device to host transfers are lossless and bounds-checked
+pub fn check_memory_round_trip<B: GpuBackend>(backend: &B) -> VortexResult<()> {
+    let data = test_signal(4096, 1);
+    let buffer = upload(backend, &data, data.len())?;
+
+    let restored = download(backend, &buffer, data.len())?;
+    assert_eq!(restored, data, "round trip must be bit-exact");
+
+    // Partial reads return the leading samples
+    let head = download(backend, &buffer, 100)?;
+    assert_eq!(head, data[..100]);
+
+    let oversized = vec![0.0f32; data.len() + 1];
+    assert!(
+        backend.copy_to_device(&buffer, &oversized).is_err(),
+        "copy larger than the buffer must fail"
+    );
+    let mut oversized = vec![0.0f32; data.len() + 1];
+    assert!(
+        backend.copy_from_device(&buffer, &mut oversized).is_err(),
+        "read larger than the buffer must fail"
+    );
+
+    backend.free_buffer(buffer)
+}
+
+/// FFT matches a naive DFT and IFFT(FFT(x)) reproduces x
+pub fn check_fft_identity<B: GpuBackend>(backend: &B) -> VortexResult<()> {
+    for fft_size in [64, 1024, 4096] {
+        let signal = test_signal(fft_size, fft_size as u32);
+        let input = upload(backend, &signal, fft_size)?;
+        let spectrum = backend.allocate_buffer(fft_size * 2 * F32_BYTES)?;
+        let output = backend.allocate_buffer(fft_size * F32_BYTES)?;
+
+        backend.process_fft(&input, &spectrum, fft_size)?;
+
+        if fft_size == 64 {
+            let bins = download(backend, &spectrum, fft_size * 2)?;
+            let mut expected = Vec::with_capacity(fft_size * 2);
+            for k in 0..fft_size {
+                let (mut re, mut im) = (0.0f64, 0.0f64);
+                for (n, &x) in signal.iter().enumerate() {
+                    let phase = -2.0 * std::f64::consts::PI * (k * n) as f64 / fft_size as f64;
+                    re += x as f64 * phase.cos();
+                    im += x as f64 * phase.sin();
+                }
+                expected.push(re as f32);
+                expected.push(im as f32);
+            }
+            assert_close(&bins, &expected, TOLERANCE * 10.0, "fft vs dft");
+        }
+
+        backend.process_ifft(&spectrum, &output, fft_size)?;
+        let restored = download(backend, &output, fft_size)?;
+        assert_close(&restored, &signal, TOLERANCE, "ifft(fft(x))");
+
+        assert!(
+            backend.process_fft(&input, &spectrum, fft_size + 1).is_err(),
+            "non power-of-two FFT size must fail"
+        );
+
+        backend.free_buffer(input)?;
+        backend.free_buffer(spectrum)?;
+        backend.free_buffer(output)?;
+    }
+
+    Ok(())
+}
+
+/// Convolution produces the full linear result of a direct-form reference
+pub fn check_convolution<B: GpuBackend>(backend: &B) -> VortexResult<()> {
+    let signal = test_signal(1000, 7);
+    let input = upload(backend, &signal, signal.len())?;
+
+    for ir_len in [1, 16, 300] {
+        let ir: Vec<f32> = test_signal(ir_len, ir_len as u32 + 11)
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| x * (-(i as f32) / 64.0).exp())
+            .collect();
+        let out_len = signal.len() + ir_len - 1;
+
+        let ir_buffer = upload(backend, &ir, ir_len)?;
+        let output = backend.allocate_buffer(out_len * F32_BYTES)?;
+        backend.process_convolution(&input, &ir_buffer, &output, signal.len(), ir_len)?;
+
+        let expected: Vec<f32> = (0..out_len)
+            .map(|n| {
+                let start = (n + 1).saturating_sub(signal.len());
+                (start..ir_len.min(n + 1))
+                    .map(|k| ir[k] as f64 * signal[n - k] as f64)
+                    .sum::<f64>() as f32
+            })
+            .collect();
+        let actual = download(backend, &output, out_len)?;
+        assert_close(&actual, &expected, TOLERANCE, &format!("convolution with {} taps", ir_len));
+
+        backend.free_buffer(ir_buffer)?;
+        backend.free_buffer(output)?;
+    }
+
+    backend.free_buffer(input)
+}
+
+/// EQ matches a `BiquadFilter` cascade designed at the backend sample rate
+pub fn check_eq<B: GpuBackend>(backend: &B) -> VortexResult<()> {
+    let bands = [
+        EqBand { frequency: 80.0, gain: 4.0, q_factor: 0.707, filter_type: EqFilterType::LowShelf },
+        EqBand { frequency: 1000.0, gain: -6.0, q_factor: 2.0, filter_type: EqFilterType::Peak },
+        EqBand { frequency: 8000.0, gain: 3.0, q_factor: 0.707, filter_type: EqFilterType::HighShelf },
+        EqBand { frequency: 30.0, gain: 0.0, q_factor: 0.707, filter_type: EqFilterType::HighPass },
+        EqBand { frequency: 18000.0, gain: 0.0, q_factor: 0.707, filter_type: EqFilterType::LowPass },
+        EqBand { frequency: 3000.0, gain: 0.0, q_factor: 4.0, filter_type: EqFilterType::Notch },
+        EqBand { frequency: 500.0, gain: 0.0, q_factor: 0.707, filter_type: EqFilterType::AllPass },
+    ];
+    let signal = test_signal(2048, 3);
+
+    for sample_rate in [44100, 96000] {
+        backend.set_sample_rate(sample_rate);
+        assert_eq!(backend.sample_rate(), sample_rate);
+
+        let input = upload(backend, &signal, signal.len())?;
+        let output = backend.allocate_buffer(signal.len() * F32_BYTES)?;
+        backend.process_eq(&input, &output, &bands, signal.len())?;
+
+        let mut expected = signal.clone();
+        for band in &bands {
+            let mut filter = BiquadFilter::new(
+                format!("{:?}", band.filter_type),
+                band.coefficients(sample_rate as f32),
+            );
+            filter.set_topology(BiquadTopology::DoublePrecision);
+            let stage_input = expected.clone();
+            filter.process(&stage_input, &mut expected, ChannelLayout::MONO);
+        }
+
+        let actual = download(backend, &output, signal.len())?;
+        assert_close(&actual, &expected, TOLERANCE, &format!("eq at {} Hz", sample_rate));
+
+        backend.free_buffer(input)?;
+        backend.free_buffer(output)?;
+    }
+
+    Ok(())
+}
+
+/// `memory_usage` tracks live allocations and stays self-consistent
+pub fn check_memory_usage<B: GpuBackend>(backend: &B) -> VortexResult<()> {
+    let before = backend.memory_usage();
+    assert_eq!(before.total_mb, backend.capabilities().max_memory_mb);
+
+    let buffer = backend.allocate_buffer(8 * MB)?;
+    let during = backend.memory_usage();
+    assert!(
+        during.used_mb >= before.used_mb + 8,
+        "8 MB allocation not reflected in used_mb ({} -> {})",
+        before.used_mb,
+        during.used_mb
+    );
+    assert!(during.available_mb <= before.available_mb.saturating_sub(8));
+    assert!(during.usage_percentage > before.usage_percentage);
+
+    for info in [&before, &during] {
+        assert!(info.used_mb + info.available_mb <= info.total_mb);
+        assert!((0.0..=100.0).contains(&info.usage_percentage));
+    }
+
+    backend.free_buffer(buffer)?;
+    assert_eq!(backend.memory_usage().used_mb, before.used_mb, "freed memory not released");
+
+    Ok(())
+}
+
+/// Run every conformance check against one backend
+pub fn run_all<B: GpuBackend>(backend: &B) -> VortexResult<()> {
+    check_allocation_limits(backend)?;
+    check_memory_round_trip(backend)?;
+    check_fft_identity(backend)?;
+    check_convolution(backend)?;
+    check_eq(backend)?;
+    check_memory_usage(backend)
+}
+
+/// Generate one `#[test]` per conformance check inside a module named `$name`
+macro_rules! gpu_backend_conformance_tests {
+    ($name:ident, $backend:ty) => {
+        mod $name {
+            use super::*;
+            use $crate::gpu::conformance;
+
+            fn backend() -> $backend {
+                <$backend as $crate::gpu::GpuBackend>::initialize().expect("backend initialization failed")
+            }
+
+            #[test]
+            fn allocation_limits() {
+                conformance::check_allocation_limits(&backend()).unwrap();
+            }
+
+            #[test]
+            fn memory_round_trip() {
+                conformance::check_memory_round_trip(&backend()).unwrap();
+            }
+
+            #[test]
+            fn fft_identity() {
+                conformance::check_fft_identity(&backend()).unwrap();
+            }
+
+            #[test]
+            fn convolution() {
+                conformance::check_convolution(&backend()).unwrap();
+            }
+
+            #[test]
+            fn eq() {
+                conformance::check_eq(&backend()).unwrap();
+            }
+
+            #[test]
+            fn memory_usage() {
+                conformance::check_memory_usage(&backend()).unwrap();
+            }
+        }
+    };
+}
+
+pub(crate) use gpu_backend_conformance_tests;
@@ -3,8 +3,17 @@
 /// This module implements the improved GPU architecture from Section 2 of the design review,
 /// using trait-based polymorphism instead of runtime enum dispatch.
 
+use crate::audio::filters::{BiquadCoefficients, BiquadFilter, BiquadTopology, ChannelLayout, Filter, FilterType};
 use crate::error::{GpuError, VortexResult};
+use parking_lot::{Mutex, RwLock};
+use rustfft::num_complex::Complex32;
+use rustfft::FftPlanner;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[cfg(test)]
+pub mod conformance;
 
 /// GPU backend identifier
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -62,6 +71,9 @@ pub trait GpuBackend: Send + Sync + Debug {
 
     /// Process convolution on GPU
     /// 
+    /// Writes the full linear convolution (`input_samples + ir_samples - 1`
+    /// samples) to `output`.
+    /// 
     /// # Arguments
     /// * `input` - Input audio buffer on device
     /// * `impulse_response` - Impulse response buffer on device
@@ -79,6 +91,9 @@ pub trait GpuBackend: Send + Sync + Debug {
 
     /// Process parametric EQ on GPU
     /// 
+    /// Bands are applied as a biquad cascade designed at the backend's
+    /// `sample_rate()`, starting from zero filter state on every call.
+    /// 
     /// # Arguments
     /// * `input` - Input audio buffer on device
     /// * `output` - Output buffer on device
@@ -95,8 +110,8 @@ pub trait GpuBackend: Send + Sync + Debug {
     /// Process FFT (Fast Fourier Transform)
     /// 
     /// # Arguments
-    /// * `input` - Input buffer (real samples)
-    /// * `output` - Output buffer (complex spectrum)
+    /// * `input` - Input buffer (`fft_size` real samples)
+    /// * `output` - Output buffer (complex spectrum, `fft_size` bins interleaved as re/im)
     /// * `fft_size` - FFT size (must be power of 2)
     fn process_fft(
         &self,
@@ -106,6 +121,9 @@ pub trait GpuBackend: Send + Sync + Debug {
     ) -> VortexResult<()>;
 
     /// Process IFFT (Inverse Fast Fourier Transform)
+    /// 
+    /// Takes the interleaved complex layout produced by `process_fft` and
+    /// writes `fft_size` real samples, normalized so that IFFT(FFT(x)) == x.
     fn process_ifft(
         &self,
         input: &Self::Buffer,
@@ -121,6 +139,12 @@ pub trait GpuBackend: Send + Sync + Debug {
 
     /// Check if GPU is available and operational
     fn is_operational(&self) -> bool;
+
+    /// Set the sample rate used to design EQ filters
+    fn set_sample_rate(&self, sample_rate: u32);
+
+    /// Get the sample rate used to design EQ filters
+    fn sample_rate(&self) -> u32;
 }
 
 /// EQ band parameters
@@ -139,6 +163,37 @@ pub enum EqFilterType {
     HighShelf,
     LowPass,
     HighPass,
+    BandPass,
+    Notch,
+    AllPass,
+}
+
+impl From<EqFilterType> for FilterType {
+    fn from(filter_type: EqFilterType) -> Self {
+        match filter_type {
+            EqFilterType::Peak => FilterType::Peaking,
+            EqFilterType::LowShelf => FilterType::LowShelf,
+            EqFilterType::HighShelf => FilterType::HighShelf,
+            EqFilterType::LowPass => FilterType::Lowpass,
+            EqFilterType::HighPass => FilterType::Highpass,
+            EqFilterType::BandPass => FilterType::Bandpass,
+            EqFilterType::Notch => FilterType::Notch,
+            EqFilterType::AllPass => FilterType::Allpass,
+        }
+    }
+}
+
+impl EqBand {
+    /// Design the biquad coefficients for this band
+    pub fn coefficients(&self, sample_rate: f32) -> BiquadCoefficients {
+        BiquadCoefficients::design(
+            self.filter_type.into(),
+            self.frequency,
+            sample_rate,
+            self.q_factor,
+            self.gain,
+        )
+    }
 }
 
 /// GPU memory information
@@ -249,6 +304,36 @@ pub struct DynGpuBuffer {
     size: usize,
     alignment: usize,
     is_device: bool,
+    // Host-side sample storage (CPU backend only)
+    host_data: RwLock<Vec<f32>>,
+    // Owning backend's allocation counter, released on drop
+    allocation_tracker: Option<Arc<AtomicUsize>>,
+}
+
+impl DynGpuBuffer {
+    /// Create a buffer backed by host memory
+    fn host(size_bytes: usize, alignment: usize, allocation_tracker: Option<Arc<AtomicUsize>>) -> Self {
+        Self {
+            size: size_bytes,
+            alignment,
+            is_device: false,
+            host_data: RwLock::new(vec![0.0; size_bytes / std::mem::size_of::<f32>()]),
+            allocation_tracker,
+        }
+    }
+
+    /// Number of f32 samples the buffer can hold
+    pub fn capacity_samples(&self) -> usize {
+        self.size / std::mem::size_of::<f32>()
+    }
+}
+
+impl Drop for DynGpuBuffer {
+    fn drop(&mut self) {
+        if let Some(tracker) = &self.allocation_tracker {
+            tracker.fetch_sub(self.size, Ordering::AcqRel);
+        }
+    }
 }
 
 impl GpuBuffer for DynGpuBuffer {
@@ -266,9 +351,88 @@ impl GpuBuffer for DynGpuBuffer {
 }
 
 /// CPU fallback backend (always available)
-#[derive(Debug)]
+///
+/// Buffers live in host memory and kernels run synchronously on the calling
+/// thread, so results are ready as soon as each call returns.
 struct CpuFallbackBackend {
     capabilities: GpuCapabilities,
+    allocated_bytes: Arc<AtomicUsize>,
+    sample_rate: AtomicU32,
+    fft_planner: Mutex<FftPlanner<f32>>,
+}
+
+impl Debug for CpuFallbackBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CpuFallbackBackend")
+            .field("capabilities", &self.capabilities)
+            .field("allocated_bytes", &self.allocated_bytes)
+            .field("sample_rate", &self.sample_rate)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CpuFallbackBackend {
+    const MAX_MEMORY_MB: usize = 1024; // Limit CPU buffer to 1GB
+
+    /// Copy the first `count` samples out of a buffer
+    fn read_samples(buffer: &DynGpuBuffer, count: usize, kernel: &str) -> VortexResult<Vec<f32>> {
+        let data = buffer.host_data.read();
+        if count > data.len() {
+            return Err(GpuError::KernelExecutionFailed {
+                kernel_name: kernel.to_string(),
+                reason: format!("input needs {} samples, buffer holds {}", count, data.len()),
+            }.into());
+        }
+        Ok(data[..count].to_vec())
+    }
+
+    /// Write samples to the start of a buffer
+    fn write_samples(buffer: &DynGpuBuffer, samples: &[f32], kernel: &str) -> VortexResult<()> {
+        let mut data = buffer.host_data.write();
+        if samples.len() > data.len() {
+            return Err(GpuError::KernelExecutionFailed {
+                kernel_name: kernel.to_string(),
+                reason: format!("output needs {} samples, buffer holds {}", samples.len(), data.len()),
+            }.into());
+        }
+        data[..samples.len()].copy_from_slice(samples);
+        Ok(())
+    }
+
+    fn check_fft_size(fft_size: usize, kernel: &str) -> VortexResult<()> {
+        if fft_size == 0 || !fft_size.is_power_of_two() {
+            return Err(GpuError::KernelExecutionFailed {
+                kernel_name: kernel.to_string(),
+                reason: format!("FFT size must be a power of 2, got {}", fft_size),
+            }.into());
+        }
+        Ok(())
+    }
+
+    /// Full linear convolution via one zero-padded FFT
+    fn fft_convolve(&self, input: &[f32], ir: &[f32]) -> Vec<f32> {
+        let out_len = input.len() + ir.len() - 1;
+        let fft_size = out_len.next_power_of_two();
+        let (forward, inverse) = {
+            let mut planner = self.fft_planner.lock();
+            (planner.plan_fft_forward(fft_size), planner.plan_fft_inverse(fft_size))
+        };
+
+        let mut a: Vec<Complex32> = input.iter().map(|&x| Complex32::new(x, 0.0)).collect();
+        a.resize(fft_size, Complex32::default());
+        let mut b: Vec<Complex32> = ir.iter().map(|&x| Complex32::new(x, 0.0)).collect();
+        b.resize(fft_size, Complex32::default());
+
+        forward.process(&mut a);
+        forward.process(&mut b);
+        let scale = 1.0 / fft_size as f32;
+        for (x, h) in a.iter_mut().zip(&b) {
+            *x = *x * h * scale;
+        }
+        inverse.process(&mut a);
+
+        a[..out_len].iter().map(|c| c.re).collect()
+    }
 }
 
 impl GpuBackend for CpuFallbackBackend {
@@ -280,10 +444,13 @@ impl GpuBackend for CpuFallbackBackend {
                 backend_type: GpuBackendType::Cpu,
                 device_name: "CPU Fallback".to_string(),
                 compute_units: num_cpus::get() as u32,
-                max_memory_mb: 1024, // Limit CPU buffer to 1GB
+                max_memory_mb: Self::MAX_MEMORY_MB,
                 supports_fp64: true,
                 supports_async_transfer: false,
             },
+            allocated_bytes: Arc::new(AtomicUsize::new(0)),
+            sample_rate: AtomicU32::new(48000),
+            fft_planner: Mutex::new(FftPlanner::new()),
         })
     }
 
@@ -292,66 +459,151 @@ impl GpuBackend for CpuFallbackBackend {
     }
 
     fn allocate_buffer(&self, size_bytes: usize) -> VortexResult<Self::Buffer> {
-        Ok(DynGpuBuffer {
-            size: size_bytes,
-            alignment: 64, // Cache line alignment
-            is_device: false,
-        })
+        let limit = Self::MAX_MEMORY_MB * 1024 * 1024;
+        let previous = self.allocated_bytes.fetch_add(size_bytes, Ordering::AcqRel);
+
+        if previous + size_bytes > limit {
+            self.allocated_bytes.fetch_sub(size_bytes, Ordering::AcqRel);
+            return Err(GpuError::MemoryAllocationFailed {
+                requested_bytes: size_bytes,
+                available_bytes: limit.saturating_sub(previous),
+            }.into());
+        }
+
+        Ok(DynGpuBuffer::host(
+            size_bytes,
+            64, // Cache line alignment
+            Some(Arc::clone(&self.allocated_bytes)),
+        ))
     }
 
-    fn free_buffer(&self, _buffer: Self::Buffer) -> VortexResult<()> {
+    fn free_buffer(&self, buffer: Self::Buffer) -> VortexResult<()> {
+        drop(buffer); // Allocation is released when the buffer drops
         Ok(())
     }
 
-    fn copy_to_device(&self, _buffer: &Self::Buffer, _host_data: &[f32]) -> VortexResult<()> {
-        Ok(()) // No-op for CPU
+    fn copy_to_device(&self, buffer: &Self::Buffer, host_data: &[f32]) -> VortexResult<()> {
+        let mut data = buffer.host_data.write();
+        if host_data.len() > data.len() {
+            return Err(GpuError::MemoryTransferFailed {
+                reason: format!(
+                    "host data has {} samples, buffer holds {}",
+                    host_data.len(),
+                    data.len()
+                ),
+            }.into());
+        }
+        data[..host_data.len()].copy_from_slice(host_data);
+        Ok(())
     }
 
-    fn copy_from_device(&self, _buffer: &Self::Buffer, _host_data: &mut [f32]) -> VortexResult<()> {
-        Ok(()) // No-op for CPU
+    fn copy_from_device(&self, buffer: &Self::Buffer, host_data: &mut [f32]) -> VortexResult<()> {
+        let data = buffer.host_data.read();
+        if host_data.len() > data.len() {
+            return Err(GpuError::MemoryTransferFailed {
+                reason: format!(
+                    "requested {} samples, buffer holds {}",
+                    host_data.len(),
+                    data.len()
+                ),
+            }.into());
+        }
+        host_data.copy_from_slice(&data[..host_data.len()]);
+        Ok(())
     }
 
     fn process_convolution(
         &self,
-        _input: &Self::Buffer,
-        _impulse_response: &Self::Buffer,
-        _output: &Self::Buffer,
-        _input_samples: usize,
-        _ir_samples: usize,
+        input: &Self::Buffer,
+        impulse_response: &Self::Buffer,
+        output: &Self::Buffer,
+        input_samples: usize,
+        ir_samples: usize,
     ) -> VortexResult<()> {
-        // TODO: Implement CPU convolution using SIMD
-        Ok(())
+        const DIRECT_MAX_IR: usize = 64;
+
+        if input_samples == 0 || ir_samples == 0 {
+            return Err(GpuError::KernelExecutionFailed {
+                kernel_name: "convolution".to_string(),
+                reason: "input and impulse response must be non-empty".to_string(),
+            }.into());
+        }
+
+        let x = Self::read_samples(input, input_samples, "convolution")?;
+        let h = Self::read_samples(impulse_response, ir_samples, "convolution")?;
+
+        let y = if ir_samples <= DIRECT_MAX_IR {
+            let mut y = vec![0.0f32; input_samples + ir_samples - 1];
+            for (i, &xi) in x.iter().enumerate() {
+                for (out, &hj) in y[i..i + ir_samples].iter_mut().zip(&h) {
+                    *out += xi * hj;
+                }
+            }
+            y
+        } else {
+            self.fft_convolve(&x, &h)
+        };
+
+        Self::write_samples(output, &y, "convolution")
     }
 
     fn process_eq(
         &self,
-        _input: &Self::Buffer,
-        _output: &Self::Buffer,
-        _bands: &[EqBand],
-        _samples: usize,
+        input: &Self::Buffer,
+        output: &Self::Buffer,
+        bands: &[EqBand],
+        samples: usize,
     ) -> VortexResult<()> {
-        // TODO: Implement CPU EQ using biquad filters
-        Ok(())
+        let mut data = Self::read_samples(input, samples, "eq")?;
+        let mut filtered = vec![0.0f32; data.len()];
+        let sample_rate = self.sample_rate() as f32;
+
+        for band in bands {
+            let mut filter = BiquadFilter::new(String::new(), band.coefficients(sample_rate));
+            filter.set_topology(BiquadTopology::for_frequency(band.frequency, sample_rate));
+            filter.process(&data, &mut filtered, ChannelLayout::MONO);
+            std::mem::swap(&mut data, &mut filtered);
+        }
+
+        Self::write_samples(output, &data, "eq")
     }
 
     fn process_fft(
         &self,
-        _input: &Self::Buffer,
-        _output: &Self::Buffer,
-        _fft_size: usize,
+        input: &Self::Buffer,
+        output: &Self::Buffer,
+        fft_size: usize,
     ) -> VortexResult<()> {
-        // TODO: Implement CPU FFT
-        Ok(())
+        Self::check_fft_size(fft_size, "fft")?;
+        let samples = Self::read_samples(input, fft_size, "fft")?;
+
+        let mut spectrum: Vec<Complex32> = samples.iter().map(|&x| Complex32::new(x, 0.0)).collect();
+        let fft = self.fft_planner.lock().plan_fft_forward(fft_size);
+        fft.process(&mut spectrum);
+
+        let interleaved: Vec<f32> = spectrum.iter().flat_map(|c| [c.re, c.im]).collect();
+        Self::write_samples(output, &interleaved, "fft")
     }
 
     fn process_ifft(
         &self,
-        _input: &Self::Buffer,
-        _output: &Self::Buffer,
-        _fft_size: usize,
+        input: &Self::Buffer,
+        output: &Self::Buffer,
+        fft_size: usize,
     ) -> VortexResult<()> {
-        // TODO: Implement CPU IFFT
-        Ok(())
+        Self::check_fft_size(fft_size, "ifft")?;
+        let interleaved = Self::read_samples(input, fft_size * 2, "ifft")?;
+
+        let mut spectrum: Vec<Complex32> = interleaved
+            .chunks_exact(2)
+            .map(|pair| Complex32::new(pair[0], pair[1]))
+            .collect();
+        let ifft = self.fft_planner.lock().plan_fft_inverse(fft_size);
+        ifft.process(&mut spectrum);
+
+        let scale = 1.0 / fft_size as f32;
+        let samples: Vec<f32> = spectrum.iter().map(|c| c.re * scale).collect();
+        Self::write_samples(output, &samples, "ifft")
     }
 
     fn synchronize(&self) -> VortexResult<()> {
@@ -359,17 +611,30 @@ impl GpuBackend for CpuFallbackBackend {
     }
 
     fn memory_usage(&self) -> GpuMemoryInfo {
+        const MB: usize = 1024 * 1024;
+        let total_bytes = Self::MAX_MEMORY_MB * MB;
+        let used_bytes = self.allocated_bytes.load(Ordering::Acquire).min(total_bytes);
+        let used_mb = used_bytes.div_ceil(MB);
+
         GpuMemoryInfo {
-            total_mb: 1024,
-            used_mb: 0,
-            available_mb: 1024,
-            usage_percentage: 0.0,
+            total_mb: Self::MAX_MEMORY_MB,
+            used_mb,
+            available_mb: Self::MAX_MEMORY_MB - used_mb,
+            usage_percentage: used_bytes as f32 / total_bytes as f32 * 100.0,
         }
     }
 
     fn is_operational(&self) -> bool {
         true // CPU is always operational
     }
+
+    fn set_sample_rate(&self, sample_rate: u32) {
+        self.sample_rate.store(sample_rate, Ordering::Release);
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate.load(Ordering::Acquire)
+    }
 }
 
 // Placeholder backends for CUDA, OpenCL, Vulkan
@@ -382,6 +647,7 @@ mod cuda_backend {
     #[derive(Debug)]
     pub struct CudaBackend {
         capabilities: GpuCapabilities,
+        sample_rate: AtomicU32,
     }
 
     impl GpuBackend for CudaBackend {
@@ -408,6 +674,8 @@ mod cuda_backend {
         fn synchronize(&self) -> VortexResult<()> { unimplemented!() }
         fn memory_usage(&self) -> GpuMemoryInfo { unimplemented!() }
         fn is_operational(&self) -> bool { false }
+        fn set_sample_rate(&self, sample_rate: u32) { self.sample_rate.store(sample_rate, Ordering::Release) }
+        fn sample_rate(&self) -> u32 { self.sample_rate.load(Ordering::Acquire) }
     }
 }
 
@@ -418,6 +686,8 @@ pub use cuda_backend::CudaBackend;
 mod tests {
     use super::*;
 
+    conformance::gpu_backend_conformance_tests!(cpu_fallback_conformance, CpuFallbackBackend);
+
     #[test]
     fn test_cpu_backend_initialization() {
         let backend = CpuFallbackBackend::initialize().unwrap();
@@ -478,37 +748,136 @@ mod tests {
     }
 
     #[test]
-    fn test_memory_operations_no_op() {
+    fn test_memory_round_trip() {
         let backend = CpuFallbackBackend::initialize().unwrap();
         let buffer = backend.allocate_buffer(1024).unwrap();
         
-        let data = vec![1.0f32; 256];
+        let data: Vec<f32> = (0..256).map(|i| i as f32 * 0.5).collect();
         assert!(backend.copy_to_device(&buffer, &data).is_ok());
         
         let mut output = vec![0.0f32; 256];
         assert!(backend.copy_from_device(&buffer, &mut output).is_ok());
+        assert_eq!(output, data);
+
+        // Transfers larger than the buffer are rejected
+        let too_large = vec![0.0f32; 257];
+        assert!(backend.copy_to_device(&buffer, &too_large).is_err());
     }
 
     #[test]
-    fn test_processing_operations_stub() {
+    fn test_allocation_accounting() {
+        let backend = CpuFallbackBackend::initialize().unwrap();
+        let buffer = backend.allocate_buffer(4 * 1024 * 1024).unwrap();
+        assert_eq!(backend.memory_usage().used_mb, 4);
+
+        backend.free_buffer(buffer).unwrap();
+        assert_eq!(backend.memory_usage().used_mb, 0);
+
+        let too_large = (CpuFallbackBackend::MAX_MEMORY_MB + 1) * 1024 * 1024;
+        assert!(backend.allocate_buffer(too_large).is_err());
+    }
+
+    #[test]
+    fn test_convolution_kernel() {
+        let backend = CpuFallbackBackend::initialize().unwrap();
+        let input = backend.allocate_buffer(256 * 4).unwrap();
+        let ir = backend.allocate_buffer(128 * 4).unwrap();
+        let output = backend.allocate_buffer(383 * 4).unwrap();
+
+        let x: Vec<f32> = (0..256).map(|i| ((i * 7) % 13) as f32 / 13.0 - 0.5).collect();
+        backend.copy_to_device(&input, &x).unwrap();
+
+        // Short IR takes the direct path, long IR the FFT path
+        for ir_len in [16, 128] {
+            let h: Vec<f32> = (0..ir_len).map(|i| 0.9f32.powi(i as i32)).collect();
+            backend.copy_to_device(&ir, &h).unwrap();
+            backend.process_convolution(&input, &ir, &output, 256, ir_len).unwrap();
+
+            let mut y = vec![0.0f32; 256 + ir_len - 1];
+            backend.copy_from_device(&output, &mut y).unwrap();
+
+            for (n, &actual) in y.iter().enumerate() {
+                let expected: f32 = (0..ir_len)
+                    .filter(|&k| k <= n && n - k < 256)
+                    .map(|k| h[k] * x[n - k])
+                    .sum();
+                assert!((actual - expected).abs() < 1e-4, "ir {} sample {}", ir_len, n);
+            }
+        }
+
+        // Output too small for the full result
+        let small = backend.allocate_buffer(256 * 4).unwrap();
+        assert!(backend.process_convolution(&input, &ir, &small, 256, 128).is_err());
+    }
+
+    #[test]
+    fn test_eq_kernel() {
         let backend = CpuFallbackBackend::initialize().unwrap();
         let input = backend.allocate_buffer(1024).unwrap();
         let output = backend.allocate_buffer(1024).unwrap();
-        let ir = backend.allocate_buffer(2048).unwrap();
-        
-        // These are stubs for now, should not panic
-        assert!(backend.process_convolution(&input, &ir, &output, 256, 512).is_ok());
-        
+
+        let dc = vec![1.0f32; 256];
+        backend.copy_to_device(&input, &dc).unwrap();
+
+        // No bands passes the signal through
+        backend.process_eq(&input, &output, &[], 256).unwrap();
+        let mut result = vec![0.0f32; 256];
+        backend.copy_from_device(&output, &mut result).unwrap();
+        assert_eq!(result, dc);
+
+        // A high-pass settles towards zero on DC
         let bands = vec![EqBand {
             frequency: 1000.0,
-            gain: 3.0,
-            q_factor: 1.0,
-            filter_type: EqFilterType::Peak,
+            gain: 0.0,
+            q_factor: 0.707,
+            filter_type: EqFilterType::HighPass,
         }];
-        assert!(backend.process_eq(&input, &output, &bands, 256).is_ok());
-        
-        assert!(backend.process_fft(&input, &output, 1024).is_ok());
-        assert!(backend.process_ifft(&input, &output, 1024).is_ok());
+        backend.process_eq(&input, &output, &bands, 256).unwrap();
+        backend.copy_from_device(&output, &mut result).unwrap();
+        assert!(result[255].abs() < 1e-3);
+
+        // A low shelf boosts DC by its gain
+        let bands = vec![EqBand {
+            frequency: 1000.0,
+            gain: 6.0,
+            q_factor: 0.707,
+            filter_type: EqFilterType::LowShelf,
+        }];
+        backend.process_eq(&input, &output, &bands, 256).unwrap();
+        backend.copy_from_device(&output, &mut result).unwrap();
+        let expected = 10f32.powf(6.0 / 20.0);
+        assert!((result[255] - expected).abs() < 1e-2);
+    }
+
+    #[test]
+    fn test_fft_ifft_round_trip() {
+        let backend = CpuFallbackBackend::initialize().unwrap();
+        let input = backend.allocate_buffer(1024 * 4).unwrap();
+        let spectrum = backend.allocate_buffer(2048 * 4).unwrap();
+        let output = backend.allocate_buffer(1024 * 4).unwrap();
+
+        // Cosine at bin 8
+        let x: Vec<f32> = (0..1024)
+            .map(|i| (2.0 * std::f32::consts::PI * 8.0 * i as f32 / 1024.0).cos())
+            .collect();
+        backend.copy_to_device(&input, &x).unwrap();
+
+        backend.process_fft(&input, &spectrum, 1024).unwrap();
+        let mut bins = vec![0.0f32; 2048];
+        backend.copy_from_device(&spectrum, &mut bins).unwrap();
+        assert!((bins[16] - 512.0).abs() < 1e-2);
+        assert!(bins[18].abs() < 1e-2);
+
+        backend.process_ifft(&spectrum, &output, 1024).unwrap();
+        let mut y = vec![0.0f32; 1024];
+        backend.copy_from_device(&output, &mut y).unwrap();
+        for (a, b) in x.iter().zip(&y) {
+            assert!((a - b).abs() < 1e-4);
+        }
+
+        // Non power-of-two sizes and undersized buffers are rejected
+        assert!(backend.process_fft(&input, &spectrum, 1000).is_err());
+        assert!(backend.process_fft(&input, &output, 1024).is_err());
     }
 
     #[test]
@@ -563,6 +932,9 @@ mod tests {
             EqFilterType::HighShelf,
             EqFilterType::LowPass,
             EqFilterType::HighPass,
+            EqFilterType::BandPass,
+            EqFilterType::Notch,
+            EqFilterType::AllPass,
         ];
         
         // Just verify they can be created and cloned
@@ -658,11 +1030,7 @@ mod tests {
 
     #[test]
     fn test_dyn_gpu_buffer_creation() {
-        let buffer = DynGpuBuffer {
-            size: 2048,
-            alignment: 64,
-            is_device: false,
-        };
+        let buffer = DynGpuBuffer::host(2048, 64, None);
         
         assert_eq!(buffer.size(), 2048);
         assert_eq!(buffer.alignment(), 64);
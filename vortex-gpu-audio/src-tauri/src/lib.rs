@@ -0,0 +1,17 @@
+//! Vortex GPU Audio processing engine
+//!
+//! The Tauri application in `main.rs` drives these modules; they are a
+//! library so benchmarks and integration tests can link them too.
+
+pub mod error;
+pub mod lockfree;
+pub mod gpu;
+pub mod validation;
+pub mod audio;
+pub mod fileio;
+pub mod network;
+
+// Lets tests assert that the audio thread never allocates
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: audio::alloc_check::CountingAllocator = audio::alloc_check::CountingAllocator;
@@ -307,6 +307,12 @@ impl AudioRingBuffer {
         let frames = self.available_frames();
         (frames as f64 * 1000.0) / self.sample_rate as f64
     }
+
+    /// Get the buffer duration in milliseconds, i.e. the latency at 100% fill
+    pub fn capacity_ms(&self) -> f64 {
+        let frames = self.buffer.capacity() / self.channels;
+        (frames as f64 * 1000.0) / self.sample_rate as f64
+    }
 }
 
 #[cfg(test)]
@@ -390,6 +396,10 @@ mod tests {
         // Check latency
         let latency = buffer.latency_ms();
         assert!(latency > 0.0 && latency < 1.0);
+        
+        // Capacity is rounded up, never down
+        assert!(buffer.capacity_ms() >= 100.0);
+        assert!((buffer.latency_ms() / buffer.capacity_ms() - buffer.fill_percentage() as f64).abs() < 1e-6);
     }
 
     #[test]
@@ -1,17 +1,12 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod error;
-mod lockfree;
-mod gpu;
-mod validation;
-mod audio;
-mod fileio;
-mod network;
-
-use error::{VortexResult, AudioError, ErrorContext};
-use gpu::{GpuProcessor, GpuBackendType};
-use validation::{PathValidator, ParameterValidator, ResourceLimits, ResourceLimitEnforcer};
+use vortex_gpu_audio::error::{VortexResult, AudioError, ErrorContext};
+use vortex_gpu_audio::gpu::{GpuProcessor, GpuBackendType};
+use vortex_gpu_audio::audio::{AudioConfig, AudioEngine};
+use vortex_gpu_audio::audio::filters::{log_frequency_grid, BiquadCoefficients, BiquadFilter, FilterChain, FilterType, ResponseCurve};
+use vortex_gpu_audio::fileio::AudioFileLoader;
+use vortex_gpu_audio::validation::{PathValidator, ParameterValidator, ResourceLimits, ResourceLimitEnforcer};
 
 use tauri::State;
 use std::sync::Arc;
@@ -22,6 +17,7 @@ pub struct AppState {
     gpu_processor: Arc<RwLock<Option<GpuProcessor>>>,
     path_validator: Arc<PathValidator>,
     resource_limits: Arc<ResourceLimitEnforcer>,
+    engine: Arc<RwLock<AudioEngine>>,
 }
 
 impl AppState {
@@ -32,6 +28,9 @@ impl AppState {
             gpu_processor: Arc::new(RwLock::new(None)),
             path_validator: Arc::new(PathValidator::new()),
             resource_limits: Arc::new(ResourceLimitEnforcer::new(limits)),
+            engine: Arc::new(RwLock::new(
+                AudioEngine::new(AudioConfig::default()).expect("default audio configuration is valid"),
+            )),
         }
     }
 }
@@ -66,15 +65,19 @@ async fn load_audio_file(path: String, state: State<'_, AppState>) -> Result<Aud
         .validate_file_size(&validated_path, state.resource_limits.limits())
         .map_err(|e| format!("File size validation failed: {}", e))?;
 
-    // TODO: Actually load and decode the file
-    // For now, return metadata
+    // Probe the stream parameters from the file itself
+    let info = AudioFileLoader::new()
+        .get_file_info(&validated_path)
+        .map_err(|e| format!("Failed to read audio file: {}", e))?;
+
     Ok(AudioFileInfo {
         path: validated_path.display().to_string(),
         size_bytes: file_size,
-        duration_secs: 0.0,
-        sample_rate: 0,
-        channels: 0,
-        format: "Unknown".to_string(),
+        duration_secs: info.duration_secs,
+        sample_rate: info.sample_rate,
+        channels: info.channels,
+        bit_depth: info.bit_depth,
+        format: format!("{:?}", info.format),
     })
 }
 
@@ -96,7 +99,7 @@ async fn get_system_status(state: State<'_, AppState>) -> Result<SystemStatus, S
 
     Ok(SystemStatus {
         gpu: gpu_info,
-        latency_ms: 0.0, // TODO: Get actual latency
+        latency_ms: state.engine.read().latency_ms(),
         buffer_usage_percent: 0.0, // TODO: Get actual buffer usage
     })
 }
@@ -125,6 +128,54 @@ async fn validate_eq_parameters(
     })
 }
 
+/// Maximum number of points in a response curve
+const MAX_RESPONSE_POINTS: usize = 4096;
+
+/// Lowest frequency of response curves
+const RESPONSE_MIN_HZ: f32 = 10.0;
+
+/// Compute magnitude, phase and group delay of an EQ on a log frequency grid
+#[tauri::command]
+async fn get_eq_response(
+    bands: Vec<ResponseBand>,
+    sample_rate: u32,
+    points: usize,
+) -> Result<ResponseCurve, String> {
+    let sample_rate = ParameterValidator::validate_sample_rate(sample_rate)
+        .map_err(|e| format!("Invalid sample rate: {}", e))?;
+    if !(2..=MAX_RESPONSE_POINTS).contains(&points) {
+        return Err(format!("Point count must be between 2 and {}, got {}", MAX_RESPONSE_POINTS, points));
+    }
+
+    let mut chain = FilterChain::new();
+    for band in bands {
+        let params = validate_eq_parameters(band.frequency, band.gain_db, band.q_factor, sample_rate).await?;
+        let coeffs = BiquadCoefficients::design(
+            band.filter_type,
+            params.frequency,
+            sample_rate as f32,
+            params.q_factor,
+            params.gain_db,
+        );
+        chain
+            .add_filter(Box::new(BiquadFilter::new(format!("{:?}", band.filter_type), coeffs)))
+            .map_err(|e| e.to_string())?;
+    }
+
+    let rate = sample_rate as f32;
+    let frequencies = log_frequency_grid(RESPONSE_MIN_HZ, rate / 2.0, points);
+    Ok(ResponseCurve::evaluate(&frequencies, rate, |f| chain.frequency_response(f, rate, 0)))
+}
+
+// Request types for commands
+#[derive(Debug, serde::Deserialize)]
+struct ResponseBand {
+    filter_type: FilterType,
+    frequency: f32,
+    gain_db: f32,
+    q_factor: f32,
+}
+
 // Response types for commands
 #[derive(Debug, serde::Serialize)]
 struct AudioFileInfo {
@@ -133,6 +184,7 @@ struct AudioFileInfo {
     duration_secs: f64,
     sample_rate: u32,
     channels: u16,
+    bit_depth: u8,
     format: String,
 }
 
@@ -169,6 +221,7 @@ pub fn run() {
             load_audio_file,
             get_system_status,
             validate_eq_parameters,
+            get_eq_response,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,5 +1,5 @@
 use vortex_gpu_audio::audio::{AudioEngine, AudioConfig};
-use vortex_gpu_audio::audio::filters::{FilterChain, BiquadFilter};
+use vortex_gpu_audio::audio::filters::{ChannelLayout, FilterChain, BiquadFilter};
 use vortex_gpu_audio::audio::dsp::{EqProcessor, DsdProcessor, Convolver, Resampler, ResamplerQuality, DsdRate};
 
 #[test]
@@ -56,7 +56,7 @@ fn test_filter_chain_integration() -> Result<(), Box<dyn std::error::Error>> {
     let input = vec![1.0; 512];
     let mut output = vec![0.0; 512];
     
-    chain.process(&input, &mut output);
+    chain.process(&input, &mut output, ChannelLayout::MONO);
     
     // Remove a filter
     chain.remove_filter(&id1)?;
@@ -67,7 +67,7 @@ fn test_filter_chain_integration() -> Result<(), Box<dyn std::error::Error>> {
 
 #[test]
 fn test_eq_processor_integration() -> Result<(), Box<dyn std::error::Error>> {
-    let mut eq = EqProcessor::new_512band(48000.0)?;
+    let mut eq = EqProcessor::new_512band(48000.0, 1)?;
     
     // Set gains for multiple bands
     eq.set_band_gain(0, 6.0)?;
@@ -92,23 +92,24 @@ fn test_dsp_pipeline_complete() -> Result<(), Box<dyn std::error::Error>> {
     // Create complete DSP pipeline
     let sample_rate = 48000.0;
     
-    // 1. Resampler (44.1kHz to 48kHz)
-    let mut resampler = Resampler::new(44100, 48000, ResamplerQuality::Standard)?;
+    // 1. Resampler (44.1kHz to 48kHz), stereo like the engine's buffers
+    let mut resampler = Resampler::new(44100, 48000, ResamplerQuality::Standard, 2)?;
     
     // 2. EQ Processor
-    let mut eq = EqProcessor::new(10, sample_rate)?;
+    let mut eq = EqProcessor::new(10, sample_rate, 2)?;
     eq.set_band_gain(0, 3.0)?;
     eq.set_band_gain(5, -6.0)?;
     
     // 3. Convolver
     let ir = vec![1.0, 0.5, 0.25, 0.125]; // Simple IR
-    let mut convolver = Convolver::new(ir, 512)?;
+    let mut convolver = Convolver::new(ir, 512, 2)?;
     
     // Process through pipeline
-    let input_44k = vec![1.0; 4410]; // 100ms at 44.1kHz
-    let mut resampled = vec![0.0; 10000];
+    let input_44k = vec![1.0; 4410 * 2]; // 100ms at 44.1kHz, interleaved
+    let mut resampled = vec![0.0; 10000 * 2];
     
-    let samples_out = resampler.process(&input_44k, &mut resampled)?;
+    let frames_out = resampler.process(&input_44k, &mut resampled)?;
+    let samples_out = frames_out * 2;
     resampled.truncate(samples_out);
     
     let mut eq_output = vec![0.0; samples_out];
@@ -124,13 +125,13 @@ fn test_dsp_pipeline_complete() -> Result<(), Box<dyn std::error::Error>> {
 
 #[test]
 fn test_dsd_processing_integration() -> Result<(), Box<dyn std::error::Error>> {
-    let mut processor = DsdProcessor::new(DsdRate::Dsd64, 44100)?;
+    let mut processor = DsdProcessor::new(DsdRate::Dsd64, 44100, 1)?;
     
     // Create DSD test data (all ones)
     let dsd_input = vec![0xFF; 1024];
     let mut pcm_output = vec![0.0; 1024];
     
-    let samples = processor.process(&dsd_input, &mut pcm_output)?;
+    let samples = processor.process(&[dsd_input], &mut pcm_output)?;
     
     assert!(samples > 0);
     assert!(samples <= pcm_output.len());
@@ -151,7 +152,7 @@ fn test_convolution_with_large_ir() -> Result<(), Box<dyn std::error::Error>> {
         *sample = (-i as f32 / 1000.0).exp();
     }
     
-    let mut convolver = Convolver::new(ir, 2048)?;
+    let mut convolver = Convolver::new(ir, 2048, 1)?;
     
     let input = vec![1.0, 0.0, 0.0, 0.0]; // Impulse
     let mut output = vec![0.0; 4];
@@ -169,7 +170,7 @@ fn test_resampler_quality_comparison() -> Result<(), Box<dyn std::error::Error>>
     let input = vec![1.0; 1000];
     
     for quality in [ResamplerQuality::Draft, ResamplerQuality::Standard, ResamplerQuality::High] {
-        let mut resampler = Resampler::new(44100, 48000, quality)?;
+        let mut resampler = Resampler::new(44100, 48000, quality, 1)?;
         let mut output = vec![0.0; 2000];
         
         let samples = resampler.process(&input, &mut output)?;